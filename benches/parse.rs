@@ -0,0 +1,39 @@
+//! Parsing benchmarks, for high-frequency polling workloads.
+//!
+//! Run with `cargo bench --bench parse`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use pjlink_bridge::*;
+
+const POLLED_LINES: [&[u8]; 4] = [b"%1POWR ?", b"%1INPT 31", b"%2INNM ?3A", b"%1AVMT 30"];
+
+fn bench_parse(criterion: &mut Criterion) {
+    criterion.bench_function("decode_line", |bencher| {
+        bencher.iter(|| {
+            for line in POLLED_LINES {
+                black_box(decode_line(black_box(line)).unwrap());
+            }
+        })
+    });
+
+    let payloads: Vec<PjLinkRawPayload> = POLLED_LINES.iter().map(|line| decode_line(line).unwrap()).collect();
+    criterion.bench_function("from_raw_payload", |bencher| {
+        bencher.iter(|| {
+            for payload in payloads.iter() {
+                black_box(PjLinkCommand::from_raw_payload(black_box(payload)));
+            }
+        })
+    });
+
+    criterion.bench_function("try_from_buffer + from_raw_payload", |bencher| {
+        bencher.iter(|| {
+            for line in POLLED_LINES {
+                let payload = PjLinkRawPayloadRef::try_from_buffer(black_box(line)).unwrap();
+                black_box(PjLinkCommand::from_raw_payload_ref(&payload));
+            }
+        })
+    });
+}
+
+criterion_group!(benches, bench_parse);
+criterion_main!(benches);
@@ -0,0 +1,27 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    #[cfg(feature = "grpc")]
+    compile_grpc_service();
+}
+
+/// Generates the gRPC service of the `grpc` feature, with the vendored
+/// protoc so it builds without a system installation.
+#[cfg(feature = "grpc")]
+fn compile_grpc_service() {
+    println!("cargo:rerun-if-changed=proto/pjlink.proto");
+
+    if std::env::var_os("PROTOC").is_none() {
+        match protoc_bin_vendored::protoc_bin_path() {
+            Ok(protoc) => std::env::set_var("PROTOC", protoc),
+            Err(e) => panic!("No vendored protoc for this platform, set PROTOC! {}", e),
+        }
+    }
+
+    // Generated clients need the 2021 prelude, controllers generate their
+    // own from the proto file
+    tonic_build::configure()
+        .build_client(false)
+        .compile_protos(&["proto/pjlink.proto"], &["proto"])
+        .unwrap_or_else(|e| panic!("Failed to compile proto/pjlink.proto! {}", e));
+}
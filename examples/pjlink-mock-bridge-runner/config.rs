@@ -0,0 +1,155 @@
+//! Configuration files for the mock projector.
+//!
+//! A configuration file sets the same options as the command-line flags, in
+//! TOML, with underscores instead of dashes. Values in the file override
+//! flags. Scenario state changes and canned responses can be written inline:
+//!
+//! ```toml
+//! port = 4352
+//! udp = true
+//! password = "secret"
+//! class_type = "2"
+//! projector_name = "hall-left"
+//! warm_up_secs = 30
+//! notify = ["192.168.0.10:4352"]
+//!
+//! [[responses]]
+//! command = "1LAMP"
+//! response = "ERR4"
+//! ```
+//!
+//! Relative `state_file` and `scenario` paths are resolved against the
+//! directory of the configuration file.
+//!
+//! `[[instances]]` tables start one projector each, with the options above
+//! overridden by the table, like `--count` with one table per projector:
+//!
+//! ```toml
+//! port = 4352
+//!
+//! [[instances]]
+//! projector_name = "hall-left"
+//!
+//! [[instances]]
+//! projector_name = "hall-right"
+//! password = "secret"
+//! ```
+
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+
+use crate::Opts;
+use crate::scenario::{PjLinkMockCannedResponse, PjLinkMockScenario, PjLinkMockStateChange};
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PjLinkMockConfig {
+    listen_address: Option<String>,
+    port: Option<u16>,
+    udp: Option<bool>,
+    udp_listen_address: Option<String>,
+    class_type: Option<String>,
+    manufacturer_name: Option<String>,
+    product_name: Option<String>,
+    projector_name: Option<String>,
+    serial_number: Option<String>,
+    software_version: Option<String>,
+    screen_resolution: Option<String>,
+    recommended_screen_resolution: Option<String>,
+    password: Option<String>,
+    response_delay_ms: Option<u64>,
+    response_jitter_ms: Option<u64>,
+    error_probability: Option<f64>,
+    drop_probability: Option<f64>,
+    state_file: Option<PathBuf>,
+    scenario: Option<PathBuf>,
+    warm_up_secs: Option<f64>,
+    cool_down_secs: Option<f64>,
+    usage_acceleration: Option<f64>,
+    lamps: Option<usize>,
+    control_stdin: Option<bool>,
+    notify: Option<Vec<SocketAddr>>,
+    count: Option<usize>,
+    /// Projectors to start, each overriding the options above
+    instances: Vec<PjLinkMockConfig>,
+    /// Inline scenario state changes, added to those of `scenario`
+    state_changes: Vec<PjLinkMockStateChange>,
+    /// Inline scenario canned responses, checked before those of `scenario`
+    responses: Vec<PjLinkMockCannedResponse>,
+}
+
+impl PjLinkMockConfig {
+    pub fn load(path: &Path) -> Result<PjLinkMockConfig, String> {
+        let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let mut config: PjLinkMockConfig = toml::from_str(&contents).map_err(|e| e.to_string())?;
+
+        config.resolve_paths(path.parent().unwrap_or_else(|| Path::new("")));
+
+        Ok(config)
+    }
+
+    fn resolve_paths(&mut self, base_directory: &Path) {
+        for file in self.state_file.iter_mut().chain(self.scenario.iter_mut()) {
+            if file.is_relative() {
+                *file = base_directory.join(&file);
+            }
+        }
+        for instance in &mut self.instances {
+            instance.resolve_paths(base_directory);
+        }
+    }
+
+    /// Overrides `opts` with the options set in this file, and returns the
+    /// inline scenario and the instances.
+    pub fn apply_to(self, opts: &mut Opts) -> Result<(PjLinkMockScenario, Vec<PjLinkMockConfig>), String> {
+        fn set<T>(option: &mut T, value: Option<T>) {
+            if let Some(value) = value {
+                *option = value;
+            }
+        }
+
+        set(&mut opts.listen_address, self.listen_address);
+        set(&mut opts.port, self.port.map(|port| port.to_string()));
+        set(&mut opts.udp, self.udp);
+        set(&mut opts.udp_listen_address, self.udp_listen_address);
+        set(&mut opts.class_type, self.class_type);
+        set(&mut opts.manufacturer_name, self.manufacturer_name);
+        set(&mut opts.product_name, self.product_name);
+        set(&mut opts.projector_name, self.projector_name);
+        set(&mut opts.serial_number, self.serial_number);
+        set(&mut opts.software_version, self.software_version);
+        set(&mut opts.screen_resolution, self.screen_resolution);
+        set(&mut opts.recommended_screen_resolution, self.recommended_screen_resolution);
+        set(&mut opts.password, self.password.map(Some));
+        set(&mut opts.response_delay_ms, self.response_delay_ms);
+        set(&mut opts.response_jitter_ms, self.response_jitter_ms);
+        set(&mut opts.error_probability, self.error_probability);
+        set(&mut opts.drop_probability, self.drop_probability);
+        set(&mut opts.state_file, self.state_file.map(Some));
+        set(&mut opts.scenario, self.scenario.map(Some));
+        set(&mut opts.warm_up_secs, self.warm_up_secs);
+        set(&mut opts.cool_down_secs, self.cool_down_secs);
+        set(&mut opts.usage_acceleration, self.usage_acceleration);
+        set(&mut opts.lamps, self.lamps);
+        set(&mut opts.control_stdin, self.control_stdin);
+        set(&mut opts.notify, self.notify);
+        set(&mut opts.count, self.count);
+
+        if self.instances.iter().any(|instance| !instance.instances.is_empty() || instance.count.is_some()) {
+            return Err("instances can't set count or instances".to_string());
+        }
+        if self.count.is_some() && !self.instances.is_empty() {
+            return Err("count and instances can't be set together".to_string());
+        }
+
+        let scenario = PjLinkMockScenario {
+            state_changes: self.state_changes,
+            responses: self.responses,
+        };
+        scenario.validate()?;
+
+        Ok((scenario, self.instances))
+    }
+}
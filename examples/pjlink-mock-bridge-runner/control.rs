@@ -0,0 +1,61 @@
+//! Out-of-band control commands for the mock projector, read from stdin
+//! while it runs.
+
+use crate::scenario::PjLinkMockStateChange;
+
+pub const PJLINK_MOCK_CONTROL_HELP: &str = "\
+Commands:
+  power <0|1|2|3>        set power status
+  input <type><number>   set input, like `input 31`
+  mute <item><state>     set AV mute, like `mute 31`
+  freeze <0|1>           set freeze status
+  error <status>         set ERST status, like `error 020000`
+  lamp-hours <hours>     set hours of every lamp
+  fail-lamps [n,...]     fail listed lamps (starting at 1), repair others
+  kill                   drop open connections on their next command
+  help                   show this message";
+
+#[derive(Clone)]
+pub enum PjLinkMockControlCommand {
+    StateChange(PjLinkMockStateChange),
+    KillConnections,
+    Help,
+}
+
+impl PjLinkMockControlCommand {
+    pub fn parse(line: &str) -> Result<PjLinkMockControlCommand, String> {
+        let mut parts = line.split_whitespace();
+        let name = parts.next().unwrap_or_default();
+        let argument = parts.next();
+        let mut state_change = PjLinkMockStateChange::default();
+
+        match (name, argument) {
+            ("kill", None) => return Ok(PjLinkMockControlCommand::KillConnections),
+            ("help", None) => return Ok(PjLinkMockControlCommand::Help),
+            ("power", Some(power)) => state_change.power = Some(power.to_string()),
+            ("input", Some(input)) => state_change.input = Some(input.to_string()),
+            ("mute", Some(mute)) => state_change.mute = Some(mute.to_string()),
+            ("freeze", Some(freeze)) => state_change.freeze = Some(freeze.to_string()),
+            ("error", Some(error_status)) => state_change.error_status = Some(error_status.to_string()),
+            ("lamp-hours", Some(lamp_hours)) => {
+                state_change.lamp_hours = Some(lamp_hours.parse().map_err(|_| format!("invalid hours {:?}", lamp_hours))?);
+            }
+            ("fail-lamps", lamps) => {
+                let lamps = lamps.unwrap_or_default()
+                    .split(',')
+                    .filter(|lamp| !lamp.is_empty())
+                    .map(|lamp| lamp.parse().map_err(|_| format!("invalid lamp {:?}", lamp)))
+                    .collect::<Result<Vec<usize>, String>>()?;
+                state_change.failed_lamps = Some(lamps);
+            }
+            _ => return Err(format!("unknown command {:?}, try `help`", line.trim())),
+        }
+
+        if parts.next().is_some() {
+            return Err(format!("too many arguments in {:?}", line.trim()));
+        }
+        state_change.validate()?;
+
+        Ok(PjLinkMockControlCommand::StateChange(state_change))
+    }
+}
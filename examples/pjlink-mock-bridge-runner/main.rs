@@ -0,0 +1,1051 @@
+mod config;
+mod control;
+mod scenario;
+
+use pjlink_bridge::*;
+use config::PjLinkMockConfig;
+use control::{PjLinkMockControlCommand, PJLINK_MOCK_CONTROL_HELP};
+use scenario::{PjLinkMockScenario, PjLinkMockStateChange};
+
+use std::fs;
+use std::io::{self, BufRead, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use clap::Parser;
+use rand::Rng;
+use log::{info, warn, LevelFilter};
+use serde::{Deserialize, Serialize};
+use simple_logger::{SimpleLogger};
+
+#[derive(Clone, Parser)]
+#[clap(version = "0.1.0", author = "Mateus Meyer Jiacomelli")]
+struct Opts {
+    /// Loads options from a TOML file, overriding flags
+    #[clap(short, long)]
+    config: Option<PathBuf>,
+    #[clap(short, long, default_value = "0.0.0.0")]
+    listen_address: String,
+    #[clap(short, long, default_value = "4352")]
+    port: String,
+    #[clap(short, long, parse(from_occurrences))]
+    verbose: i32,
+    #[clap(long)]
+    no_log: bool,
+    #[clap(short, long)]
+    udp: bool,
+    #[clap(long, default_value = "0.0.0.0")]
+    udp_listen_address: String,
+    #[clap(long, default_value = "2")]
+    class_type: String,
+    #[clap(long, default_value = "mateusmeyer mocks")]
+    manufacturer_name: String,
+    #[clap(long, default_value = "projector-mock")]
+    product_name: String,
+    #[clap(long, default_value = "projector-001")]
+    projector_name: String,
+    #[clap(long, default_value = "faa13ebee21677a2c064fd6ce067b50e")]
+    serial_number: String,
+    #[clap(long, default_value = "1.0")]
+    software_version: String,
+    #[clap(long, default_value = "1920x1080")]
+    screen_resolution: String,
+    #[clap(long, default_value = "1920x1080")]
+    recommended_screen_resolution: String,
+    #[clap(long)]
+    password: Option<String>,
+    /// Delay every response by this many milliseconds
+    #[clap(long, default_value = "0")]
+    response_delay_ms: u64,
+    /// Add up to this many random milliseconds to the response delay
+    #[clap(long, default_value = "0")]
+    response_jitter_ms: u64,
+    /// Probability (0.0 to 1.0) of answering a command with ERR3 or ERR4
+    #[clap(long, default_value = "0")]
+    error_probability: f64,
+    /// Probability (0.0 to 1.0) of dropping the connection instead of answering
+    #[clap(long, default_value = "0")]
+    drop_probability: f64,
+    /// Saves projector state to this JSON file on change, and loads it on startup
+    #[clap(long)]
+    state_file: Option<PathBuf>,
+    /// Loads timed state changes and canned responses from a TOML or YAML file
+    #[clap(long)]
+    scenario: Option<PathBuf>,
+    /// Seconds spent warming up after `POWR 1` before reporting On
+    #[clap(long, default_value = "0")]
+    warm_up_secs: f64,
+    /// Seconds spent cooling down after `POWR 0` before reporting Off
+    #[clap(long, default_value = "0")]
+    cool_down_secs: f64,
+    /// Multiplies elapsed on-time when accumulating lamp and filter hours
+    #[clap(long, default_value = "1")]
+    usage_acceleration: f64,
+    /// Number of lamps, from 1 to 8
+    #[clap(long, default_value = "1")]
+    lamps: usize,
+    /// Reads control commands (state changes, errors, killing connections) from stdin
+    #[clap(long)]
+    control_stdin: bool,
+    /// Sends Class 2 status notifications (2POWR, 2INPT, 2ERST) to this address on state changes
+    #[clap(long)]
+    notify: Vec<SocketAddr>,
+    /// Starts this many projectors on sequential ports, numbering their
+    /// names, serial numbers and state files
+    #[clap(long, default_value = "1")]
+    count: usize,
+}
+
+pub fn main() {
+    let mut opts = Opts::parse();
+    let (inline_scenario, instance_configs) = match opts.config.clone() {
+        Some(config_path) => match PjLinkMockConfig::load(&config_path).and_then(|config| config.apply_to(&mut opts)) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("Invalid --config {}: {}", config_path.display(), e);
+                std::process::exit(1);
+            }
+        },
+        None => (PjLinkMockScenario::default(), Vec::new()),
+    };
+
+    if !opts.no_log {
+        SimpleLogger::new()
+            .with_level(match opts.verbose {
+                1 => LevelFilter::Error,
+                2 => LevelFilter::Warn,
+                3 => LevelFilter::Info,
+                4 => LevelFilter::Debug,
+                5 => LevelFilter::Trace,
+                _ => LevelFilter::Warn
+            })
+            .with_module_level("pjlink_bridge", match opts.verbose {
+                1 => LevelFilter::Error,
+                2 => LevelFilter::Warn,
+                3 => LevelFilter::Info,
+                4 => LevelFilter::Debug,
+                5 => LevelFilter::Trace,
+                _ => LevelFilter::Info
+            })
+            .init()
+            .unwrap();
+    }
+
+    if !instance_configs.is_empty() {
+        opts.count = instance_configs.len();
+    }
+    if opts.count == 0 {
+        eprintln!("Invalid --count: must be at least 1");
+        std::process::exit(1);
+    }
+
+    let mut instances = Vec::with_capacity(opts.count);
+    for index in 0..opts.count {
+        let mut instance_opts = opts.numbered(index);
+        let mut instance_scenario = inline_scenario.clone();
+
+        if let Some(instance_config) = instance_configs.get(index) {
+            match instance_config.clone().apply_to(&mut instance_opts) {
+                Ok((scenario, _)) => {
+                    instance_scenario.state_changes.extend(scenario.state_changes);
+                    instance_scenario.responses.splice(0..0, scenario.responses);
+                }
+                Err(e) => {
+                    eprintln!("Invalid instance {} in --config: {}", index + 1, e);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        instances.push(start_projector(instance_opts, instance_scenario));
+    }
+
+    if opts.control_stdin {
+        spawn_stdin_control(instances.iter().map(|(projector, _)| projector.clone()).collect());
+    }
+
+    for (_, server_thread) in instances {
+        server_thread.join().unwrap();
+    }
+}
+
+impl Opts {
+    /// Returns the options of the projector at `index` (starting at 0) when
+    /// starting [count](Opts::count) projectors: the port is increased by
+    /// `index`, and names, serial numbers and state files are suffixed with
+    /// `-<index + 1>`. A single projector keeps the options as they are.
+    fn numbered(&self, index: usize) -> Opts {
+        let mut opts = self.clone();
+        if self.count <= 1 {
+            return opts;
+        }
+
+        let suffix = format!("-{}", index + 1);
+        opts.port = match self.port.parse::<u16>().ok().and_then(|port| port.checked_add(index as u16)) {
+            Some(port) => port.to_string(),
+            None => {
+                eprintln!("Invalid --port: {} projectors don't fit after port {}", self.count, self.port);
+                std::process::exit(1);
+            }
+        };
+        opts.projector_name = with_suffix(&self.projector_name, &suffix, 64);
+        opts.serial_number = with_suffix(&self.serial_number, &suffix, 32);
+        opts.state_file = self.state_file.as_ref().map(|state_file| {
+            let stem = state_file.file_stem().unwrap_or_default().to_string_lossy();
+            let mut file_name = format!("{}{}", stem, suffix);
+            if let Some(extension) = state_file.extension() {
+                file_name.push('.');
+                file_name.push_str(&extension.to_string_lossy());
+            }
+            state_file.with_file_name(file_name)
+        });
+
+        opts
+    }
+}
+
+/// Appends `suffix` to `value`, dropping characters at the end of `value`
+/// to keep the result within `max_len` bytes.
+fn with_suffix(value: &str, suffix: &str, max_len: usize) -> String {
+    let mut value = value.to_string();
+    while !value.is_empty() && value.len() + suffix.len() > max_len {
+        value.pop();
+    }
+    value.push_str(suffix);
+    value
+}
+
+/// Validates `opts`, and starts serving a mock projector with them.
+fn start_projector(
+    opts: Opts,
+    inline_scenario: PjLinkMockScenario,
+) -> (Arc<Mutex<PjLinkMockProjector>>, thread::JoinHandle<()>) {
+    let tcp_bind_address = opts.listen_address;
+    let password = match opts.password.map(PjLinkPassword::new).transpose() {
+        Ok(password) => password.map(String::from),
+        Err(e) => {
+            eprintln!("Invalid --password: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    for (name, probability) in [("--error-probability", opts.error_probability), ("--drop-probability", opts.drop_probability)] {
+        if !(0.0..=1.0).contains(&probability) {
+            eprintln!("Invalid {}: must be between 0.0 and 1.0", name);
+            std::process::exit(1);
+        }
+    }
+
+    if opts.class_type != "1" && opts.class_type != "2" {
+        eprintln!("Invalid --class-type: must be 1 or 2");
+        std::process::exit(1);
+    }
+
+    if !(1..=8).contains(&opts.lamps) {
+        eprintln!("Invalid --lamps: must be between 1 and 8");
+        std::process::exit(1);
+    }
+
+    for (name, secs) in [
+        ("--warm-up-secs", opts.warm_up_secs),
+        ("--cool-down-secs", opts.cool_down_secs),
+        ("--usage-acceleration", opts.usage_acceleration),
+    ] {
+        if !secs.is_finite() || secs < 0.0 {
+            eprintln!("Invalid {}: must be a positive number", name);
+            std::process::exit(1);
+        }
+    }
+
+    let mut scenario = match &opts.scenario {
+        Some(scenario_path) => match PjLinkMockScenario::load(scenario_path) {
+            Ok(scenario) => scenario,
+            Err(e) => {
+                eprintln!("Invalid --scenario {}: {}", scenario_path.display(), e);
+                std::process::exit(1);
+            }
+        },
+        None => PjLinkMockScenario::default(),
+    };
+    scenario.state_changes.extend(inline_scenario.state_changes);
+    scenario.responses.splice(0..0, inline_scenario.responses);
+    let state_changes = std::mem::take(&mut scenario.state_changes);
+
+    let handler = PjLinkMockProjector::new(PjLinkMockProjectorOptions {
+        password,
+        class_type: opts.class_type.as_bytes()[0],
+        manufacturer_name: Vec::from(opts.manufacturer_name.as_bytes()),
+        product_name: Vec::from(opts.product_name.as_bytes()),
+        projector_name: Vec::from(opts.projector_name.as_bytes()),
+        serial_number: Vec::from(opts.serial_number.as_bytes()),
+        software_version: Vec::from(opts.software_version.as_bytes()),
+        screen_resolution: Vec::from(opts.screen_resolution.as_bytes()),
+        recommended_screen_resolution: Vec::from(opts.recommended_screen_resolution.as_bytes()),
+        faults: PjLinkMockFaultOptions {
+            response_delay: Duration::from_millis(opts.response_delay_ms),
+            response_jitter: Duration::from_millis(opts.response_jitter_ms),
+            error_probability: opts.error_probability,
+            drop_probability: opts.drop_probability,
+        },
+        state_file: opts.state_file,
+        scenario,
+        warm_up: Duration::from_secs_f64(opts.warm_up_secs),
+        cool_down: Duration::from_secs_f64(opts.cool_down_secs),
+        usage_acceleration: opts.usage_acceleration,
+        lamp_count: opts.lamps,
+        notification_targets: opts.notify,
+    });
+
+    let projector = Arc::new(Mutex::new(handler));
+    spawn_state_changes(projector.clone(), state_changes);
+    let shared_handler: PjLinkHandlerShared = projector.clone();
+
+    #[cfg(unix)]
+    match PjLinkActivatedSockets::from_env() {
+        Ok(Some(sockets)) => {
+            info!("Using sockets passed by the service manager");
+            let udp_socket = if opts.udp { sockets.udp_socket } else { None };
+            return (projector, serve(shared_handler, sockets.tcp_listener, udp_socket));
+        }
+        Ok(None) => {}
+        Err(e) => {
+            eprintln!("Invalid sockets passed by the service manager: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    let tcp_listener = TcpListener::bind(format!("{}:{}", tcp_bind_address, opts.port)).unwrap();
+    let udp_socket = if opts.udp {
+        Some(UdpSocket::bind(format!("{}:{}", opts.udp_listen_address, opts.port)).unwrap())
+    } else {
+        None
+    };
+
+    (projector, serve(shared_handler, tcp_listener, udp_socket))
+}
+
+/// Serves the projector on `tcp_listener`, answering search requests on
+/// `udp_socket` if any. Connections are accepted here, instead of by
+/// [PjLinkServer], so they can be dropped, see [PjLinkMockConnection].
+fn serve(shared_handler: PjLinkHandlerShared, tcp_listener: TcpListener, udp_socket: Option<UdpSocket>) -> thread::JoinHandle<()> {
+    let listener = PjLinkListener::new_with_options(shared_handler, tcp_listener.try_clone().unwrap(), udp_socket, PjLinkListenerOptions::default());
+
+    #[cfg(feature = "discovery")]
+    if let Some(udp_addr) = listener.local_udp_addr() {
+        let listener = listener.clone();
+        thread::spawn(move || {
+            info!("Running UDP Listener on {}", udp_addr);
+            listener.listen_multicast();
+        });
+    }
+
+    thread::spawn(move || {
+        info!("Running TCP Listener on {}", tcp_listener.local_addr().unwrap());
+        for stream in tcp_listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let listener = listener.clone();
+                    thread::spawn(move || listener.serve_transport(PjLinkMockConnection(stream)));
+                }
+                Err(e) => warn!("Failed to accept connection: {}", e),
+            }
+        }
+    })
+}
+
+/// Response of the mock projector meaning the connection must be dropped,
+/// like a crashing projector would, instead of answered.
+const PJLINK_MOCK_DROP_CONNECTION: &[u8] = b"\0DROP";
+
+/// Controller connection of the mock projector, closed instead of sending a
+/// [PJLINK_MOCK_DROP_CONNECTION] response.
+struct PjLinkMockConnection(TcpStream);
+
+impl Read for PjLinkMockConnection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Write for PjLinkMockConnection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.windows(PJLINK_MOCK_DROP_CONNECTION.len()).any(|window| window == PJLINK_MOCK_DROP_CONNECTION) {
+            self.0.shutdown(Shutdown::Both)?;
+            return Err(io::Error::new(io::ErrorKind::ConnectionAborted, "connection dropped by the mock projector"));
+        }
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl PjLinkTransport for PjLinkMockConnection {
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        PjLinkTransport::peer_addr(&self.0)
+    }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        PjLinkTransport::set_read_timeout(&mut self.0, timeout)
+    }
+}
+
+/// Reads control commands from stdin until it's closed, applying them to
+/// every projector.
+fn spawn_stdin_control(projectors: Vec<Arc<Mutex<PjLinkMockProjector>>>) {
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let line = match line {
+                Ok(line) if line.trim().is_empty() => continue,
+                Ok(line) => line,
+                Err(_) => break,
+            };
+
+            match PjLinkMockControlCommand::parse(&line) {
+                Ok(PjLinkMockControlCommand::Help) => println!("{}", PJLINK_MOCK_CONTROL_HELP),
+                Ok(command) => {
+                    for projector in &projectors {
+                        if let Ok(mut projector) = projector.lock() {
+                            projector.handle_control_command(command.clone());
+                        }
+                    }
+                    println!("ok");
+                }
+                Err(e) => eprintln!("error: {}", e),
+            }
+        }
+    });
+}
+
+/// Applies scenario state changes when they're due.
+fn spawn_state_changes(projector: Arc<Mutex<PjLinkMockProjector>>, mut state_changes: Vec<PjLinkMockStateChange>) {
+    if state_changes.is_empty() {
+        return;
+    }
+
+    state_changes.sort_by(|a, b| a.after_secs.total_cmp(&b.after_secs));
+    let started_at = Instant::now();
+
+    thread::spawn(move || {
+        for state_change in state_changes {
+            if let Some(wait) = state_change.after().checked_sub(started_at.elapsed()) {
+                thread::sleep(wait);
+            }
+            if let Ok(mut projector) = projector.lock() {
+                projector.apply_state_change(&state_change);
+            }
+        }
+    });
+}
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+struct PjLinkMockProjectorState{
+    power_on: u8,
+    error_fan_status: u8,
+    error_lamp_status: u8,
+    error_temperature_status: u8,
+    error_cover_open_status: u8,
+    error_filter_status: u8,
+    error_other_status: u8,
+    lamps: Vec<PjLinkMockLampState>,
+    filter_usage_secs: f64,
+    mute_status: [u8; 2],
+    input_status: [u8; 2],
+    available_inputs: Vec<u8>,
+    freeze_status: u8,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+struct PjLinkMockLampState {
+    usage_secs: f64,
+    is_failed: bool,
+}
+
+struct PjLinkMockProjectorOptions {
+    password: Option<String>,
+    class_type: u8,
+    manufacturer_name: Vec<u8>,
+    product_name: Vec<u8>,
+    projector_name: Vec<u8>,
+    serial_number: Vec<u8>,
+    software_version: Vec<u8>,
+    screen_resolution: Vec<u8>,
+    recommended_screen_resolution: Vec<u8>,
+    faults: PjLinkMockFaultOptions,
+    state_file: Option<PathBuf>,
+    scenario: PjLinkMockScenario,
+    warm_up: Duration,
+    cool_down: Duration,
+    usage_acceleration: f64,
+    lamp_count: usize,
+    notification_targets: Vec<SocketAddr>,
+}
+
+/// Misbehaviour injected into responses, to test controllers against
+/// slow or faulty projectors.
+struct PjLinkMockFaultOptions {
+    response_delay: Duration,
+    response_jitter: Duration,
+    error_probability: f64,
+    drop_probability: f64,
+}
+
+struct PjLinkMockProjector {
+    options: PjLinkMockProjectorOptions,
+    state: PjLinkMockProjectorState,
+    /// Power status to report after warm-up or cool-down, and when it ends
+    power_transition: Option<(u8, Instant)>,
+    usage_updated_at: Instant,
+    state_tracker: Option<PjLinkStateTracker>,
+    /// Highest connection ID that sent a command
+    last_connection_id: u64,
+    /// Connections with lower IDs are dropped on their next command
+    drop_connections_below: u64,
+}
+
+impl PjLinkMockProjector {
+    fn new(options: PjLinkMockProjectorOptions) -> Self {
+        let state = match &options.state_file {
+            Some(state_file) if state_file.exists() => match Self::load_state(state_file) {
+                Ok(state) => {
+                    info!("Loaded state from {}", state_file.display());
+                    Some(state)
+                }
+                Err(e) => {
+                    warn!("Failed to load state from {}, using defaults: {}", state_file.display(), e);
+                    None
+                }
+            },
+            _ => None,
+        };
+
+        let mut state = state.unwrap_or_else(Self::default_state);
+        state.lamps.resize(options.lamp_count, Self::default_lamp_state());
+
+        let state_tracker = if options.notification_targets.is_empty() {
+            None
+        } else {
+            let targets = options.notification_targets.iter().copied().map(PjLinkNotificationTarget::from).collect();
+            match PjLinkStateTracker::new(targets, Duration::from_millis(200)) {
+                Ok(state_tracker) => Some(state_tracker),
+                Err(e) => {
+                    warn!("Failed to start status notifications: {}", e);
+                    None
+                }
+            }
+        };
+
+        let projector = PjLinkMockProjector {
+            options,
+            state,
+            power_transition: None,
+            usage_updated_at: Instant::now(),
+            state_tracker,
+            last_connection_id: 0,
+            drop_connections_below: 0,
+        };
+        projector.notify_state();
+        projector
+    }
+
+    fn handle_control_command(&mut self, command: PjLinkMockControlCommand) {
+        match command {
+            PjLinkMockControlCommand::StateChange(state_change) => self.apply_state_change(&state_change),
+            PjLinkMockControlCommand::KillConnections => {
+                info!("Dropping connections up to {}", self.last_connection_id);
+                self.drop_connections_below = self.last_connection_id + 1;
+            }
+            PjLinkMockControlCommand::Help => {}
+        }
+    }
+
+    /// Saves state and sends notifications for changed items.
+    fn on_state_changed(&self) {
+        self.save_state();
+        self.notify_state();
+    }
+
+    fn notify_state(&self) {
+        if let Some(state_tracker) = &self.state_tracker {
+            state_tracker.set_power(self.state.power_on);
+            state_tracker.set_input(self.state.input_status[0], self.state.input_status[1]);
+            state_tracker.set_error_status(self.error_status());
+        }
+    }
+
+    fn error_status(&self) -> [u8; 6] {
+        [
+            self.state.error_fan_status,
+            if self.state.lamps.iter().any(|lamp| lamp.is_failed) {
+                PjLinkErrorStatusCommandStatusItem::Error
+            } else {
+                self.state.error_lamp_status
+            },
+            self.state.error_temperature_status,
+            self.state.error_cover_open_status,
+            self.state.error_filter_status,
+            self.state.error_other_status,
+        ]
+    }
+
+    /// Lamps are lit while the projector is on or warming up, unless failed.
+    fn is_lamp_lit(&self, lamp: &PjLinkMockLampState) -> bool {
+        !lamp.is_failed && (self.state.power_on == PjLinkPowerCommandStatus::On
+            || self.state.power_on == PjLinkPowerCommandStatus::WarmUp)
+    }
+
+    /// Accumulates usage of lit lamps, and of the filter while any lamp is
+    /// lit, since the last update.
+    fn update_usage(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.usage_updated_at).as_secs_f64() * self.options.usage_acceleration;
+        self.usage_updated_at = now;
+
+        let lit_lamps: Vec<bool> = self.state.lamps.iter().map(|lamp| self.is_lamp_lit(lamp)).collect();
+        if !lit_lamps.contains(&true) {
+            return;
+        }
+
+        let mut has_hours_changed = false;
+        for (lamp, is_lit) in self.state.lamps.iter_mut().zip(lit_lamps) {
+            if is_lit {
+                let previous_hours = Self::to_hours(lamp.usage_secs);
+                lamp.usage_secs += elapsed;
+                has_hours_changed |= Self::to_hours(lamp.usage_secs) != previous_hours;
+            }
+        }
+        self.state.filter_usage_secs += elapsed;
+
+        if has_hours_changed {
+            self.on_state_changed();
+        }
+    }
+
+    fn to_hours(usage_secs: f64) -> u64 {
+        (usage_secs / 3600.0) as u64
+    }
+
+    /// Starts warming up or cooling down, or switches power immediately if
+    /// the transition has no duration.
+    fn start_power_transition(&mut self, transition_status: u8, target_status: u8, duration: Duration) {
+        if duration.is_zero() {
+            self.state.power_on = target_status;
+        } else {
+            self.state.power_on = transition_status;
+            self.power_transition = Some((target_status, Instant::now() + duration));
+        }
+    }
+
+    /// Finishes warm-up or cool-down if it's due.
+    fn update_power_transition(&mut self) {
+        if let Some((target_status, ends_at)) = self.power_transition {
+            if Instant::now() >= ends_at {
+                info!("Power transition finished");
+                self.state.power_on = target_status;
+                self.power_transition = None;
+                self.on_state_changed();
+            }
+        }
+    }
+
+    fn load_state(state_file: &PathBuf) -> io::Result<PjLinkMockProjectorState> {
+        let contents = fs::read(state_file)?;
+        let mut state: PjLinkMockProjectorState = serde_json::from_slice(&contents).map_err(io::Error::from)?;
+
+        // Transitions don't survive restarts
+        state.power_on = match state.power_on {
+            PjLinkPowerCommandStatus::WarmUp => PjLinkPowerCommandStatus::On,
+            PjLinkPowerCommandStatus::Cooling => PjLinkPowerCommandStatus::Off,
+            power_on => power_on,
+        };
+
+        Ok(state)
+    }
+
+    fn save_state(&self) {
+        if let Some(state_file) = &self.options.state_file {
+            let result = serde_json::to_vec_pretty(&self.state)
+                .map_err(io::Error::from)
+                .and_then(|contents| fs::write(state_file, contents));
+
+            if let Err(e) = result {
+                warn!("Failed to save state to {}: {}", state_file.display(), e);
+            }
+        }
+    }
+
+    fn apply_state_change(&mut self, state_change: &PjLinkMockStateChange) {
+        info!("Applying state change");
+        self.update_usage();
+        let previous_state = self.state.clone();
+
+        if let Some(power) = &state_change.power {
+            self.state.power_on = power.as_bytes()[0];
+            self.power_transition = None;
+        }
+        if let Some(input) = &state_change.input {
+            self.state.input_status.copy_from_slice(input.as_bytes());
+        }
+        if let Some(mute) = &state_change.mute {
+            self.state.mute_status.copy_from_slice(mute.as_bytes());
+        }
+        if let Some(freeze) = &state_change.freeze {
+            self.state.freeze_status = freeze.as_bytes()[0];
+        }
+        if let Some(error_status) = &state_change.error_status {
+            let error_status = error_status.as_bytes();
+            self.state.error_fan_status = error_status[0];
+            self.state.error_lamp_status = error_status[1];
+            self.state.error_temperature_status = error_status[2];
+            self.state.error_cover_open_status = error_status[3];
+            self.state.error_filter_status = error_status[4];
+            self.state.error_other_status = error_status[5];
+        }
+        if let Some(lamp_hours) = state_change.lamp_hours {
+            for lamp in self.state.lamps.iter_mut() {
+                lamp.usage_secs = lamp_hours as f64 * 3600.0;
+            }
+        }
+        if let Some(failed_lamps) = &state_change.failed_lamps {
+            for (index, lamp) in self.state.lamps.iter_mut().enumerate() {
+                lamp.is_failed = failed_lamps.contains(&(index + 1));
+            }
+        }
+        if let Some(filter_hours) = state_change.filter_hours {
+            self.state.filter_usage_secs = filter_hours as f64 * 3600.0;
+        }
+
+        if self.state != previous_state {
+            self.on_state_changed();
+        }
+    }
+
+    fn default_lamp_state() -> PjLinkMockLampState {
+        PjLinkMockLampState {
+            usage_secs: 120.0 * 3600.0,
+            is_failed: false,
+        }
+    }
+
+    fn default_state() -> PjLinkMockProjectorState {
+        PjLinkMockProjectorState {
+            power_on: PjLinkPowerCommandStatus::Off,
+            error_fan_status: PjLinkErrorStatusCommandStatusItem::Normal,
+            error_lamp_status: PjLinkErrorStatusCommandStatusItem::Normal,
+            error_temperature_status: PjLinkErrorStatusCommandStatusItem::Normal,
+            error_cover_open_status: PjLinkErrorStatusCommandStatusItem::Normal,
+            error_filter_status: PjLinkErrorStatusCommandStatusItem::Normal,
+            error_other_status: PjLinkErrorStatusCommandStatusItem::Normal,
+            lamps: vec![Self::default_lamp_state()],
+            filter_usage_secs: 0.0,
+            mute_status: [PjLinkMuteCommandStatus::AudioAndVideo, PjLinkMuteCommandStatus::NonMute],
+            input_status: [PjLinkInputCommandStatus::RGB, b'1'],
+            available_inputs: vec![
+                PjLinkInputCommandStatus::RGB, b'1', b' ',
+                PjLinkInputCommandStatus::RGB, b'2', b' ',
+                PjLinkInputCommandStatus::Digital, b'1', b' ',
+                PjLinkInputCommandStatus::Storage, b'1',
+            ],
+            freeze_status: b'0'
+        }
+    }
+}
+
+impl PjLinkMockProjector {
+    /// Sleeps for the configured delay, and returns a random ERR3/ERR4
+    /// response if an error should be injected.
+    fn inject_faults(&mut self) -> Option<PjLinkResponse> {
+        let faults = &self.options.faults;
+        let mut rng = rand::thread_rng();
+
+        let jitter_ms = faults.response_jitter.as_millis() as u64;
+        let delay = faults.response_delay + Duration::from_millis(if jitter_ms > 0 { rng.gen_range(0..=jitter_ms) } else { 0 });
+        if !delay.is_zero() {
+            thread::sleep(delay);
+        }
+
+        if rng.gen_bool(faults.error_probability) {
+            if rng.gen_bool(0.5) {
+                info!("Injecting ERR3");
+                Some(PjLinkResponse::UnavailableTime)
+            } else {
+                info!("Injecting ERR4");
+                Some(PjLinkResponse::ProjectorOrDisplayFailure)
+            }
+        } else {
+            None
+        }
+    }
+}
+
+impl PjLinkHandler for PjLinkMockProjector{
+
+    fn handle_command(&mut self, command: PjLinkCommand, raw_command: &PjLinkRawPayload, connection_id: &u64) -> PjLinkResponse {
+        if self.should_drop_connection(connection_id) {
+            return PjLinkResponse::Multiple(PJLINK_MOCK_DROP_CONNECTION.to_vec());
+        }
+        if let Some(response) = self.inject_faults() {
+            return response;
+        }
+        self.update_usage();
+        self.update_power_transition();
+        if let Some(response) = self.options.scenario.find_response(raw_command, self.state.power_on) {
+            info!("Sending scenario response");
+            return response;
+        }
+
+        if self.power_transition.is_some() && command.is_set_command() {
+            info!("Rejecting command during warm-up/cool-down");
+            return PjLinkResponse::UnavailableTime;
+        }
+
+        let previous_state = self.state.clone();
+        let response = self.handle_mock_command(command);
+        if self.state != previous_state {
+            self.on_state_changed();
+        }
+
+        response
+    }
+
+    fn get_password(&mut self, _connection_id: &u64) -> Option<String> {
+        self.options.password.clone()
+    }
+}
+
+impl PjLinkMockProjector {
+    /// Returns `true` if the connection was killed, or a dropped connection
+    /// should be injected.
+    fn should_drop_connection(&mut self, connection_id: &u64) -> bool {
+        self.last_connection_id = self.last_connection_id.max(*connection_id);
+        if *connection_id < self.drop_connections_below {
+            info!("Dropping killed connection {}", connection_id);
+            return true;
+        }
+
+        let should_drop = rand::thread_rng().gen_bool(self.options.faults.drop_probability);
+        if should_drop {
+            info!("Injecting dropped connection {}", connection_id);
+        }
+        should_drop
+    }
+}
+
+impl PjLinkMockProjector {
+    fn handle_mock_command(&mut self, command: PjLinkCommand) -> PjLinkResponse {
+        match command {
+            // #region Power Control Instruction / POWR
+            PjLinkCommand::Power1(PjLinkPowerCommandParameter::Query) => {
+                info!("Query Power Status");
+                PjLinkResponse::Single(self.state.power_on)
+            }
+            PjLinkCommand::Power1(PjLinkPowerCommandParameter::On) => {
+                info!("Power On Projector");
+                if self.state.power_on != PjLinkPowerCommandStatus::On {
+                    self.start_power_transition(PjLinkPowerCommandStatus::WarmUp, PjLinkPowerCommandStatus::On, self.options.warm_up);
+                }
+                PjLinkResponse::Ok
+            }
+            PjLinkCommand::Power1(PjLinkPowerCommandParameter::Off) => {
+                info!("Power Off Projector");
+                if self.state.power_on != PjLinkPowerCommandStatus::Off {
+                    self.start_power_transition(PjLinkPowerCommandStatus::Cooling, PjLinkPowerCommandStatus::Off, self.options.cool_down);
+                }
+                PjLinkResponse::Ok
+            }
+            // #endregion
+            // #region Input Switch Instruction / INPT
+            PjLinkCommand::Input1(PjLinkInputCommandParameter::Query) | PjLinkCommand::Input2(PjLinkInputCommandParameter::Query) => {
+                info!("Input1|2 Query");
+                PjLinkResponse::Multiple(Vec::from(self.state.input_status))
+            },
+            PjLinkCommand::Input1(input) | PjLinkCommand::Input2(input) => {
+                info!("Input1|2 Set");
+
+                match input {
+                    PjLinkInputCommandParameter::RGB(value) => {
+                        self.state.input_status = [PjLinkInputCommandStatus::RGB, value];
+                    }
+                    PjLinkInputCommandParameter::Video(value) => {
+                        self.state.input_status = [PjLinkInputCommandStatus::Video, value];
+                    }
+                    PjLinkInputCommandParameter::Digital(value) => {
+                        self.state.input_status = [PjLinkInputCommandStatus::Digital, value];
+                    }
+                    PjLinkInputCommandParameter::Storage(value) => {
+                        self.state.input_status = [PjLinkInputCommandStatus::Storage, value];
+                    }
+                    PjLinkInputCommandParameter::Network(value) => {
+                        self.state.input_status = [PjLinkInputCommandStatus::Network, value];
+                    }
+                    PjLinkInputCommandParameter::Internal(value) => {
+                        self.state.input_status = [PjLinkInputCommandStatus::Internal, value];
+                    }
+                    _ => return PjLinkResponse::OutOfParameter
+                };
+
+                PjLinkResponse::Ok
+            },
+            // #endregion
+            // #region Mute Instruction / AVMT
+            PjLinkCommand::AvMute1(PjLinkMuteCommandParameter::Query) => {
+                info!("AV Mute Query");
+                PjLinkResponse::Multiple(Vec::from(self.state.mute_status))
+            }
+            PjLinkCommand::AvMute1(parameter) => {
+                info!("AV Mute Set");
+                let mut mute = PjLinkAvMuteState::from_status(self.state.mute_status);
+                let response = mute.apply(parameter);
+                self.state.mute_status = mute.query();
+
+                response
+            }
+            // #endregion  
+            // #region Error Status Query / ERST
+            PjLinkCommand::ErrorStatus1 => {
+                info!("Error Status Query");
+                PjLinkResponse::Multiple(Vec::from(self.error_status()))
+            }
+            // #endregion
+            // #region Lamp Number/Lighting Hour Query / LAMP
+            PjLinkCommand::Lamp1 => {
+                info!("Lamp Query");
+                let lamps: Vec<String> = self.state.lamps.iter()
+                    .map(|lamp| format!("{} {}", Self::to_hours(lamp.usage_secs), if self.is_lamp_lit(lamp) { '1' } else { '0' }))
+                    .collect();
+                PjLinkResponse::Multiple(Vec::from(lamps.join(" ")))
+            }
+            // #endregion
+            // #region Input Toggling List Query / INST
+            PjLinkCommand::InputTogglingList1 | PjLinkCommand::InputTogglingList2 => {
+                info!("Input Toggling List Query");
+                PjLinkResponse::Multiple(self.state.available_inputs.clone())
+            }
+            // #endregion
+            // #region Projector/Display Name Query / NAME
+            PjLinkCommand::Name1 => {
+                info!("Name Query");
+                PjLinkResponse::Multiple(self.options.projector_name.clone())
+            }
+            // #endregion
+            // #region Manufacture Name Information Query / INF1
+            PjLinkCommand::InfoManufacturer1 => {
+                info!("Info Manufacturer Query");
+                PjLinkResponse::Multiple(self.options.manufacturer_name.clone())
+            }
+            // #endregion
+            // #region Product Name Information Query / INF2
+            PjLinkCommand::InfoProductName1 => {
+                info!("Info Product Name Query");
+                PjLinkResponse::Multiple(self.options.product_name.clone())
+            }
+            // #endregion
+            // #region Other Information Query / INFO
+            PjLinkCommand::InfoOther1 => {
+                info!("Info Other Query");
+                PjLinkResponse::Multiple(vec![])
+            }
+            // #endregion
+            // #region Class Information Query / CLSS
+            PjLinkCommand::Class1 => {
+                info!("Class Information Query");
+                PjLinkResponse::Single(self.options.class_type)
+            }
+            // #endregion
+            // #region Serial Number Query / SNUM
+            PjLinkCommand::SerialNumber2 => {
+                info!("Serial Number Query");
+                PjLinkResponse::Multiple(self.options.serial_number.clone())
+            }
+            // #endregion
+            // #region Software Version Query / SVER
+            PjLinkCommand::SoftwareVersion2 => {
+                info!("Software Version Query");
+                PjLinkResponse::Multiple(self.options.software_version.clone())
+            }
+            // #endregion
+            // #region Input Terminal Name Query / INNM
+            PjLinkCommand::InputTerminalName2(input_type) => {
+                info!("Input Terminal Name Query");
+                match input_type {
+                    PjLinkInputCommandParameter::RGB(input) => PjLinkResponse::Multiple(Vec::from(format!("VGA{}", input as char))),
+                    PjLinkInputCommandParameter::Video(input) => PjLinkResponse::Multiple(Vec::from(format!("Analog{}", input as char))),
+                    PjLinkInputCommandParameter::Digital(input) => PjLinkResponse::Multiple(Vec::from(format!("HDMI{}", input as char))),
+                    PjLinkInputCommandParameter::Network(input) => PjLinkResponse::Multiple(Vec::from(format!("Network{}", input as char))),
+                    PjLinkInputCommandParameter::Storage(input) => PjLinkResponse::Multiple(Vec::from(format!("Storage{}", input as char))),
+                    PjLinkInputCommandParameter::Internal(input) => PjLinkResponse::Multiple(Vec::from(format!("Internal{}", input as char))),
+                    _ => PjLinkResponse::OutOfParameter
+                }
+            }
+            // #endregion
+            // #region Input Resolution Query / IRES
+            PjLinkCommand::InputResolution2 => {
+                info!("Input Resolution Query");
+                PjLinkResponse::Multiple(self.options.screen_resolution.clone())
+            }
+            // #endregion
+            // #region Recommend Resolution Query / RRES
+            PjLinkCommand::RecommendResolution2 => {
+                info!("Recommend Resolution Query");
+                PjLinkResponse::Multiple(self.options.recommended_screen_resolution.clone())
+            }
+            // #endregion
+            // #region Filter Usage Time Query / FILT
+            PjLinkCommand::FilterUsageTime2 => {
+                info!("Filter Usage Time Query");
+                PjLinkResponse::Multiple(Vec::from(Self::to_hours(self.state.filter_usage_secs).to_string()))
+            }
+            // #endregion
+            // #region Lamp Replacement Model Number Query / RLMP
+            PjLinkCommand::LampReplacementModelNumber2 => {
+                info!("Lamp Replacement Model Number Query");
+                PjLinkResponse::Empty
+            }
+            // #endregion
+            // #region Filter Replacement Model Number Query / RFIL
+            PjLinkCommand::FilterReplacementModelNumber2 => {
+                info!("Filter Replacement Model Number Query");
+                PjLinkResponse::Empty
+            }
+            // #endregion
+            // #region Speaker Volume Adjustment Instruction / SVOL
+            PjLinkCommand::SpeakerVolumeAdjustment2(param) => {
+                info!("Speaker Volume Adjustment Set");
+                if let PjLinkVolumeCommandParameter::Unknown = param {
+                    PjLinkResponse::OutOfParameter
+                } else {
+                    PjLinkResponse::Ok
+                }
+            },
+            // #endregion
+            // #region Microphone Volume Adjustment Instruction / MVOL
+            PjLinkCommand::MicrophoneVolumeAdjustment2(param) => {
+                info!("Microphone Volume Adjustment Set");
+                if let PjLinkVolumeCommandParameter::Unknown = param {
+                    PjLinkResponse::OutOfParameter
+                } else {
+                    PjLinkResponse::Ok
+                }
+            }
+            // #endregion
+            // #region Freeze Instruction / FREZ
+            PjLinkCommand::Freeze2(PjLinkFreezeCommandParameter::Query) => {
+                info!("Freeze Instruction Query");
+                PjLinkResponse::Single(self.state.freeze_status)
+            }
+            PjLinkCommand::Freeze2(instruction) => {
+                info!("Freeze Instruction Set");
+                self.state.freeze_status = match instruction {
+                    PjLinkFreezeCommandParameter::Freeze => b'1',
+                    PjLinkFreezeCommandParameter::Unfreeze => b'0',
+                    _ => return PjLinkResponse::OutOfParameter
+                };
+                PjLinkResponse::Ok
+            }
+            // #endregion
+            _ => PjLinkResponse::OutOfParameter
+        }
+    }
+}
\ No newline at end of file
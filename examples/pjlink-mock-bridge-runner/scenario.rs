@@ -0,0 +1,140 @@
+//! Scenario files for the mock projector.
+//!
+//! A scenario describes timed state changes and canned responses, in TOML or
+//! YAML (selected by the `.yaml`/`.yml` extension):
+//!
+//! ```toml
+//! # Lamp error after 60 seconds
+//! [[state_changes]]
+//! after_secs = 60
+//! error_status = "020000"
+//!
+//! # ERR3 for power commands while warming up
+//! [[responses]]
+//! command = "1POWR"
+//! when_power = "3"
+//! response = "ERR3"
+//! ```
+
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use serde::Deserialize;
+use pjlink_bridge::*;
+
+#[derive(Clone, Default, Deserialize)]
+pub struct PjLinkMockScenario {
+    #[serde(default)]
+    pub state_changes: Vec<PjLinkMockStateChange>,
+    #[serde(default)]
+    pub responses: Vec<PjLinkMockCannedResponse>,
+}
+
+/// State change applied `after_secs` seconds after startup. Values are the
+/// same as sent in PJLink responses, like `power = "1"` or `input = "31"`,
+/// except for hours, which are numbers.
+#[derive(Clone, Default, Deserialize)]
+pub struct PjLinkMockStateChange {
+    pub after_secs: f64,
+    pub power: Option<String>,
+    pub input: Option<String>,
+    pub mute: Option<String>,
+    pub freeze: Option<String>,
+    /// ERST value: fan, lamp, temperature, cover open, filter and other
+    pub error_status: Option<String>,
+    /// Sets hours of every lamp
+    pub lamp_hours: Option<u64>,
+    /// Lamps (starting at 1) that failed and won't light. Other lamps are repaired.
+    pub failed_lamps: Option<Vec<usize>>,
+    pub filter_hours: Option<u64>,
+}
+
+/// Response sent instead of the mock's own for matching commands.
+#[derive(Clone, Deserialize)]
+pub struct PjLinkMockCannedResponse {
+    /// Command body with class, like `1POWR`
+    pub command: String,
+    /// Only matches this transmission parameter, if set
+    pub parameter: Option<String>,
+    /// Only matches while the power status is this value, if set
+    pub when_power: Option<String>,
+    /// `OK`, `ERR1` to `ERR4`, or a value
+    pub response: String,
+}
+
+impl PjLinkMockScenario {
+    pub fn load(path: &Path) -> Result<PjLinkMockScenario, String> {
+        let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let is_yaml = matches!(path.extension().and_then(|extension| extension.to_str()), Some("yaml") | Some("yml"));
+
+        let scenario: PjLinkMockScenario = if is_yaml {
+            serde_yaml::from_str(&contents).map_err(|e| e.to_string())?
+        } else {
+            toml::from_str(&contents).map_err(|e| e.to_string())?
+        };
+        scenario.validate()?;
+
+        Ok(scenario)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for change in &self.state_changes {
+            change.validate()?;
+        }
+        for response in &self.responses {
+            if response.command.len() != 5 {
+                return Err(format!("invalid command {:?}, expected a command body with class like 1POWR", response.command));
+            }
+            check_len("when_power", &response.when_power, 1)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the canned response for a command, if any matches.
+    pub fn find_response(&self, raw_command: &PjLinkRawPayload, power: u8) -> Option<PjLinkResponse> {
+        self.responses.iter()
+            .find(|response| {
+                response.command.as_bytes() == raw_command.command_body_with_class
+                    && response.parameter.as_ref().is_none_or(|parameter| parameter.as_bytes() == raw_command.transmission_parameter.as_slice())
+                    && response.when_power.as_ref().is_none_or(|when_power| when_power.as_bytes()[0] == power)
+            })
+            .map(|response| to_response(&response.response))
+    }
+}
+
+impl PjLinkMockStateChange {
+    pub fn after(&self) -> Duration {
+        Duration::from_secs_f64(self.after_secs)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.after_secs.is_finite() || self.after_secs < 0.0 {
+            return Err(format!("invalid after_secs {}", self.after_secs));
+        }
+        check_len("power", &self.power, 1)?;
+        check_len("input", &self.input, 2)?;
+        check_len("mute", &self.mute, 2)?;
+        check_len("freeze", &self.freeze, 1)?;
+        check_len("error_status", &self.error_status, 6)
+    }
+}
+
+fn check_len(name: &str, value: &Option<String>, len: usize) -> Result<(), String> {
+    match value {
+        Some(value) if value.len() != len => Err(format!("invalid {} {:?}, expected {} characters", name, value, len)),
+        _ => Ok(()),
+    }
+}
+
+fn to_response(response: &str) -> PjLinkResponse {
+    match response {
+        "OK" => PjLinkResponse::Ok,
+        "ERR1" => PjLinkResponse::Undefined,
+        "ERR2" => PjLinkResponse::OutOfParameter,
+        "ERR3" => PjLinkResponse::UnavailableTime,
+        "ERR4" => PjLinkResponse::ProjectorOrDisplayFailure,
+        "" => PjLinkResponse::Empty,
+        value => PjLinkResponse::Multiple(Vec::from(value.as_bytes())),
+    }
+}
@@ -0,0 +1,195 @@
+//! Interactive PJLink controller: connects to a projector and sends commands
+//! typed on stdin, like `power on` or `input rgb 2`.
+//!
+//! Run with `cargo run --example pjlink-repl --features client -- 127.0.0.1:4352`.
+
+use pjlink_bridge::*;
+
+use std::io::{self, BufRead, Write};
+use clap::Parser;
+
+const PJLINK_REPL_HELP: &str = "\
+Commands:
+  power <on|off>                        switch power
+  input <rgb|video|digital|storage|network|internal> <number>
+                                        select input, like `input rgb 2`
+  mute <audio|video|all> <on|off>       set AV mute
+  volume <up|down>                      adjust speaker volume (Class 2)
+  status                                query power, input, mute, errors and lamps
+  info                                  query name, manufacturer, product and class
+  %1POWR ?                              send a raw command line
+  help                                  show this message
+  quit                                  close the connection";
+
+#[derive(Parser)]
+#[clap(version = "0.1.0", author = "Mateus Meyer Jiacomelli")]
+struct Opts {
+    /// Projector address
+    #[clap(default_value = "127.0.0.1:4352")]
+    address: String,
+    #[clap(long)]
+    password: Option<String>,
+}
+
+enum PjLinkReplCommand {
+    Send([u8; 5], Vec<u8>),
+    Raw(String),
+    Queries(&'static [(&'static str, [u8; 5])]),
+    Help,
+    Quit,
+}
+
+const PJLINK_REPL_STATUS_QUERIES: &[(&str, [u8; 5])] = &[
+    ("Power", *b"1POWR"),
+    ("Input", *b"1INPT"),
+    ("AV mute", *b"1AVMT"),
+    ("Errors", *b"1ERST"),
+    ("Lamps", *b"1LAMP"),
+];
+
+const PJLINK_REPL_INFO_QUERIES: &[(&str, [u8; 5])] = &[
+    ("Name", *b"1NAME"),
+    ("Manufacturer", *b"1INF1"),
+    ("Product", *b"1INF2"),
+    ("Class", *b"1CLSS"),
+];
+
+impl PjLinkReplCommand {
+    fn parse(line: &str) -> Result<PjLinkReplCommand, String> {
+        let line = line.trim();
+        if line.starts_with(PJLINK_HEADER as char) {
+            return Ok(PjLinkReplCommand::Raw(line.to_string()));
+        }
+
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        let command = match parts.as_slice() {
+            ["power", "on"] => PjLinkReplCommand::Send(*b"1POWR", b"1".to_vec()),
+            ["power", "off"] => PjLinkReplCommand::Send(*b"1POWR", b"0".to_vec()),
+            ["input", input_type, number] => {
+                let input_type = match *input_type {
+                    "rgb" => PjLinkInputCommandStatus::RGB,
+                    "video" => PjLinkInputCommandStatus::Video,
+                    "digital" => PjLinkInputCommandStatus::Digital,
+                    "storage" => PjLinkInputCommandStatus::Storage,
+                    "network" => PjLinkInputCommandStatus::Network,
+                    "internal" => PjLinkInputCommandStatus::Internal,
+                    _ => return Err(format!("unknown input type {:?}", input_type)),
+                };
+                let number = match number.as_bytes() {
+                    [number] if number.is_ascii_alphanumeric() => number.to_ascii_uppercase(),
+                    _ => return Err(format!("invalid input number {:?}", number)),
+                };
+                let command_body_with_class = match number.is_ascii_digit() && input_type != PjLinkInputCommandStatus::Internal {
+                    true => *b"1INPT",
+                    false => *b"2INPT",
+                };
+                PjLinkReplCommand::Send(command_body_with_class, vec![input_type, number])
+            }
+            ["mute", item, state] => {
+                let item = match *item {
+                    "audio" => PjLinkMuteCommandStatus::Audio,
+                    "video" => PjLinkMuteCommandStatus::Video,
+                    "all" => PjLinkMuteCommandStatus::AudioAndVideo,
+                    _ => return Err(format!("unknown mute item {:?}", item)),
+                };
+                let state = match *state {
+                    "on" => PjLinkMuteCommandStatus::Mute,
+                    "off" => PjLinkMuteCommandStatus::NonMute,
+                    _ => return Err(format!("invalid mute state {:?}", state)),
+                };
+                PjLinkReplCommand::Send(*b"1AVMT", vec![item, state])
+            }
+            ["volume", "up"] => PjLinkReplCommand::Send(*b"2SVOL", b"1".to_vec()),
+            ["volume", "down"] => PjLinkReplCommand::Send(*b"2SVOL", b"0".to_vec()),
+            ["status"] => PjLinkReplCommand::Queries(PJLINK_REPL_STATUS_QUERIES),
+            ["info"] => PjLinkReplCommand::Queries(PJLINK_REPL_INFO_QUERIES),
+            ["help"] => PjLinkReplCommand::Help,
+            ["quit"] | ["exit"] => PjLinkReplCommand::Quit,
+            _ => return Err(format!("unknown command {:?}, try `help`", line)),
+        };
+
+        Ok(command)
+    }
+}
+
+/// Describes a response parameter, like `ERR3` or `1` to `POWR`.
+fn describe_response(command_body_with_class: &[u8; 5], parameter: &[u8]) -> String {
+    let description = match parameter {
+        b"OK" => "done",
+        b"ERR1" => "undefined command",
+        b"ERR2" => "out of parameter",
+        b"ERR3" => "unavailable time",
+        b"ERR4" => "projector or display failure",
+        _ => match (&command_body_with_class[1..], parameter) {
+            (b"POWR", [PjLinkPowerCommandStatus::Off]) => "off",
+            (b"POWR", [PjLinkPowerCommandStatus::On]) => "on",
+            (b"POWR", [PjLinkPowerCommandStatus::Cooling]) => "cooling down",
+            (b"POWR", [PjLinkPowerCommandStatus::WarmUp]) => "warming up",
+            (b"AVMT", b"11") => "video muted",
+            (b"AVMT", b"21") => "audio muted",
+            (b"AVMT", b"31") => "audio and video muted",
+            (b"AVMT", b"30") => "not muted",
+            _ => return String::from_utf8_lossy(parameter).into_owned(),
+        },
+    };
+
+    format!("{} ({})", String::from_utf8_lossy(parameter), description)
+}
+
+fn print_response(label: &str, command_body_with_class: [u8; 5], result: Result<PjLinkRawPayload, PjLinkError>) {
+    match result {
+        Ok(response) => println!("{}: {}", label, describe_response(&command_body_with_class, &response.transmission_parameter)),
+        Err(e) => println!("{}: {}", label, e),
+    }
+}
+
+pub fn main() {
+    let opts = Opts::parse();
+
+    let mut client = match PjLinkTestClient::connect(&opts.address, opts.password.as_deref()) {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("Cannot connect to {}: {}", opts.address, e);
+            std::process::exit(1);
+        }
+    };
+    println!("Connected to {}. Type `help` for commands.", opts.address);
+
+    let stdin = io::stdin();
+    loop {
+        print!("pjlink> ");
+        let _ = io::stdout().flush();
+
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("Cannot read stdin: {}", e);
+                break;
+            }
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match PjLinkReplCommand::parse(&line) {
+            Ok(PjLinkReplCommand::Send(command_body_with_class, parameter)) => {
+                let label = String::from_utf8_lossy(&command_body_with_class[1..]).into_owned();
+                print_response(&label, command_body_with_class, client.send_command(command_body_with_class, &parameter));
+            }
+            Ok(PjLinkReplCommand::Raw(line)) => match client.send_raw(line.as_bytes()) {
+                Ok(response) => println!("{}", String::from_utf8_lossy(&response)),
+                Err(e) => println!("{}", e),
+            },
+            Ok(PjLinkReplCommand::Queries(queries)) => {
+                for (label, command_body_with_class) in queries {
+                    print_response(label, *command_body_with_class, client.send_command(*command_body_with_class, &[PJLINK_QUERY]));
+                }
+            }
+            Ok(PjLinkReplCommand::Help) => println!("{}", PJLINK_REPL_HELP),
+            Ok(PjLinkReplCommand::Quit) => break,
+            Err(e) => println!("{}", e),
+        }
+    }
+}
@@ -0,0 +1,220 @@
+//! Procedural macros for `pjlink-bridge`, enabled by its `macros` feature.
+//!
+//! Use them through `pjlink_bridge::pjlink_handler` instead of depending on
+//! this crate directly.
+
+use std::collections::HashSet;
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, ImplItem, ItemImpl};
+
+/// Implements `PjLinkHandler` for the type of an inherent `impl` block,
+/// routing each command to the method with the matching name.
+///
+/// Every method is optional. Commands without a method are answered with
+/// `ERR1`, and commands with a method but an invalid parameter with `ERR2`.
+/// Methods may return anything implementing `PjLinkIntoResponse`, like
+/// `PjLinkResponse`, `()` (`OK`), `u8`, `bool`, `String` or
+/// `Result<T, PjLinkResponse>`.
+///
+/// | Method | Command |
+/// |---|---|
+/// | `password(&mut self, connection_id: &u64) -> Option<String>` | Password, no authentication if missing |
+/// | `power_query(&mut self)` | `%1POWR ?` |
+/// | `set_power(&mut self, on: bool)` | `%1POWR 0` and `%1POWR 1` |
+/// | `input_query(&mut self)` | `%1INPT ?` and `%2INPT ?` |
+/// | `set_input(&mut self, input: PjLinkInputCommandParameter)` | `%1INPT` and `%2INPT` |
+/// | `av_mute_query(&mut self)` | `%1AVMT ?` |
+/// | `set_av_mute(&mut self, mute: PjLinkMuteCommandParameter)` | `%1AVMT` |
+/// | `error_status_query(&mut self)` | `%1ERST ?` |
+/// | `lamp_query(&mut self)` | `%1LAMP ?` |
+/// | `input_list_query(&mut self)` | `%1INST ?` and `%2INST ?` |
+/// | `name_query(&mut self)` | `%1NAME ?` |
+/// | `manufacturer_query(&mut self)` | `%1INF1 ?` |
+/// | `product_query(&mut self)` | `%1INF2 ?` |
+/// | `other_info_query(&mut self)` | `%1INFO ?` |
+/// | `class_query(&mut self)` | `%1CLSS ?` |
+/// | `serial_query(&mut self)` | `%2SNUM ?` |
+/// | `sw_version_query(&mut self)` | `%2SVER ?` |
+/// | `input_name_query(&mut self, input: PjLinkInputCommandParameter)` | `%2INNM ?` |
+/// | `input_resolution_query(&mut self)` | `%2IRES ?` |
+/// | `recommended_resolution_query(&mut self)` | `%2RRES ?` |
+/// | `filter_usage_query(&mut self)` | `%2FILT ?` |
+/// | `lamp_model_query(&mut self)` | `%2RLMP ?` |
+/// | `filter_model_query(&mut self)` | `%2RFIL ?` |
+/// | `speaker_volume(&mut self, increase: bool)` | `%2SVOL` |
+/// | `microphone_volume(&mut self, increase: bool)` | `%2MVOL` |
+/// | `freeze_query(&mut self)` | `%2FREZ ?` |
+/// | `set_freeze(&mut self, freeze: bool)` | `%2FREZ 0` and `%2FREZ 1` |
+/// | `handle_unmapped(&mut self, command: PjLinkCommand, raw_command: &PjLinkRawPayload, connection_id: &u64)` | Any other command |
+///
+/// ## Examples
+/// ```ignore
+/// use pjlink_bridge::*;
+///
+/// struct Projector {
+///     power: u8,
+/// }
+///
+/// #[pjlink_handler]
+/// impl Projector {
+///     fn power_query(&mut self) -> u8 {
+///         self.power
+///     }
+///
+///     fn set_power(&mut self, on: bool) {
+///         self.power = if on { PjLinkPowerCommandStatus::On } else { PjLinkPowerCommandStatus::Off };
+///     }
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn pjlink_handler(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let item_impl = parse_macro_input!(item as ItemImpl);
+
+    if let Some((_, trait_path, _)) = &item_impl.trait_ {
+        return syn::Error::new_spanned(trait_path, "#[pjlink_handler] must be used on an inherent impl block")
+            .to_compile_error()
+            .into();
+    }
+
+    let methods: HashSet<String> = item_impl.items.iter()
+        .filter_map(|item| match item {
+            ImplItem::Fn(method) => Some(method.sig.ident.to_string()),
+            _ => None,
+        })
+        .collect();
+
+    let self_ty = &item_impl.self_ty;
+    let (impl_generics, _, where_clause) = item_impl.generics.split_for_impl();
+    let password = match methods.contains("password") {
+        true => quote! { self.password(connection_id) },
+        false => quote! { let _ = connection_id; ::std::option::Option::None },
+    };
+    let arms = command_arms(&methods);
+    let fallback = match methods.contains("handle_unmapped") {
+        true => quote! { command => self.handle_unmapped(command, raw_command, connection_id), },
+        false => quote! { _ => { let _ = (raw_command, connection_id); PjLinkResponse::Undefined } },
+    };
+
+    let expanded = quote! {
+        #item_impl
+
+        impl #impl_generics ::pjlink_bridge::PjLinkHandler for #self_ty #where_clause {
+            fn get_password(&mut self, connection_id: &u64) -> ::std::option::Option<::std::string::String> {
+                #password
+            }
+
+            #[allow(unreachable_patterns)]
+            fn handle_command(
+                &mut self,
+                command: ::pjlink_bridge::PjLinkCommand,
+                raw_command: &::pjlink_bridge::PjLinkRawPayload,
+                connection_id: &u64,
+            ) -> ::pjlink_bridge::PjLinkResponse {
+                #[allow(unused_imports)]
+                use ::pjlink_bridge::{
+                    PjLinkCommand, PjLinkFreezeCommandParameter, PjLinkInputCommandParameter, PjLinkIntoResponse,
+                    PjLinkMuteCommandParameter, PjLinkPowerCommandParameter, PjLinkResponse, PjLinkVolumeCommandParameter,
+                };
+
+                match command {
+                    #(#arms)*
+                    #fallback
+                }
+            }
+        }
+    };
+
+    expanded.into()
+}
+
+fn command_arms(methods: &HashSet<String>) -> Vec<TokenStream2> {
+    let has = |name: &str| methods.contains(name);
+    let mut arms = Vec::new();
+
+    let queries: [(&str, TokenStream2); 19] = [
+        ("power_query", quote! { PjLinkCommand::Power1(PjLinkPowerCommandParameter::Query) }),
+        ("input_query", quote! {
+            PjLinkCommand::Input1(PjLinkInputCommandParameter::Query) | PjLinkCommand::Input2(PjLinkInputCommandParameter::Query)
+        }),
+        ("av_mute_query", quote! { PjLinkCommand::AvMute1(PjLinkMuteCommandParameter::Query) }),
+        ("error_status_query", quote! { PjLinkCommand::ErrorStatus1 }),
+        ("lamp_query", quote! { PjLinkCommand::Lamp1 }),
+        ("input_list_query", quote! { PjLinkCommand::InputTogglingList1 | PjLinkCommand::InputTogglingList2 }),
+        ("name_query", quote! { PjLinkCommand::Name1 }),
+        ("manufacturer_query", quote! { PjLinkCommand::InfoManufacturer1 }),
+        ("product_query", quote! { PjLinkCommand::InfoProductName1 }),
+        ("other_info_query", quote! { PjLinkCommand::InfoOther1 }),
+        ("class_query", quote! { PjLinkCommand::Class1 }),
+        ("serial_query", quote! { PjLinkCommand::SerialNumber2 }),
+        ("sw_version_query", quote! { PjLinkCommand::SoftwareVersion2 }),
+        ("input_resolution_query", quote! { PjLinkCommand::InputResolution2 }),
+        ("recommended_resolution_query", quote! { PjLinkCommand::RecommendResolution2 }),
+        ("filter_usage_query", quote! { PjLinkCommand::FilterUsageTime2 }),
+        ("lamp_model_query", quote! { PjLinkCommand::LampReplacementModelNumber2 }),
+        ("filter_model_query", quote! { PjLinkCommand::FilterReplacementModelNumber2 }),
+        ("freeze_query", quote! { PjLinkCommand::Freeze2(PjLinkFreezeCommandParameter::Query) }),
+    ];
+
+    for (name, pattern) in queries.iter() {
+        if has(name) {
+            let method = syn::Ident::new(name, proc_macro2::Span::call_site());
+            arms.push(quote! { #pattern => self.#method().into_response(), });
+        }
+    }
+
+    if has("set_power") {
+        arms.push(quote! {
+            PjLinkCommand::Power1(PjLinkPowerCommandParameter::On) => self.set_power(true).into_response(),
+            PjLinkCommand::Power1(PjLinkPowerCommandParameter::Off) => self.set_power(false).into_response(),
+            PjLinkCommand::Power1(PjLinkPowerCommandParameter::Unknown) => PjLinkResponse::OutOfParameter,
+        });
+    }
+    if has("set_input") {
+        arms.push(quote! {
+            PjLinkCommand::Input1(PjLinkInputCommandParameter::Query)
+            | PjLinkCommand::Input2(PjLinkInputCommandParameter::Query) => PjLinkResponse::Undefined,
+            PjLinkCommand::Input1(PjLinkInputCommandParameter::Unknown)
+            | PjLinkCommand::Input2(PjLinkInputCommandParameter::Unknown) => PjLinkResponse::OutOfParameter,
+            PjLinkCommand::Input1(input) | PjLinkCommand::Input2(input) => self.set_input(input).into_response(),
+        });
+    }
+    if has("set_av_mute") {
+        arms.push(quote! {
+            PjLinkCommand::AvMute1(PjLinkMuteCommandParameter::Query) => PjLinkResponse::Undefined,
+            PjLinkCommand::AvMute1(PjLinkMuteCommandParameter::Unknown) => PjLinkResponse::OutOfParameter,
+            PjLinkCommand::AvMute1(mute) => self.set_av_mute(mute).into_response(),
+        });
+    }
+    if has("input_name_query") {
+        arms.push(quote! {
+            PjLinkCommand::InputTerminalName2(PjLinkInputCommandParameter::Query)
+            | PjLinkCommand::InputTerminalName2(PjLinkInputCommandParameter::Unknown) => PjLinkResponse::OutOfParameter,
+            PjLinkCommand::InputTerminalName2(input) => self.input_name_query(input).into_response(),
+        });
+    }
+    if has("speaker_volume") {
+        arms.push(quote! {
+            PjLinkCommand::SpeakerVolumeAdjustment2(PjLinkVolumeCommandParameter::Increase) => self.speaker_volume(true).into_response(),
+            PjLinkCommand::SpeakerVolumeAdjustment2(PjLinkVolumeCommandParameter::Decrase) => self.speaker_volume(false).into_response(),
+            PjLinkCommand::SpeakerVolumeAdjustment2(PjLinkVolumeCommandParameter::Unknown) => PjLinkResponse::OutOfParameter,
+        });
+    }
+    if has("microphone_volume") {
+        arms.push(quote! {
+            PjLinkCommand::MicrophoneVolumeAdjustment2(PjLinkVolumeCommandParameter::Increase) => self.microphone_volume(true).into_response(),
+            PjLinkCommand::MicrophoneVolumeAdjustment2(PjLinkVolumeCommandParameter::Decrase) => self.microphone_volume(false).into_response(),
+            PjLinkCommand::MicrophoneVolumeAdjustment2(PjLinkVolumeCommandParameter::Unknown) => PjLinkResponse::OutOfParameter,
+        });
+    }
+    if has("set_freeze") {
+        arms.push(quote! {
+            PjLinkCommand::Freeze2(PjLinkFreezeCommandParameter::Freeze) => self.set_freeze(true).into_response(),
+            PjLinkCommand::Freeze2(PjLinkFreezeCommandParameter::Unfreeze) => self.set_freeze(false).into_response(),
+            PjLinkCommand::Freeze2(PjLinkFreezeCommandParameter::Unknown) => PjLinkResponse::OutOfParameter,
+        });
+    }
+
+    arms
+}
@@ -0,0 +1,118 @@
+//! Sockets passed by a service manager (Unix only).
+
+use std::env;
+use std::io;
+use std::net::{TcpListener, UdpSocket};
+use std::os::unix::io::{FromRawFd, RawFd};
+use socket2::{Socket, Type};
+
+use crate::PjLinkError;
+
+/// First file descriptor passed by socket activation, `SD_LISTEN_FDS_START`.
+const PJLINK_LISTEN_FDS_START: RawFd = 3;
+
+/// Sockets passed to the process by systemd socket activation, or by
+/// tools following the same protocol, like `systemfd`.
+///
+/// Serve them with [PjLinkServer::from_listeners](crate::PjLinkServer::from_listeners).
+/// The service manager keeps the sockets open while the process restarts,
+/// so controllers don't see the port closed.
+///
+/// A matching systemd socket unit listens on TCP and UDP port 4352:
+/// ```ini
+/// [Socket]
+/// ListenStream=4352
+/// ListenDatagram=4352
+/// ```
+///
+/// ## Examples
+/// ```no_run
+/// use std::sync::{Arc, Mutex};
+/// use pjlink_bridge::*;
+///
+/// # fn example(projector: Arc<Mutex<dyn PjLinkHandler>>) {
+/// let handle = match PjLinkActivatedSockets::from_env().unwrap() {
+///     Some(sockets) => PjLinkServer::from_listeners(projector, sockets.tcp_listener, sockets.udp_socket).unwrap(),
+///     None => PjLinkServer::listen_tcp_udp(projector, "0.0.0.0".into(), "0.0.0.0".into(), "4352".into()).unwrap(),
+/// };
+/// handle.join().unwrap();
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct PjLinkActivatedSockets {
+    /// First passed stream socket
+    pub tcp_listener: TcpListener,
+    /// First passed datagram socket, if any
+    pub udp_socket: Option<UdpSocket>,
+}
+
+impl PjLinkActivatedSockets {
+    /// Takes the sockets listed by the `LISTEN_FDS` and `LISTEN_PID`
+    /// environment variables, and removes the variables so child processes
+    /// don't take them again.
+    ///
+    /// Returns `None` if no sockets were passed to this process. Fails if
+    /// the passed sockets include no stream socket.
+    ///
+    /// Must be called once; later calls return `None`.
+    pub fn from_env() -> Result<Option<PjLinkActivatedSockets>, PjLinkError> {
+        let fd_count = listen_fd_count(
+            env::var("LISTEN_PID").ok().as_deref(),
+            env::var("LISTEN_FDS").ok().as_deref(),
+            std::process::id(),
+        );
+        env::remove_var("LISTEN_PID");
+        env::remove_var("LISTEN_FDS");
+        env::remove_var("LISTEN_FDNAMES");
+
+        let fd_count = match fd_count {
+            Some(fd_count) => fd_count,
+            None => return Ok(Option::None),
+        };
+
+        let mut tcp_listener = Option::None;
+        let mut udp_socket = Option::None;
+
+        for fd in PJLINK_LISTEN_FDS_START..PJLINK_LISTEN_FDS_START + fd_count {
+            // SAFETY: the service manager passes these descriptors to this
+            // process only, and the environment variables naming them were
+            // removed above, so they are taken once.
+            let socket = unsafe { Socket::from_raw_fd(fd) };
+
+            match socket.r#type()? {
+                Type::STREAM if tcp_listener.is_none() => tcp_listener = Option::Some(TcpListener::from(socket)),
+                Type::DGRAM if udp_socket.is_none() => udp_socket = Option::Some(UdpSocket::from(socket)),
+                _ => {}
+            }
+        }
+
+        match tcp_listener {
+            Some(tcp_listener) => Ok(Option::Some(PjLinkActivatedSockets { tcp_listener, udp_socket })),
+            None => Err(PjLinkError::Io(io::Error::new(io::ErrorKind::NotFound, "no stream socket passed in LISTEN_FDS"))),
+        }
+    }
+}
+
+/// Returns the number of passed descriptors, if they're meant for the
+/// process `pid`.
+fn listen_fd_count(listen_pid: Option<&str>, listen_fds: Option<&str>, pid: u32) -> Option<RawFd> {
+    if listen_pid?.parse::<u32>().ok()? != pid {
+        return Option::None;
+    }
+
+    listen_fds?.parse::<RawFd>().ok().filter(|fd_count| *fd_count > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_counts_descriptors_meant_for_this_process() {
+        assert_eq!(listen_fd_count(Some("42"), Some("2"), 42), Some(2));
+        assert_eq!(listen_fd_count(Some("41"), Some("2"), 42), None);
+        assert_eq!(listen_fd_count(None, Some("2"), 42), None);
+        assert_eq!(listen_fd_count(Some("42"), Some("0"), 42), None);
+        assert_eq!(listen_fd_count(Some("42"), Some("two"), 42), None);
+    }
+}
@@ -0,0 +1,484 @@
+//! PJLink authentication helpers.
+
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::PjLinkIpNetwork;
+
+/// Maximum PJLink password length, in bytes.
+///
+/// PJLink specification limits passwords to 32 alphanumeric characters.
+pub const PJLINK_PASSWORD_MAX_LENGTH: usize = 32;
+
+/// A password that complies with PJLink specification constraints.
+///
+/// PJLink passwords must have between 1 and 32 ASCII alphanumeric characters.
+/// Using a [PjLinkPassword](self::PjLinkPassword) ensures invalid passwords
+/// are rejected at configuration time, instead of producing digests that
+/// controllers will never match.
+///
+/// ## Examples
+/// ```
+/// use pjlink_bridge::*;
+///
+/// let password = PjLinkPassword::new("JBMIAProjectorLink").unwrap();
+/// assert_eq!(password.as_str(), "JBMIAProjectorLink");
+///
+/// assert!(PjLinkPassword::new("not valid!").is_err());
+/// ```
+#[derive(Clone, PartialEq, Eq)]
+pub struct PjLinkPassword(String);
+
+impl PjLinkPassword {
+    /// Validates and creates a new [PjLinkPassword](self::PjLinkPassword).
+    ///
+    /// **Arguments**:
+    /// * `password`: Password string. Must have between 1 and
+    ///   [PJLINK_PASSWORD_MAX_LENGTH](self::PJLINK_PASSWORD_MAX_LENGTH)
+    ///   ASCII alphanumeric characters.
+    pub fn new<S: Into<String>>(password: S) -> Result<PjLinkPassword, PjLinkPasswordError> {
+        let password = password.into();
+
+        if password.is_empty() {
+            return Err(PjLinkPasswordError::Empty);
+        }
+
+        if let Some((position, character)) = password
+            .chars()
+            .enumerate()
+            .find(|(_, character)| !character.is_ascii_alphanumeric())
+        {
+            return Err(PjLinkPasswordError::InvalidCharacter { position, character });
+        }
+
+        if password.len() > PJLINK_PASSWORD_MAX_LENGTH {
+            return Err(PjLinkPasswordError::TooLong(password.len()));
+        }
+
+        Ok(PjLinkPassword(password))
+    }
+
+    /// Returns the password as a string slice.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for PjLinkPassword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PjLinkPassword(***)")
+    }
+}
+
+impl FromStr for PjLinkPassword {
+    type Err = PjLinkPasswordError;
+
+    fn from_str(password: &str) -> Result<Self, Self::Err> {
+        PjLinkPassword::new(password)
+    }
+}
+
+impl TryFrom<String> for PjLinkPassword {
+    type Error = PjLinkPasswordError;
+
+    fn try_from(password: String) -> Result<Self, Self::Error> {
+        PjLinkPassword::new(password)
+    }
+}
+
+impl From<PjLinkPassword> for String {
+    fn from(password: PjLinkPassword) -> Self {
+        password.0
+    }
+}
+
+/// Reasons a password is rejected by [PjLinkPassword::new](self::PjLinkPassword::new).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PjLinkPasswordError {
+    /// Password has no characters. Use no password at all to disable
+    /// authentication instead.
+    Empty,
+    /// Password is longer than [PJLINK_PASSWORD_MAX_LENGTH](self::PJLINK_PASSWORD_MAX_LENGTH).
+    /// Contains the received length, in bytes.
+    TooLong(usize),
+    /// Password contains a character that isn't ASCII alphanumeric.
+    InvalidCharacter {
+        /// Character position (zero-based)
+        position: usize,
+        /// The offending character
+        character: char,
+    },
+}
+
+impl fmt::Display for PjLinkPasswordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PjLinkPasswordError::Empty => write!(f, "PJLink password cannot be empty"),
+            PjLinkPasswordError::TooLong(length) => write!(
+                f,
+                "PJLink password has {} characters, maximum is {}",
+                length,
+                PJLINK_PASSWORD_MAX_LENGTH
+            ),
+            PjLinkPasswordError::InvalidCharacter { position, character } => write!(
+                f,
+                "PJLink password contains invalid character {:?} at position {}; only ASCII alphanumeric characters are allowed",
+                character,
+                position
+            ),
+        }
+    }
+}
+
+impl Error for PjLinkPasswordError {}
+
+/// Source of passwords for incoming connections.
+///
+/// When set on [PjLinkListenerOptions](crate::PjLinkListenerOptions), it's
+/// used instead of [PjLinkHandler::get_password](crate::PjLinkHandler::get_password).
+/// It's consulted once per connection, right before sending the security
+/// header, so implementations may change the returned password at any time.
+pub trait PjLinkPasswordProvider: Send + Sync {
+    /// Returns the password for a new connection, or `None` to disable
+    /// authentication for it.
+    fn get_password(&self, connection_id: &u64) -> Option<PjLinkPassword>;
+
+    /// Returns the password for a new connection from `peer_addr`, so
+    /// controllers can authenticate with different passwords. `peer_addr`
+    /// is `None` for transports without addresses, like Unix sockets.
+    ///
+    /// Calls [get_password](self::PjLinkPasswordProvider::get_password) by
+    /// default.
+    fn get_password_for_peer(&self, connection_id: &u64, _peer_addr: Option<&SocketAddr>) -> Option<PjLinkPassword> {
+        self.get_password(connection_id)
+    }
+
+    /// Returns the read-only password for a new connection from
+    /// `peer_addr`, if any. Controllers authenticating with it can only
+    /// send queries: the listener answers set commands with `ERR2`.
+    ///
+    /// A connection with only a read-only password still requires
+    /// authentication, and can't get full control. `None` by default.
+    fn get_read_only_password_for_peer(&self, _connection_id: &u64, _peer_addr: Option<&SocketAddr>) -> Option<PjLinkPassword> {
+        Option::None
+    }
+
+    /// Current session generation.
+    ///
+    /// Connections remember the generation seen when they started, and are
+    /// closed once this value changes. The default implementation never
+    /// terminates sessions.
+    fn session_generation(&self) -> u64 {
+        0
+    }
+}
+
+/// A [PjLinkPasswordProvider](self::PjLinkPasswordProvider) whose password
+/// can be replaced while the server is running.
+///
+/// ## Examples
+/// ```
+/// use pjlink_bridge::*;
+///
+/// let provider = PjLinkSwappablePassword::new(None);
+/// provider.set_password(Some(PjLinkPassword::new("first").unwrap()));
+///
+/// // New connections use "second", and open sessions are closed.
+/// provider.set_password_and_terminate_sessions(Some(PjLinkPassword::new("second").unwrap()));
+/// ```
+pub struct PjLinkSwappablePassword {
+    password: RwLock<Option<PjLinkPassword>>,
+    session_generation: AtomicU64,
+}
+
+impl PjLinkSwappablePassword {
+    /// Creates a new provider with the initial password (`None` disables
+    /// authentication).
+    pub fn new(password: Option<PjLinkPassword>) -> PjLinkSwappablePassword {
+        PjLinkSwappablePassword {
+            password: RwLock::new(password),
+            session_generation: AtomicU64::new(0),
+        }
+    }
+
+    /// Replaces the password used by new connections. Open sessions are kept.
+    pub fn set_password(&self, password: Option<PjLinkPassword>) {
+        match self.password.write() {
+            Ok(mut current) => *current = password,
+            Err(poisoned) => *poisoned.into_inner() = password,
+        }
+    }
+
+    /// Replaces the password used by new connections and closes all open
+    /// sessions, forcing controllers to authenticate again.
+    pub fn set_password_and_terminate_sessions(&self, password: Option<PjLinkPassword>) {
+        self.set_password(password);
+        self.session_generation.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+impl PjLinkPasswordProvider for PjLinkSwappablePassword {
+    fn get_password(&self, _connection_id: &u64) -> Option<PjLinkPassword> {
+        match self.password.read() {
+            Ok(password) => password.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    fn session_generation(&self) -> u64 {
+        self.session_generation.load(Ordering::SeqCst)
+    }
+}
+
+/// A [PjLinkPasswordProvider](self::PjLinkPasswordProvider) choosing the
+/// password by the controller address, like one for the AV booth and
+/// another for the building management system.
+///
+/// Networks are checked in the order they were added, and the first one
+/// containing the controller address sets its password. Other controllers,
+/// and connections without address, use the default password.
+///
+/// ## Examples
+/// ```
+/// use pjlink_bridge::*;
+///
+/// let mut provider = PjLinkPerClientPassword::new(Some(PjLinkPassword::new("default").unwrap()));
+/// provider
+///     .add("10.0.5.0/24".parse().unwrap(), Some(PjLinkPassword::new("booth").unwrap()))
+///     .add("127.0.0.1".parse().unwrap(), Some(PjLinkPassword::new("tooling").unwrap()));
+///
+/// let booth = "10.0.5.20:50000".parse().unwrap();
+/// assert_eq!(provider.get_password_for_peer(&0, Some(&booth)).unwrap().as_str(), "booth");
+/// assert_eq!(provider.get_password_for_peer(&0, None).unwrap().as_str(), "default");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PjLinkPerClientPassword {
+    default_password: Option<PjLinkPassword>,
+    networks: Vec<(PjLinkIpNetwork, Option<PjLinkPassword>)>,
+}
+
+impl PjLinkPerClientPassword {
+    /// Creates a new provider with the password of controllers outside all
+    /// networks (`None` disables authentication for them).
+    pub fn new(default_password: Option<PjLinkPassword>) -> PjLinkPerClientPassword {
+        PjLinkPerClientPassword { default_password, networks: Vec::new() }
+    }
+
+    /// Sets the password of controllers in `network` (`None` disables
+    /// authentication for them).
+    pub fn add(&mut self, network: PjLinkIpNetwork, password: Option<PjLinkPassword>) -> &mut PjLinkPerClientPassword {
+        self.networks.push((network, password));
+        self
+    }
+}
+
+impl PjLinkPasswordProvider for PjLinkPerClientPassword {
+    fn get_password(&self, _connection_id: &u64) -> Option<PjLinkPassword> {
+        self.default_password.clone()
+    }
+
+    fn get_password_for_peer(&self, connection_id: &u64, peer_addr: Option<&SocketAddr>) -> Option<PjLinkPassword> {
+        let network_password = peer_addr.and_then(|peer_addr| {
+            self.networks.iter().find(|(network, _)| network.contains(&peer_addr.ip()))
+        });
+
+        match network_password {
+            Some((_, password)) => password.clone(),
+            None => self.get_password(connection_id),
+        }
+    }
+}
+
+/// A [PjLinkPasswordProvider](self::PjLinkPasswordProvider) with two
+/// passwords: controllers authenticating with the control password can
+/// send any command, and controllers authenticating with the read-only
+/// password only queries.
+///
+/// ## Examples
+#[cfg_attr(feature = "server", doc = "```")]
+#[cfg_attr(not(feature = "server"), doc = "```ignore")]
+/// use std::sync::Arc;
+/// use pjlink_bridge::*;
+///
+/// let provider = PjLinkTieredPassword::new(
+///     PjLinkPassword::new("operator").unwrap(),
+///     PjLinkPassword::new("monitoring").unwrap(),
+/// );
+///
+/// let options = PjLinkListenerOptions {
+///     password_provider: Some(Arc::new(provider)),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone)]
+pub struct PjLinkTieredPassword {
+    control_password: PjLinkPassword,
+    read_only_password: PjLinkPassword,
+}
+
+impl PjLinkTieredPassword {
+    /// **Arguments**:
+    /// * `control_password`: Password allowing every command
+    /// * `read_only_password`: Password allowing only queries
+    pub fn new(control_password: PjLinkPassword, read_only_password: PjLinkPassword) -> PjLinkTieredPassword {
+        PjLinkTieredPassword { control_password, read_only_password }
+    }
+}
+
+impl PjLinkPasswordProvider for PjLinkTieredPassword {
+    fn get_password(&self, _connection_id: &u64) -> Option<PjLinkPassword> {
+        Option::Some(self.control_password.clone())
+    }
+
+    fn get_read_only_password_for_peer(&self, _connection_id: &u64, _peer_addr: Option<&SocketAddr>) -> Option<PjLinkPassword> {
+        Option::Some(self.read_only_password.clone())
+    }
+}
+
+/// Result of an authentication attempt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PjLinkAuthOutcome {
+    /// Controller sent the right password digest.
+    Accepted,
+    /// Controller sent the digest of the read-only password. See
+    /// [PjLinkPasswordProvider::get_read_only_password_for_peer](self::PjLinkPasswordProvider::get_read_only_password_for_peer).
+    AcceptedReadOnly,
+    /// Controller sent a wrong password digest.
+    Denied,
+    /// Controller's first message is too short to contain a password digest.
+    Malformed,
+}
+
+impl PjLinkAuthOutcome {
+    /// Returns `true` if the controller was authenticated.
+    pub fn is_accepted(&self) -> bool {
+        matches!(self, PjLinkAuthOutcome::Accepted | PjLinkAuthOutcome::AcceptedReadOnly)
+    }
+}
+
+/// Authentication attempt, as reported to
+/// [PjLinkHandler::on_auth_attempt](crate::PjLinkHandler::on_auth_attempt).
+#[derive(Debug, Clone)]
+pub struct PjLinkAuthAttempt {
+    /// Connection ID
+    pub connection_id: u64,
+    /// Controller address, if it's still known
+    pub peer_addr: Option<SocketAddr>,
+    /// Attempt result
+    pub outcome: PjLinkAuthOutcome,
+}
+
+/// Re-authentication policy for long-lived sessions.
+///
+/// PJLink authenticates a controller only once per connection. With this
+/// policy, authenticated sessions are closed once they get too old or
+/// handled too many commands, forcing the controller to reconnect and go
+/// through the password challenge again. Sessions without authentication
+/// are not affected.
+///
+/// ## Examples
+/// ```
+/// use std::time::Duration;
+/// use pjlink_bridge::*;
+///
+/// let policy = PjLinkReauthPolicy {
+///     max_session_duration: Some(Duration::from_secs(8 * 60 * 60)),
+///     max_commands: Some(10_000),
+/// };
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PjLinkReauthPolicy {
+    /// Maximum time since authentication. `None` means unlimited.
+    pub max_session_duration: Option<Duration>,
+    /// Maximum commands handled since authentication. `None` means unlimited.
+    pub max_commands: Option<u64>,
+}
+
+impl PjLinkReauthPolicy {
+    /// Returns `true` if a session authenticated at `authenticated_at`,
+    /// that handled `commands` commands since then, must authenticate again.
+    pub fn is_expired(&self, authenticated_at: Instant, commands: u64) -> bool {
+        self.is_expired_at(authenticated_at, commands, Instant::now())
+    }
+
+    /// Same as [is_expired](self::PjLinkReauthPolicy::is_expired), at `now`.
+    pub fn is_expired_at(&self, authenticated_at: Instant, commands: u64, now: Instant) -> bool {
+        let is_too_old = self.max_session_duration
+            .is_some_and(|max_duration| now.saturating_duration_since(authenticated_at) >= max_duration);
+        let has_too_many_commands = self.max_commands
+            .is_some_and(|max_commands| commands >= max_commands);
+
+        is_too_old || has_too_many_commands
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_accepts_alphanumeric_password_up_to_max_length() {
+        let password = "a".repeat(PJLINK_PASSWORD_MAX_LENGTH);
+        assert_eq!(PjLinkPassword::new(password.clone()).unwrap().as_str(), password);
+    }
+
+    #[test]
+    fn it_rejects_empty_long_and_non_alphanumeric_passwords() {
+        assert_eq!(PjLinkPassword::new(""), Err(PjLinkPasswordError::Empty));
+        assert_eq!(
+            PjLinkPassword::new("a".repeat(PJLINK_PASSWORD_MAX_LENGTH + 1)),
+            Err(PjLinkPasswordError::TooLong(PJLINK_PASSWORD_MAX_LENGTH + 1))
+        );
+        assert_eq!(
+            PjLinkPassword::new("pass word"),
+            Err(PjLinkPasswordError::InvalidCharacter { position: 4, character: ' ' })
+        );
+    }
+
+    #[test]
+    fn it_swaps_password_and_bumps_generation_only_when_terminating() {
+        let provider = PjLinkSwappablePassword::new(None);
+        assert_eq!(provider.get_password(&0), None);
+
+        provider.set_password(Some(PjLinkPassword::new("first").unwrap()));
+        assert_eq!(provider.get_password(&0).unwrap().as_str(), "first");
+        assert_eq!(provider.session_generation(), 0);
+
+        provider.set_password_and_terminate_sessions(None);
+        assert_eq!(provider.get_password(&0), None);
+        assert_eq!(provider.session_generation(), 1);
+    }
+
+    #[test]
+    fn it_chooses_password_by_first_matching_network() {
+        let mut provider = PjLinkPerClientPassword::new(Some(PjLinkPassword::new("default").unwrap()));
+        provider
+            .add("127.0.0.1".parse().unwrap(), None)
+            .add("10.0.0.0/8".parse().unwrap(), Some(PjLinkPassword::new("lan").unwrap()))
+            .add("10.0.5.0/24".parse().unwrap(), Some(PjLinkPassword::new("booth").unwrap()));
+
+        let password_for = |peer_addr: &str| provider.get_password_for_peer(&0, Some(&peer_addr.parse().unwrap()));
+        assert_eq!(password_for("127.0.0.1:1000"), None);
+        assert_eq!(password_for("10.0.5.20:1000").unwrap().as_str(), "lan");
+        assert_eq!(password_for("192.168.0.1:1000").unwrap().as_str(), "default");
+    }
+
+    #[test]
+    fn it_expires_sessions_by_command_count_and_duration() {
+        let now = Instant::now();
+        assert!(!PjLinkReauthPolicy::default().is_expired(now, u64::MAX));
+
+        let by_commands = PjLinkReauthPolicy { max_commands: Some(2), ..Default::default() };
+        assert!(!by_commands.is_expired(now, 1));
+        assert!(by_commands.is_expired(now, 2));
+
+        let by_duration = PjLinkReauthPolicy { max_session_duration: Some(Duration::from_secs(0)), ..Default::default() };
+        assert!(by_duration.is_expired(now, 0));
+    }
+}
@@ -0,0 +1,282 @@
+//! Recording of PJLink sessions, to reproduce interoperability problems
+//! offline.
+
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::{PjLinkCaptureDirection, PjLinkError};
+
+/// First bytes of [Binary](self::PjLinkCaptureFormat::Binary) captures.
+pub const PJLINK_CAPTURE_BINARY_MAGIC: &[u8; 8] = b"PJLKCAP1";
+
+/// File format of a [PjLinkSessionCapture](self::PjLinkSessionCapture).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PjLinkCaptureFormat {
+    /// One JSON object per line, like:
+    /// ```json
+    /// {"timestamp_us":1700000000000000,"connection_id":1,"peer":"192.168.0.5:50312","direction":"received","data":"%1POWR ?"}
+    /// ```
+    /// `peer` is `null` for transports without peer address. `data` holds
+    /// each byte as the character with the same code point (Latin-1), so
+    /// invalid UTF-8 is kept.
+    #[default]
+    JsonLines,
+    /// [PJLINK_CAPTURE_BINARY_MAGIC](self::PJLINK_CAPTURE_BINARY_MAGIC),
+    /// then each record as big-endian fields: timestamp in microseconds
+    /// (`u64`), connection ID (`u64`), direction (`u8`, 0 received and 1
+    /// sent), peer address length (`u8`) and text, data length (`u32`) and
+    /// data.
+    Binary,
+}
+
+/// Record of a [JsonLines](self::PjLinkCaptureFormat::JsonLines) capture.
+#[derive(Serialize, Deserialize)]
+struct PjLinkJsonCaptureRecord {
+    timestamp_us: u64,
+    connection_id: u64,
+    peer: Option<String>,
+    direction: String,
+    data: String,
+}
+
+/// Data received or sent on a connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PjLinkCaptureRecord {
+    /// Microseconds since the Unix epoch
+    pub timestamp_us: u64,
+    pub connection_id: u64,
+    pub peer_addr: Option<SocketAddr>,
+    pub direction: PjLinkCaptureDirection,
+    pub data: Vec<u8>,
+}
+
+impl PjLinkCaptureRecord {
+    /// Returns the record encoded as `format`, with the line terminator of
+    /// [JsonLines](self::PjLinkCaptureFormat::JsonLines).
+    pub fn encode(&self, format: PjLinkCaptureFormat) -> Vec<u8> {
+        match format {
+            PjLinkCaptureFormat::JsonLines => {
+                let direction = match self.direction {
+                    PjLinkCaptureDirection::Received => "received",
+                    PjLinkCaptureDirection::Sent => "sent",
+                };
+                let record = PjLinkJsonCaptureRecord {
+                    timestamp_us: self.timestamp_us,
+                    connection_id: self.connection_id,
+                    peer: self.peer_addr.map(|peer_addr| peer_addr.to_string()),
+                    direction: String::from(direction),
+                    data: self.data.iter().map(|byte| *byte as char).collect(),
+                };
+
+                // Numbers and strings always serialize
+                let mut encoded = serde_json::to_vec(&record).unwrap_or_default();
+                encoded.push(b'\n');
+                encoded
+            }
+            PjLinkCaptureFormat::Binary => {
+                let peer = self.peer_addr.map(|peer_addr| peer_addr.to_string()).unwrap_or_default();
+                let peer = &peer[..peer.len().min(u8::MAX as usize)];
+                let mut encoded = Vec::with_capacity(22 + peer.len() + self.data.len());
+
+                encoded.extend_from_slice(&self.timestamp_us.to_be_bytes());
+                encoded.extend_from_slice(&self.connection_id.to_be_bytes());
+                encoded.push(match self.direction {
+                    PjLinkCaptureDirection::Received => 0,
+                    PjLinkCaptureDirection::Sent => 1,
+                });
+                encoded.push(peer.len() as u8);
+                encoded.extend_from_slice(peer.as_bytes());
+                encoded.extend_from_slice(&(self.data.len() as u32).to_be_bytes());
+                encoded.extend_from_slice(&self.data);
+                encoded
+            }
+        }
+    }
+
+    /// Reads every record of a capture written by
+    /// [PjLinkSessionCapture](self::PjLinkSessionCapture), in either format.
+    pub fn read_all<R: Read>(mut reader: R) -> Result<Vec<PjLinkCaptureRecord>, PjLinkError> {
+        let mut capture = Vec::new();
+        reader.read_to_end(&mut capture)?;
+
+        match capture.strip_prefix(&PJLINK_CAPTURE_BINARY_MAGIC[..]) {
+            Some(records) => decode_binary_records(records),
+            None => capture.split(|byte| *byte == b'\n')
+                .filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+                .map(decode_json_record)
+                .collect(),
+        }
+    }
+}
+
+fn invalid_capture(reason: &str) -> PjLinkError {
+    PjLinkError::Io(io::Error::new(io::ErrorKind::InvalidData, format!("invalid capture: {}", reason)))
+}
+
+fn decode_json_record(line: &[u8]) -> Result<PjLinkCaptureRecord, PjLinkError> {
+    let record: PjLinkJsonCaptureRecord = serde_json::from_slice(line).map_err(|e| invalid_capture(&e.to_string()))?;
+
+    let direction = match record.direction.as_str() {
+        "received" => PjLinkCaptureDirection::Received,
+        "sent" => PjLinkCaptureDirection::Sent,
+        _ => return Err(invalid_capture("unknown direction")),
+    };
+    let peer_addr = match record.peer {
+        Some(peer) => Option::Some(peer.parse().map_err(|_| invalid_capture("invalid peer"))?),
+        None => Option::None,
+    };
+    let data = record.data
+        .chars()
+        .map(|character| u8::try_from(u32::from(character)).map_err(|_| invalid_capture("data character above U+00FF")))
+        .collect::<Result<Vec<u8>, PjLinkError>>()?;
+
+    Ok(PjLinkCaptureRecord {
+        timestamp_us: record.timestamp_us,
+        connection_id: record.connection_id,
+        peer_addr,
+        direction,
+        data,
+    })
+}
+
+fn decode_binary_records(mut records: &[u8]) -> Result<Vec<PjLinkCaptureRecord>, PjLinkError> {
+    fn take<'a>(records: &mut &'a [u8], length: usize) -> Result<&'a [u8], PjLinkError> {
+        let (taken, rest) = records.split_at_checked(length).ok_or_else(|| invalid_capture("truncated record"))?;
+        *records = rest;
+        Ok(taken)
+    }
+    fn take_u64(records: &mut &[u8]) -> Result<u64, PjLinkError> {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(take(records, 8)?);
+        Ok(u64::from_be_bytes(bytes))
+    }
+
+    let mut decoded = Vec::new();
+
+    while !records.is_empty() {
+        let timestamp_us = take_u64(&mut records)?;
+        let connection_id = take_u64(&mut records)?;
+        let direction = match take(&mut records, 1)?[0] {
+            0 => PjLinkCaptureDirection::Received,
+            1 => PjLinkCaptureDirection::Sent,
+            _ => return Err(invalid_capture("unknown direction")),
+        };
+        let peer_length = take(&mut records, 1)?[0] as usize;
+        let peer = take(&mut records, peer_length)?;
+        let peer_addr = match peer.is_empty() {
+            true => Option::None,
+            false => Option::Some(
+                std::str::from_utf8(peer).ok().and_then(|peer| peer.parse().ok()).ok_or_else(|| invalid_capture("invalid peer"))?,
+            ),
+        };
+        let mut data_length = [0u8; 4];
+        data_length.copy_from_slice(take(&mut records, 4)?);
+        let data = take(&mut records, u32::from_be_bytes(data_length) as usize)?.to_vec();
+
+        decoded.push(PjLinkCaptureRecord { timestamp_us, connection_id, peer_addr, direction, data });
+    }
+
+    Ok(decoded)
+}
+
+/// Records every line received and every byte sent on the connections of a
+/// listener, with timestamps, connection ID and peer address, when set on
+/// [PjLinkListenerOptions::capture](crate::PjLinkListenerOptions::capture).
+///
+/// Records are written and flushed as they happen, so captures are complete
+/// up to a crash. Write errors are logged and otherwise ignored, so a full
+/// disk doesn't stop the bridge.
+///
+/// ## Examples
+/// ```no_run
+/// use std::sync::Arc;
+/// use pjlink_bridge::*;
+///
+/// let capture = PjLinkSessionCapture::create("/tmp/pjlink.ndjson", PjLinkCaptureFormat::JsonLines).unwrap();
+/// let options = PjLinkListenerOptions {
+///     capture: Some(Arc::new(capture)),
+///     ..Default::default()
+/// };
+/// ```
+pub struct PjLinkSessionCapture {
+    writer: Mutex<Box<dyn Write + Send>>,
+    format: PjLinkCaptureFormat,
+}
+
+impl PjLinkSessionCapture {
+    /// Creates (or truncates) the capture file at `path`.
+    pub fn create<P: AsRef<Path>>(path: P, format: PjLinkCaptureFormat) -> Result<PjLinkSessionCapture, PjLinkError> {
+        let file = File::create(path)?;
+        Self::new(BufWriter::new(file), format)
+    }
+
+    /// Writes the capture to `writer`, like a pipe or an in-memory buffer.
+    pub fn new<W: Write + Send + 'static>(mut writer: W, format: PjLinkCaptureFormat) -> Result<PjLinkSessionCapture, PjLinkError> {
+        if format == PjLinkCaptureFormat::Binary {
+            writer.write_all(PJLINK_CAPTURE_BINARY_MAGIC)?;
+            writer.flush()?;
+        }
+
+        Ok(PjLinkSessionCapture { writer: Mutex::new(Box::new(writer)), format })
+    }
+
+    /// Returns the file format.
+    pub fn format(&self) -> PjLinkCaptureFormat {
+        self.format
+    }
+
+    /// Writes a record timestamped now.
+    pub fn record(&self, connection_id: u64, peer_addr: Option<SocketAddr>, direction: PjLinkCaptureDirection, data: &[u8]) {
+        let timestamp_us = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_micros() as u64);
+        let record = PjLinkCaptureRecord { timestamp_us, connection_id, peer_addr, direction, data: data.to_vec() };
+        let encoded = record.encode(self.format);
+
+        let mut writer = match self.writer.lock() {
+            Ok(writer) => writer,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if let Err(e) = writer.write_all(&encoded).and_then(|_| writer.flush()) {
+            debug!("Failed to write session capture! ConnectionId: {}, {}", connection_id, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_encodes_capture_records() {
+        let record = PjLinkCaptureRecord {
+            timestamp_us: 1_700_000_000_123_456,
+            connection_id: 3,
+            peer_addr: Some("10.0.0.5:50312".parse().unwrap()),
+            direction: PjLinkCaptureDirection::Sent,
+            data: b"%1POWR=1\r\xff".to_vec(),
+        };
+
+        assert_eq!(
+            String::from_utf8(record.encode(PjLinkCaptureFormat::JsonLines)).unwrap(),
+            "{\"timestamp_us\":1700000000123456,\"connection_id\":3,\"peer\":\"10.0.0.5:50312\",\"direction\":\"sent\",\"data\":\"%1POWR=1\\rÿ\"}\n",
+        );
+
+        let binary = record.encode(PjLinkCaptureFormat::Binary);
+        assert_eq!(binary[16..18], [1, 14]);
+        assert_eq!(&binary[18..32], b"10.0.0.5:50312");
+        assert_eq!(binary[32..36], 10u32.to_be_bytes());
+        assert_eq!(&binary[36..], b"%1POWR=1\r\xff");
+
+        let mut capture = PJLINK_CAPTURE_BINARY_MAGIC.to_vec();
+        capture.extend(binary);
+        assert_eq!(PjLinkCaptureRecord::read_all(&capture[..]).unwrap(), vec![record.clone()]);
+        assert_eq!(PjLinkCaptureRecord::read_all(&record.encode(PjLinkCaptureFormat::JsonLines)[..]).unwrap(), vec![record]);
+        assert!(PjLinkCaptureRecord::read_all(&b"PJLKCAP1\x00"[..]).is_err());
+    }
+}
@@ -0,0 +1,97 @@
+//! Time source of time-dependent features, replaceable in tests.
+
+#[cfg(any(test, feature = "mock"))]
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Source of the current time for warm-up and cool-down transitions, lamp
+/// hours, notification debouncing and session expiry.
+///
+/// [PjLinkSystemClock](self::PjLinkSystemClock) is used by default. Tests
+/// can use [PjLinkMockClock](self::PjLinkMockClock) to move time forward
+/// without sleeping. Socket timeouts always use the system clock.
+pub trait PjLinkClock: Send + Sync {
+    /// Returns the current time.
+    fn now(&self) -> Instant;
+
+    /// Returns the time elapsed since `earlier`, or zero if it's later than
+    /// now.
+    fn elapsed_since(&self, earlier: Instant) -> Duration {
+        self.now().saturating_duration_since(earlier)
+    }
+}
+
+/// [PjLinkClock](self::PjLinkClock) returning [Instant::now](std::time::Instant::now).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PjLinkSystemClock;
+
+impl PjLinkClock for PjLinkSystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// [PjLinkClock](self::PjLinkClock) that only moves when
+/// [advance](self::PjLinkMockClock::advance) is called.
+///
+/// Available with the `mock` feature.
+///
+/// ## Examples
+/// ```
+/// use std::sync::Arc;
+/// use std::time::Duration;
+/// use pjlink_bridge::*;
+///
+/// let clock = Arc::new(PjLinkMockClock::new());
+/// let mut power = PjLinkPowerStateMachine::new(Duration::from_secs(30), Duration::ZERO).with_clock(clock.clone());
+///
+/// power.handle(PjLinkPowerCommandParameter::On);
+/// clock.advance(Duration::from_secs(29));
+/// assert_eq!(power.state(), PjLinkPowerCommandStatus::WarmUp);
+/// clock.advance(Duration::from_secs(1));
+/// assert_eq!(power.state(), PjLinkPowerCommandStatus::On);
+/// ```
+#[cfg(any(test, feature = "mock"))]
+#[derive(Debug)]
+pub struct PjLinkMockClock {
+    started_at: Instant,
+    elapsed: Mutex<Duration>,
+}
+
+#[cfg(any(test, feature = "mock"))]
+impl PjLinkMockClock {
+    /// Creates a clock stopped at the current time.
+    pub fn new() -> PjLinkMockClock {
+        PjLinkMockClock {
+            started_at: Instant::now(),
+            elapsed: Mutex::new(Duration::ZERO),
+        }
+    }
+
+    /// Moves the clock forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        match self.elapsed.lock() {
+            Ok(mut elapsed) => *elapsed += duration,
+            Err(poisoned) => *poisoned.into_inner() += duration,
+        }
+    }
+}
+
+#[cfg(any(test, feature = "mock"))]
+impl Default for PjLinkMockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(any(test, feature = "mock"))]
+impl PjLinkClock for PjLinkMockClock {
+    fn now(&self) -> Instant {
+        let elapsed = match self.elapsed.lock() {
+            Ok(elapsed) => *elapsed,
+            Err(poisoned) => *poisoned.into_inner(),
+        };
+
+        self.started_at + elapsed
+    }
+}
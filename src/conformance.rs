@@ -0,0 +1,449 @@
+//! PJLink specification conformance checks for handlers.
+
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{PjLinkCommand, PjLinkHandler, PjLinkRawPayload, PjLinkResponse};
+
+/// Interval between power status queries while waiting for a power
+/// transition.
+const PJLINK_CONFORMANCE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Power state a [PjLinkConformanceCheck](self::PjLinkConformanceCheck) was
+/// made in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PjLinkConformanceState {
+    /// After `%1POWR 0`
+    Standby,
+    /// After `%1POWR 1`
+    PoweredOn,
+}
+
+impl fmt::Display for PjLinkConformanceState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PjLinkConformanceState::Standby => write!(f, "standby"),
+            PjLinkConformanceState::PoweredOn => write!(f, "powered on"),
+        }
+    }
+}
+
+/// Result of a single specification requirement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PjLinkConformanceCheck {
+    /// Requirement description, like `1POWR query returns 0, 1, 2 or 3`
+    pub requirement: &'static str,
+    /// Power state the command was sent in
+    pub state: PjLinkConformanceState,
+    /// Sent command line, without terminator
+    pub command: String,
+    /// Received response parameter, or a description of the failure
+    pub response: String,
+    /// Whether the response satisfies the requirement
+    pub passed: bool,
+}
+
+/// Results of a [PjLinkConformanceSuite](self::PjLinkConformanceSuite) run.
+///
+/// Its `Display` implementation lists every check, one per line.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PjLinkConformanceReport {
+    /// Every check made, in order
+    pub checks: Vec<PjLinkConformanceCheck>,
+}
+
+impl PjLinkConformanceReport {
+    /// Returns `true` if every check passed.
+    pub fn is_conformant(&self) -> bool {
+        self.checks.iter().all(|check| check.passed)
+    }
+
+    /// Returns failed checks.
+    pub fn failures(&self) -> impl Iterator<Item = &PjLinkConformanceCheck> {
+        self.checks.iter().filter(|check| !check.passed)
+    }
+}
+
+impl fmt::Display for PjLinkConformanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            writeln!(
+                f,
+                "[{}] {} ({}): {} -> {}",
+                if check.passed { "PASS" } else { "FAIL" },
+                check.requirement,
+                check.state,
+                check.command,
+                check.response
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Drives a [PjLinkHandler](crate::PjLinkHandler) through the mandatory
+/// PJLink Class 1 (and Class 2, if `%1CLSS ?` reports it) command matrix, in
+/// standby and powered on states, including invalid parameters and unknown
+/// commands.
+///
+/// Commands are passed straight to
+/// [PjLinkHandler::handle_command](crate::PjLinkHandler::handle_command); no
+/// network connection is made. A panicking handler fails the check instead of
+/// aborting the run.
+///
+/// ## Examples
+/// ```
+/// use std::time::Duration;
+/// use pjlink_bridge::*;
+///
+/// struct Projector;
+///
+/// impl PjLinkHandler for Projector {
+///     fn get_password(&mut self, _connection_id: &u64) -> Option<String> {
+///         None
+///     }
+///
+///     fn handle_command(&mut self, _command: PjLinkCommand, _raw_command: &PjLinkRawPayload, _connection_id: &u64) -> PjLinkResponse {
+///         PjLinkResponse::Ok
+///     }
+/// }
+///
+/// let suite = PjLinkConformanceSuite {
+///     power_timeout: Duration::from_secs(1),
+///     ..Default::default()
+/// };
+/// let report = suite.run(&mut Projector);
+///
+/// // Projector answers OK to everything
+/// assert!(!report.is_conformant());
+/// println!("{}", report);
+/// ```
+#[derive(Debug, Clone)]
+pub struct PjLinkConformanceSuite {
+    /// Connection ID passed to the handler
+    pub connection_id: u64,
+    /// How long to wait for warm-up or cool-down to finish after power commands
+    pub power_timeout: Duration,
+}
+
+impl Default for PjLinkConformanceSuite {
+    fn default() -> Self {
+        PjLinkConformanceSuite {
+            connection_id: 0,
+            power_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+impl PjLinkConformanceSuite {
+    /// Runs every check against `handler`, leaving it powered on.
+    pub fn run(&self, handler: &mut dyn PjLinkHandler) -> PjLinkConformanceReport {
+        let mut run = PjLinkConformanceRun {
+            suite: self,
+            handler,
+            report: PjLinkConformanceReport::default(),
+        };
+
+        run.power(b'0', PjLinkConformanceState::Standby);
+        run.check_queries(PjLinkConformanceState::Standby);
+
+        run.power(b'1', PjLinkConformanceState::PoweredOn);
+        run.check_queries(PjLinkConformanceState::PoweredOn);
+        run.check_invalid_parameters();
+
+        if run.is_class_2() {
+            run.check_class_2_queries();
+        }
+
+        run.report
+    }
+}
+
+struct PjLinkConformanceRun<'a> {
+    suite: &'a PjLinkConformanceSuite,
+    handler: &'a mut dyn PjLinkHandler,
+    report: PjLinkConformanceReport,
+}
+
+impl<'a> PjLinkConformanceRun<'a> {
+    fn power(&mut self, power: u8, state: PjLinkConformanceState) {
+        let requirement = if power == b'1' {
+            "1POWR 1 returns OK and power status reaches 1"
+        } else {
+            "1POWR 0 returns OK and power status reaches 0"
+        };
+
+        let response = self.send(*b"1POWR", &[power]);
+        if !matches!(response, Ok(PjLinkResponse::Ok)) {
+            self.record(requirement, state, *b"1POWR", &[power], response, false);
+            return;
+        }
+
+        let started_at = Instant::now();
+        loop {
+            let response = self.send(*b"1POWR", b"?");
+            let has_reached = response.as_ref().is_ok_and(|response| value_matches(response, |value| value == [power]));
+
+            if has_reached || started_at.elapsed() >= self.suite.power_timeout {
+                self.record(requirement, state, *b"1POWR", b"?", response, has_reached);
+                return;
+            }
+            thread::sleep(PJLINK_CONFORMANCE_POLL_INTERVAL);
+        }
+    }
+
+    fn check_queries(&mut self, state: PjLinkConformanceState) {
+        let is_on = state == PjLinkConformanceState::PoweredOn;
+
+        self.check("1POWR query returns 0, 1, 2 or 3", state, *b"1POWR", b"?",
+            |response| value_matches(response, |value| value.len() == 1 && (b'0'..=b'3').contains(&value[0])));
+        self.check("1INPT query returns an input (or ERR3 in standby)", state, *b"1INPT", b"?",
+            |response| (!is_on && is_unavailable(response)) || value_matches(response, |value| is_input(value, false)));
+        self.check("1AVMT query returns 10, 11, 20, 21, 30 or 31 (or ERR3 in standby)", state, *b"1AVMT", b"?",
+            |response| (!is_on && is_unavailable(response)) || value_matches(response, is_mute_status));
+        self.check("1ERST query returns 6 digits from 0 to 2", state, *b"1ERST", b"?",
+            |response| value_matches(response, |value| value.len() == 6 && value.iter().all(|item| (b'0'..=b'2').contains(item))));
+        self.check("1LAMP query returns lamp hours and status pairs (or ERR1 without lamps)", state, *b"1LAMP", b"?",
+            |response| matches!(response, PjLinkResponse::Undefined) || value_matches(response, is_lamp_list));
+        self.check("1INST query returns a list of inputs", state, *b"1INST", b"?",
+            |response| value_matches(response, |value| is_input_list(value, false)));
+        for (requirement, command_body_with_class) in [
+            ("1NAME query returns a name", *b"1NAME"),
+            ("1INF1 query returns a manufacturer name", *b"1INF1"),
+            ("1INF2 query returns a product name", *b"1INF2"),
+            ("1INFO query returns other information", *b"1INFO"),
+        ] {
+            self.check(requirement, state, command_body_with_class, b"?", is_value_or_empty);
+        }
+        self.check("1CLSS query returns 1 or 2", state, *b"1CLSS", b"?",
+            |response| value_matches(response, |value| value == b"1" || value == b"2"));
+        self.check("Unknown class 1 command returns ERR1", state, *b"1ZZZZ", b"?",
+            |response| matches!(response, PjLinkResponse::Undefined));
+    }
+
+    fn check_invalid_parameters(&mut self) {
+        let state = PjLinkConformanceState::PoweredOn;
+        let is_out_of_parameter = |response: &PjLinkResponse| matches!(response, PjLinkResponse::OutOfParameter);
+
+        self.check("1POWR with invalid parameter returns ERR2", state, *b"1POWR", b"9", is_out_of_parameter);
+        self.check("1INPT with invalid parameter returns ERR2", state, *b"1INPT", b"99", is_out_of_parameter);
+        self.check("1AVMT with invalid parameter returns ERR2", state, *b"1AVMT", b"99", is_out_of_parameter);
+    }
+
+    fn check_class_2_queries(&mut self) {
+        let state = PjLinkConformanceState::PoweredOn;
+
+        for (requirement, command_body_with_class) in [
+            ("2SNUM query returns a serial number", *b"2SNUM"),
+            ("2SVER query returns a software version", *b"2SVER"),
+            ("2RLMP query returns a lamp model number", *b"2RLMP"),
+            ("2RFIL query returns a filter model number", *b"2RFIL"),
+        ] {
+            self.check(requirement, state, command_body_with_class, b"?", is_value_or_empty);
+        }
+        self.check("2INPT query returns an input", state, *b"2INPT", b"?",
+            |response| value_matches(response, |value| is_input(value, true)));
+        self.check("2INST query returns a list of inputs", state, *b"2INST", b"?",
+            |response| value_matches(response, |value| is_input_list(value, true)));
+        self.check("2IRES query returns a resolution, - or *", state, *b"2IRES", b"?",
+            |response| value_matches(response, |value| value == b"-" || value == b"*" || is_resolution(value)));
+        self.check("2RRES query returns a resolution", state, *b"2RRES", b"?",
+            |response| value_matches(response, is_resolution));
+        self.check("2FILT query returns filter hours (or ERR1 without filter)", state, *b"2FILT", b"?",
+            |response| matches!(response, PjLinkResponse::Undefined) || value_matches(response, is_number));
+        self.check("2FREZ query returns 0 or 1", state, *b"2FREZ", b"?",
+            |response| value_matches(response, |value| value == b"0" || value == b"1"));
+        self.check("Unknown class 2 command returns ERR1", state, *b"2ZZZZ", b"?",
+            |response| matches!(response, PjLinkResponse::Undefined));
+    }
+
+    fn is_class_2(&mut self) -> bool {
+        self.send(*b"1CLSS", b"?").is_ok_and(|response| value_matches(&response, |value| value == b"2"))
+    }
+
+    fn check<F: Fn(&PjLinkResponse) -> bool>(
+        &mut self,
+        requirement: &'static str,
+        state: PjLinkConformanceState,
+        command_body_with_class: [u8; 5],
+        transmission_parameter: &[u8],
+        is_valid: F,
+    ) {
+        let response = self.send(command_body_with_class, transmission_parameter);
+        let passed = response.as_ref().is_ok_and(&is_valid);
+        self.record(requirement, state, command_body_with_class, transmission_parameter, response, passed);
+    }
+
+    fn record(
+        &mut self,
+        requirement: &'static str,
+        state: PjLinkConformanceState,
+        command_body_with_class: [u8; 5],
+        transmission_parameter: &[u8],
+        response: Result<PjLinkResponse, String>,
+        passed: bool,
+    ) {
+        self.report.checks.push(PjLinkConformanceCheck {
+            requirement,
+            state,
+            command: format!(
+                "%{} {}",
+                String::from_utf8_lossy(&command_body_with_class),
+                String::from_utf8_lossy(transmission_parameter)
+            ),
+            response: match response {
+                Ok(response) => describe_response(&response),
+                Err(e) => e,
+            },
+            passed,
+        });
+    }
+
+    /// Sends a command to the handler, catching panics.
+    fn send(&mut self, command_body_with_class: [u8; 5], transmission_parameter: &[u8]) -> Result<PjLinkResponse, String> {
+        let raw_command = PjLinkRawPayload::new_command(command_body_with_class, transmission_parameter.to_vec());
+        let handler = &mut *self.handler;
+        let connection_id = self.suite.connection_id;
+
+        panic::catch_unwind(AssertUnwindSafe(|| {
+            let command = PjLinkCommand::from_raw_payload(&raw_command);
+            handler.handle_command(command, &raw_command, &connection_id)
+        })).map_err(|_| String::from("handler panicked"))
+    }
+}
+
+fn describe_response(response: &PjLinkResponse) -> String {
+    match response {
+        PjLinkResponse::Ok => String::from("OK"),
+        PjLinkResponse::Undefined => String::from("ERR1"),
+        PjLinkResponse::OutOfParameter => String::from("ERR2"),
+        PjLinkResponse::UnavailableTime => String::from("ERR3"),
+        PjLinkResponse::ProjectorOrDisplayFailure => String::from("ERR4"),
+        PjLinkResponse::Single(value) => String::from_utf8_lossy(&[*value]).into_owned(),
+        PjLinkResponse::Multiple(value) => String::from_utf8_lossy(value).into_owned(),
+        PjLinkResponse::Empty => String::from("(empty)"),
+    }
+}
+
+fn value_matches<F: Fn(&[u8]) -> bool>(response: &PjLinkResponse, is_valid: F) -> bool {
+    match response {
+        PjLinkResponse::Single(value) => is_valid(&[*value]),
+        PjLinkResponse::Multiple(value) => is_valid(value),
+        _ => false,
+    }
+}
+
+fn is_value_or_empty(response: &PjLinkResponse) -> bool {
+    matches!(response, PjLinkResponse::Empty | PjLinkResponse::Single(_) | PjLinkResponse::Multiple(_))
+}
+
+fn is_unavailable(response: &PjLinkResponse) -> bool {
+    matches!(response, PjLinkResponse::UnavailableTime)
+}
+
+fn is_number(value: &[u8]) -> bool {
+    !value.is_empty() && value.iter().all(u8::is_ascii_digit)
+}
+
+fn is_input(value: &[u8], is_class_2: bool) -> bool {
+    let max_type = if is_class_2 { b'6' } else { b'5' };
+
+    value.len() == 2
+        && (b'1'..=max_type).contains(&value[0])
+        && ((b'1'..=b'9').contains(&value[1]) || (is_class_2 && value[1].is_ascii_uppercase()))
+}
+
+fn is_input_list(value: &[u8], is_class_2: bool) -> bool {
+    value.split(|item| *item == b' ').all(|input| is_input(input, is_class_2))
+}
+
+fn is_mute_status(value: &[u8]) -> bool {
+    value.len() == 2 && (b'1'..=b'3').contains(&value[0]) && (value[1] == b'0' || value[1] == b'1')
+}
+
+fn is_lamp_list(value: &[u8]) -> bool {
+    let items: Vec<&[u8]> = value.split(|item| *item == b' ').collect();
+
+    !items.is_empty()
+        && items.len().is_multiple_of(2)
+        && items.len() <= 16
+        && items.chunks(2).all(|lamp| is_number(lamp[0]) && (lamp[1] == b"0" || lamp[1] == b"1"))
+}
+
+fn is_resolution(value: &[u8]) -> bool {
+    match value.iter().position(|item| *item == b'x') {
+        Some(separator) => is_number(&value[..separator]) && is_number(&value[separator + 1..]),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PjLinkPowerCommandParameter, PjLinkMuteCommandParameter, PjLinkInputCommandParameter};
+
+    /// Minimal class 1 projector.
+    struct Class1Projector {
+        power: u8,
+    }
+
+    impl PjLinkHandler for Class1Projector {
+        fn get_password(&mut self, _connection_id: &u64) -> Option<String> {
+            Option::None
+        }
+
+        fn handle_command(&mut self, command: PjLinkCommand, _raw_command: &PjLinkRawPayload, _connection_id: &u64) -> PjLinkResponse {
+            match command {
+                PjLinkCommand::Power1(PjLinkPowerCommandParameter::Query) => PjLinkResponse::Single(self.power),
+                PjLinkCommand::Power1(PjLinkPowerCommandParameter::On) => {
+                    self.power = b'1';
+                    PjLinkResponse::Ok
+                }
+                PjLinkCommand::Power1(PjLinkPowerCommandParameter::Off) => {
+                    self.power = b'0';
+                    PjLinkResponse::Ok
+                }
+                PjLinkCommand::Input1(PjLinkInputCommandParameter::Query) => PjLinkResponse::Multiple(b"31".to_vec()),
+                PjLinkCommand::AvMute1(PjLinkMuteCommandParameter::Query) => PjLinkResponse::Multiple(b"30".to_vec()),
+                PjLinkCommand::ErrorStatus1 => PjLinkResponse::Multiple(b"000000".to_vec()),
+                PjLinkCommand::Lamp1 => PjLinkResponse::Multiple(b"120 1 80 0".to_vec()),
+                PjLinkCommand::InputTogglingList1 => PjLinkResponse::Multiple(b"11 31".to_vec()),
+                PjLinkCommand::Name1 | PjLinkCommand::InfoManufacturer1 | PjLinkCommand::InfoProductName1 => PjLinkResponse::Multiple(b"mock".to_vec()),
+                PjLinkCommand::InfoOther1 => PjLinkResponse::Empty,
+                PjLinkCommand::Class1 => PjLinkResponse::Single(b'1'),
+                PjLinkCommand::Unknown => PjLinkResponse::Undefined,
+                _ => PjLinkResponse::OutOfParameter,
+            }
+        }
+    }
+
+    #[test]
+    fn it_passes_conformant_handler() {
+        let report = PjLinkConformanceSuite::default().run(&mut Class1Projector { power: b'0' });
+        assert!(report.is_conformant(), "{}", report);
+    }
+
+    #[test]
+    fn it_reports_failures() {
+        struct AlwaysOk;
+
+        impl PjLinkHandler for AlwaysOk {
+            fn get_password(&mut self, _connection_id: &u64) -> Option<String> {
+                Option::None
+            }
+
+            fn handle_command(&mut self, _command: PjLinkCommand, _raw_command: &PjLinkRawPayload, _connection_id: &u64) -> PjLinkResponse {
+                PjLinkResponse::Ok
+            }
+        }
+
+        let suite = PjLinkConformanceSuite { power_timeout: Duration::ZERO, ..Default::default() };
+        let report = suite.run(&mut AlwaysOk);
+
+        assert!(!report.is_conformant());
+        assert!(report.failures().any(|check| check.requirement == "Unknown class 1 command returns ERR1"));
+    }
+}
@@ -0,0 +1,175 @@
+//! Declarative description of a projector's static capabilities.
+
+use crate::{
+    PjLinkClassCommandStatus, PjLinkCommand, PjLinkDeviceInfo, PjLinkHandler, PjLinkInputCommandParameter, PjLinkInputTable,
+    PjLinkRawPayload, PjLinkResponse,
+};
+use crate::input::input_parameter_bytes;
+
+/// Static capabilities of a projector: class, inputs and their terminal
+/// names, lamps and information strings.
+///
+/// A [PjLinkListener](crate::PjLinkListener) with
+/// [PjLinkListenerOptions::descriptor](crate::PjLinkListenerOptions::descriptor)
+/// answers capability queries from it, and rejects invalid set commands,
+/// without calling the handler:
+/// * `%1CLSS ?` with [class](self::PjLinkProjectorDescriptor::class)
+/// * `%1INST ?`, `%2INST ?` and `%2INNM ?` from [inputs](self::PjLinkProjectorDescriptor::inputs)
+/// * `%1INPT` and `%2INPT` selecting unlisted inputs with `ERR2`
+/// * `%1LAMP ?` with `ERR1` if the projector has no lamp
+/// * Class 2 commands with `ERR1` if the class is 1
+/// * [info](self::PjLinkProjectorDescriptor::info) fields, like [PjLinkListenerOptions::device_info](crate::PjLinkListenerOptions::device_info)
+///
+/// The handler answers the rest, like power, the selected input and error
+/// status. See [PjLinkCallbackHandler](self::PjLinkCallbackHandler) to
+/// answer them with a closure.
+///
+/// ## Examples
+#[cfg_attr(feature = "server", doc = "```")]
+#[cfg_attr(not(feature = "server"), doc = "```ignore")]
+/// use pjlink_bridge::*;
+///
+/// let mut descriptor = PjLinkProjectorDescriptor::new(PjLinkClassCommandStatus::Class2);
+/// descriptor.inputs
+///     .add(PjLinkInputCommandStatus::Digital, b'1', PjLinkName::truncated("HDMI 1"))
+///     .add(PjLinkInputCommandStatus::Digital, b'2', PjLinkName::truncated("HDMI 2"));
+/// descriptor.lamp_count = 1;
+/// descriptor.info.manufacturer = Some(String::from("ACME"));
+///
+/// let options = PjLinkListenerOptions {
+///     descriptor: Some(descriptor),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PjLinkProjectorDescriptor {
+    /// Supported PJLink class, as a [PjLinkClassCommandStatus](crate::PjLinkClassCommandStatus) value
+    pub class: u8,
+    /// Available inputs and their terminal names
+    pub inputs: PjLinkInputTable,
+    /// Number of lamps. Projectors without lamps answer `%1LAMP ?` with `ERR1`.
+    pub lamp_count: u8,
+    /// Information strings, like name, manufacturer and recommended resolution
+    pub info: PjLinkDeviceInfo,
+}
+
+impl PjLinkProjectorDescriptor {
+    /// Creates a descriptor without inputs, lamps or information.
+    pub fn new(class: u8) -> PjLinkProjectorDescriptor {
+        PjLinkProjectorDescriptor {
+            class,
+            inputs: PjLinkInputTable::new(),
+            lamp_count: 0,
+            info: PjLinkDeviceInfo::default(),
+        }
+    }
+
+    /// Returns the response to `raw_command`, if it's a capability query or
+    /// an invalid set command.
+    pub fn response_to(&self, raw_command: &PjLinkRawPayload) -> Option<PjLinkResponse> {
+        if self.class == PjLinkClassCommandStatus::Class1 && raw_command.command_body_with_class[0] == b'2' {
+            return Option::Some(PjLinkResponse::Undefined);
+        }
+
+        match PjLinkCommand::from_raw_payload(raw_command) {
+            PjLinkCommand::Class1 if raw_command.is_query() => Option::Some(PjLinkResponse::Single(self.class)),
+            PjLinkCommand::InputTogglingList1 => Option::Some(self.inputs.list(false)),
+            PjLinkCommand::InputTogglingList2 => Option::Some(self.inputs.list(true)),
+            PjLinkCommand::InputTerminalName2(parameter) => Option::Some(self.inputs.terminal_name(&parameter)),
+            PjLinkCommand::Input1(parameter) | PjLinkCommand::Input2(parameter) if !self.is_listed_input(&parameter) => {
+                Option::Some(PjLinkResponse::OutOfParameter)
+            }
+            PjLinkCommand::Lamp1 if self.lamp_count == 0 => Option::Some(PjLinkResponse::Undefined),
+            _ => self.info.response_to(raw_command),
+        }
+    }
+
+    /// Returns `true` for queries and listed inputs.
+    fn is_listed_input(&self, parameter: &PjLinkInputCommandParameter) -> bool {
+        match parameter {
+            PjLinkInputCommandParameter::Query => true,
+            _ => input_parameter_bytes(parameter).is_some_and(|selected| {
+                self.inputs.inputs().iter().any(|input| [input.input_type, input.index] == selected)
+            }),
+        }
+    }
+}
+
+impl Default for PjLinkProjectorDescriptor {
+    fn default() -> PjLinkProjectorDescriptor {
+        PjLinkProjectorDescriptor::new(PjLinkClassCommandStatus::Class1)
+    }
+}
+
+/// [PjLinkHandler](crate::PjLinkHandler) answering every command with a
+/// closure, for projectors whose static capabilities are described by a
+/// [PjLinkProjectorDescriptor](self::PjLinkProjectorDescriptor).
+///
+/// ## Examples
+/// ```
+/// use std::sync::{Arc, Mutex};
+/// use pjlink_bridge::*;
+///
+/// let mut power = PjLinkPowerCommandStatus::Off;
+/// let handler = PjLinkCallbackHandler::new(None, move |command| match command {
+///     PjLinkCommand::Power1(PjLinkPowerCommandParameter::Query) => PjLinkResponse::Single(power),
+///     PjLinkCommand::Power1(PjLinkPowerCommandParameter::On) => {
+///         power = PjLinkPowerCommandStatus::On;
+///         PjLinkResponse::Ok
+///     }
+///     _ => PjLinkResponse::Undefined,
+/// });
+/// let shared_handler: PjLinkHandlerShared = Arc::new(Mutex::new(handler));
+/// ```
+pub struct PjLinkCallbackHandler<F: FnMut(PjLinkCommand) -> PjLinkResponse + Send> {
+    password: Option<String>,
+    callback: F,
+}
+
+impl<F: FnMut(PjLinkCommand) -> PjLinkResponse + Send> PjLinkCallbackHandler<F> {
+    /// **Arguments**:
+    /// * `password`: Connection password, or `None` to disable authentication
+    /// * `callback`: Answers commands
+    pub fn new(password: Option<String>, callback: F) -> PjLinkCallbackHandler<F> {
+        PjLinkCallbackHandler { password, callback }
+    }
+}
+
+impl<F: FnMut(PjLinkCommand) -> PjLinkResponse + Send> PjLinkHandler for PjLinkCallbackHandler<F> {
+    fn get_password(&mut self, _connection_id: &u64) -> Option<String> {
+        self.password.clone()
+    }
+
+    fn handle_command(&mut self, command: PjLinkCommand, _raw_command: &PjLinkRawPayload, _connection_id: &u64) -> PjLinkResponse {
+        (self.callback)(command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PjLinkInputCommandStatus, PjLinkName, PJLINK_QUERY};
+
+    #[test]
+    fn it_answers_capability_queries_and_rejects_unlisted_inputs() {
+        let mut descriptor = PjLinkProjectorDescriptor::new(PjLinkClassCommandStatus::Class2);
+        descriptor.inputs.add(PjLinkInputCommandStatus::Digital, b'1', PjLinkName::truncated("HDMI 1"));
+        descriptor.info.name = Some(String::from("Hall"));
+        let send = |descriptor: &PjLinkProjectorDescriptor, command_body_with_class: &[u8; 5], transmission_parameter: &[u8]| {
+            descriptor.response_to(&PjLinkRawPayload::new_command(*command_body_with_class, transmission_parameter.to_vec()))
+        };
+
+        assert_eq!(send(&descriptor, b"1CLSS", &[PJLINK_QUERY]), Some(PjLinkResponse::Single(b'2')));
+        assert_eq!(send(&descriptor, b"2INST", &[PJLINK_QUERY]), Some(PjLinkResponse::Multiple(b"31".to_vec())));
+        assert_eq!(send(&descriptor, b"2INNM", b"?31"), Some(PjLinkResponse::Multiple(b"HDMI 1".to_vec())));
+        assert_eq!(send(&descriptor, b"1INPT", b"32"), Some(PjLinkResponse::OutOfParameter));
+        assert_eq!(send(&descriptor, b"1INPT", b"31"), None);
+        assert_eq!(send(&descriptor, b"1INPT", &[PJLINK_QUERY]), None);
+        assert_eq!(send(&descriptor, b"1LAMP", &[PJLINK_QUERY]), Some(PjLinkResponse::Undefined));
+        assert_eq!(send(&descriptor, b"1NAME", &[PJLINK_QUERY]), Some(PjLinkResponse::Multiple(b"Hall".to_vec())));
+        assert_eq!(send(&descriptor, b"1POWR", &[PJLINK_QUERY]), None);
+
+        descriptor.class = PjLinkClassCommandStatus::Class1;
+        assert_eq!(send(&descriptor, b"2INST", &[PJLINK_QUERY]), Some(PjLinkResponse::Undefined));
+    }
+}
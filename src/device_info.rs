@@ -0,0 +1,98 @@
+//! Static projector information answered by the listener.
+
+use crate::{PjLinkRawPayload, PjLinkResponse, PJLINK_QUERY};
+
+/// Static projector information. A [PjLinkListener](crate::PjLinkListener)
+/// with [PjLinkListenerOptions::device_info](crate::PjLinkListenerOptions::device_info)
+/// answers queries of the set fields itself, so handlers only implement
+/// dynamic commands like `POWR`, `INPT`, `AVMT` or `ERST`.
+///
+/// Fields left as `None` are answered by the handler. An empty string is
+/// answered as an empty response, like `%2SVER=`.
+///
+/// ## Examples
+#[cfg_attr(feature = "server", doc = "```")]
+#[cfg_attr(not(feature = "server"), doc = "```ignore")]
+/// use pjlink_bridge::*;
+///
+/// let options = PjLinkListenerOptions {
+///     device_info: PjLinkDeviceInfo {
+///         name: Some(String::from("Auditorium")),
+///         manufacturer: Some(String::from("ACME")),
+///         product: Some(String::from("Beamer 3000")),
+///         ..Default::default()
+///     },
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PjLinkDeviceInfo {
+    /// Projector name (`%1NAME ?`)
+    pub name: Option<String>,
+    /// Manufacturer name (`%1INF1 ?`)
+    pub manufacturer: Option<String>,
+    /// Product name (`%1INF2 ?`)
+    pub product: Option<String>,
+    /// Other information (`%1INFO ?`)
+    pub other_info: Option<String>,
+    /// Serial number (`%2SNUM ?`)
+    pub serial: Option<String>,
+    /// Software version (`%2SVER ?`)
+    pub sw_version: Option<String>,
+    /// Recommended resolution, like `1920x1080` (`%2RRES ?`)
+    pub rres: Option<String>,
+    /// Lamp replacement model number (`%2RLMP ?`)
+    pub rlmp: Option<String>,
+    /// Filter replacement model number (`%2RFIL ?`)
+    pub rfil: Option<String>,
+}
+
+impl PjLinkDeviceInfo {
+    /// Returns the response to `raw_command`, if it's a query of a set field.
+    pub fn response_to(&self, raw_command: &PjLinkRawPayload) -> Option<PjLinkResponse> {
+        if raw_command.transmission_parameter != [PJLINK_QUERY] {
+            return Option::None;
+        }
+
+        let value = match &raw_command.command_body_with_class {
+            b"1NAME" => &self.name,
+            b"1INF1" => &self.manufacturer,
+            b"1INF2" => &self.product,
+            b"1INFO" => &self.other_info,
+            b"2SNUM" => &self.serial,
+            b"2SVER" => &self.sw_version,
+            b"2RRES" => &self.rres,
+            b"2RLMP" => &self.rlmp,
+            b"2RFIL" => &self.rfil,
+            _ => return Option::None,
+        };
+
+        value.as_ref().map(|value| match value.is_empty() {
+            true => PjLinkResponse::Empty,
+            false => PjLinkResponse::Multiple(Vec::from(value.as_bytes())),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_answers_queries_of_set_fields() {
+        let device_info = PjLinkDeviceInfo {
+            name: Some(String::from("Auditorium")),
+            sw_version: Some(String::new()),
+            ..Default::default()
+        };
+        let query = |command_body_with_class: &[u8; 5]| {
+            device_info.response_to(&PjLinkRawPayload::new_command(*command_body_with_class, vec![PJLINK_QUERY]))
+        };
+
+        assert_eq!(query(b"1NAME"), Some(PjLinkResponse::Multiple(b"Auditorium".to_vec())));
+        assert_eq!(query(b"2SVER"), Some(PjLinkResponse::Empty));
+        assert_eq!(query(b"1INF1"), None);
+        assert_eq!(query(b"1POWR"), None);
+        assert!(device_info.response_to(&PjLinkRawPayload::new_command(*b"1NAME", vec![b'1'])).is_none());
+    }
+}
@@ -0,0 +1,203 @@
+//! Table of projectors seen on the network, for controllers and monitors.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use log::{debug, trace};
+
+use crate::{
+    spawn_named_thread, PjLinkError, PjLinkNotificationTarget, PJLINK_BROADCAST_MESSAGE_ACKN, PJLINK_BROADCAST_MESSAGE_LKUP,
+    PJLINK_HEADER, PJLINK_RESPONSE_SEPARATOR, PJLINK_TERMINATOR,
+};
+use crate::protocol::{PJLINK_BROADCAST_SEARCH_START, PJLINK_MAX_BROADCAST_BUFFER_SIZE};
+
+/// Projector listed in a [PjLinkDeviceTable](self::PjLinkDeviceTable).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PjLinkSeenDevice {
+    /// MAC address, in uppercase, like `00:1A:2B:3C:4D:5E`
+    pub mac_address: String,
+    /// Source addresses the projector was seen from, oldest first
+    pub addresses: Vec<IpAddr>,
+    /// When the projector was first seen
+    pub first_seen: Instant,
+    /// When the projector was last seen
+    pub last_seen: Instant,
+}
+
+/// Projectors seen on the network through `%2ACKN` (search answers) and
+/// `%2LKUP` (startup announcements) datagrams, keyed by MAC address.
+///
+/// Entries not seen for the expiry time are dropped, so the table only lists
+/// projectors still on the network as long as they're searched periodically.
+///
+/// ## Examples
+/// ```no_run
+/// use std::net::UdpSocket;
+/// use std::sync::Arc;
+/// use std::time::Duration;
+/// use pjlink_bridge::*;
+///
+/// let table = Arc::new(PjLinkDeviceTable::new(Duration::from_secs(300)));
+/// let socket = UdpSocket::bind("0.0.0.0:4352").unwrap();
+/// socket.set_broadcast(true).unwrap();
+///
+/// PjLinkDeviceTable::search(&socket, PjLinkNotificationTarget::Broadcast(PJLINK_DEFAULT_PORT)).unwrap();
+/// table.clone().listen(socket);
+///
+/// for device in table.devices() {
+///     println!("{} at {:?}", device.mac_address, device.addresses);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct PjLinkDeviceTable {
+    devices: Mutex<HashMap<String, PjLinkSeenDevice>>,
+    expire_after: Duration,
+}
+
+impl PjLinkDeviceTable {
+    /// Creates an empty table.
+    ///
+    /// **Arguments**:
+    /// * `expire_after`: Time after which projectors not seen again are dropped
+    pub fn new(expire_after: Duration) -> PjLinkDeviceTable {
+        PjLinkDeviceTable {
+            devices: Mutex::new(HashMap::new()),
+            expire_after,
+        }
+    }
+
+    /// Sends a `%2SRCH` search request from `socket`. Answers are recorded
+    /// by [listen](self::PjLinkDeviceTable::listen) on the same socket.
+    pub fn search(socket: &UdpSocket, target: PjLinkNotificationTarget) -> Result<(), PjLinkError> {
+        socket.send_to(PJLINK_BROADCAST_SEARCH_START, target.to_socket_addr())?;
+
+        Ok(())
+    }
+
+    /// Records every `%2ACKN` and `%2LKUP` datagram received on `socket` on
+    /// a new thread named `pjlink-device-table`, until receiving fails.
+    /// Fails with [Io](crate::PjLinkError::Io) if the thread can't be
+    /// started.
+    pub fn listen(self: Arc<Self>, socket: UdpSocket) -> Result<JoinHandle<()>, PjLinkError> {
+        spawn_named_thread(String::from("pjlink-device-table"), move || {
+            let mut buffer = [0u8; PJLINK_MAX_BROADCAST_BUFFER_SIZE + 1];
+
+            loop {
+                match socket.recv_from(&mut buffer) {
+                    Ok((length, origin)) => {
+                        if self.record_datagram(&buffer[..length], origin).is_none() {
+                            trace!("Device table: ignored datagram. Origin: {}, RawMessage: {:?}", origin, &buffer[..length]);
+                        }
+                    }
+                    Err(e) => {
+                        debug!("Device table: receiving failed, stopping. {}", e);
+                        return;
+                    }
+                }
+            }
+        })
+    }
+
+    /// Records the projector announced by `datagram`, if it's a `%2ACKN` or
+    /// `%2LKUP` message, and returns its MAC address.
+    pub fn record_datagram(&self, datagram: &[u8], origin: SocketAddr) -> Option<String> {
+        let mac_address = parse_announcement(datagram)?;
+        self.record_at(&mac_address, origin.ip(), Instant::now());
+
+        Option::Some(mac_address)
+    }
+
+    /// Returns the projectors seen within the expiry time, ordered by MAC
+    /// address. Expired entries are dropped.
+    pub fn devices(&self) -> Vec<PjLinkSeenDevice> {
+        self.devices_at(Instant::now())
+    }
+
+    /// Returns the projector with `mac_address`, if seen within the expiry
+    /// time.
+    pub fn get(&self, mac_address: &str) -> Option<PjLinkSeenDevice> {
+        let mac_address = mac_address.to_ascii_uppercase();
+        self.devices_at(Instant::now()).into_iter().find(|device| device.mac_address == mac_address)
+    }
+
+    fn record_at(&self, mac_address: &str, address: IpAddr, now: Instant) {
+        if let Ok(mut devices) = self.devices.lock() {
+            let device = devices.entry(mac_address.to_string()).or_insert_with(|| PjLinkSeenDevice {
+                mac_address: mac_address.to_string(),
+                addresses: Vec::new(),
+                first_seen: now,
+                last_seen: now,
+            });
+
+            device.last_seen = now;
+            device.addresses.retain(|seen_address| *seen_address != address);
+            device.addresses.push(address);
+        }
+    }
+
+    fn devices_at(&self, now: Instant) -> Vec<PjLinkSeenDevice> {
+        let mut devices: Vec<PjLinkSeenDevice> = match self.devices.lock() {
+            Ok(mut devices) => {
+                devices.retain(|_, device| now.saturating_duration_since(device.last_seen) < self.expire_after);
+                devices.values().cloned().collect()
+            }
+            Err(_) => Vec::new(),
+        };
+        devices.sort_by(|a, b| a.mac_address.cmp(&b.mac_address));
+
+        devices
+    }
+}
+
+/// Returns the uppercase MAC address of a `%2ACKN=<mac>\r` or
+/// `%2LKUP=<mac>\r` datagram.
+fn parse_announcement(datagram: &[u8]) -> Option<String> {
+    let datagram = datagram.strip_suffix(&[PJLINK_TERMINATOR])?;
+    let (header, mac_address) = datagram.split_at_checked(7)?;
+
+    if header[0] != PJLINK_HEADER
+        || header[6] != PJLINK_RESPONSE_SEPARATOR
+        || (&header[1..6] != PJLINK_BROADCAST_MESSAGE_ACKN && &header[1..6] != PJLINK_BROADCAST_MESSAGE_LKUP)
+    {
+        return Option::None;
+    }
+
+    let is_mac_address = mac_address.len() == 17
+        && mac_address.chunks(3).all(|octet| {
+            octet[0].is_ascii_hexdigit() && octet[1].is_ascii_hexdigit() && octet.get(2).is_none_or(|separator| *separator == b':')
+        });
+
+    match is_mac_address {
+        true => Option::Some(String::from_utf8_lossy(mac_address).to_ascii_uppercase()),
+        false => Option::None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_tracks_and_expires_announced_projectors() {
+        let table = PjLinkDeviceTable::new(Duration::from_secs(60));
+        let origin: SocketAddr = "192.168.0.20:4352".parse().unwrap();
+
+        assert_eq!(table.record_datagram(b"%2ACKN=00:1a:2b:3c:4d:5e\r", origin), Some(String::from("00:1A:2B:3C:4D:5E")));
+        assert_eq!(table.record_datagram(b"%2POWR=1\r", origin), None);
+        assert_eq!(table.record_datagram(b"%2LKUP=00:1a:2b:3c:4d\r", origin), None);
+
+        let started_at = Instant::now();
+        table.record_at("AA:BB:CC:DD:EE:FF", "10.0.0.5".parse().unwrap(), started_at);
+        table.record_at("AA:BB:CC:DD:EE:FF", "10.0.0.6".parse().unwrap(), started_at + Duration::from_secs(30));
+
+        let devices = table.devices_at(started_at + Duration::from_secs(45));
+        assert_eq!(devices.len(), 2);
+        assert_eq!(devices[1].addresses, vec!["10.0.0.5".parse::<IpAddr>().unwrap(), "10.0.0.6".parse().unwrap()]);
+        assert_eq!(devices[1].last_seen - devices[1].first_seen, Duration::from_secs(30));
+
+        let devices = table.devices_at(started_at + Duration::from_secs(100));
+        assert!(devices.is_empty());
+    }
+}
@@ -0,0 +1,113 @@
+//! Password digests used by PJLink authentication.
+
+/// Computes the password digest controllers send with their first command,
+/// from the salt sent in the security header followed by the password.
+///
+/// PJLink uses MD5, implemented by [PjLinkMd5Digest](self::PjLinkMd5Digest),
+/// the default with the `md5` feature. Set another implementation as
+/// [PjLinkListenerOptions::digest](crate::PjLinkListenerOptions::digest)
+/// to use a certified or vendored crypto library. Without the `md5`
+/// feature, it must be set for authentication to succeed.
+///
+/// ## Examples
+#[cfg_attr(feature = "server", doc = "```")]
+#[cfg_attr(not(feature = "server"), doc = "```ignore")]
+/// use std::sync::Arc;
+/// use pjlink_bridge::*;
+///
+/// # fn vendored_md5(_data: &[u8]) -> [u8; 16] { [0; 16] }
+/// struct VendoredMd5;
+///
+/// impl PjLinkDigest for VendoredMd5 {
+///     fn hex_digest(&self, data: &[u8]) -> String {
+///         vendored_md5(data).iter().map(|byte| format!("{:02x}", byte)).collect()
+///     }
+/// }
+///
+/// let options = PjLinkListenerOptions {
+///     digest: Some(Arc::new(VendoredMd5)),
+///     ..Default::default()
+/// };
+/// ```
+pub trait PjLinkDigest: Send + Sync {
+    /// Returns the digest of `data`, as lowercase hexadecimal.
+    fn hex_digest(&self, data: &[u8]) -> String;
+}
+
+/// MD5 [PjLinkDigest](self::PjLinkDigest), as PJLink specifies, using the
+/// `md5` crate. Available with the `md5` feature.
+///
+/// ## Examples
+/// ```
+/// use pjlink_bridge::*;
+///
+/// assert_eq!(PjLinkMd5Digest.hex_digest(b"498e4a67JBMIAProjectorLink"), "5d8409bc1c3fa39749434aa3a5c38682");
+/// ```
+#[cfg(any(test, feature = "md5"))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PjLinkMd5Digest;
+
+#[cfg(any(test, feature = "md5"))]
+impl PjLinkDigest for PjLinkMd5Digest {
+    fn hex_digest(&self, data: &[u8]) -> String {
+        format!("{:x}", md5::compute(data))
+    }
+}
+
+/// Returns the digest used when none is set: MD5 with the `md5` feature,
+/// none otherwise.
+#[cfg(feature = "server")]
+pub(crate) fn default_digest<'a>() -> Option<&'a (dyn PjLinkDigest + 'static)> {
+    #[cfg(any(test, feature = "md5"))]
+    return Option::Some(&PjLinkMd5Digest);
+    #[cfg(not(any(test, feature = "md5")))]
+    return Option::None;
+}
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use crate::{PjLinkCommand, PjLinkHandler, PjLinkListenerOptions, PjLinkMemoryTransport, PjLinkRawPayload, PjLinkResponse, PjLinkServer};
+
+    struct ReversedDigest;
+
+    impl PjLinkDigest for ReversedDigest {
+        fn hex_digest(&self, data: &[u8]) -> String {
+            PjLinkMd5Digest.hex_digest(data).chars().rev().collect()
+        }
+    }
+
+    struct PowerOnHandler;
+
+    impl PjLinkHandler for PowerOnHandler {
+        fn get_password(&mut self, _connection_id: &u64) -> Option<String> {
+            Option::Some(String::from("secret"))
+        }
+
+        fn handle_command(&mut self, _command: PjLinkCommand, _raw_command: &PjLinkRawPayload, _connection_id: &u64) -> PjLinkResponse {
+            PjLinkResponse::Single(b'1')
+        }
+    }
+
+    #[test]
+    fn it_authenticates_with_custom_digest() {
+        let (mut client, server) = PjLinkMemoryTransport::pair();
+        let options = PjLinkListenerOptions { digest: Option::Some(Arc::new(ReversedDigest)), ..Default::default() };
+        thread::spawn(move || PjLinkServer::serve_transport_with_options(Arc::new(Mutex::new(PowerOnHandler)), server, options));
+
+        let mut header = [0u8; 18];
+        client.read_exact(&mut header).unwrap();
+        let mut salted_password = header[9..17].to_vec();
+        salted_password.extend_from_slice(b"secret");
+
+        let mut command = ReversedDigest.hex_digest(&salted_password).into_bytes();
+        command.extend_from_slice(b"%1POWR ?\r");
+        client.write_all(&command).unwrap();
+        let mut response = [0u8; 9];
+        client.read_exact(&mut response).unwrap();
+        assert_eq!(&response, b"%1POWR=1\r");
+    }
+}
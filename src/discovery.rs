@@ -0,0 +1,425 @@
+//! PJLink Class 2 discovery (search) helpers.
+
+#[cfg(feature = "server")]
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+#[cfg(feature = "server")]
+use std::sync::Mutex;
+use std::time::Duration;
+#[cfg(feature = "server")]
+use std::time::Instant;
+
+use crate::PjLinkNotificationTarget;
+#[cfg(feature = "server")]
+use crate::{PJLINK_BROADCAST_MESSAGE_ACKN, PJLINK_HEADER, PJLINK_RESPONSE_SEPARATOR, PJLINK_TERMINATOR};
+#[cfg(feature = "server")]
+use crate::protocol::{PJLINK_BROADCAST_SEARCH_START, PJLINK_MAX_BROADCAST_BUFFER_SIZE};
+
+/// Destination port of `%2ACKN` answers to search requests.
+///
+/// The specification answers on the port the projector listens on, but some
+/// controllers listen on a different port than they search from.
+///
+/// ## Examples
+#[cfg_attr(feature = "server", doc = "```")]
+#[cfg_attr(not(feature = "server"), doc = "```ignore")]
+/// use pjlink_bridge::*;
+///
+/// let options = PjLinkListenerOptions {
+///     search_response_port: PjLinkSearchResponsePort::Fixed(PJLINK_DEFAULT_PORT),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PjLinkSearchResponsePort {
+    /// Port the UDP socket is bound to, as the specification requires
+    #[default]
+    Listener,
+    /// Port the search request was sent from
+    Origin,
+    /// Explicit port
+    Fixed(u16),
+}
+
+impl PjLinkSearchResponsePort {
+    /// Returns the port to answer a search request coming from `origin`.
+    ///
+    /// **Arguments**:
+    /// * `origin`: Address the search request came from
+    /// * `listener_port`: Port the UDP socket is bound to
+    pub fn resolve(&self, origin: &SocketAddr, listener_port: u16) -> u16 {
+        match self {
+            PjLinkSearchResponsePort::Listener => listener_port,
+            PjLinkSearchResponsePort::Origin => origin.port(),
+            PjLinkSearchResponsePort::Fixed(port) => *port,
+        }
+    }
+}
+
+/// Socket `%2ACKN` answers to search requests are sent from.
+///
+/// ## Examples
+#[cfg_attr(feature = "server", doc = "```")]
+#[cfg_attr(not(feature = "server"), doc = "```ignore")]
+/// use pjlink_bridge::*;
+///
+/// let options = PjLinkListenerOptions {
+///     search_response_socket: PjLinkSearchResponseSocket::Ephemeral,
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PjLinkSearchResponseSocket {
+    /// The UDP socket search requests are received on, so answers come from
+    /// the PJLink port, as controllers filtering on source port expect
+    #[default]
+    Listener,
+    /// A new socket bound to an ephemeral port for every answer
+    Ephemeral,
+}
+
+/// Source of the MAC address answered to search requests (`%2ACKN`).
+///
+/// [PjLinkSystemMacAddress](self::PjLinkSystemMacAddress), looking up a
+/// network interface with `mac_address`, is used by default. Without the
+/// `mac_address` feature, or when the lookup fails, a null MAC address is
+/// answered. Set a fixed address, or another lookup, as
+/// [PjLinkListenerOptions::mac_address](crate::PjLinkListenerOptions::mac_address).
+///
+/// ## Examples
+#[cfg_attr(feature = "server", doc = "```")]
+#[cfg_attr(not(feature = "server"), doc = "```ignore")]
+/// use std::sync::Arc;
+/// use pjlink_bridge::*;
+///
+/// let options = PjLinkListenerOptions {
+///     mac_address: Some(Arc::new([0x00, 0x1a, 0x2b, 0x3c, 0x4d, 0x5e])),
+///     ..Default::default()
+/// };
+/// ```
+pub trait PjLinkMacAddressProvider: Send + Sync {
+    /// Returns the MAC address, or `None` if it's unknown.
+    fn mac_address(&self) -> Option<[u8; 6]>;
+}
+
+impl PjLinkMacAddressProvider for [u8; 6] {
+    fn mac_address(&self) -> Option<[u8; 6]> {
+        Option::Some(*self)
+    }
+}
+
+/// [PjLinkMacAddressProvider](self::PjLinkMacAddressProvider) returning the
+/// address of the first network interface that has one, using
+/// `mac_address`. Available with the `mac_address` feature.
+#[cfg(feature = "mac_address")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PjLinkSystemMacAddress;
+
+#[cfg(feature = "mac_address")]
+impl PjLinkMacAddressProvider for PjLinkSystemMacAddress {
+    fn mac_address(&self) -> Option<[u8; 6]> {
+        // TODO a way to get mac address by broadcast address' associated
+        // interface
+        match mac_address::get_mac_address() {
+            Ok(Some(mac)) => Option::Some(mac.bytes()),
+            Ok(None) | Err(_) => Option::None,
+        }
+    }
+}
+
+/// Returns the provider used when none is set: the system lookup with the
+/// `mac_address` feature, none otherwise.
+#[cfg(feature = "server")]
+pub(crate) fn default_mac_address_provider<'a>() -> Option<&'a (dyn PjLinkMacAddressProvider + 'static)> {
+    #[cfg(feature = "mac_address")]
+    return Option::Some(&PjLinkSystemMacAddress);
+    #[cfg(not(feature = "mac_address"))]
+    return Option::None;
+}
+
+/// Multicast group the listener UDP socket joins, so search requests and
+/// status notifications can cross routers, unlike broadcasts.
+///
+/// Search requests sent to the group are answered like broadcast ones.
+/// Notifications reach the group when it's a notification target, see
+/// [target](self::PjLinkMulticastGroup::target).
+///
+/// ## Examples
+#[cfg_attr(feature = "server", doc = "```")]
+#[cfg_attr(not(feature = "server"), doc = "```ignore")]
+/// use pjlink_bridge::*;
+///
+/// let multicast = PjLinkMulticastGroup {
+///     group: "239.255.43.52".parse().unwrap(),
+///     interface: "0.0.0.0".parse().unwrap(),
+///     ttl: 4,
+/// };
+/// let options = PjLinkListenerOptions {
+///     multicast: Some(multicast),
+///     notification_targets: vec![multicast.target(4352)],
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PjLinkMulticastGroup {
+    /// Group address, like `239.255.43.52`
+    pub group: Ipv4Addr,
+    /// Address of the local interface joining the group. `0.0.0.0` lets the
+    /// system choose.
+    pub interface: Ipv4Addr,
+    /// Routers datagrams sent to the group can cross, plus one
+    pub ttl: u32,
+}
+
+impl PjLinkMulticastGroup {
+    /// Joins the group on `socket`, and sets the TTL of datagrams it sends
+    /// to multicast groups.
+    pub fn join(&self, socket: &UdpSocket) -> io::Result<()> {
+        self.set_ttl(socket)?;
+        socket.join_multicast_v4(&self.group, &self.interface)
+    }
+
+    /// Sets the TTL of datagrams `socket` sends to multicast groups, for
+    /// sockets that only send.
+    pub fn set_ttl(&self, socket: &UdpSocket) -> io::Result<()> {
+        socket.set_multicast_ttl_v4(self.ttl)
+    }
+
+    /// Returns the notification target sending to the group, on `port`.
+    pub fn target(&self, port: u16) -> PjLinkNotificationTarget {
+        PjLinkNotificationTarget::Address(SocketAddr::new(IpAddr::V4(self.group), port))
+    }
+}
+
+/// Limits on `%2ACKN` answers, so a controller searching in a tight loop,
+/// or spoofed search requests, can't use the projector to flood the network.
+///
+/// Search requests over the limits are rejected with
+/// [RateLimited](self::PjLinkDatagramRejection::RateLimited). Unlimited by
+/// default.
+///
+/// ## Examples
+#[cfg_attr(feature = "server", doc = "```")]
+#[cfg_attr(not(feature = "server"), doc = "```ignore")]
+/// use std::time::Duration;
+/// use pjlink_bridge::*;
+///
+/// let options = PjLinkListenerOptions {
+///     search_rate_limit: PjLinkSearchRateLimit {
+///         per_source_interval: Some(Duration::from_secs(1)),
+///         max_per_second: Some(50),
+///     },
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PjLinkSearchRateLimit {
+    /// Minimum time between answers to the same source address.
+    pub per_source_interval: Option<Duration>,
+    /// Maximum answers per second, to all sources together.
+    pub max_per_second: Option<u32>,
+}
+
+/// Source addresses remembered by a [PjLinkSearchRateLimiter] before
+/// addresses outside the per-source interval are forgotten.
+#[cfg(feature = "server")]
+const PJLINK_SEARCH_RATE_LIMITER_MAX_SOURCES: usize = 4096;
+
+/// Answer counters enforcing a [PjLinkSearchRateLimit](self::PjLinkSearchRateLimit).
+#[cfg(feature = "server")]
+#[derive(Debug, Default)]
+pub(crate) struct PjLinkSearchRateLimiter {
+    state: Mutex<PjLinkSearchRateLimiterState>,
+}
+
+#[cfg(feature = "server")]
+#[derive(Debug, Default)]
+struct PjLinkSearchRateLimiterState {
+    last_answered_at: HashMap<IpAddr, Instant>,
+    window_started_at: Option<Instant>,
+    window_answers: u32,
+}
+
+#[cfg(feature = "server")]
+impl PjLinkSearchRateLimiter {
+    /// Returns `true`, counting the answer, if a search request from
+    /// `origin` may be answered now.
+    pub(crate) fn try_answer(&self, limit: &PjLinkSearchRateLimit, origin: IpAddr) -> bool {
+        self.try_answer_at(limit, origin, Instant::now())
+    }
+
+    fn try_answer_at(&self, limit: &PjLinkSearchRateLimit, origin: IpAddr, now: Instant) -> bool {
+        if limit.per_source_interval.is_none() && limit.max_per_second.is_none() {
+            return true;
+        }
+
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        if let Some(max_per_second) = limit.max_per_second {
+            let is_window_expired = state.window_started_at
+                .is_none_or(|window_started_at| now.saturating_duration_since(window_started_at) >= Duration::from_secs(1));
+            if is_window_expired {
+                state.window_started_at = Option::Some(now);
+                state.window_answers = 0;
+            }
+            if state.window_answers >= max_per_second {
+                return false;
+            }
+        }
+
+        if let Some(per_source_interval) = limit.per_source_interval {
+            let is_too_soon = state.last_answered_at.get(&origin)
+                .is_some_and(|last_answered_at| now.saturating_duration_since(*last_answered_at) < per_source_interval);
+            if is_too_soon {
+                return false;
+            }
+
+            if state.last_answered_at.len() >= PJLINK_SEARCH_RATE_LIMITER_MAX_SOURCES {
+                state.last_answered_at
+                    .retain(|_, last_answered_at| now.saturating_duration_since(*last_answered_at) < per_source_interval);
+            }
+            state.last_answered_at.insert(origin, now);
+        }
+
+        state.window_answers += 1;
+        true
+    }
+}
+
+/// Reason a UDP datagram was not answered, sent in
+/// [PjLinkServerEvent::UdpDatagramRejected](crate::PjLinkServerEvent::UdpDatagramRejected).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PjLinkDatagramRejection {
+    /// The datagram has no terminator, or data after it
+    MisplacedTerminator,
+    /// The datagram is terminated, but isn't as long as `%2SRCH`
+    InvalidLength(usize),
+    /// The datagram isn't a search request
+    UnknownCommand,
+    /// The datagram was sent from port 0, which can't be answered
+    InvalidSourcePort,
+    /// The origin is outside [search_allowed_networks](crate::PjLinkListenerOptions::search_allowed_networks)
+    OriginNotAllowed,
+    /// Answering would exceed [search_rate_limit](crate::PjLinkListenerOptions::search_rate_limit)
+    RateLimited,
+}
+
+impl fmt::Display for PjLinkDatagramRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PjLinkDatagramRejection::MisplacedTerminator => write!(f, "terminator is missing or not at the end"),
+            PjLinkDatagramRejection::InvalidLength(length) => write!(f, "invalid length {}", length),
+            PjLinkDatagramRejection::UnknownCommand => write!(f, "not a search request"),
+            PjLinkDatagramRejection::InvalidSourcePort => write!(f, "source port is 0"),
+            PjLinkDatagramRejection::OriginNotAllowed => write!(f, "origin is outside allowed networks"),
+            PjLinkDatagramRejection::RateLimited => write!(f, "search rate limit exceeded"),
+        }
+    }
+}
+
+/// Checks that `datagram` is exactly a `%2SRCH` request from an answerable
+/// origin.
+#[cfg(feature = "server")]
+pub(crate) fn validate_search_datagram(datagram: &[u8], origin: &SocketAddr) -> Result<(), PjLinkDatagramRejection> {
+    if origin.port() == 0 {
+        return Err(PjLinkDatagramRejection::InvalidSourcePort);
+    }
+    if datagram.iter().position(|byte| *byte == PJLINK_TERMINATOR) != datagram.len().checked_sub(1) {
+        return Err(PjLinkDatagramRejection::MisplacedTerminator);
+    }
+    if datagram.len() != PJLINK_BROADCAST_SEARCH_START.len() {
+        return Err(PjLinkDatagramRejection::InvalidLength(datagram.len()));
+    }
+    if datagram != PJLINK_BROADCAST_SEARCH_START {
+        return Err(PjLinkDatagramRejection::UnknownCommand);
+    }
+
+    Ok(())
+}
+
+/// Encodes the `%2ACKN=<mac>\r` answer to a search request in place, so
+/// answering doesn't allocate.
+#[cfg(feature = "server")]
+pub(crate) fn encode_search_response(mac_address: [u8; 6]) -> [u8; PJLINK_MAX_BROADCAST_BUFFER_SIZE] {
+    const HEX_DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+    let mut datagram = [b':'; PJLINK_MAX_BROADCAST_BUFFER_SIZE];
+
+    datagram[0] = PJLINK_HEADER;
+    datagram[1..6].copy_from_slice(PJLINK_BROADCAST_MESSAGE_ACKN);
+    datagram[6] = PJLINK_RESPONSE_SEPARATOR;
+    for (index, octet) in mac_address.iter().enumerate() {
+        datagram[7 + index * 3] = HEX_DIGITS[usize::from(octet >> 4)];
+        datagram[8 + index * 3] = HEX_DIGITS[usize::from(octet & 0x0f)];
+    }
+    datagram[PJLINK_MAX_BROADCAST_BUFFER_SIZE - 1] = PJLINK_TERMINATOR;
+
+    datagram
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_sets_multicast_ttl_and_target() {
+        let multicast = PjLinkMulticastGroup { group: Ipv4Addr::new(239, 255, 43, 52), interface: Ipv4Addr::UNSPECIFIED, ttl: 4 };
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+
+        multicast.set_ttl(&socket).unwrap();
+        assert_eq!(socket.multicast_ttl_v4().unwrap(), 4);
+        assert_eq!(multicast.target(4352).to_socket_addr(), "239.255.43.52:4352".parse().unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "server")]
+    fn it_accepts_only_exact_search_requests() {
+        let origin: SocketAddr = "192.168.0.10:4352".parse().unwrap();
+        assert_eq!(validate_search_datagram(b"%2SRCH\r", &origin), Ok(()));
+        assert_eq!(validate_search_datagram(b"%2SRCH", &origin), Err(PjLinkDatagramRejection::MisplacedTerminator));
+        assert_eq!(validate_search_datagram(b"%2SRCH\r\0", &origin), Err(PjLinkDatagramRejection::MisplacedTerminator));
+        assert_eq!(validate_search_datagram(b"%2SRCH \r", &origin), Err(PjLinkDatagramRejection::InvalidLength(8)));
+        assert_eq!(validate_search_datagram(b"%2LKUP\r", &origin), Err(PjLinkDatagramRejection::UnknownCommand));
+        assert_eq!(
+            validate_search_datagram(b"%2SRCH\r", &"192.168.0.10:0".parse().unwrap()),
+            Err(PjLinkDatagramRejection::InvalidSourcePort)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "server")]
+    fn it_limits_search_answers() {
+        let limiter = PjLinkSearchRateLimiter::default();
+        let limit = PjLinkSearchRateLimit { per_source_interval: Some(Duration::from_secs(1)), max_per_second: Some(2) };
+        let started_at = Instant::now();
+        let first: IpAddr = "192.168.0.10".parse().unwrap();
+        let second: IpAddr = "192.168.0.11".parse().unwrap();
+        let third: IpAddr = "192.168.0.12".parse().unwrap();
+
+        assert!(limiter.try_answer_at(&limit, first, started_at));
+        assert!(!limiter.try_answer_at(&limit, first, started_at + Duration::from_millis(500)));
+        assert!(limiter.try_answer_at(&limit, second, started_at + Duration::from_millis(500)));
+        assert!(!limiter.try_answer_at(&limit, third, started_at + Duration::from_millis(600)));
+        assert!(limiter.try_answer_at(&limit, first, started_at + Duration::from_millis(1000)));
+        assert!(limiter.try_answer_at(&limit, third, started_at + Duration::from_millis(1100)));
+        assert!(PjLinkSearchRateLimiter::default().try_answer(&PjLinkSearchRateLimit::default(), first));
+    }
+
+    #[test]
+    #[cfg(feature = "server")]
+    fn it_encodes_search_responses() {
+        assert_eq!(&encode_search_response([0x00, 0x1a, 0x2b, 0x3c, 0x4d, 0xfe]), b"%2ACKN=00:1A:2B:3C:4D:FE\r");
+    }
+
+    #[test]
+    fn it_resolves_search_response_port() {
+        let origin: SocketAddr = "192.168.0.10:50123".parse().unwrap();
+        assert_eq!(PjLinkSearchResponsePort::Listener.resolve(&origin, 4352), 4352);
+        assert_eq!(PjLinkSearchResponsePort::Origin.resolve(&origin, 4352), 50123);
+        assert_eq!(PjLinkSearchResponsePort::Fixed(14352).resolve(&origin, 4352), 14352);
+    }
+}
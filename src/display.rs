@@ -0,0 +1,213 @@
+//! [Debug](std::fmt::Debug) and [Display](std::fmt::Display) implementations
+//! of protocol types. Display renders the wire form, without terminator.
+
+use std::fmt;
+
+use crate::{
+    PjLinkCommand, PjLinkFreezeCommandParameter, PjLinkInputCommandParameter, PjLinkInputCommandStatus,
+    PjLinkMuteCommandParameter, PjLinkMuteCommandStatus, PjLinkPowerCommandParameter, PjLinkRawPayload, PjLinkRawPayloadRef, PjLinkResponse,
+    PjLinkVolumeCommandParameter, PJLINK_HEADER, PJLINK_QUERY,
+};
+
+/// Renders `%1POWR ?` or `%1POWR=1`.
+impl fmt::Display for PjLinkRawPayload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_payload_ref().fmt(f)
+    }
+}
+
+impl fmt::Debug for PjLinkRawPayload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_payload_ref().debug_fields(f.debug_struct("PjLinkRawPayload"))
+    }
+}
+
+/// Renders `%1POWR ?` or `%1POWR=1`.
+impl fmt::Display for PjLinkRawPayloadRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}{}{}",
+            PJLINK_HEADER as char,
+            String::from_utf8_lossy(&self.command_body_with_class),
+            self.separator as char,
+            String::from_utf8_lossy(self.transmission_parameter)
+        )
+    }
+}
+
+impl fmt::Debug for PjLinkRawPayloadRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.debug_fields(f.debug_struct("PjLinkRawPayloadRef"))
+    }
+}
+
+impl PjLinkRawPayloadRef<'_> {
+    fn debug_fields(&self, mut debug_struct: fmt::DebugStruct<'_, '_>) -> fmt::Result {
+        debug_struct
+            .field("command_body_with_class", &String::from_utf8_lossy(&self.command_body_with_class))
+            .field("separator", &(self.separator as char))
+            .field("transmission_parameter", &String::from_utf8_lossy(self.transmission_parameter))
+            .finish()
+    }
+}
+
+/// Renders the response transmission parameter, like `OK`, `ERR2` or `1`.
+impl fmt::Display for PjLinkResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let PjLinkResponse::Single(value) = self {
+            return write!(f, "{}", *value as char);
+        }
+
+        f.write_str(&String::from_utf8_lossy(self.transmission_parameter()))
+    }
+}
+
+impl fmt::Debug for PjLinkResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PjLinkResponse::Ok => f.write_str("Ok"),
+            PjLinkResponse::Undefined => f.write_str("Undefined"),
+            PjLinkResponse::OutOfParameter => f.write_str("OutOfParameter"),
+            PjLinkResponse::UnavailableTime => f.write_str("UnavailableTime"),
+            PjLinkResponse::ProjectorOrDisplayFailure => f.write_str("ProjectorOrDisplayFailure"),
+            PjLinkResponse::Single(value) => f.debug_tuple("Single").field(&(*value as char)).finish(),
+            PjLinkResponse::Multiple(value) => f.debug_tuple("Multiple").field(&String::from_utf8_lossy(value)).finish(),
+            PjLinkResponse::Empty => f.write_str("Empty"),
+        }
+    }
+}
+
+/// Renders the command line, like `%1POWR 1`. Unknown parameters and
+/// commands, which can't be rendered, are described between parentheses.
+impl fmt::Display for PjLinkCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let query = Option::Some(vec![PJLINK_QUERY]);
+        let (command_body_with_class, transmission_parameter): (&str, Option<Vec<u8>>) = match self {
+            PjLinkCommand::Search2 => return f.write_str("%2SRCH"),
+            PjLinkCommand::Power1(parameter) => ("1POWR", match parameter {
+                PjLinkPowerCommandParameter::Off => Option::Some(vec![b'0']),
+                PjLinkPowerCommandParameter::On => Option::Some(vec![b'1']),
+                PjLinkPowerCommandParameter::Query => query,
+                PjLinkPowerCommandParameter::Unknown => Option::None,
+            }),
+            PjLinkCommand::Input1(parameter) => ("1INPT", input_parameter(parameter)),
+            PjLinkCommand::Input2(parameter) => ("2INPT", input_parameter(parameter)),
+            PjLinkCommand::AvMute1(parameter) => ("1AVMT", match parameter {
+                PjLinkMuteCommandParameter::Video(mute) => Option::Some(vec![PjLinkMuteCommandStatus::Video, mute_status(*mute)]),
+                PjLinkMuteCommandParameter::Audio(mute) => Option::Some(vec![PjLinkMuteCommandStatus::Audio, mute_status(*mute)]),
+                PjLinkMuteCommandParameter::AudioAndVideo(mute) => {
+                    Option::Some(vec![PjLinkMuteCommandStatus::AudioAndVideo, mute_status(*mute)])
+                }
+                PjLinkMuteCommandParameter::Query => query,
+                PjLinkMuteCommandParameter::Unknown => Option::None,
+            }),
+            PjLinkCommand::ErrorStatus1 => ("1ERST", query),
+            PjLinkCommand::Lamp1 => ("1LAMP", query),
+            PjLinkCommand::InputTogglingList1 => ("1INST", query),
+            PjLinkCommand::InputTogglingList2 => ("2INST", query),
+            PjLinkCommand::Name1 => ("1NAME", query),
+            PjLinkCommand::InfoManufacturer1 => ("1INF1", query),
+            PjLinkCommand::InfoProductName1 => ("1INF2", query),
+            PjLinkCommand::InfoOther1 => ("1INFO", query),
+            PjLinkCommand::Class1 => ("1CLSS", query),
+            PjLinkCommand::SerialNumber2 => ("2SNUM", query),
+            PjLinkCommand::SoftwareVersion2 => ("2SVER", query),
+            PjLinkCommand::InputTerminalName2(parameter) => ("2INNM", input_parameter(parameter).map(|input| {
+                let mut transmission_parameter = vec![PJLINK_QUERY];
+                transmission_parameter.extend(input);
+                transmission_parameter
+            })),
+            PjLinkCommand::InputResolution2 => ("2IRES", query),
+            PjLinkCommand::RecommendResolution2 => ("2RRES", query),
+            PjLinkCommand::FilterUsageTime2 => ("2FILT", query),
+            PjLinkCommand::LampReplacementModelNumber2 => ("2RLMP", query),
+            PjLinkCommand::FilterReplacementModelNumber2 => ("2RFIL", query),
+            PjLinkCommand::SpeakerVolumeAdjustment2(parameter) => ("2SVOL", volume_parameter(parameter)),
+            PjLinkCommand::MicrophoneVolumeAdjustment2(parameter) => ("2MVOL", volume_parameter(parameter)),
+            PjLinkCommand::Freeze2(parameter) => ("2FREZ", match parameter {
+                PjLinkFreezeCommandParameter::Freeze => Option::Some(vec![b'1']),
+                PjLinkFreezeCommandParameter::Unfreeze => Option::Some(vec![b'0']),
+                PjLinkFreezeCommandParameter::Query => query,
+                PjLinkFreezeCommandParameter::Unknown => Option::None,
+            }),
+            PjLinkCommand::Unknown => return f.write_str("(unknown command)"),
+        };
+
+        match transmission_parameter {
+            Some(transmission_parameter) => write!(
+                f,
+                "%{} {}",
+                command_body_with_class,
+                String::from_utf8_lossy(&transmission_parameter)
+            ),
+            None => write!(f, "%{} (unknown parameter)", command_body_with_class),
+        }
+    }
+}
+
+fn input_parameter(parameter: &PjLinkInputCommandParameter) -> Option<Vec<u8>> {
+    let (input_type, input_number) = match parameter {
+        PjLinkInputCommandParameter::RGB(number) => (PjLinkInputCommandStatus::RGB, number),
+        PjLinkInputCommandParameter::Video(number) => (PjLinkInputCommandStatus::Video, number),
+        PjLinkInputCommandParameter::Digital(number) => (PjLinkInputCommandStatus::Digital, number),
+        PjLinkInputCommandParameter::Storage(number) => (PjLinkInputCommandStatus::Storage, number),
+        PjLinkInputCommandParameter::Network(number) => (PjLinkInputCommandStatus::Network, number),
+        PjLinkInputCommandParameter::Internal(number) => (PjLinkInputCommandStatus::Internal, number),
+        PjLinkInputCommandParameter::Query => return Option::Some(vec![PJLINK_QUERY]),
+        PjLinkInputCommandParameter::Unknown => return Option::None,
+    };
+
+    Option::Some(vec![input_type, *input_number])
+}
+
+fn volume_parameter(parameter: &PjLinkVolumeCommandParameter) -> Option<Vec<u8>> {
+    match parameter {
+        PjLinkVolumeCommandParameter::Increase => Option::Some(vec![b'1']),
+        PjLinkVolumeCommandParameter::Decrase => Option::Some(vec![b'0']),
+        PjLinkVolumeCommandParameter::Unknown => Option::None,
+    }
+}
+
+fn mute_status(mute: bool) -> u8 {
+    match mute {
+        true => PjLinkMuteCommandStatus::Mute,
+        false => PjLinkMuteCommandStatus::NonMute,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_renders_wire_form() {
+        let raw_command = PjLinkRawPayload::new_command(*b"1INPT", vec![b'3', b'1']);
+        assert_eq!(raw_command.to_string(), "%1INPT 31");
+        assert_eq!(PjLinkCommand::from_raw_payload(&raw_command).to_string(), "%1INPT 31");
+        assert_eq!(PjLinkRawPayload::new_response(*b"1POWR", vec![b'1']).to_string(), "%1POWR=1");
+
+        let raw_command = PjLinkRawPayload::new_command(*b"2INNM", vec![PJLINK_QUERY, b'6', b'1']);
+        assert_eq!(PjLinkCommand::from_raw_payload(&raw_command).to_string(), "%2INNM ?61");
+        let raw_command = PjLinkRawPayload::new_command(*b"1AVMT", vec![b'9', b'9']);
+        assert_eq!(PjLinkCommand::from_raw_payload(&raw_command).to_string(), "%1AVMT (unknown parameter)");
+
+        assert_eq!(PjLinkResponse::OutOfParameter.to_string(), "ERR2");
+        assert_eq!(PjLinkResponse::Single(b'1').to_string(), "1");
+        assert_eq!(PjLinkResponse::Empty.to_string(), "");
+    }
+
+    #[test]
+    fn it_formats_debug_readably() {
+        let raw_command = PjLinkRawPayload::new_command(*b"1POWR", vec![PJLINK_QUERY]);
+        assert_eq!(
+            format!("{:?}", raw_command),
+            "PjLinkRawPayload { command_body_with_class: \"1POWR\", separator: ' ', transmission_parameter: \"?\" }"
+        );
+        assert_eq!(format!("{:?}", PjLinkResponse::Single(b'1')), "Single('1')");
+        assert_eq!(
+            format!("{:?}", PjLinkCommand::from_raw_payload(&raw_command)),
+            "Power1(Query)"
+        );
+    }
+}
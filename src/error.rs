@@ -0,0 +1,163 @@
+//! Error type of fallible `pjlink-bridge` operations.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+
+/// Error returned by fallible `pjlink-bridge` operations, like starting a
+/// server, sending status notifications or talking to a server with the
+/// test client.
+///
+/// ## Examples
+#[cfg_attr(feature = "server", doc = "```")]
+#[cfg_attr(not(feature = "server"), doc = "```ignore")]
+/// use std::net::TcpListener;
+/// use std::sync::{Arc, Mutex};
+/// use pjlink_bridge::*;
+///
+/// struct Projector;
+///
+/// impl PjLinkHandler for Projector {
+///     fn get_password(&mut self, _connection_id: &u64) -> Option<String> {
+///         None
+///     }
+///
+///     fn handle_command(&mut self, _command: PjLinkCommand, _raw_command: &PjLinkRawPayload, _connection_id: &u64) -> PjLinkResponse {
+///         PjLinkResponse::Undefined
+///     }
+/// }
+///
+/// let taken = TcpListener::bind("127.0.0.1:0").unwrap();
+/// let port = taken.local_addr().unwrap().port().to_string();
+///
+/// match PjLinkServer::listen_tcp_only(Arc::new(Mutex::new(Projector)), String::from("127.0.0.1"), port) {
+///     Err(PjLinkError::Bind { .. }) => {}
+///     _ => panic!("port should be in use"),
+/// }
+///
+/// assert!(matches!(decode_line(b"%1PO"), Err(PjLinkError::Parse { .. })));
+/// ```
+#[derive(Debug)]
+pub enum PjLinkError {
+    /// A listener socket couldn't be bound.
+    Bind {
+        /// Address the socket was bound to.
+        address: String,
+        source: io::Error,
+    },
+    /// Reading from or writing to a socket failed.
+    Io(io::Error),
+    /// A received line isn't a valid PJLink command or response.
+    Parse {
+        /// Received line, without terminator.
+        line: Vec<u8>,
+        reason: &'static str,
+    },
+    /// A received line has a class other than `1` or `2`. Listeners answer
+    /// it with `ERR1`, like any undefined command.
+    InvalidClass {
+        /// Received line, without terminator.
+        line: Vec<u8>,
+        class: u8,
+    },
+    /// Authentication failed (`PJLINK ERRA`), or the server requires a
+    /// password and none was provided.
+    Authentication(&'static str),
+    /// A server thread stopped by panicking.
+    Shutdown {
+        /// Name of the thread.
+        thread: String,
+    },
+}
+
+impl PjLinkError {
+    pub(crate) fn bind<A: fmt::Display>(address: A, source: io::Error) -> PjLinkError {
+        PjLinkError::Bind { address: address.to_string(), source }
+    }
+
+    pub(crate) fn parse(line: &[u8], reason: &'static str) -> PjLinkError {
+        PjLinkError::Parse { line: line.to_vec(), reason }
+    }
+}
+
+impl fmt::Display for PjLinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PjLinkError::Bind { address, source } => write!(f, "failed to bind {}: {}", address, source),
+            PjLinkError::Io(e) => write!(f, "PJLink I/O error: {}", e),
+            PjLinkError::Parse { line, reason } => write!(
+                f,
+                "invalid PJLink line ({}): {:?}",
+                reason,
+                String::from_utf8_lossy(line)
+            ),
+            PjLinkError::InvalidClass { line, class } => write!(
+                f,
+                "invalid PJLink class {:?}: {:?}",
+                *class as char,
+                String::from_utf8_lossy(line)
+            ),
+            PjLinkError::Authentication(reason) => write!(f, "PJLink authentication failed: {}", reason),
+            PjLinkError::Shutdown { thread } => write!(f, "thread {} stopped unexpectedly", thread),
+        }
+    }
+}
+
+impl Error for PjLinkError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PjLinkError::Bind { source, .. } => Option::Some(source),
+            PjLinkError::Io(e) => Option::Some(e),
+            _ => Option::None,
+        }
+    }
+}
+
+/// Frame that failed parsing, as reported to
+/// [PjLinkHandler::handle_invalid](crate::PjLinkHandler::handle_invalid).
+#[derive(Debug)]
+pub struct PjLinkInvalidFrameContext<'a> {
+    /// Connection ID
+    pub connection_id: u64,
+    /// Controller address, if it's still known
+    pub peer_addr: Option<SocketAddr>,
+    /// Why the frame is invalid
+    pub error: &'a PjLinkError,
+}
+
+/// What the listener does after a frame fails parsing. Returned by
+/// [PjLinkHandler::handle_invalid](crate::PjLinkHandler::handle_invalid).
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum PjLinkInvalidFrameAction {
+    /// Answers frames with an invalid class with `ERR1`, and closes the
+    /// connection on other invalid frames.
+    #[default]
+    Default,
+    /// Closes the connection.
+    Close,
+    /// Drops the frame and waits for the next one.
+    Ignore,
+    /// Sends a raw line, without terminator, and waits for the next frame.
+    Respond(Vec<u8>),
+}
+
+impl From<io::Error> for PjLinkError {
+    fn from(error: io::Error) -> PjLinkError {
+        PjLinkError::Io(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_exposes_the_io_source() {
+        let error = PjLinkError::bind("127.0.0.1:4352", io::Error::from(io::ErrorKind::AddrInUse));
+
+        assert!(error.to_string().starts_with("failed to bind 127.0.0.1:4352"));
+        let source = error.source().unwrap().downcast_ref::<io::Error>().unwrap();
+        assert_eq!(source.kind(), io::ErrorKind::AddrInUse);
+    }
+}
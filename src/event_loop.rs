@@ -0,0 +1,665 @@
+//! Single-threaded listener multiplexing every connection with `mio`
+//! (`event-loop` feature).
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::net::SocketAddr;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::sync::atomic::AtomicU64;
+use std::time::{Duration, Instant};
+use log::{info, debug, warn};
+use mio::net::{TcpListener, TcpStream};
+#[cfg(feature = "discovery")]
+use mio::net::UdpSocket;
+use mio::{Events, Interest, Poll, Token};
+use socket2::SockRef;
+
+use crate::{
+    PjLinkConnectionHandler, PjLinkError, PjLinkFrameDecoder, PjLinkFramingMode, PjLinkHandlerShared, PjLinkListener, PjLinkListenerOptions,
+    PjLinkListenerShared, PjLinkServer, PjLinkServerHandle, spawn_named_thread,
+};
+use crate::handle::PjLinkSharedThread;
+#[cfg(feature = "discovery")]
+use crate::PJLINK_MAX_BROADCAST_BUFFER_SIZE;
+use crate::session::{PjLinkSession, PjLinkSessionStep};
+use crate::stats::PjLinkConnectionPermit;
+
+/// Tokens of each listener's sockets: listener `n` uses tokens `2n` (TCP)
+/// and `2n + 1` (UDP); connection tokens follow the last listener's.
+const PJLINK_EVENT_LOOP_TOKENS_PER_LISTENER: usize = 2;
+const PJLINK_EVENT_LOOP_TCP_TOKEN: usize = 0;
+#[cfg(feature = "discovery")]
+const PJLINK_EVENT_LOOP_UDP_TOKEN: usize = 1;
+/// Pending output past which a connection isn't read from until the
+/// controller reads its responses.
+const PJLINK_EVENT_LOOP_MAX_OUTPUT_SIZE: usize = 64 * 1024;
+
+/// Sockets of a listener served by the event loop.
+struct PjLinkEventLoopListener<'l, 'a> {
+    listener: &'l PjLinkListener<'a>,
+    connection_handler: PjLinkConnectionHandler,
+    tcp_listener: TcpListener,
+    index: usize,
+    #[cfg(feature = "discovery")]
+    udp_socket: Option<UdpSocket>,
+    #[cfg(feature = "discovery")]
+    udp_port: u16,
+}
+
+impl<'l, 'a> PjLinkEventLoopListener<'l, 'a> {
+    /// Puts the listener sockets in non-blocking mode and registers them
+    /// with `poll`, as the `index`-th listener.
+    fn register(listener: &'l PjLinkListener<'a>, poll: &Poll, index: usize) -> Result<PjLinkEventLoopListener<'l, 'a>, PjLinkError> {
+        let first_token = index * PJLINK_EVENT_LOOP_TOKENS_PER_LISTENER;
+
+        let tcp_listener = listener.tcp_listener.try_clone()?;
+        tcp_listener.set_nonblocking(true)?;
+        let mut tcp_listener = TcpListener::from_std(tcp_listener);
+        poll.registry().register(&mut tcp_listener, Token(first_token + PJLINK_EVENT_LOOP_TCP_TOKEN), Interest::READABLE)?;
+
+        #[cfg(feature = "discovery")]
+        let udp_socket = match listener.current_udp_socket() {
+            Some(socket) if !listener.shared_options.is_class_1_only() => {
+                let socket = socket.try_clone()?;
+                socket.set_nonblocking(true)?;
+                socket.set_broadcast(true)?;
+                let mut socket = UdpSocket::from_std(socket);
+                poll.registry().register(&mut socket, Token(first_token + PJLINK_EVENT_LOOP_UDP_TOKEN), Interest::READABLE)?;
+                listener.udp_health.set_running(true);
+                Option::Some(socket)
+            }
+            _ => Option::None,
+        };
+        #[cfg(feature = "discovery")]
+        let udp_port = match &udp_socket {
+            Some(socket) => socket.local_addr()?.port(),
+            None => 0,
+        };
+
+        info!("Running TCP event loop on {}", listener.tcp_listener.local_addr()?);
+
+        Ok(PjLinkEventLoopListener {
+            listener,
+            connection_handler: listener.connection_handler(),
+            tcp_listener,
+            index,
+            #[cfg(feature = "discovery")]
+            udp_socket,
+            #[cfg(feature = "discovery")]
+            udp_port,
+        })
+    }
+
+    /// Accepts pending connections, registering them with `poll`.
+    fn accept(&self, poll: &Poll, connections: &mut HashMap<Token, PjLinkEventLoopConnection>, next_token: &mut usize) {
+        loop {
+            let (mut stream, peer_addr) = match self.tcp_listener.accept() {
+                Ok(accepted) => accepted,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    debug!("Error on received connection! {}", e);
+                    self.listener.tcp_health.record_error(&e);
+                    break;
+                }
+            };
+            let Some(permit) = self.connection_handler.try_open_connection(Option::Some(peer_addr)) else {
+                continue;
+            };
+            if let Err(e) = self.listener.shared_options.tcp.apply_to_socket(&SockRef::from(&stream)) {
+                debug!("Failed to apply TCP options to connection! {}", e);
+            }
+
+            let token = Token(*next_token);
+            *next_token += 1;
+            if let Err(e) = poll.registry().register(&mut stream, token, Interest::READABLE | Interest::WRITABLE) {
+                debug!("Failed to register connection! {}", e);
+                continue;
+            }
+
+            let connection_handler = &self.connection_handler;
+            let mut output = Vec::new();
+            let session = PjLinkSession::open(connection_handler, connection_handler.next_connection_id(), Option::Some(peer_addr), &mut output);
+            let mut connection = PjLinkEventLoopConnection {
+                stream,
+                listener: self.index,
+                session,
+                decoder: match connection_handler.options.parameter_limit.max_frame_length() {
+                    Some(max_frame_length) => PjLinkFrameDecoder::with_max_frame_length(max_frame_length),
+                    None => PjLinkFrameDecoder::new(),
+                },
+                frame: Vec::new(),
+                output,
+                frame_started_at: Option::None,
+                is_read_pending: false,
+                is_closing: false,
+                _permit: permit,
+            };
+
+            if connection.send() {
+                connections.insert(token, connection);
+            } else if let Err(e) = poll.registry().deregister(&mut connection.stream) {
+                debug!("Failed to deregister connection! {}", e);
+            }
+        }
+    }
+
+    /// Answers pending datagrams.
+    #[cfg(feature = "discovery")]
+    fn receive_datagrams(&self) {
+        let socket = match &self.udp_socket {
+            Some(socket) => socket,
+            None => return,
+        };
+        let mut input_command_buffer = [0u8; PJLINK_MAX_BROADCAST_BUFFER_SIZE];
+        loop {
+            match socket.recv_from(&mut input_command_buffer) {
+                Ok((length, origin)) => {
+                    self.listener.udp_health.record_success();
+                    self.connection_handler.handle_datagram(
+                        &input_command_buffer[..length],
+                        origin,
+                        self.udp_port,
+                        &self.listener.udp_health,
+                        &self.listener.search_limiter,
+                        |response, destination| socket.send_to(response, destination),
+                    );
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    self.listener.udp_health.record_error(&e);
+                    debug!("UDP message handling failed: {}", e);
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Returns when `connection` must be closed, with this listener's
+    /// frame and handshake timeouts.
+    fn expires_at(&self, connection: &PjLinkEventLoopConnection) -> Option<Instant> {
+        connection.expires_at(self.listener.shared_options.frame_timeout, self.listener.shared_options.handshake_timeout)
+    }
+}
+
+/// TCP connection served by the event loop.
+struct PjLinkEventLoopConnection {
+    stream: TcpStream,
+    /// Index of the listener that accepted the connection
+    listener: usize,
+    session: PjLinkSession,
+    decoder: PjLinkFrameDecoder,
+    frame: Vec<u8>,
+    output: Vec<u8>,
+    frame_started_at: Option<Instant>,
+    /// The socket may have unread bytes: it hasn't returned `WouldBlock`
+    /// since it was last reported readable.
+    is_read_pending: bool,
+    is_closing: bool,
+    /// Slot of [max_connections](crate::PjLinkListenerOptions::max_connections),
+    /// released when the connection is dropped
+    _permit: PjLinkConnectionPermit,
+}
+
+impl PjLinkEventLoopConnection {
+    /// Returns `true` if the socket may have unread bytes and responses
+    /// already queued aren't piling up.
+    fn is_ready_to_receive(&self) -> bool {
+        self.is_read_pending && !self.is_closing && self.output.len() < PJLINK_EVENT_LOOP_MAX_OUTPUT_SIZE
+    }
+
+    /// Reads one chunk and handles the complete frames in it, so pipelining
+    /// controllers can't hold the event loop thread. Returns `false` if the
+    /// connection was closed by the controller.
+    fn receive(&mut self, connection_handler: &PjLinkConnectionHandler) -> bool {
+        match self.decoder.read_from(&mut self.stream) {
+            Ok(0) => return false,
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => self.is_read_pending = false,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => {
+                debug!("Failed to read command! {}, {}", self.session.log_context(), e);
+                return false;
+            }
+        }
+
+        let mut handled_frames = 0;
+        while !self.is_closing {
+            if self.decoder.is_frame_too_long() {
+                debug!("Failed to read command! {}, {}", self.session.log_context(), self.decoder.frame_too_long_error());
+                return false;
+            }
+            let frame = match self.decoder.next_frame() {
+                Some(frame) => frame,
+                None => break,
+            };
+            self.frame.clear();
+            self.frame.extend_from_slice(frame);
+
+            let session = &mut self.session;
+            let frame = &mut self.frame;
+            let output = &mut self.output;
+            // Handler panics close this connection only, like in a
+            // connection thread.
+            let step = panic::catch_unwind(AssertUnwindSafe(|| session.handle_frame(connection_handler, frame, output)))
+                .unwrap_or(PjLinkSessionStep::Close);
+
+            self.is_closing = step == PjLinkSessionStep::Close;
+            handled_frames += 1;
+        }
+
+        // The stray LF of a CR LF line isn't the start of the next frame
+        if connection_handler.options.framing == PjLinkFramingMode::Lenient {
+            self.decoder.skip_line_feeds();
+        }
+        self.frame_started_at = match self.decoder.pending_len() {
+            0 => Option::None,
+            _ if handled_frames > 0 => Option::Some(Instant::now()),
+            _ => self.frame_started_at.or_else(|| Option::Some(Instant::now())),
+        };
+
+        true
+    }
+
+    /// Writes as much pending output as the socket accepts. Returns `false`
+    /// if writing failed.
+    fn send(&mut self) -> bool {
+        while !self.output.is_empty() {
+            match self.stream.write(&self.output) {
+                Ok(0) => return false,
+                Ok(size) => {
+                    self.session.record_sent(size);
+                    self.output.drain(..size);
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return true,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    warn!("Failed to write response! {}, {}", self.session.log_context(), e);
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Returns when the connection must be closed, if a frame is incomplete
+    /// or the handshake isn't complete.
+    fn expires_at(&self, frame_timeout: Option<Duration>, handshake_timeout: Option<Duration>) -> Option<Instant> {
+        let frame_expires_at = frame_timeout.zip(self.frame_started_at)
+            .map(|(frame_timeout, frame_started_at)| frame_started_at + frame_timeout);
+
+        match (frame_expires_at, self.session.handshake_deadline(handshake_timeout)) {
+            (Some(frame_expires_at), Some(handshake_deadline)) => Option::Some(frame_expires_at.min(handshake_deadline)),
+            (frame_expires_at, handshake_deadline) => frame_expires_at.or(handshake_deadline),
+        }
+    }
+
+    /// Receives if ready, then sends pending output. Returns `false` if the
+    /// connection must be closed.
+    fn serve(&mut self, connection_handler: &PjLinkConnectionHandler) -> bool {
+        let mut is_open = true;
+        if self.is_ready_to_receive() {
+            is_open = self.receive(connection_handler);
+        }
+
+        is_open && self.send() && !self.is_finished()
+    }
+
+    /// Returns `true` once closing output has been sent.
+    fn is_finished(&self) -> bool {
+        self.is_closing && self.output.is_empty()
+    }
+}
+
+impl<'a> PjLinkListener<'a> {
+    /// Serves every TCP connection and the UDP socket on the current thread,
+    /// multiplexed with `mio`, instead of spawning a thread per connection.
+    ///
+    /// Meant for embedded gateways serving many mostly-idle controllers.
+    /// The handler runs on the event loop thread, so it should answer
+    /// quickly; slow handlers delay every other connection. Connections are
+    /// read one chunk at a time in turn, and a connection whose controller
+    /// doesn't read its responses isn't read from until it does.
+    ///
+    /// Puts the listener sockets in non-blocking mode, so
+    /// [listen](self::PjLinkListener::listen) and
+    /// [listen_multicast](self::PjLinkListener::listen_multicast) must not be
+    /// used on the same listener. Returns only if polling fails.
+    ///
+    /// Available with the `event-loop` feature.
+    pub fn listen_event_loop(&self) -> Result<(), PjLinkError> {
+        Self::listen_event_loop_many(&[self])
+    }
+
+    /// Serves every listener on the current thread, multiplexed with one
+    /// `mio` poll, like [listen_event_loop](self::PjLinkListener::listen_event_loop).
+    pub(crate) fn listen_event_loop_many(listeners: &[&PjLinkListener<'a>]) -> Result<(), PjLinkError> {
+        let mut poll = Poll::new()?;
+        let mut events = Events::with_capacity(256);
+
+        let mut event_loop_listeners = Vec::with_capacity(listeners.len());
+        for (index, listener) in listeners.iter().enumerate() {
+            event_loop_listeners.push(PjLinkEventLoopListener::register(listener, &poll, index)?);
+        }
+
+        let mut connections: HashMap<Token, PjLinkEventLoopConnection> = HashMap::new();
+        let mut next_token = listeners.len() * PJLINK_EVENT_LOOP_TOKENS_PER_LISTENER;
+
+        loop {
+            let poll_timeout = match connections.values().any(PjLinkEventLoopConnection::is_ready_to_receive) {
+                true => Option::Some(Duration::ZERO),
+                false => connections.values()
+                    .filter_map(|connection| event_loop_listeners[connection.listener].expires_at(connection))
+                    .min()
+                    .map(|expires_at| expires_at.saturating_duration_since(Instant::now())),
+            };
+
+            if let Err(e) = poll.poll(&mut events, poll_timeout) {
+                if e.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(PjLinkError::Io(e));
+            }
+
+            for event in events.iter() {
+                let token = event.token();
+                let index = token.0 / PJLINK_EVENT_LOOP_TOKENS_PER_LISTENER;
+                let event_loop_listener = match event_loop_listeners.get(index) {
+                    Some(event_loop_listener) => event_loop_listener,
+                    None => {
+                        let connection = match connections.get_mut(&token) {
+                            Some(connection) => connection,
+                            None => continue,
+                        };
+
+                        if event.is_readable() {
+                            connection.is_read_pending = true;
+                        }
+                        if !connection.serve(&event_loop_listeners[connection.listener].connection_handler) {
+                            Self::close_event_loop_connection(&poll, &mut connections, token);
+                        }
+                        continue;
+                    }
+                };
+
+                match token.0 % PJLINK_EVENT_LOOP_TOKENS_PER_LISTENER {
+                    PJLINK_EVENT_LOOP_TCP_TOKEN => {
+                        event_loop_listener.accept(&poll, &mut connections, &mut next_token);
+                    }
+                    #[cfg(feature = "discovery")]
+                    PJLINK_EVENT_LOOP_UDP_TOKEN => {
+                        event_loop_listener.receive_datagrams();
+                    }
+                    _ => {}
+                }
+            }
+
+            // Events are edge-triggered: connections with unread bytes left
+            // get another chunk read in turn, without waiting for an event
+            let ready_tokens: Vec<Token> = connections.iter()
+                .filter(|(_, connection)| connection.is_ready_to_receive())
+                .map(|(token, _)| *token)
+                .collect();
+            for token in ready_tokens {
+                let is_open = connections.get_mut(&token)
+                    .is_some_and(|connection| connection.serve(&event_loop_listeners[connection.listener].connection_handler));
+                if !is_open {
+                    Self::close_event_loop_connection(&poll, &mut connections, token);
+                }
+            }
+
+            let now = Instant::now();
+            let expired_tokens: Vec<Token> = connections.iter()
+                .filter(|(_, connection)| event_loop_listeners[connection.listener].expires_at(connection)
+                    .is_some_and(|expires_at| expires_at <= now))
+                .map(|(token, _)| *token)
+                .collect();
+
+            for token in expired_tokens {
+                debug!("Frame or handshake not completed in time, closing! Token: {}", token.0);
+                Self::close_event_loop_connection(&poll, &mut connections, token);
+            }
+        }
+    }
+
+    fn close_event_loop_connection(poll: &Poll, connections: &mut HashMap<Token, PjLinkEventLoopConnection>, token: Token) {
+        if let Some(mut connection) = connections.remove(&token) {
+            debug!("Closing connection! {}", connection.session.log_context());
+            if let Err(e) = poll.registry().deregister(&mut connection.stream) {
+                debug!("Failed to deregister connection! {}", e);
+            }
+        }
+    }
+}
+
+impl PjLinkServer {
+    /// Hosts several projectors in the same process, each one listening on
+    /// its own TCP and UDP address.
+    ///
+    /// Every projector is served by a single `pjlink-event-loop` thread,
+    /// like [listen_event_loop](crate::PjLinkListener::listen_event_loop),
+    /// so a rack of projectors doesn't need threads per projector or
+    /// connection. Handlers run on that thread and should answer quickly.
+    /// All listeners share the same options and connection counter, so
+    /// connection IDs are unique across projectors.
+    ///
+    /// Every address is bound before the thread is started: fails with
+    /// [Bind](crate::PjLinkError::Bind) on the first address that can't be
+    /// bound, releasing the ones bound before it.
+    ///
+    /// **Arguments**:
+    /// * `projectors`: Handler and bind address (TCP and UDP use the same address) of each projector
+    ///
+    /// Available with the `event-loop` feature.
+    pub fn listen_many<'a>(projectors: Vec<(PjLinkHandlerShared, SocketAddr)>) -> Result<Vec<PjLinkServerHandle<'a>>, PjLinkError> {
+        Self::listen_many_with_options(projectors, PjLinkListenerOptions::default())
+    }
+
+    pub fn listen_many_with_options<'a>(
+        projectors: Vec<(PjLinkHandlerShared, SocketAddr)>,
+        options: PjLinkListenerOptions,
+    ) -> Result<Vec<PjLinkServerHandle<'a>>, PjLinkError> {
+        let shared_options = Arc::new(options);
+        let shared_connection_counter = Arc::new(AtomicU64::new(0));
+
+        let bound_projectors = projectors.into_iter().map(|(handler, bind_address)| {
+            let tcp_listener = Self::bind_tcp(bind_address)?;
+            let udp_socket = match shared_options.is_class_1_only() {
+                true => Option::None,
+                false => Option::Some(Self::bind_udp(bind_address)?),
+            };
+            Ok((handler, tcp_listener, udp_socket))
+        }).collect::<Result<Vec<_>, PjLinkError>>()?;
+
+        let listeners: Vec<PjLinkListenerShared<'static>> = bound_projectors.into_iter()
+            .map(|(handler, tcp_listener, udp_socket)| PjLinkListener::new_shared(
+                handler,
+                tcp_listener,
+                udp_socket,
+                shared_options.clone(),
+                shared_connection_counter.clone(),
+            ))
+            .collect();
+
+        let listeners_clone = listeners.clone();
+        let thread = spawn_named_thread(String::from("pjlink-event-loop"), move || {
+            let listeners: Vec<&PjLinkListener> = listeners_clone.iter().map(|listener| listener.as_ref()).collect();
+            if let Err(e) = PjLinkListener::listen_event_loop_many(&listeners) {
+                warn!("Event loop stopped! {}", e);
+            }
+        })?;
+        let thread = Arc::new(PjLinkSharedThread::new(thread));
+
+        Ok(listeners.into_iter().map(|listener| PjLinkServerHandle::shared(listener, thread.clone())).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::net::{TcpListener as StdTcpListener, TcpStream as StdTcpStream};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+    use crate::{PjLinkCommand, PjLinkHandler, PjLinkListenerOptions, PjLinkParameterLimit, PjLinkRawPayload, PjLinkResponse};
+
+    struct PowerHandler;
+
+    impl PjLinkHandler for PowerHandler {
+        fn get_password(&mut self, _connection_id: &u64) -> Option<String> {
+            Option::None
+        }
+
+        fn handle_command(&mut self, _command: PjLinkCommand, _raw_command: &PjLinkRawPayload, _connection_id: &u64) -> PjLinkResponse {
+            PjLinkResponse::Single(b'1')
+        }
+    }
+
+    /// Acknowledges commands only when run on the shared event loop thread
+    struct EventLoopThreadHandler;
+
+    impl PjLinkHandler for EventLoopThreadHandler {
+        fn get_password(&mut self, _connection_id: &u64) -> Option<String> {
+            Option::None
+        }
+
+        fn handle_command(&mut self, _command: PjLinkCommand, _raw_command: &PjLinkRawPayload, _connection_id: &u64) -> PjLinkResponse {
+            match thread::current().name() {
+                Some("pjlink-event-loop") => PjLinkResponse::Ok,
+                _ => PjLinkResponse::Undefined,
+            }
+        }
+    }
+
+    fn read_line(reader: &mut BufReader<StdTcpStream>) -> String {
+        let mut line = Vec::new();
+        reader.read_until(b'\r', &mut line).unwrap();
+        String::from_utf8(line).unwrap()
+    }
+
+    #[test]
+    fn it_serves_many_connections_on_one_thread() {
+        let tcp_listener = StdTcpListener::bind("127.0.0.1:0").unwrap();
+        let address = tcp_listener.local_addr().unwrap();
+        let options = PjLinkListenerOptions { frame_timeout: Some(Duration::from_millis(100)), ..Default::default() };
+        let listener = PjLinkListener::new_with_options(Arc::new(Mutex::new(PowerHandler)), tcp_listener, None, options);
+        thread::spawn(move || listener.listen_event_loop());
+
+        let mut clients: Vec<BufReader<StdTcpStream>> = (0..3)
+            .map(|_| {
+                let stream = StdTcpStream::connect(address).unwrap();
+                stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+                BufReader::new(stream)
+            })
+            .collect();
+
+        for client in clients.iter_mut() {
+            assert_eq!(read_line(client), "PJLINK 0\r");
+            client.get_mut().write_all(b"%1POWR ?\r%1INPT ?\r").unwrap();
+        }
+        for client in clients.iter_mut() {
+            assert_eq!(read_line(client), "%1POWR=1\r");
+            assert_eq!(read_line(client), "%1INPT=1\r");
+        }
+
+        let trickling_client = &mut clients[0];
+        trickling_client.get_mut().write_all(b"%1PO").unwrap();
+        assert_eq!(read_line(trickling_client), "");
+    }
+
+    #[test]
+    fn it_keeps_idle_crlf_connections_open_in_lenient_mode() {
+        let tcp_listener = StdTcpListener::bind("127.0.0.1:0").unwrap();
+        let address = tcp_listener.local_addr().unwrap();
+        let options = PjLinkListenerOptions {
+            frame_timeout: Some(Duration::from_millis(100)),
+            framing: PjLinkFramingMode::Lenient,
+            ..Default::default()
+        };
+        let listener = PjLinkListener::new_with_options(Arc::new(Mutex::new(PowerHandler)), tcp_listener, None, options);
+        thread::spawn(move || listener.listen_event_loop());
+
+        let stream = StdTcpStream::connect(address).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let mut client = BufReader::new(stream);
+        assert_eq!(read_line(&mut client), "PJLINK 0\r");
+        client.get_mut().write_all(b"%1POWR ?\r\n").unwrap();
+        assert_eq!(read_line(&mut client), "%1POWR=1\r");
+
+        thread::sleep(Duration::from_millis(300));
+        client.get_mut().write_all(b"%1POWR ?\r\n").unwrap();
+        assert_eq!(read_line(&mut client), "%1POWR=1\r");
+    }
+
+    #[test]
+    fn it_keeps_serving_other_connections_while_one_floods_frames() {
+        let tcp_listener = StdTcpListener::bind("127.0.0.1:0").unwrap();
+        let address = tcp_listener.local_addr().unwrap();
+        let options = PjLinkListenerOptions { parameter_limit: PjLinkParameterLimit::Unlimited, ..Default::default() };
+        let listener = PjLinkListener::new_with_options(Arc::new(Mutex::new(PowerHandler)), tcp_listener, None, options);
+        thread::spawn(move || listener.listen_event_loop());
+
+        // Never reads its responses, so the listener must stop reading it
+        // instead of buffering everything it sends
+        let flooding_stream = StdTcpStream::connect(address).unwrap();
+        flooding_stream.set_write_timeout(Some(Duration::from_secs(2))).unwrap();
+        let flooding_client = thread::spawn(move || {
+            let flood = b"%1POWR ?\r".repeat(1024 * 1024);
+            let mut flooding_stream = flooding_stream;
+            let mut written = 0;
+            while written < flood.len() {
+                match flooding_stream.write(&flood[written..]) {
+                    Ok(size) => written += size,
+                    Err(_) => break,
+                }
+            }
+            (written, flood.len())
+        });
+
+        thread::sleep(Duration::from_millis(100));
+        let stream = StdTcpStream::connect(address).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let mut client = BufReader::new(stream);
+        assert_eq!(read_line(&mut client), "PJLINK 0\r");
+        client.get_mut().write_all(b"%1POWR ?\r").unwrap();
+        assert_eq!(read_line(&mut client), "%1POWR=1\r");
+
+        let (written, flood_len) = flooding_client.join().unwrap();
+        assert!(written < flood_len);
+    }
+
+    #[test]
+    fn it_serves_every_projector_on_one_shared_thread() {
+        let handles = PjLinkServer::listen_many(vec![
+            (Arc::new(Mutex::new(EventLoopThreadHandler)), "127.0.0.1:0".parse().unwrap()),
+            (Arc::new(Mutex::new(EventLoopThreadHandler)), "127.0.0.1:0".parse().unwrap()),
+        ]).unwrap();
+
+        for handle in &handles {
+            let stream = StdTcpStream::connect(handle.local_tcp_addr().unwrap()).unwrap();
+            stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            let mut client = BufReader::new(stream);
+            assert_eq!(read_line(&mut client), "PJLINK 0\r");
+            client.get_mut().write_all(b"%1POWR 1\r").unwrap();
+            assert_eq!(read_line(&mut client), "%1POWR=OK\r");
+            assert!(handle.is_healthy());
+        }
+    }
+
+    #[test]
+    fn it_releases_bound_projectors_when_a_later_bind_fails() {
+        let occupied = StdTcpListener::bind("127.0.0.1:0").unwrap();
+        let free_address = StdTcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+
+        let result = PjLinkServer::listen_many(vec![
+            (Arc::new(Mutex::new(PowerHandler)), free_address),
+            (Arc::new(Mutex::new(PowerHandler)), occupied.local_addr().unwrap()),
+        ]);
+
+        assert!(matches!(result, Err(PjLinkError::Bind { .. })));
+        assert!(StdTcpListener::bind(free_address).is_ok());
+        assert!(std::net::UdpSocket::bind(free_address).is_ok());
+    }
+}
@@ -0,0 +1,83 @@
+//! Lifecycle events of a listener.
+
+use std::net::SocketAddr;
+use std::sync::mpsc::Sender;
+
+use crate::{PjLinkAuthOutcome, PjLinkResponseKind};
+#[cfg(feature = "discovery")]
+use crate::PjLinkDatagramRejection;
+
+/// Activity of a [PjLinkListener](crate::PjLinkListener), sent to
+/// [PjLinkListenerOptions::event_sender](crate::PjLinkListenerOptions::event_sender).
+///
+/// ## Examples
+/// ```
+/// use std::sync::mpsc;
+/// use std::thread;
+/// use pjlink_bridge::*;
+///
+/// let (event_sender, events) = mpsc::channel();
+/// let options = PjLinkListenerOptions {
+///     event_sender: Some(event_sender),
+///     ..Default::default()
+/// };
+///
+/// thread::spawn(move || {
+///     for event in events {
+///         if let PjLinkServerEvent::AuthFailed { peer_addr, .. } = event {
+///             println!("Wrong password from {:?}", peer_addr);
+///         }
+///     }
+/// });
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PjLinkServerEvent {
+    /// A connection was opened, and its security header sent
+    ConnectionOpened {
+        connection_id: u64,
+        peer_addr: Option<SocketAddr>,
+    },
+    /// A controller sent a wrong or malformed password hash. The connection
+    /// is closed next.
+    AuthFailed {
+        connection_id: u64,
+        peer_addr: Option<SocketAddr>,
+        outcome: PjLinkAuthOutcome,
+    },
+    /// A command was answered
+    CommandHandled {
+        connection_id: u64,
+        /// Command body with class, like `*b"1POWR"`
+        command_body_with_class: [u8; 5],
+        response_kind: PjLinkResponseKind,
+    },
+    /// The handler lock is poisoned, because a handler call panicked. The
+    /// command was answered with `ERR3`, without calling the handler.
+    HandlerPoisoned {
+        connection_id: u64,
+    },
+    /// A connection was closed
+    ConnectionClosed {
+        connection_id: u64,
+        peer_addr: Option<SocketAddr>,
+    },
+    /// A `%2SRCH` request was answered
+    #[cfg(feature = "discovery")]
+    UdpSearchAnswered {
+        origin: SocketAddr,
+    },
+    /// A UDP datagram was not answered
+    #[cfg(feature = "discovery")]
+    UdpDatagramRejected {
+        origin: SocketAddr,
+        reason: PjLinkDatagramRejection,
+    },
+}
+
+/// Sends `event` if events are observed. Events are dropped once the
+/// receiver is gone.
+pub(crate) fn send_event(event_sender: &Option<Sender<PjLinkServerEvent>>, event: PjLinkServerEvent) {
+    if let Some(event_sender) = event_sender {
+        let _ = event_sender.send(event);
+    }
+}
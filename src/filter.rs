@@ -0,0 +1,161 @@
+//! Command filtering for locked down deployments.
+
+use crate::{PjLinkCommand, PjLinkMiddleware, PjLinkMiddlewareContext, PjLinkRawPayload, PjLinkResponse};
+
+/// Response sent for commands rejected by a
+/// [PjLinkCommandFilter](self::PjLinkCommandFilter).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PjLinkFilterRejection {
+    /// Responds with ERR2 ([PjLinkResponse::OutOfParameter](crate::PjLinkResponse::OutOfParameter))
+    OutOfParameter,
+    /// Responds with ERR3 ([PjLinkResponse::UnavailableTime](crate::PjLinkResponse::UnavailableTime))
+    UnavailableTime,
+}
+
+impl PjLinkFilterRejection {
+    fn to_response(self) -> PjLinkResponse {
+        match self {
+            PjLinkFilterRejection::OutOfParameter => PjLinkResponse::OutOfParameter,
+            PjLinkFilterRejection::UnavailableTime => PjLinkResponse::UnavailableTime,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PjLinkFilterRule {
+    ReadOnly,
+    Allow(Vec<[u8; 5]>),
+    Deny(Vec<[u8; 5]>),
+}
+
+/// [PjLinkMiddleware](crate::PjLinkMiddleware) that rejects commands before
+/// they reach the handler, so monitoring-only deployments can be locked down.
+///
+/// Rejected commands are answered with ERR2 by default; see
+/// [with_rejection](self::PjLinkCommandFilter::with_rejection).
+///
+/// ## Examples
+/// ```
+/// use pjlink_bridge::*;
+///
+/// struct Projector;
+///
+/// impl PjLinkHandler for Projector {
+///     fn get_password(&mut self, _connection_id: &u64) -> Option<String> {
+///         None
+///     }
+///
+///     fn handle_command(&mut self, _command: PjLinkCommand, _raw_command: &PjLinkRawPayload, _connection_id: &u64) -> PjLinkResponse {
+///         PjLinkResponse::Ok
+///     }
+/// }
+///
+/// // Answers queries, rejects `%1POWR 1` and every other command with ERR3.
+/// let handler = PjLinkMiddlewareHandler::new(Projector)
+///     .layer(PjLinkCommandFilter::read_only().with_rejection(PjLinkFilterRejection::UnavailableTime));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PjLinkCommandFilter {
+    rule: PjLinkFilterRule,
+    rejection: PjLinkFilterRejection,
+}
+
+impl PjLinkCommandFilter {
+    /// Passes only queries through (see
+    /// [PjLinkRawPayload::is_query_command](crate::PjLinkRawPayload::is_query_command)),
+    /// rejecting set commands, malformed commands and vendor-specific commands
+    /// alike.
+    pub fn read_only() -> PjLinkCommandFilter {
+        Self::with_rule(PjLinkFilterRule::ReadOnly)
+    }
+
+    /// Rejects every command not listed.
+    ///
+    /// **Arguments**:
+    /// * `commands`: Allowed command bodies with class. Value example: `vec![*b"1POWR", *b"1ERST"]`
+    pub fn allow_only(commands: Vec<[u8; 5]>) -> PjLinkCommandFilter {
+        Self::with_rule(PjLinkFilterRule::Allow(commands))
+    }
+
+    /// Rejects listed commands, passing everything else through.
+    ///
+    /// **Arguments**:
+    /// * `commands`: Rejected command bodies with class. Value example: `vec![*b"2FREZ"]`
+    pub fn deny(commands: Vec<[u8; 5]>) -> PjLinkCommandFilter {
+        Self::with_rule(PjLinkFilterRule::Deny(commands))
+    }
+
+    /// Sets the response sent for rejected commands.
+    pub fn with_rejection(mut self, rejection: PjLinkFilterRejection) -> PjLinkCommandFilter {
+        self.rejection = rejection;
+        self
+    }
+
+    /// Returns `true` if the command would be passed to the handler.
+    ///
+    /// **Arguments**:
+    /// * `raw_command`: Raw command
+    pub fn is_allowed(&self, raw_command: &PjLinkRawPayload) -> bool {
+        let command_body_with_class = &raw_command.command_body_with_class;
+
+        match &self.rule {
+            PjLinkFilterRule::ReadOnly => raw_command.is_query_command(),
+            PjLinkFilterRule::Allow(commands) => commands.contains(command_body_with_class),
+            PjLinkFilterRule::Deny(commands) => !commands.contains(command_body_with_class),
+        }
+    }
+
+    fn with_rule(rule: PjLinkFilterRule) -> PjLinkCommandFilter {
+        PjLinkCommandFilter {
+            rule,
+            rejection: PjLinkFilterRejection::OutOfParameter,
+        }
+    }
+}
+
+impl PjLinkMiddleware for PjLinkCommandFilter {
+    fn before_command(&mut self, _command: &PjLinkCommand, context: &PjLinkMiddlewareContext) -> Option<PjLinkResponse> {
+        if self.is_allowed(context.raw_command) {
+            Option::None
+        } else {
+            Option::Some(self.rejection.to_response())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_allowed(filter: &PjLinkCommandFilter, command_body_with_class: [u8; 5], transmission_parameter: &[u8]) -> bool {
+        filter.is_allowed(&PjLinkRawPayload::new_command(command_body_with_class, transmission_parameter.to_vec()))
+    }
+
+    #[test]
+    fn it_passes_only_queries_when_read_only() {
+        let filter = PjLinkCommandFilter::read_only();
+
+        assert!(is_allowed(&filter, *b"1POWR", b"?"));
+        assert!(is_allowed(&filter, *b"1INPT", b"?"));
+        assert!(is_allowed(&filter, *b"1LAMP", b"?"));
+        assert!(is_allowed(&filter, *b"2INNM", b"?11"));
+        assert!(!is_allowed(&filter, *b"1POWR", b"1"));
+        assert!(!is_allowed(&filter, *b"1INPT", b"31"));
+        assert!(!is_allowed(&filter, *b"1AVMT", b"31"));
+        assert!(!is_allowed(&filter, *b"2FREZ", b"1"));
+        assert!(!is_allowed(&filter, *b"2SVOL", b"1"));
+        assert!(!is_allowed(&filter, *b"1POWR", b"2"));
+        assert!(!is_allowed(&filter, *b"1XVND", b"1"));
+    }
+
+    #[test]
+    fn it_filters_by_command_body() {
+        let allow = PjLinkCommandFilter::allow_only(vec![*b"1POWR"]);
+        assert!(is_allowed(&allow, *b"1POWR", b"1"));
+        assert!(!is_allowed(&allow, *b"1NAME", b"?"));
+
+        let deny = PjLinkCommandFilter::deny(vec![*b"1POWR"]);
+        assert!(!is_allowed(&deny, *b"1POWR", b"?"));
+        assert!(is_allowed(&deny, *b"1NAME", b"?"));
+    }
+}
@@ -0,0 +1,485 @@
+//! Splits the bytes read from a connection into PJLink lines.
+
+use std::io::{self, Read};
+use std::ops::Range;
+#[cfg(feature = "server")]
+use std::time::{Duration, Instant};
+#[cfg(feature = "server")]
+use log::trace;
+
+use crate::PJLINK_TERMINATOR;
+use crate::protocol::PJLINK_MIN_LINE_LENGTH;
+#[cfg(feature = "server")]
+use crate::{PjLinkLogContext, PjLinkTransport};
+
+/// Size of the chunks read from a connection. Fits a few pipelined PJLink
+/// lines, which are at most 136 bytes long.
+const PJLINK_READ_CHUNK_SIZE: usize = 512;
+
+/// Length of the password hash prefixing the first command of
+/// authenticated connections.
+const PJLINK_PASSWORD_HASH_LENGTH: usize = 32;
+
+/// Splits received bytes into PJLink lines (frames), ended by a
+/// [terminator](crate::PJLINK_TERMINATOR).
+///
+/// Bytes may arrive split across reads, or with several lines in one read
+/// when a controller pipelines commands. The decoder keeps everything after
+/// the last complete frame, and yields queued frames in the order they were
+/// received.
+///
+/// ## Examples
+/// ```
+/// use pjlink_bridge::*;
+///
+/// let mut decoder = PjLinkFrameDecoder::new();
+/// decoder.extend(b"%1POWR ?\r%1IN");
+/// decoder.extend(b"PT ?\r%1AV");
+///
+/// assert_eq!(decoder.next_frame(), Some(&b"%1POWR ?"[..]));
+/// assert_eq!(decoder.next_frame(), Some(&b"%1INPT ?"[..]));
+/// assert_eq!(decoder.next_frame(), None);
+/// assert_eq!(decoder.pending_len(), 4);
+/// ```
+///
+/// A decoder created with [with_max_frame_length](self::PjLinkFrameDecoder::with_max_frame_length)
+/// stops buffering a frame once it's longer than the limit, so a client
+/// never sending a terminator can't make the buffer grow forever:
+/// ```
+/// use pjlink_bridge::*;
+///
+/// let mut decoder = PjLinkFrameDecoder::with_max_frame_length(8);
+/// decoder.extend(b"%1NAME 0123456789");
+///
+/// assert!(decoder.is_frame_too_long());
+/// assert_eq!(decoder.read_from(&mut &b"\r"[..]).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+/// ```
+#[derive(Debug, Default)]
+pub struct PjLinkFrameDecoder {
+    buffer: Vec<u8>,
+    start: usize,
+    max_frame_length: Option<usize>,
+}
+
+impl PjLinkFrameDecoder {
+    pub fn new() -> PjLinkFrameDecoder {
+        PjLinkFrameDecoder::default()
+    }
+
+    /// Creates a decoder refusing frames longer than `max_frame_length`
+    /// bytes, terminator excluded. See [PjLinkParameterLimit::max_frame_length](self::PjLinkParameterLimit::max_frame_length).
+    pub fn with_max_frame_length(max_frame_length: usize) -> PjLinkFrameDecoder {
+        PjLinkFrameDecoder { max_frame_length: Option::Some(max_frame_length), ..Default::default() }
+    }
+
+    /// Appends received bytes. Bytes are appended even past the maximum
+    /// frame length: check [is_frame_too_long](self::PjLinkFrameDecoder::is_frame_too_long)
+    /// before taking the next frame.
+    pub fn extend(&mut self, bytes: &[u8]) {
+        self.compact();
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Reads once from `reader`, appending the received bytes. Returns the
+    /// number of bytes read, `0` meaning end-of-file.
+    ///
+    /// Fails with [InvalidData](std::io::ErrorKind::InvalidData), without
+    /// reading, if the incomplete frame already received is longer than the
+    /// maximum frame length.
+    pub fn read_from<R: Read>(&mut self, reader: &mut R) -> io::Result<usize> {
+        self.compact();
+
+        let incomplete_frame_len = match self.buffer.iter().rposition(|byte| *byte == PJLINK_TERMINATOR) {
+            Some(terminator) => self.buffer.len() - terminator - 1,
+            None => self.buffer.len(),
+        };
+        if self.is_longer_than_max(incomplete_frame_len) {
+            return Err(self.frame_too_long_error());
+        }
+
+        let len = self.buffer.len();
+        self.buffer.resize(len + PJLINK_READ_CHUNK_SIZE, 0);
+        let result = reader.read(&mut self.buffer[len..]);
+        self.buffer.truncate(len + *result.as_ref().unwrap_or(&0));
+
+        result
+    }
+
+    /// Removes and returns the next complete frame, without terminator.
+    pub fn next_frame(&mut self) -> Option<&[u8]> {
+        let frame_start = self.start;
+        let frame_len = self.buffer[frame_start..].iter().position(|byte| *byte == PJLINK_TERMINATOR)?;
+        self.start += frame_len + 1;
+
+        Option::Some(&self.buffer[frame_start..frame_start + frame_len])
+    }
+
+    /// Returns whether a complete frame is queued.
+    pub fn has_frame(&self) -> bool {
+        self.buffer[self.start..].contains(&PJLINK_TERMINATOR)
+    }
+
+    /// Returns `true` if the next frame, or the part of it received so far,
+    /// is longer than the maximum frame length.
+    pub fn is_frame_too_long(&self) -> bool {
+        let pending = &self.buffer[self.start..];
+        let frame_len = pending.iter().position(|byte| *byte == PJLINK_TERMINATOR).unwrap_or(pending.len());
+
+        self.is_longer_than_max(frame_len)
+    }
+
+    /// Returns the error of frames longer than the maximum frame length.
+    pub(crate) fn frame_too_long_error(&self) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("frame longer than {} bytes", self.max_frame_length.unwrap_or_default()),
+        )
+    }
+
+    fn is_longer_than_max(&self, frame_len: usize) -> bool {
+        self.max_frame_length.is_some_and(|max_frame_length| frame_len > max_frame_length)
+    }
+
+    /// Drops line feeds received before the next frame, left by controllers
+    /// terminating lines with CR LF, so they aren't taken for the start of
+    /// a frame.
+    #[cfg(feature = "server")]
+    pub(crate) fn skip_line_feeds(&mut self) {
+        self.start += self.buffer[self.start..].iter().take_while(|byte| **byte == b'\n').count();
+    }
+
+    /// Returns the number of received bytes not yet returned as a frame.
+    pub fn pending_len(&self) -> usize {
+        self.buffer.len() - self.start
+    }
+
+    /// Drops bytes of frames already returned.
+    fn compact(&mut self) {
+        if self.start > 0 {
+            self.buffer.drain(..self.start);
+            self.start = 0;
+        }
+    }
+}
+
+/// How strictly received lines are delimited, set with
+/// [PjLinkListenerOptions::framing](crate::PjLinkListenerOptions::framing).
+///
+/// ## Examples
+/// ```
+/// use pjlink_bridge::*;
+///
+/// // "%1POWR ?\r\n%1INPT ?  \r" leaves a stray LF before the second line
+/// assert_eq!(PjLinkFramingMode::Lenient.trim(b"\n%1INPT ?  "), b"%1INPT ?");
+/// assert_eq!(PjLinkFramingMode::Strict.trim(b"\n%1INPT ?  "), b"\n%1INPT ?  ");
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PjLinkFramingMode {
+    /// Lines are used as received, as the specification requires. Stray
+    /// bytes make them invalid.
+    #[default]
+    Strict,
+    /// Line feeds around lines, left by controllers terminating lines with
+    /// CR LF, and trailing spaces are removed before parsing. Lines left
+    /// empty are ignored.
+    Lenient,
+}
+
+impl PjLinkFramingMode {
+    /// Returns `frame` without the bytes this mode ignores.
+    pub fn trim<'a>(&self, frame: &'a [u8]) -> &'a [u8] {
+        &frame[self.trimmed_range(frame)]
+    }
+
+    /// Same as [trim](self::PjLinkFramingMode::trim), in place.
+    #[cfg(feature = "server")]
+    pub(crate) fn trim_in_place(&self, frame: &mut Vec<u8>) {
+        let range = self.trimmed_range(frame);
+        frame.truncate(range.end);
+        frame.drain(..range.start);
+    }
+
+    fn trimmed_range(&self, frame: &[u8]) -> Range<usize> {
+        match self {
+            PjLinkFramingMode::Strict => 0..frame.len(),
+            PjLinkFramingMode::Lenient => {
+                let start = frame.iter().position(|byte| *byte != b'\n').unwrap_or(frame.len());
+                let end = frame.iter().rposition(|byte| *byte != b'\n' && *byte != b' ').map_or(start, |last| last + 1);
+                start..end.max(start)
+            }
+        }
+    }
+}
+
+/// Longest transmission parameter the specification allows, in bytes.
+pub const PJLINK_MAX_PARAMETER_LENGTH: usize = 128;
+
+/// Longest transmission parameter passed to the handler, set with
+/// [PjLinkListenerOptions::parameter_limit](crate::PjLinkListenerOptions::parameter_limit).
+/// Commands with longer parameters are answered with `ERR2` by the listener.
+///
+/// ## Examples
+/// ```
+/// use pjlink_bridge::*;
+///
+/// assert_eq!(PjLinkParameterLimit::Spec.max_length(), Some(PJLINK_MAX_PARAMETER_LENGTH));
+/// assert_eq!(PjLinkParameterLimit::Unlimited.max_length(), None);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PjLinkParameterLimit {
+    /// [PJLINK_MAX_PARAMETER_LENGTH](self::PJLINK_MAX_PARAMETER_LENGTH) bytes
+    #[default]
+    Spec,
+    /// Custom limit, in bytes, for vendor commands with longer parameters
+    Max(usize),
+    /// Parameters of any length are passed to the handler, and received
+    /// lines aren't limited either
+    Unlimited,
+}
+
+impl PjLinkParameterLimit {
+    /// Returns the longest allowed parameter, in bytes, or `None` if
+    /// unlimited.
+    pub fn max_length(&self) -> Option<usize> {
+        match self {
+            PjLinkParameterLimit::Spec => Option::Some(PJLINK_MAX_PARAMETER_LENGTH),
+            PjLinkParameterLimit::Max(max_length) => Option::Some(*max_length),
+            PjLinkParameterLimit::Unlimited => Option::None,
+        }
+    }
+
+    /// Returns the longest line received before closing the connection, in
+    /// bytes, terminator excluded, or `None` if unlimited: the longest
+    /// allowed parameter, plus header, command body and separator, plus the
+    /// password hash prefixing the first command.
+    ///
+    /// Lines a bit longer than the parameter limit are answered with `ERR2`,
+    /// but controllers sending longer ones are disconnected, so they can't
+    /// make the connection buffer grow forever.
+    ///
+    /// ## Examples
+    /// ```
+    /// use pjlink_bridge::*;
+    ///
+    /// assert_eq!(PjLinkParameterLimit::Spec.max_frame_length(), Some(167));
+    /// assert_eq!(PjLinkParameterLimit::Unlimited.max_frame_length(), None);
+    /// ```
+    pub fn max_frame_length(&self) -> Option<usize> {
+        self.max_length().map(|max_length| PJLINK_PASSWORD_HASH_LENGTH + PJLINK_MIN_LINE_LENGTH + max_length)
+    }
+
+    /// Returns `true` if `transmission_parameter` is longer than allowed.
+    pub fn is_exceeded_by(&self, transmission_parameter: &[u8]) -> bool {
+        self.max_length().is_some_and(|max_length| transmission_parameter.len() > max_length)
+    }
+}
+
+/// Reads frames from a connection through a [PjLinkFrameDecoder](self::PjLinkFrameDecoder),
+/// enforcing the listener [frame_timeout](crate::PjLinkListenerOptions::frame_timeout).
+#[cfg(feature = "server")]
+pub(crate) struct PjLinkFrameReader {
+    decoder: PjLinkFrameDecoder,
+    framing: PjLinkFramingMode,
+    frame_timeout: Option<Duration>,
+    deadline: Option<Instant>,
+    read_timeout: Option<Duration>,
+}
+
+#[cfg(feature = "server")]
+impl PjLinkFrameReader {
+    pub(crate) fn new(frame_timeout: Option<Duration>, max_frame_length: Option<usize>, framing: PjLinkFramingMode) -> PjLinkFrameReader {
+        PjLinkFrameReader {
+            decoder: match max_frame_length {
+                Some(max_frame_length) => PjLinkFrameDecoder::with_max_frame_length(max_frame_length),
+                None => PjLinkFrameDecoder::new(),
+            },
+            framing,
+            frame_timeout,
+            deadline: Option::None,
+            read_timeout: Option::None,
+        }
+    }
+
+    /// Limits reading, including waiting for the first byte, until
+    /// `deadline`. Used for the listener
+    /// [handshake_timeout](crate::PjLinkListenerOptions::handshake_timeout).
+    pub(crate) fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+    }
+
+    /// Replaces `line` with the next frame, reading from `stream` until one
+    /// is complete.
+    ///
+    /// Fails with [UnexpectedEof](std::io::ErrorKind::UnexpectedEof) if the
+    /// connection is closed before a terminator is received, and with
+    /// [TimedOut](std::io::ErrorKind::TimedOut) if the frame isn't complete
+    /// within the frame timeout after its first byte, or the deadline passes,
+    /// and with [InvalidData](std::io::ErrorKind::InvalidData) if the frame
+    /// is longer than the maximum frame length.
+    pub(crate) fn read_frame<T: PjLinkTransport>(
+        &mut self,
+        stream: &mut T,
+        line: &mut Vec<u8>,
+        log_context: &PjLinkLogContext,
+    ) -> io::Result<()> {
+        self.skip_ignored_bytes();
+        let mut frame_started_at = match self.decoder.pending_len() {
+            0 => Option::None,
+            _ => Option::Some(Instant::now()),
+        };
+
+        loop {
+            if self.decoder.is_frame_too_long() {
+                return Err(self.decoder.frame_too_long_error());
+            }
+            if let Some(frame) = self.decoder.next_frame() {
+                line.clear();
+                line.extend_from_slice(frame);
+                return Ok(());
+            }
+
+            let frame_remaining = match (self.frame_timeout, frame_started_at) {
+                (Some(frame_timeout), Some(frame_started_at)) => match frame_timeout.checked_sub(frame_started_at.elapsed()) {
+                    Some(remaining) if !remaining.is_zero() => Option::Some(remaining),
+                    _ => return Err(io::Error::new(io::ErrorKind::TimedOut, "frame not completed within frame timeout")),
+                },
+                _ => Option::None,
+            };
+            let deadline_remaining = match self.deadline {
+                Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                    Some(remaining) if !remaining.is_zero() => Option::Some(remaining),
+                    _ => return Err(io::Error::new(io::ErrorKind::TimedOut, "handshake not completed within handshake timeout")),
+                },
+                None => Option::None,
+            };
+
+            let read_timeout = match (frame_remaining, deadline_remaining) {
+                (Some(frame_remaining), Some(deadline_remaining)) => Option::Some(frame_remaining.min(deadline_remaining)),
+                (frame_remaining, deadline_remaining) => frame_remaining.or(deadline_remaining),
+            };
+            if read_timeout != self.read_timeout {
+                stream.set_read_timeout(read_timeout)?;
+                self.read_timeout = read_timeout;
+            }
+
+            match self.decoder.read_from(stream) {
+                Ok(0) => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed before terminator")),
+                Ok(size) => {
+                    trace!("Read command chunk. {}, Size: {}", log_context, size);
+                    self.skip_ignored_bytes();
+                    if self.decoder.pending_len() > 0 {
+                        frame_started_at.get_or_insert_with(Instant::now);
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) if (frame_started_at.is_some() || self.deadline.is_some())
+                    && matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Drops the line feeds lenient framing ignores, so the stray LF of a
+    /// CR LF line doesn't start the frame timeout of the next one.
+    fn skip_ignored_bytes(&mut self) {
+        if self.framing == PjLinkFramingMode::Lenient {
+            self.decoder.skip_line_feeds();
+        }
+    }
+}
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::thread;
+    use crate::PjLinkMemoryTransport;
+
+    #[test]
+    fn it_splits_pipelined_and_fragmented_lines() {
+        let log_context = PjLinkLogContext::new(0, Option::None);
+        let mut reader = PjLinkFrameReader::new(Option::None, Option::None, PjLinkFramingMode::Strict);
+        let (mut client, mut server) = PjLinkMemoryTransport::pair();
+        let mut line = Vec::new();
+
+        client.write_all(b"%1POWR ?\r%1INPT ?\r%2SV").unwrap();
+        reader.read_frame(&mut server, &mut line, &log_context).unwrap();
+        assert_eq!(line, b"%1POWR ?");
+        assert!(reader.decoder.has_frame());
+        reader.read_frame(&mut server, &mut line, &log_context).unwrap();
+        assert_eq!(line, b"%1INPT ?");
+
+        client.write_all(b"ER ?").unwrap();
+        client.write_all(b"\r").unwrap();
+        reader.read_frame(&mut server, &mut line, &log_context).unwrap();
+        assert_eq!(line, b"%2SVER ?");
+
+        client.write_all(b"%1AV").unwrap();
+        drop(client);
+        let error = reader.read_frame(&mut server, &mut line, &log_context).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(reader.decoder.pending_len(), 4);
+    }
+
+    #[test]
+    fn it_times_out_incomplete_frames() {
+        let log_context = PjLinkLogContext::new(0, Option::None);
+        let mut reader = PjLinkFrameReader::new(Option::Some(Duration::from_millis(50)), Option::None, PjLinkFramingMode::Strict);
+        let (mut client, mut server) = PjLinkMemoryTransport::pair();
+        let mut line = Vec::new();
+
+        client.write_all(b"%1POWR ?\r%1PO").unwrap();
+        reader.read_frame(&mut server, &mut line, &log_context).unwrap();
+        let error = reader.read_frame(&mut server, &mut line, &log_context).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+
+        let mut reader = PjLinkFrameReader::new(Option::None, Option::None, PjLinkFramingMode::Strict);
+        reader.set_deadline(Option::Some(Instant::now() + Duration::from_millis(50)));
+        let error = reader.read_frame(&mut server, &mut line, &log_context).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn it_does_not_time_out_idle_connections_after_crlf_lines() {
+        let log_context = PjLinkLogContext::new(0, Option::None);
+        let mut reader = PjLinkFrameReader::new(Option::Some(Duration::from_millis(50)), Option::None, PjLinkFramingMode::Lenient);
+        let (mut client, mut server) = PjLinkMemoryTransport::pair();
+        let mut line = Vec::new();
+
+        client.write_all(b"%1POWR ?\r\n").unwrap();
+        reader.read_frame(&mut server, &mut line, &log_context).unwrap();
+        assert_eq!(line, b"%1POWR ?");
+
+        let client_thread = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(150));
+            client.write_all(b"%1INPT ?\r\n").unwrap();
+            client
+        });
+        reader.read_frame(&mut server, &mut line, &log_context).unwrap();
+        assert_eq!(line, b"%1INPT ?");
+        client_thread.join().unwrap();
+    }
+
+    #[test]
+    fn it_refuses_frames_longer_than_the_limit() {
+        let log_context = PjLinkLogContext::new(0, Option::None);
+        let mut reader = PjLinkFrameReader::new(Option::None, PjLinkParameterLimit::Spec.max_frame_length(), PjLinkFramingMode::Strict);
+        let (mut client, mut server) = PjLinkMemoryTransport::pair();
+        let mut line = Vec::new();
+
+        client.write_all(format!("%2XNAM {}\r", "a".repeat(PJLINK_MAX_PARAMETER_LENGTH)).as_bytes()).unwrap();
+        reader.read_frame(&mut server, &mut line, &log_context).unwrap();
+        assert_eq!(line.len(), PJLINK_MIN_LINE_LENGTH + PJLINK_MAX_PARAMETER_LENGTH);
+
+        client.write_all(&[b'a'; 4096]).unwrap();
+        let error = reader.read_frame(&mut server, &mut line, &log_context).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(reader.decoder.pending_len() < 4096);
+
+        let mut reader = PjLinkFrameReader::new(Option::None, Option::Some(8), PjLinkFramingMode::Strict);
+        let (mut client, mut server) = PjLinkMemoryTransport::pair();
+        client.write_all(b"%1NAME 0123456789\r").unwrap();
+        let error = reader.read_frame(&mut server, &mut line, &log_context).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,134 @@
+//! PJLink sessions of gateway clients (`grpc` and `jsonrpc` features).
+
+use std::net::SocketAddr;
+use log::debug;
+
+use crate::{PjLinkConnectionHandler, PjLinkRawPayload, PjLinkResponse, encode_payload, PJLINK_TERMINATOR};
+use crate::session::{PjLinkSession, PjLinkSessionStep};
+
+/// Why a gateway call was refused before reaching the handler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PjLinkGatewayError {
+    /// The listener requires a password and the client hasn't sent one,
+    /// or its session ended (expired or password changed) and it must
+    /// authenticate again.
+    Unauthenticated,
+    /// The client sent a wrong password, on this call or an earlier one.
+    Denied,
+}
+
+/// PJLink session of one gateway client (gRPC channel or JSON-RPC
+/// connection), so its calls go through
+/// authentication, read-only passwords, statistics, events, capture and the
+/// command observer like commands of a PJLink connection.
+///
+/// The client keeps one connection ID for its lifetime. The session is
+/// opened on first use, and opened again after it ends. A wrong password
+/// ends it for good: like PJLink connections closed after ERRA, the client
+/// must reconnect before trying another one.
+pub(crate) struct PjLinkGatewaySession {
+    connection_handler: PjLinkConnectionHandler,
+    connection_id: u64,
+    peer_addr: Option<SocketAddr>,
+    session: Option<PjLinkSession>,
+    is_denied: bool,
+}
+
+impl PjLinkGatewaySession {
+    pub(crate) fn new(connection_handler: PjLinkConnectionHandler, peer_addr: Option<SocketAddr>) -> PjLinkGatewaySession {
+        let connection_id = connection_handler.next_connection_id();
+
+        PjLinkGatewaySession {
+            connection_handler,
+            connection_id,
+            peer_addr,
+            session: Option::None,
+            is_denied: false,
+        }
+    }
+
+    pub(crate) fn connection_id(&self) -> u64 {
+        self.connection_id
+    }
+
+    /// Returns `true` if the client sent a wrong password, so every call
+    /// fails with [Denied](PjLinkGatewayError::Denied) until it reconnects.
+    #[cfg_attr(not(feature = "jsonrpc"), allow(dead_code))]
+    pub(crate) fn is_denied(&self) -> bool {
+        self.is_denied
+    }
+
+    /// Authenticates with `password`, if the session needs one. Succeeds
+    /// without checking it otherwise. A wrong password ends the session,
+    /// and every later call fails with [Denied](PjLinkGatewayError::Denied).
+    pub(crate) fn authenticate(&mut self, password: &str) -> Result<(), PjLinkGatewayError> {
+        self.ensure_not_denied()?;
+        let (session, connection_handler) = self.session();
+        if !session.is_auth_pending() {
+            return Ok(());
+        }
+
+        if session.authenticate(connection_handler, password).is_accepted() {
+            return Ok(());
+        }
+
+        debug!("Gateway client sent a wrong password, ending session! ConnectionId: {}", self.connection_id);
+        self.session = Option::None;
+        self.is_denied = true;
+        Err(PjLinkGatewayError::Denied)
+    }
+
+    /// Fails with [Unauthenticated](PjLinkGatewayError::Unauthenticated)
+    /// unless the client may send commands.
+    pub(crate) fn ensure_authenticated(&mut self) -> Result<(), PjLinkGatewayError> {
+        self.ensure_not_denied()?;
+        match self.session().0.is_auth_pending() {
+            true => Err(PjLinkGatewayError::Unauthenticated),
+            false => Ok(()),
+        }
+    }
+
+    /// Sends `raw_command` through the session, returning the response.
+    pub(crate) fn send(&mut self, raw_command: &PjLinkRawPayload) -> Result<PjLinkResponse, PjLinkGatewayError> {
+        self.ensure_authenticated()?;
+        if raw_command.transmission_parameter.contains(&PJLINK_TERMINATOR) {
+            return Ok(PjLinkResponse::OutOfParameter);
+        }
+
+        let mut frame = encode_payload(raw_command);
+        frame.pop();
+        let mut output = Vec::new();
+        let (session, connection_handler) = self.session();
+        let step = session.handle_frame(connection_handler, &mut frame, &mut output);
+        session.record_sent(output.len());
+
+        if step == PjLinkSessionStep::Close {
+            debug!("Gateway session ended! ConnectionId: {}", self.connection_id);
+            self.session = Option::None;
+            if output.is_empty() {
+                return Err(PjLinkGatewayError::Unauthenticated);
+            }
+        }
+
+        // Responses are "%1POWR=<parameter>\r"
+        Ok(match output.strip_suffix(&[PJLINK_TERMINATOR]).and_then(|line| line.get(7..)) {
+            Some(transmission_parameter) if output[6] == b'=' => PjLinkResponse::from(transmission_parameter),
+            _ => PjLinkResponse::Undefined,
+        })
+    }
+
+    fn ensure_not_denied(&self) -> Result<(), PjLinkGatewayError> {
+        match self.is_denied {
+            true => Err(PjLinkGatewayError::Denied),
+            false => Ok(()),
+        }
+    }
+
+    /// Returns the session, opening it if needed, with the connection
+    /// handler it runs on. Gateway clients don't see the security header.
+    fn session(&mut self) -> (&mut PjLinkSession, &PjLinkConnectionHandler) {
+        let (connection_handler, connection_id, peer_addr) = (&self.connection_handler, self.connection_id, self.peer_addr);
+        let session = self.session.get_or_insert_with(|| PjLinkSession::open(connection_handler, connection_id, peer_addr, &mut Vec::new()));
+        (session, connection_handler)
+    }
+}
@@ -0,0 +1,464 @@
+//! gRPC control-plane gateway (`grpc` feature).
+
+use std::io;
+use std::net::TcpListener;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Context, Poll};
+use log::{info, debug};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::sync::mpsc;
+use tokio_stream::StreamExt;
+use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
+use tonic::{Request, Response, Status};
+use tonic::transport::Server;
+use tonic::transport::server::Connected;
+
+use crate::{
+    PjLinkError, PjLinkListener, PjLinkRawPayload, PjLinkResponse, PjLinkStatusCommand,
+    spawn_named_thread,
+};
+use crate::gateway::{PjLinkGatewayError, PjLinkGatewaySession};
+use crate::server::PjLinkStatusSubscribers;
+use self::pjlink_grpc::pj_link_control_server::{PjLinkControl, PjLinkControlServer};
+use self::pjlink_grpc::{
+    CommandResult, GetInputRequest, GetPowerRequest, GetStatusRequest, InputStatus, Notification, NotificationsRequest, PowerStatus,
+    ProjectorStatus, SetInputRequest, SetPowerRequest,
+};
+
+/// Messages and service of `proto/pjlink.proto`, generated by `tonic-build`,
+/// served by [PjLinkListener::listen_grpc](crate::PjLinkListener::listen_grpc).
+/// Controllers generate their client from the same file.
+#[allow(clippy::all)]
+pub mod pjlink_grpc {
+    tonic::include_proto!("pjlink");
+}
+
+/// Metadata key of the password gRPC clients send when the listener
+/// requires one. It's checked like the password of a PJLink connection,
+/// read-only passwords included, once per client channel. After a wrong
+/// password, every call on the channel fails until the client reconnects.
+///
+/// The password travels in cleartext metadata, not as a salted digest:
+/// serve the gateway over TLS, or on a trusted network only.
+pub const PJLINK_GRPC_PASSWORD_METADATA: &str = "pjlink-password";
+
+/// Status messages buffered for each notification stream before the
+/// gateway stops reading from the listener.
+const PJLINK_GRPC_NOTIFICATION_BUFFER: usize = 16;
+
+/// TCP connection of a gRPC client, handing its gateway session to every
+/// call made on it.
+struct PjLinkGrpcStream {
+    stream: tokio::net::TcpStream,
+    session: Arc<Mutex<PjLinkGatewaySession>>,
+}
+
+impl Connected for PjLinkGrpcStream {
+    type ConnectInfo = Arc<Mutex<PjLinkGatewaySession>>;
+
+    fn connect_info(&self) -> Self::ConnectInfo {
+        self.session.clone()
+    }
+}
+
+impl AsyncRead for PjLinkGrpcStream {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for PjLinkGrpcStream {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.stream).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(mut self: Pin<&mut Self>, cx: &mut Context<'_>, bufs: &[io::IoSlice<'_>]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.stream).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.stream.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+}
+
+/// Gateway session of the channel a call came on, with the password sent
+/// along, if any.
+struct PjLinkGrpcCaller {
+    session: Option<Arc<Mutex<PjLinkGatewaySession>>>,
+    password: Option<String>,
+}
+
+impl PjLinkGrpcCaller {
+    /// Returns the caller of `request`. Passwords that aren't ASCII can't
+    /// match, so they're ignored.
+    fn of<T>(request: &Request<T>) -> PjLinkGrpcCaller {
+        PjLinkGrpcCaller {
+            session: request.extensions().get::<Arc<Mutex<PjLinkGatewaySession>>>().cloned(),
+            password: request.metadata()
+                .get(PJLINK_GRPC_PASSWORD_METADATA)
+                .and_then(|password| password.to_str().ok())
+                .map(String::from),
+        }
+    }
+
+    /// Runs `call` on a blocking thread with the session, authenticated
+    /// with the caller password first if it needs one.
+    async fn with_session<R, F>(&self, call: F) -> Result<R, Status>
+    where
+        R: Send + 'static,
+        F: FnOnce(&mut PjLinkGatewaySession) -> Result<R, PjLinkGatewayError> + Send + 'static,
+    {
+        let session = self.session.clone().ok_or_else(|| Status::internal("Missing gateway session"))?;
+        let password = self.password.clone();
+
+        tokio::task::spawn_blocking(move || {
+            // Handler panics are answered by the session itself, its state
+            // stays consistent
+            let mut session = session.lock().unwrap_or_else(PoisonError::into_inner);
+            if let Some(password) = password {
+                session.authenticate(&password)?;
+            }
+            call(&mut session)
+        })
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .map_err(Self::to_status)
+    }
+
+    fn to_status(error: PjLinkGatewayError) -> Status {
+        match error {
+            PjLinkGatewayError::Unauthenticated => Status::unauthenticated("Password required"),
+            PjLinkGatewayError::Denied => Status::unauthenticated("ERRA"),
+        }
+    }
+}
+
+/// `PjLinkControl` service, answering calls with the listener handler.
+struct PjLinkGrpcService {
+    status_subscribers: Arc<PjLinkStatusSubscribers>,
+}
+
+impl PjLinkGrpcService {
+    /// Sends a command through the caller session on a blocking thread,
+    /// returning the transmission parameter of value responses, or the
+    /// PJLink error as a gRPC status.
+    async fn send(&self, caller: &PjLinkGrpcCaller, command_body_with_class: [u8; 5], transmission_parameter: Vec<u8>) -> Result<Vec<u8>, Status> {
+        let raw_command = PjLinkRawPayload::new_command(command_body_with_class, transmission_parameter);
+
+        let response = caller.with_session(move |session| {
+            debug!(
+                "gRPC call. ConnectionId: {}, CmdBodyWithClass: {}",
+                session.connection_id(),
+                String::from_utf8_lossy(&raw_command.command_body_with_class),
+            );
+            session.send(&raw_command)
+        }).await?;
+
+        match response {
+            PjLinkResponse::Ok | PjLinkResponse::Empty => Ok(Vec::new()),
+            PjLinkResponse::Single(value) => Ok(vec![value]),
+            PjLinkResponse::Multiple(value) => Ok(value),
+            PjLinkResponse::Undefined => Err(Status::unimplemented("ERR1")),
+            PjLinkResponse::OutOfParameter => Err(Status::invalid_argument("ERR2")),
+            PjLinkResponse::UnavailableTime => Err(Status::unavailable("ERR3")),
+            PjLinkResponse::ProjectorOrDisplayFailure => Err(Status::internal("ERR4")),
+        }
+    }
+
+    /// Returns the `INPT` command body of the requested class, or `None` if
+    /// it's not 1 or 2.
+    fn input_command_body(class: u32) -> Option<[u8; 5]> {
+        match class {
+            0 | 1 => Option::Some(*b"1INPT"),
+            2 => Option::Some(*b"2INPT"),
+            _ => Option::None,
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl PjLinkControl for PjLinkGrpcService {
+    async fn get_power(&self, request: Request<GetPowerRequest>) -> Result<Response<PowerStatus>, Status> {
+        let caller = PjLinkGrpcCaller::of(&request);
+        match self.send(&caller, *b"1POWR", b"?".to_vec()).await?.as_slice() {
+            [power @ b'0'..=b'3'] => Ok(Response::new(PowerStatus { state: i32::from(power - b'0') })),
+            power => Err(Status::internal(format!("Invalid power status {:?}", String::from_utf8_lossy(power)))),
+        }
+    }
+
+    async fn set_power(&self, request: Request<SetPowerRequest>) -> Result<Response<CommandResult>, Status> {
+        let caller = PjLinkGrpcCaller::of(&request);
+        let power = match request.into_inner().on {
+            true => b'1',
+            false => b'0',
+        };
+
+        self.send(&caller, *b"1POWR", vec![power]).await?;
+        Ok(Response::new(CommandResult {}))
+    }
+
+    async fn get_input(&self, request: Request<GetInputRequest>) -> Result<Response<InputStatus>, Status> {
+        let caller = PjLinkGrpcCaller::of(&request);
+        let command_body_with_class = Self::input_command_body(request.into_inner().class)
+            .ok_or_else(|| Status::invalid_argument("class must be 1 or 2"))?;
+        let input = self.send(&caller, command_body_with_class, b"?".to_vec()).await?;
+
+        Ok(Response::new(InputStatus { input: String::from_utf8_lossy(&input).into_owned() }))
+    }
+
+    async fn set_input(&self, request: Request<SetInputRequest>) -> Result<Response<CommandResult>, Status> {
+        let caller = PjLinkGrpcCaller::of(&request);
+        let request = request.into_inner();
+        let command_body_with_class = Self::input_command_body(request.class)
+            .ok_or_else(|| Status::invalid_argument("class must be 1 or 2"))?;
+
+        self.send(&caller, command_body_with_class, request.input.into_bytes()).await?;
+        Ok(Response::new(CommandResult {}))
+    }
+
+    async fn get_status(&self, request: Request<GetStatusRequest>) -> Result<Response<ProjectorStatus>, Status> {
+        let caller = PjLinkGrpcCaller::of(&request);
+        caller.with_session(PjLinkGatewaySession::ensure_authenticated).await?;
+
+        let mut values = Vec::with_capacity(5);
+        for command_body_with_class in [b"1POWR", b"1INPT", b"1ERST", b"1AVMT", b"1NAME"] {
+            let value = self.send(&caller, *command_body_with_class, b"?".to_vec()).await.unwrap_or_default();
+            values.push(String::from_utf8_lossy(&value).into_owned());
+        }
+
+        let mut values = values.into_iter();
+        Ok(Response::new(ProjectorStatus {
+            power: values.next().unwrap_or_default(),
+            input: values.next().unwrap_or_default(),
+            error_status: values.next().unwrap_or_default(),
+            av_mute: values.next().unwrap_or_default(),
+            name: values.next().unwrap_or_default(),
+        }))
+    }
+
+    type NotificationsStream = ReceiverStream<Result<Notification, Status>>;
+
+    async fn notifications(&self, request: Request<NotificationsRequest>) -> Result<Response<Self::NotificationsStream>, Status> {
+        PjLinkGrpcCaller::of(&request).with_session(PjLinkGatewaySession::ensure_authenticated).await?;
+
+        let status_messages = self.status_subscribers.subscribe();
+        let (sender, receiver) = mpsc::channel(PJLINK_GRPC_NOTIFICATION_BUFFER);
+
+        // Relays until the client goes away, noticed on the next message
+        spawn_named_thread(String::from("pjlink-grpc-notify"), move || {
+            for status_message in status_messages {
+                if sender.blocking_send(Ok(Self::to_notification(&status_message))).is_err() {
+                    break;
+                }
+            }
+        }).map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+}
+
+impl PjLinkGrpcService {
+    fn to_notification(status_message: &PjLinkStatusCommand) -> Notification {
+        let raw_payload = status_message.to_raw_payload();
+
+        Notification {
+            command: String::from_utf8_lossy(&raw_payload.command_body_with_class[1..]).into_owned(),
+            value: String::from_utf8_lossy(&raw_payload.transmission_parameter).into_owned(),
+        }
+    }
+}
+
+impl<'a> PjLinkListener<'a> {
+    /// Serves the `PjLinkControl` gRPC service of `proto/pjlink.proto` on
+    /// `grpc_listener`, so management systems can control the projector
+    /// without PJLink framing.
+    ///
+    /// Each client channel is served like a PJLink connection, with one
+    /// connection ID, and its calls are answered by this listener's
+    /// handler and options. If the listener requires a password, calls must
+    /// carry it in the [PJLINK_GRPC_PASSWORD_METADATA](crate::PJLINK_GRPC_PASSWORD_METADATA)
+    /// metadata until one is accepted, and fail with `UNAUTHENTICATED`
+    /// otherwise. A wrong password locks the channel, the client must
+    /// reconnect to try another one. Passwords are sent in cleartext, unless
+    /// the channel is wrapped in TLS. The `Notifications` stream relays status messages sent by
+    /// [notify](crate::PjLinkListener::notify).
+    /// Blocks the current thread on a Tokio runtime, until the server fails.
+    /// Available with the `grpc` feature.
+    ///
+    /// ## Examples
+    /// ```no_run
+    /// use std::net::TcpListener;
+    /// use std::thread;
+    /// use pjlink_bridge::*;
+    ///
+    /// # fn example(listener: PjLinkListenerShared<'static>) {
+    /// let grpc_listener = TcpListener::bind("0.0.0.0:50051").unwrap();
+    /// let listener_clone = listener.clone();
+    ///
+    /// thread::spawn(move || listener_clone.listen_grpc(grpc_listener));
+    /// listener.listen();
+    /// # }
+    /// ```
+    pub fn listen_grpc(&self, grpc_listener: TcpListener) -> Result<(), PjLinkError> {
+        if let Ok(local_addr) = grpc_listener.local_addr() {
+            info!("Running gRPC Listener on {}", local_addr);
+        }
+
+        grpc_listener.set_nonblocking(true)?;
+        let connection_handler = self.connection_handler();
+        let service = PjLinkGrpcService {
+            status_subscribers: self.shared_status_subscribers.clone(),
+        };
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .thread_name("pjlink-grpc")
+            .build()?;
+
+        runtime.block_on(async move {
+            // Each channel gets one gateway session, and one connection ID
+            let incoming = TcpListenerStream::new(tokio::net::TcpListener::from_std(grpc_listener)?).map(move |stream| {
+                stream.map(|stream| {
+                    let peer_addr = stream.peer_addr().ok();
+                    let session = PjLinkGatewaySession::new(connection_handler.clone(), peer_addr);
+                    PjLinkGrpcStream { stream, session: Arc::new(Mutex::new(session)) }
+                })
+            });
+
+            Server::builder()
+                .add_service(PjLinkControlServer::new(service))
+                .serve_with_incoming(incoming)
+                .await
+                .map_err(|e| PjLinkError::Io(io::Error::other(e.to_string())))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use crate::{PjLinkCommand, PjLinkHandler, PjLinkListenerShared, PjLinkPowerCommandParameter, PjLinkPowerCommandStatus};
+    use tonic::client::Grpc;
+    use tonic::codec::{ProstCodec, Streaming};
+    use tonic::codegen::http::uri::PathAndQuery;
+    use tonic::transport::Channel;
+    use super::pjlink_grpc::PowerState;
+
+    struct PowerHandler {
+        power: u8,
+        connection_ids: Vec<u64>,
+    }
+
+    impl PjLinkHandler for PowerHandler {
+        fn get_password(&mut self, _connection_id: &u64) -> Option<String> {
+            Option::Some(String::from("secret"))
+        }
+
+        fn handle_command(&mut self, command: PjLinkCommand, _raw_command: &PjLinkRawPayload, connection_id: &u64) -> PjLinkResponse {
+            self.connection_ids.push(*connection_id);
+            match command {
+                PjLinkCommand::Power1(PjLinkPowerCommandParameter::Query) => PjLinkResponse::Single(self.power),
+                PjLinkCommand::Power1(PjLinkPowerCommandParameter::On) => {
+                    self.power = PjLinkPowerCommandStatus::On;
+                    PjLinkResponse::Ok
+                }
+                _ => PjLinkResponse::Undefined,
+            }
+        }
+    }
+
+    async fn call<Q, R>(channel: &Channel, path: &'static str, request: Q, password: Option<&str>) -> Result<R, Status>
+    where
+        Q: prost::Message + Send + Sync + 'static,
+        R: prost::Message + Default + Send + Sync + 'static,
+    {
+        let mut client = Grpc::new(channel.clone());
+        client.ready().await.unwrap();
+        let mut request = Request::new(request);
+        if let Some(password) = password {
+            request.metadata_mut().insert(PJLINK_GRPC_PASSWORD_METADATA, password.parse().unwrap());
+        }
+        client.unary(request, PathAndQuery::from_static(path), ProstCodec::default()).await.map(Response::into_inner)
+    }
+
+    fn listen(handler: Arc<Mutex<PowerHandler>>) -> (PjLinkListenerShared<'static>, String) {
+        let grpc_listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}", grpc_listener.local_addr().unwrap());
+        let listener = PjLinkListener::new_without_broadcast(handler, TcpListener::bind("127.0.0.1:0").unwrap());
+        let listener_clone = listener.clone();
+        thread::spawn(move || listener_clone.listen_grpc(grpc_listener));
+
+        (listener, address)
+    }
+
+    #[test]
+    fn it_answers_calls_and_streams_notifications() {
+        let (listener, address) = listen(Arc::new(Mutex::new(PowerHandler { power: PjLinkPowerCommandStatus::Off, connection_ids: Vec::new() })));
+
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        runtime.block_on(async {
+            let channel = Channel::from_shared(address).unwrap().connect().await.unwrap();
+
+            let _: CommandResult = call(&channel, "/pjlink.PjLinkControl/SetPower", SetPowerRequest { on: true }, Some("secret")).await.unwrap();
+            let power: PowerStatus = call(&channel, "/pjlink.PjLinkControl/GetPower", GetPowerRequest {}, None).await.unwrap();
+            assert_eq!(power.state(), PowerState::On);
+
+            let error = call::<_, InputStatus>(&channel, "/pjlink.PjLinkControl/GetInput", GetInputRequest { class: 1 }, None).await.unwrap_err();
+            assert_eq!(error.code(), tonic::Code::Unimplemented);
+
+            let mut client = Grpc::new(channel.clone());
+            client.ready().await.unwrap();
+            let mut notifications: Streaming<Notification> = client
+                .server_streaming(
+                    Request::new(NotificationsRequest {}),
+                    PathAndQuery::from_static("/pjlink.PjLinkControl/Notifications"),
+                    ProstCodec::default(),
+                )
+                .await
+                .unwrap()
+                .into_inner();
+            listener.notify(&PjLinkStatusCommand::Power2(b'2')).unwrap();
+            let notification = notifications.message().await.unwrap().unwrap();
+            assert_eq!((notification.command.as_str(), notification.value.as_str()), ("POWR", "2"));
+        });
+    }
+
+    #[test]
+    fn it_refuses_calls_without_the_password() {
+        let handler = Arc::new(Mutex::new(PowerHandler { power: PjLinkPowerCommandStatus::Off, connection_ids: Vec::new() }));
+        let (_listener, address) = listen(handler.clone());
+
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        runtime.block_on(async {
+            let channel = Channel::from_shared(address.clone()).unwrap().connect().await.unwrap();
+
+            let error = call::<_, CommandResult>(&channel, "/pjlink.PjLinkControl/SetPower", SetPowerRequest { on: true }, None).await.unwrap_err();
+            assert_eq!(error.code(), tonic::Code::Unauthenticated);
+            let error = call::<_, ProjectorStatus>(&channel, "/pjlink.PjLinkControl/GetStatus", GetStatusRequest {}, None).await.unwrap_err();
+            assert_eq!(error.code(), tonic::Code::Unauthenticated);
+            let error = call::<_, CommandResult>(&channel, "/pjlink.PjLinkControl/SetPower", SetPowerRequest { on: true }, Some("wrong")).await.unwrap_err();
+            assert_eq!((error.code(), error.message()), (tonic::Code::Unauthenticated, "ERRA"));
+            let error = call::<_, CommandResult>(&channel, "/pjlink.PjLinkControl/SetPower", SetPowerRequest { on: true }, Some("secret")).await.unwrap_err();
+            assert_eq!((error.code(), error.message()), (tonic::Code::Unauthenticated, "ERRA"));
+            assert_eq!(handler.lock().unwrap().power, PjLinkPowerCommandStatus::Off);
+
+            let channel = Channel::from_shared(address).unwrap().connect().await.unwrap();
+            let _: PowerStatus = call(&channel, "/pjlink.PjLinkControl/GetPower", GetPowerRequest {}, Some("secret")).await.unwrap();
+            let _: PowerStatus = call(&channel, "/pjlink.PjLinkControl/GetPower", GetPowerRequest {}, None).await.unwrap();
+        });
+
+        let connection_ids = handler.lock().unwrap().connection_ids.clone();
+        assert_eq!(connection_ids.len(), 2);
+        assert_eq!(connection_ids[0], connection_ids[1]);
+    }
+}
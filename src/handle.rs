@@ -0,0 +1,343 @@
+//! Handle of a running server.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+#[cfg(feature = "event-loop")]
+use std::sync::{Mutex, TryLockError};
+#[cfg(feature = "event-loop")]
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+
+use crate::{PjLinkError, PjLinkHandlerLockState, PjLinkListenerShared, PjLinkReloadableConfig, PjLinkServerHealth};
+
+/// Running server started by [PjLinkServer](crate::PjLinkServer), with its
+/// TCP and (optional) UDP listener threads.
+///
+/// ## Examples
+/// ```
+/// use std::sync::{Arc, Mutex};
+/// use pjlink_bridge::*;
+///
+/// struct Projector;
+///
+/// impl PjLinkHandler for Projector {
+///     fn get_password(&mut self, _connection_id: &u64) -> Option<String> {
+///         None
+///     }
+///
+///     fn handle_command(&mut self, _command: PjLinkCommand, _raw_command: &PjLinkRawPayload, _connection_id: &u64) -> PjLinkResponse {
+///         PjLinkResponse::Undefined
+///     }
+/// }
+///
+/// let handle = PjLinkServer::listen_tcp_only(
+///     Arc::new(Mutex::new(Projector)),
+///     String::from("127.0.0.1"),
+///     String::from("0"),
+/// ).unwrap();
+///
+/// assert_ne!(handle.local_tcp_addr().unwrap().port(), 0);
+/// assert!(handle.local_udp_addr().is_none());
+/// assert!(handle.is_healthy());
+/// ```
+pub struct PjLinkServerHandle<'a> {
+    listener: PjLinkListenerShared<'a>,
+    threads: PjLinkServerThreads,
+}
+
+/// Threads serving the listener of a [PjLinkServerHandle].
+enum PjLinkServerThreads {
+    /// TCP accept thread, and UDP thread if search requests are received
+    Dedicated {
+        tcp_thread: JoinHandle<()>,
+        udp_thread: Option<JoinHandle<()>>,
+    },
+    /// Event loop thread serving other listeners too
+    #[cfg(feature = "event-loop")]
+    Shared(Arc<PjLinkSharedThread>),
+}
+
+/// Thread shared by the handles of [listen_many](crate::PjLinkServer::listen_many).
+/// The first handle joining it waits for it; the others wait for that join.
+#[cfg(feature = "event-loop")]
+pub(crate) struct PjLinkSharedThread {
+    name: String,
+    thread: Mutex<Option<JoinHandle<()>>>,
+    is_panicked: AtomicBool,
+}
+
+#[cfg(feature = "event-loop")]
+impl PjLinkSharedThread {
+    pub(crate) fn new(thread: JoinHandle<()>) -> PjLinkSharedThread {
+        PjLinkSharedThread {
+            name: thread.thread().name().unwrap_or_default().to_string(),
+            thread: Mutex::new(Option::Some(thread)),
+            is_panicked: AtomicBool::new(false),
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        match self.thread.try_lock() {
+            Ok(thread) => thread.as_ref().is_none_or(JoinHandle::is_finished),
+            // Another handle is waiting for it
+            Err(TryLockError::WouldBlock) => false,
+            Err(TryLockError::Poisoned(_)) => true,
+        }
+    }
+
+    fn join(&self) -> Result<(), PjLinkError> {
+        let mut thread = match self.thread.lock() {
+            Ok(thread) => thread,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if let Some(thread) = thread.take() {
+            self.is_panicked.store(thread.join().is_err(), Ordering::SeqCst);
+        }
+
+        match self.is_panicked.load(Ordering::SeqCst) {
+            true => Err(PjLinkError::Shutdown { thread: self.name.clone() }),
+            false => Ok(()),
+        }
+    }
+}
+
+impl<'a> PjLinkServerHandle<'a> {
+    pub(crate) fn new(
+        listener: PjLinkListenerShared<'a>,
+        tcp_thread: JoinHandle<()>,
+        udp_thread: Option<JoinHandle<()>>,
+    ) -> PjLinkServerHandle<'a> {
+        PjLinkServerHandle { listener, threads: PjLinkServerThreads::Dedicated { tcp_thread, udp_thread } }
+    }
+
+    /// Creates the handle of a listener served by a thread shared with
+    /// other listeners.
+    #[cfg(feature = "event-loop")]
+    pub(crate) fn shared(listener: PjLinkListenerShared<'a>, thread: Arc<PjLinkSharedThread>) -> PjLinkServerHandle<'a> {
+        PjLinkServerHandle { listener, threads: PjLinkServerThreads::Shared(thread) }
+    }
+
+    /// Returns the listener, to send status notifications or read statistics.
+    pub fn listener(&self) -> &PjLinkListenerShared<'a> {
+        &self.listener
+    }
+
+    /// Returns the address the TCP listener is bound to. Useful when binding
+    /// port `0`.
+    pub fn local_tcp_addr(&self) -> Result<SocketAddr, PjLinkError> {
+        self.listener.local_tcp_addr()
+    }
+
+    /// Returns the address the UDP socket is bound to, or `None` if the
+    /// server doesn't listen to UDP.
+    pub fn local_udp_addr(&self) -> Option<SocketAddr> {
+        self.listener.local_udp_addr()
+    }
+
+    /// Returns the current reloadable configuration.
+    pub fn config(&self) -> Arc<PjLinkReloadableConfig> {
+        self.listener.config()
+    }
+
+    /// Replaces the descriptor, password provider and notification targets
+    /// at once, without dropping controller connections. See
+    /// [PjLinkReloadableConfig](crate::PjLinkReloadableConfig).
+    pub fn reload(&self, config: PjLinkReloadableConfig) {
+        self.listener.reload(config);
+    }
+
+    /// Returns whether the TCP and UDP loops are running, whether the
+    /// handler lock is held, and the last socket error, for liveness
+    /// probes. See [PjLinkServerHealth](crate::PjLinkServerHealth).
+    pub fn health(&self) -> PjLinkServerHealth {
+        let tcp_error = self.listener.tcp_health.last_error();
+        let udp_error = self.listener.udp_health.last_error();
+        let last_error = match (tcp_error, udp_error) {
+            (Some(tcp_error), Some(udp_error)) => Option::Some(if tcp_error.0 > udp_error.0 { tcp_error } else { udp_error }),
+            (tcp_error, udp_error) => tcp_error.or(udp_error),
+        };
+
+        let (tcp_alive, udp_alive) = match &self.threads {
+            PjLinkServerThreads::Dedicated { tcp_thread, udp_thread } => (
+                !tcp_thread.is_finished(),
+                udp_thread.as_ref().map(|udp_thread| !udp_thread.is_finished() && self.listener.udp_health.is_running()),
+            ),
+            #[cfg(feature = "event-loop")]
+            PjLinkServerThreads::Shared(thread) => {
+                let is_alive = !thread.is_finished();
+                let receives_search_requests = cfg!(feature = "discovery")
+                    && self.listener.local_udp_addr().is_some()
+                    && !self.listener.shared_options.is_class_1_only();
+                (is_alive, receives_search_requests.then(|| is_alive && self.listener.udp_health.is_running()))
+            }
+        };
+
+        PjLinkServerHealth {
+            tcp_alive,
+            udp_alive,
+            handler_lock: PjLinkHandlerLockState::of(&self.listener.shared_handler),
+            last_error: last_error.map(|(_, last_error)| last_error),
+        }
+    }
+
+    /// Returns `true` while every listener loop is running and the handler
+    /// lock isn't poisoned. Same as [health](self::PjLinkServerHandle::health)`().is_healthy()`.
+    pub fn is_healthy(&self) -> bool {
+        self.health().is_healthy()
+    }
+
+    /// Waits for the listener threads to finish. Fails with
+    /// [Shutdown](crate::PjLinkError::Shutdown) if any of them panicked.
+    pub fn join(self) -> Result<(), PjLinkError> {
+        match self.threads {
+            PjLinkServerThreads::Dedicated { tcp_thread, udp_thread } => {
+                let tcp_result = Self::join_thread(tcp_thread);
+                let udp_result = match udp_thread {
+                    Some(udp_thread) => Self::join_thread(udp_thread),
+                    None => Ok(()),
+                };
+
+                tcp_result.and(udp_result)
+            }
+            #[cfg(feature = "event-loop")]
+            PjLinkServerThreads::Shared(thread) => thread.join(),
+        }
+    }
+
+    fn join_thread(thread: JoinHandle<()>) -> Result<(), PjLinkError> {
+        let name = thread.thread().name().unwrap_or_default().to_string();
+        thread.join().map_err(|_| PjLinkError::Shutdown { thread: name })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PjLinkServerThreads;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpStream;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use crate::{
+        PjLinkClassCommandStatus, PjLinkCommand, PjLinkHandler, PjLinkHandlerLockState, PjLinkPassword, PjLinkProjectorDescriptor,
+        PjLinkRawPayload, PjLinkResponse, PjLinkServer, PjLinkServerHealth, PjLinkSwappablePassword,
+    };
+
+    struct UndefinedHandler;
+
+    impl PjLinkHandler for UndefinedHandler {
+        fn get_password(&mut self, _connection_id: &u64) -> Option<String> {
+            Option::None
+        }
+
+        fn handle_command(&mut self, _command: PjLinkCommand, _raw_command: &PjLinkRawPayload, _connection_id: &u64) -> PjLinkResponse {
+            PjLinkResponse::Undefined
+        }
+    }
+
+    #[test]
+    fn it_reports_bound_addresses_and_running_threads() {
+        let handle = PjLinkServer::listen_tcp_udp(
+            Arc::new(Mutex::new(UndefinedHandler)),
+            String::from("127.0.0.1"),
+            String::from("127.0.0.1"),
+            String::from("0"),
+        ).unwrap();
+
+        assert_ne!(handle.local_tcp_addr().unwrap().port(), 0);
+        assert_ne!(handle.local_udp_addr().unwrap().port(), 0);
+        // The UDP loop reports itself running once its thread has started
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while !handle.is_healthy() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(handle.is_healthy());
+        assert!(handle.listener().udp_health().is_some());
+    }
+
+    #[test]
+    fn it_names_listener_threads_after_their_port() {
+        let handle = PjLinkServer::listen_tcp_udp(
+            Arc::new(Mutex::new(UndefinedHandler)),
+            String::from("127.0.0.1"),
+            String::from("127.0.0.1"),
+            String::from("0"),
+        ).unwrap();
+
+        let (tcp_thread, udp_thread) = match &handle.threads {
+            PjLinkServerThreads::Dedicated { tcp_thread, udp_thread } => (tcp_thread, udp_thread),
+            #[cfg(feature = "event-loop")]
+            PjLinkServerThreads::Shared(_) => panic!("listener threads are shared"),
+        };
+        let tcp_port = handle.local_tcp_addr().unwrap().port();
+        assert_eq!(tcp_thread.thread().name(), Some(format!("pjlink-tcp-accept-{}", tcp_port).as_str()));
+        // Only receiving search requests needs a UDP thread
+        if let Some(udp_thread) = udp_thread {
+            let udp_port = handle.local_udp_addr().unwrap().port();
+            assert_eq!(udp_thread.thread().name(), Some(format!("pjlink-udp-{}", udp_port).as_str()));
+        }
+    }
+
+    #[test]
+    fn it_reports_health_with_handler_lock_state() {
+        let handler = Arc::new(Mutex::new(UndefinedHandler));
+        let handle = PjLinkServer::listen_tcp_only(handler.clone(), String::from("127.0.0.1"), String::from("0")).unwrap();
+
+        let health = handle.health();
+        assert_eq!(health, PjLinkServerHealth {
+            tcp_alive: true,
+            udp_alive: None,
+            handler_lock: PjLinkHandlerLockState::Free,
+            last_error: None,
+        });
+        assert!(health.is_healthy());
+
+        let held = handler.lock().unwrap();
+        let health = handle.health();
+        assert_eq!(health.handler_lock, PjLinkHandlerLockState::Held);
+        assert!(health.is_healthy());
+        assert!(handle.is_healthy());
+        drop(held);
+
+        let poisoning_handler = handler.clone();
+        let _ = thread::spawn(move || {
+            let _held = poisoning_handler.lock().unwrap();
+            panic!("handler panicked");
+        }).join();
+        assert_eq!(handle.health().handler_lock, PjLinkHandlerLockState::Poisoned);
+        assert!(!handle.is_healthy());
+    }
+
+    #[test]
+    fn it_reloads_config_without_dropping_connections() {
+        let handle = PjLinkServer::listen_tcp_only(
+            Arc::new(Mutex::new(UndefinedHandler)),
+            String::from("127.0.0.1"),
+            String::from("0"),
+        ).unwrap();
+        let connect = || {
+            let stream = TcpStream::connect(handle.local_tcp_addr().unwrap()).unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut security_header = Vec::new();
+            reader.read_until(b'\r', &mut security_header).unwrap();
+            (stream, reader, security_header)
+        };
+        let send = |stream: &mut TcpStream, reader: &mut BufReader<TcpStream>, command: &[u8]| {
+            let mut response = Vec::new();
+            stream.write_all(command).unwrap();
+            reader.read_until(b'\r', &mut response).unwrap();
+            response
+        };
+
+        let (mut stream, mut reader, _) = connect();
+        assert_eq!(send(&mut stream, &mut reader, b"%1CLSS ?\r"), b"%1CLSS=ERR1\r");
+
+        let mut config = (*handle.config()).clone();
+        config.descriptor = Some(PjLinkProjectorDescriptor::new(PjLinkClassCommandStatus::Class2));
+        config.password_provider = Some(Arc::new(PjLinkSwappablePassword::new(Some(PjLinkPassword::new("secret").unwrap()))));
+        handle.reload(config);
+
+        assert_eq!(send(&mut stream, &mut reader, b"%1CLSS ?\r"), b"%1CLSS=2\r");
+        assert_eq!(connect().2[..8], *b"PJLINK 1");
+    }
+}
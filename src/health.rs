@@ -0,0 +1,230 @@
+//! Listener health reporting.
+
+// Without the `discovery` feature nothing receives on the UDP socket, so
+// only the snapshot is used.
+#![cfg_attr(not(feature = "discovery"), allow(dead_code))]
+
+use std::io;
+use std::sync::{Mutex, TryLockError};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::PjLinkHandlerShared;
+
+/// Consecutive UDP receive errors after which the UDP socket is bound again.
+pub(crate) const PJLINK_UDP_REBIND_AFTER_ERRORS: u32 = 10;
+
+/// Initial wait after an UDP receive error. Doubles on each consecutive error.
+const PJLINK_UDP_ERROR_BACKOFF_MIN: Duration = Duration::from_millis(10);
+
+/// Maximum wait after an UDP receive error.
+const PJLINK_UDP_ERROR_BACKOFF_MAX: Duration = Duration::from_secs(5);
+
+/// Snapshot of the UDP listener health, returned by
+/// [PjLinkListener::udp_health](crate::PjLinkListener::udp_health).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PjLinkUdpHealth {
+    /// UDP receive loop is running
+    pub is_running: bool,
+    /// Receive errors since the last successfully received datagram
+    pub consecutive_errors: u32,
+    /// Receive errors since the listener started
+    pub total_errors: u64,
+    /// How many times the UDP socket was bound again after persistent errors
+    pub rebind_count: u64,
+    /// Last receive or bind error
+    pub last_error: Option<String>,
+    /// Received datagrams that weren't answered, like malformed search
+    /// requests or requests from disallowed networks
+    pub rejected_datagrams: u64,
+}
+
+impl PjLinkUdpHealth {
+    /// Returns `true` if the UDP loop is running and the last receive
+    /// succeeded.
+    pub fn is_healthy(&self) -> bool {
+        self.is_running && self.consecutive_errors == 0
+    }
+}
+
+/// State of the handler lock when server health was checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PjLinkHandlerLockState {
+    /// No thread holds the handler
+    Free,
+    /// A connection holds the handler, handling a command. If it stays held
+    /// across checks, the handler is slow or stuck, and commands wait for it.
+    Held,
+    /// A thread panicked while holding the handler
+    Poisoned,
+}
+
+impl PjLinkHandlerLockState {
+    /// Checks the lock of `handler`, without waiting for it.
+    pub(crate) fn of(handler: &PjLinkHandlerShared) -> PjLinkHandlerLockState {
+        match handler.try_lock() {
+            Ok(_) => PjLinkHandlerLockState::Free,
+            Err(TryLockError::WouldBlock) => PjLinkHandlerLockState::Held,
+            Err(TryLockError::Poisoned(_)) => PjLinkHandlerLockState::Poisoned,
+        }
+    }
+}
+
+/// Health of a running server, returned by
+/// [PjLinkServerHandle::health](crate::PjLinkServerHandle::health).
+///
+/// ## Examples
+/// ```no_run
+/// use std::process;
+/// use pjlink_bridge::*;
+///
+/// # fn example(handle: PjLinkServerHandle) {
+/// // Liveness probe command
+/// let health = handle.health();
+/// if !health.is_healthy() {
+///     eprintln!("Unhealthy: {:?}", health);
+///     process::exit(1);
+/// }
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PjLinkServerHealth {
+    /// TCP accept loop is running
+    pub tcp_alive: bool,
+    /// UDP receive loop is running, or `None` if the server doesn't listen
+    /// to UDP
+    pub udp_alive: Option<bool>,
+    /// Whether a connection was holding the handler when checked
+    pub handler_lock: PjLinkHandlerLockState,
+    /// Last TCP accept, UDP receive or UDP bind error, if any
+    pub last_error: Option<String>,
+}
+
+impl PjLinkServerHealth {
+    /// Returns `true` if the TCP loop, and the UDP loop if any, are running,
+    /// and the handler lock isn't poisoned. A held lock or past errors
+    /// don't make the server unhealthy.
+    pub fn is_healthy(&self) -> bool {
+        self.tcp_alive
+            && self.udp_alive != Option::Some(false)
+            && self.handler_lock != PjLinkHandlerLockState::Poisoned
+    }
+}
+
+/// TCP accept errors of a listener.
+#[derive(Default)]
+pub(crate) struct PjLinkTcpHealthState {
+    last_error: Mutex<Option<(Instant, String)>>,
+}
+
+impl PjLinkTcpHealthState {
+    pub(crate) fn record_error(&self, error: &io::Error) {
+        if let Ok(mut last_error) = self.last_error.lock() {
+            *last_error = Option::Some((Instant::now(), error.to_string()));
+        }
+    }
+
+    /// Returns the last error, with the time it happened.
+    pub(crate) fn last_error(&self) -> Option<(Instant, String)> {
+        self.last_error.lock().ok().and_then(|last_error| last_error.clone())
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct PjLinkUdpHealthState {
+    is_running: AtomicBool,
+    consecutive_errors: AtomicU32,
+    total_errors: AtomicU64,
+    rebind_count: AtomicU64,
+    last_error: Mutex<Option<(Instant, String)>>,
+    rejected_datagrams: AtomicU64,
+}
+
+impl PjLinkUdpHealthState {
+    pub(crate) fn set_running(&self, is_running: bool) {
+        self.is_running.store(is_running, Ordering::SeqCst);
+    }
+
+    pub(crate) fn record_success(&self) {
+        self.consecutive_errors.store(0, Ordering::SeqCst);
+    }
+
+    /// Records an error, returning the consecutive error count.
+    pub(crate) fn record_error(&self, error: &io::Error) -> u32 {
+        self.total_errors.fetch_add(1, Ordering::SeqCst);
+
+        if let Ok(mut last_error) = self.last_error.lock() {
+            *last_error = Option::Some((Instant::now(), error.to_string()));
+        }
+
+        self.consecutive_errors.fetch_add(1, Ordering::SeqCst).saturating_add(1)
+    }
+
+    pub(crate) fn record_rejected(&self) {
+        self.rejected_datagrams.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub(crate) fn record_rebind(&self) {
+        self.rebind_count.fetch_add(1, Ordering::SeqCst);
+        self.consecutive_errors.store(0, Ordering::SeqCst);
+    }
+
+    pub(crate) fn is_running(&self) -> bool {
+        self.is_running.load(Ordering::SeqCst)
+    }
+
+    /// Returns the last error, with the time it happened.
+    pub(crate) fn last_error(&self) -> Option<(Instant, String)> {
+        self.last_error.lock().ok().and_then(|last_error| last_error.clone())
+    }
+
+    pub(crate) fn snapshot(&self) -> PjLinkUdpHealth {
+        PjLinkUdpHealth {
+            is_running: self.is_running.load(Ordering::SeqCst),
+            consecutive_errors: self.consecutive_errors.load(Ordering::SeqCst),
+            total_errors: self.total_errors.load(Ordering::SeqCst),
+            rebind_count: self.rebind_count.load(Ordering::SeqCst),
+            last_error: self.last_error().map(|(_, last_error)| last_error),
+            rejected_datagrams: self.rejected_datagrams.load(Ordering::SeqCst),
+        }
+    }
+}
+
+/// Returns how long to wait after `consecutive_errors` UDP errors.
+pub(crate) fn udp_error_backoff(consecutive_errors: u32) -> Duration {
+    let exponent = consecutive_errors.saturating_sub(1).min(16);
+    PJLINK_UDP_ERROR_BACKOFF_MIN
+        .saturating_mul(1 << exponent)
+        .min(PJLINK_UDP_ERROR_BACKOFF_MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_doubles_backoff_up_to_maximum() {
+        assert_eq!(udp_error_backoff(1), Duration::from_millis(10));
+        assert_eq!(udp_error_backoff(3), Duration::from_millis(40));
+        assert_eq!(udp_error_backoff(u32::MAX), PJLINK_UDP_ERROR_BACKOFF_MAX);
+    }
+
+    #[test]
+    fn it_tracks_consecutive_errors_until_success_or_rebind() {
+        let health = PjLinkUdpHealthState::default();
+        health.set_running(true);
+        assert!(health.snapshot().is_healthy());
+
+        let error = io::Error::other("interface down");
+        assert_eq!(health.record_error(&error), 1);
+        assert_eq!(health.record_error(&error), 2);
+        assert!(!health.snapshot().is_healthy());
+
+        health.record_rebind();
+        let snapshot = health.snapshot();
+        assert!(snapshot.is_healthy());
+        assert_eq!(snapshot.total_errors, 2);
+        assert_eq!(snapshot.rebind_count, 1);
+        assert_eq!(snapshot.last_error.as_deref(), Some("interface down"));
+    }
+}
@@ -0,0 +1,76 @@
+//! Wire-level hex dumps.
+
+use std::fmt::Write;
+
+/// Log target of [hex_dump](crate::PjLinkListenerOptions::hex_dump) messages,
+/// so they can be enabled separately from other trace logs.
+pub const PJLINK_WIRE_LOG_TARGET: &str = "pjlink_bridge::wire";
+
+/// Direction of wire data, in hex dumps and `PjLinkCaptureRecord`s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PjLinkCaptureDirection {
+    /// Line received from the controller, without terminator
+    Received,
+    /// Bytes sent to the controller, as written
+    Sent,
+}
+
+/// Formats `data` like Wireshark and `hexdump -C`: offset, 16 bytes in hex,
+/// and the same bytes as ASCII, with `.` for non-printable ones.
+///
+/// ## Examples
+/// ```
+/// use pjlink_bridge::*;
+///
+/// assert_eq!(
+///     format_hex_dump(b"%1POWR ?\r"),
+///     "00000000  25 31 50 4f 57 52 20 3f  0d                       |%1POWR ?.|",
+/// );
+/// ```
+pub fn format_hex_dump(data: &[u8]) -> String {
+    let mut dump = String::with_capacity(data.len().div_ceil(16) * 78);
+
+    for (line_index, line) in data.chunks(16).enumerate() {
+        if line_index > 0 {
+            dump.push('\n');
+        }
+        let _ = write!(dump, "{:08x} ", line_index * 16);
+
+        for column in 0..16 {
+            if column == 8 {
+                dump.push(' ');
+            }
+            match line.get(column) {
+                Some(byte) => {
+                    let _ = write!(dump, " {:02x}", byte);
+                }
+                None => dump.push_str("   "),
+            }
+        }
+
+        dump.push_str("  |");
+        dump.extend(line.iter().map(|byte| match byte {
+            0x20..=0x7e => *byte as char,
+            _ => '.',
+        }));
+        dump.push('|');
+    }
+
+    dump
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_formats_multiline_hex_dumps() {
+        let dump = format_hex_dump(b"%1NAME=Projector \xe2\x80\x94 Hall\r");
+        let lines: Vec<&str> = dump.lines().collect();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], "00000000  25 31 4e 41 4d 45 3d 50  72 6f 6a 65 63 74 6f 72  |%1NAME=Projector|");
+        assert_eq!(lines[1], "00000010  20 e2 80 94 20 48 61 6c  6c 0d                    | ... Hall.|");
+        assert_eq!(format_hex_dump(b""), "");
+    }
+}
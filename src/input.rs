@@ -0,0 +1,184 @@
+//! Input terminal table handlers can embed to answer `INPT`, `INST` and
+//! `INNM` consistently.
+
+use crate::{PjLinkCommand, PjLinkInputCommandParameter, PjLinkInputCommandStatus, PjLinkIntoResponse, PjLinkName, PjLinkResponse};
+
+/// Input terminal registered in a [PjLinkInputTable](self::PjLinkInputTable).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PjLinkInput {
+    /// Input type, as a [PjLinkInputCommandStatus](crate::PjLinkInputCommandStatus) value
+    pub input_type: u8,
+    /// Input number: `1` to `9`, or `A` to `Z` for Class 2 only inputs
+    pub index: u8,
+    /// Terminal name, answered to `%2INNM ?`
+    pub name: PjLinkName,
+}
+
+impl PjLinkInput {
+    /// Returns `true` if Class 1 controllers can select this input.
+    pub fn is_class_1(&self) -> bool {
+        self.input_type != PjLinkInputCommandStatus::Internal && self.index.is_ascii_digit()
+    }
+
+    fn matches(&self, parameter: &PjLinkInputCommandParameter) -> bool {
+        input_parameter_bytes(parameter) == Option::Some([self.input_type, self.index])
+    }
+}
+
+/// Inputs of a projector, registered once, and the selected one.
+///
+/// Answers `%1INPT`/`%2INPT` (selecting only registered inputs, `ERR2`
+/// otherwise), `%1INST ?`/`%2INST ?` and `%2INNM ?` from the same table, so
+/// they never disagree. The first registered input is selected initially.
+///
+/// ## Examples
+/// ```
+/// use pjlink_bridge::*;
+///
+/// let mut inputs = PjLinkInputTable::new();
+/// inputs
+///     .add(PjLinkInputCommandStatus::RGB, b'1', PjLinkName::truncated("VGA"))
+///     .add(PjLinkInputCommandStatus::Digital, b'1', PjLinkName::truncated("HDMI 1"))
+///     .add(PjLinkInputCommandStatus::Internal, b'1', PjLinkName::truncated("Media player"));
+///
+/// assert_eq!(inputs.handle(&PjLinkCommand::InputTogglingList1), Some(PjLinkResponse::Multiple(b"11 31".to_vec())));
+/// assert_eq!(inputs.handle(&PjLinkCommand::Input1(PjLinkInputCommandParameter::Digital(b'1'))), Some(PjLinkResponse::Ok));
+/// assert_eq!(inputs.handle(&PjLinkCommand::Input1(PjLinkInputCommandParameter::Video(b'1'))), Some(PjLinkResponse::OutOfParameter));
+/// assert_eq!(inputs.current(), Some([PjLinkInputCommandStatus::Digital, b'1']));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PjLinkInputTable {
+    inputs: Vec<PjLinkInput>,
+    current: Option<usize>,
+}
+
+impl PjLinkInputTable {
+    pub fn new() -> PjLinkInputTable {
+        PjLinkInputTable::default()
+    }
+
+    /// Registers an input, replacing the name of an already registered one.
+    ///
+    /// **Arguments**:
+    /// * `input_type`: Input type, as a [PjLinkInputCommandStatus](crate::PjLinkInputCommandStatus) value
+    /// * `index`: Input number: `b'1'` to `b'9'`, or `b'A'` to `b'Z'` for Class 2 only inputs
+    /// * `name`: Terminal name
+    pub fn add(&mut self, input_type: u8, index: u8, name: PjLinkName) -> &mut PjLinkInputTable {
+        match self.inputs.iter_mut().find(|input| input.input_type == input_type && input.index == index) {
+            Some(input) => input.name = name,
+            None => {
+                self.inputs.push(PjLinkInput { input_type, index, name });
+                self.current.get_or_insert(0);
+            }
+        }
+
+        self
+    }
+
+    /// Returns the registered inputs, in registration order.
+    pub fn inputs(&self) -> &[PjLinkInput] {
+        &self.inputs
+    }
+
+    /// Returns the selected input as type and number, like `[b'3', b'1']`.
+    pub fn current(&self) -> Option<[u8; 2]> {
+        self.current.map(|index| [self.inputs[index].input_type, self.inputs[index].index])
+    }
+
+    /// Answers an input command, or returns `None` if `command` isn't one.
+    pub fn handle(&mut self, command: &PjLinkCommand) -> Option<PjLinkResponse> {
+        let response = match command {
+            PjLinkCommand::Input1(parameter) | PjLinkCommand::Input2(parameter) => self.select(parameter),
+            PjLinkCommand::InputTogglingList1 => self.list(false),
+            PjLinkCommand::InputTogglingList2 => self.list(true),
+            PjLinkCommand::InputTerminalName2(parameter) => self.terminal_name(parameter),
+            _ => return Option::None,
+        };
+
+        Option::Some(response)
+    }
+
+    /// Answers `%1INPT` and `%2INPT`: queries with the selected input (or
+    /// `ERR1` if the table is empty), and selections of unregistered inputs
+    /// with `ERR2`.
+    pub fn select(&mut self, parameter: &PjLinkInputCommandParameter) -> PjLinkResponse {
+        if let PjLinkInputCommandParameter::Query = parameter {
+            return match self.current() {
+                Some(current) => PjLinkResponse::Multiple(current.to_vec()),
+                None => PjLinkResponse::Undefined,
+            };
+        }
+
+        match self.inputs.iter().position(|input| input.matches(parameter)) {
+            Some(index) => {
+                self.current = Option::Some(index);
+                PjLinkResponse::Ok
+            }
+            None => PjLinkResponse::OutOfParameter,
+        }
+    }
+
+    /// Answers `%1INST ?` (`is_class_2` is `false`), listing Class 1 inputs
+    /// only, and `%2INST ?`, listing every input.
+    pub fn list(&self, is_class_2: bool) -> PjLinkResponse {
+        let mut list = Vec::with_capacity(self.inputs.len() * 3);
+
+        for input in self.inputs.iter().filter(|input| is_class_2 || input.is_class_1()) {
+            if !list.is_empty() {
+                list.push(b' ');
+            }
+            list.extend_from_slice(&[input.input_type, input.index]);
+        }
+
+        list.into_response()
+    }
+
+    /// Answers `%2INNM ?` with the name of a registered input, or `ERR2`.
+    pub fn terminal_name(&self, parameter: &PjLinkInputCommandParameter) -> PjLinkResponse {
+        match self.inputs.iter().find(|input| input.matches(parameter)) {
+            Some(input) => input.name.clone().into_response(),
+            None => PjLinkResponse::OutOfParameter,
+        }
+    }
+}
+
+/// Returns the input type and number of an input parameter, or `None` for
+/// queries and unknown inputs.
+pub(crate) fn input_parameter_bytes(parameter: &PjLinkInputCommandParameter) -> Option<[u8; 2]> {
+    let (input_type, index) = match *parameter {
+        PjLinkInputCommandParameter::RGB(index) => (PjLinkInputCommandStatus::RGB, index),
+        PjLinkInputCommandParameter::Video(index) => (PjLinkInputCommandStatus::Video, index),
+        PjLinkInputCommandParameter::Digital(index) => (PjLinkInputCommandStatus::Digital, index),
+        PjLinkInputCommandParameter::Storage(index) => (PjLinkInputCommandStatus::Storage, index),
+        PjLinkInputCommandParameter::Network(index) => (PjLinkInputCommandStatus::Network, index),
+        PjLinkInputCommandParameter::Internal(index) => (PjLinkInputCommandStatus::Internal, index),
+        PjLinkInputCommandParameter::Query | PjLinkInputCommandParameter::Unknown => return Option::None,
+    };
+
+    Option::Some([input_type, index])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_keeps_input_commands_consistent() {
+        let mut inputs = PjLinkInputTable::new();
+        inputs
+            .add(PjLinkInputCommandStatus::Digital, b'1', PjLinkName::truncated("HDMI 1"))
+            .add(PjLinkInputCommandStatus::Network, b'A', PjLinkName::truncated("Stream"));
+
+        assert_eq!(inputs.list(false), PjLinkResponse::Multiple(b"31".to_vec()));
+        assert_eq!(inputs.list(true), PjLinkResponse::Multiple(b"31 5A".to_vec()));
+        assert_eq!(inputs.select(&PjLinkInputCommandParameter::Query), PjLinkResponse::Multiple(b"31".to_vec()));
+        assert_eq!(inputs.select(&PjLinkInputCommandParameter::Network(b'A')), PjLinkResponse::Ok);
+        assert_eq!(inputs.select(&PjLinkInputCommandParameter::Unknown), PjLinkResponse::OutOfParameter);
+        assert_eq!(inputs.current(), Some([PjLinkInputCommandStatus::Network, b'A']));
+        assert_eq!(
+            inputs.terminal_name(&PjLinkInputCommandParameter::Network(b'A')),
+            PjLinkResponse::Multiple(b"Stream".to_vec())
+        );
+        assert_eq!(inputs.terminal_name(&PjLinkInputCommandParameter::RGB(b'1')), PjLinkResponse::OutOfParameter);
+    }
+}
@@ -0,0 +1,517 @@
+//! JSON-RPC 2.0 control interface (`jsonrpc` feature).
+
+use std::convert::TryFrom;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use log::{info, debug, warn};
+use serde_json::{json, Map, Value};
+
+use crate::{PjLinkError, PjLinkListener, PjLinkRawPayload, PjLinkResponse, PjLinkStatusCommand, spawn_named_thread};
+use crate::gateway::{PjLinkGatewayError, PjLinkGatewaySession};
+use crate::server::PjLinkStatusSubscribers;
+
+/// Longest accepted request line, so clients can't make the connection
+/// buffer grow forever.
+const PJLINK_JSON_RPC_MAX_LINE_LENGTH: usize = 64 * 1024;
+
+/// JSON-RPC error code and message.
+type PjLinkJsonRpcError = (i64, &'static str);
+
+const PJLINK_JSON_RPC_PARSE_ERROR: PjLinkJsonRpcError = (-32700, "Parse error");
+const PJLINK_JSON_RPC_INVALID_REQUEST: PjLinkJsonRpcError = (-32600, "Invalid Request");
+const PJLINK_JSON_RPC_METHOD_NOT_FOUND: PjLinkJsonRpcError = (-32601, "Method not found");
+const PJLINK_JSON_RPC_INVALID_PARAMS: PjLinkJsonRpcError = (-32602, "Invalid params");
+const PJLINK_JSON_RPC_INTERNAL_ERROR: PjLinkJsonRpcError = (-32603, "Internal error");
+const PJLINK_JSON_RPC_UNAUTHENTICATED: PjLinkJsonRpcError = (-32000, "Unauthenticated");
+const PJLINK_JSON_RPC_PASSWORD_DENIED: PjLinkJsonRpcError = (-32005, "ERRA");
+
+/// Connection of a JSON-RPC client, answering requests through its own
+/// PJLink session.
+struct PjLinkJsonRpcConnection {
+    session: PjLinkGatewaySession,
+    status_subscribers: Arc<PjLinkStatusSubscribers>,
+    connection_id: u64,
+    writer: Arc<Mutex<TcpStream>>,
+    /// Subscriber ID and thread relaying status messages, once subscribed
+    subscription: Option<(u64, JoinHandle<()>)>,
+}
+
+impl PjLinkJsonRpcConnection {
+    fn serve(&mut self, stream: TcpStream) {
+        let mut reader = BufReader::new(stream);
+        let mut line = Vec::new();
+
+        loop {
+            line.clear();
+            match reader.by_ref().take(PJLINK_JSON_RPC_MAX_LINE_LENGTH as u64).read_until(b'\n', &mut line) {
+                Ok(0) => return,
+                Ok(length) if length == PJLINK_JSON_RPC_MAX_LINE_LENGTH && line.last() != Option::Some(&b'\n') => {
+                    debug!("JSON-RPC request too long, closing! ConnectionId: {}", self.connection_id);
+                    return;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    debug!("Error on reading JSON-RPC connection! ConnectionId: {}, {}", self.connection_id, e);
+                    return;
+                }
+            }
+
+            let text = String::from_utf8_lossy(&line);
+            if text.trim().is_empty() {
+                continue;
+            }
+
+            let response = match serde_json::from_str::<Value>(&text) {
+                Ok(message) => self.handle_message(&message),
+                Err(e) => {
+                    debug!("Received invalid JSON! ConnectionId: {}, {}", self.connection_id, e);
+                    Option::Some(error_response(Value::Null, PJLINK_JSON_RPC_PARSE_ERROR))
+                }
+            };
+
+            if let Some(response) = response {
+                if let Err(e) = write_line(&self.writer, &response) {
+                    debug!("Error on writing JSON-RPC response! ConnectionId: {}, {}", self.connection_id, e);
+                    return;
+                }
+            }
+
+            // Like PJLink connections after ERRA, so passwords can't be
+            // guessed without reconnecting
+            if self.session.is_denied() {
+                debug!("JSON-RPC client sent a wrong password, closing! ConnectionId: {}", self.connection_id);
+                return;
+            }
+        }
+    }
+
+    /// Answers a request or a batch, or returns `None` if it only contained
+    /// notifications.
+    fn handle_message(&mut self, message: &Value) -> Option<Value> {
+        match message.as_array().map(Vec::as_slice) {
+            Some([]) => Option::Some(error_response(Value::Null, PJLINK_JSON_RPC_INVALID_REQUEST)),
+            Some(requests) => {
+                let responses: Vec<Value> = requests.iter().filter_map(|request| self.handle_request(request)).collect();
+                match responses.is_empty() {
+                    true => Option::None,
+                    false => Option::Some(Value::Array(responses)),
+                }
+            }
+            None => self.handle_request(message),
+        }
+    }
+
+    fn handle_request(&mut self, request: &Value) -> Option<Value> {
+        let id = request.get("id").cloned();
+        let method = match (request.get("jsonrpc").and_then(Value::as_str), request.get("method").and_then(Value::as_str)) {
+            (Some("2.0"), Some(method)) => method,
+            _ => return Option::Some(error_response(id.unwrap_or(Value::Null), PJLINK_JSON_RPC_INVALID_REQUEST)),
+        };
+
+        debug!("JSON-RPC call. ConnectionId: {}, Method: {}", self.connection_id, method);
+        let result = self.call(method, request.get("params").unwrap_or(&Value::Null));
+
+        // Requests without ID are notifications, never answered
+        let id = id?;
+        Option::Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(error) => error_response(id, error),
+        })
+    }
+
+    fn call(&mut self, method: &str, params: &Value) -> Result<Value, PjLinkJsonRpcError> {
+        match method {
+            "auth" => {
+                let password = params.get("password").and_then(Value::as_str).ok_or(PJLINK_JSON_RPC_INVALID_PARAMS)?;
+                self.session.authenticate(password).map_err(to_json_rpc_error)?;
+                Ok(Value::Bool(true))
+            }
+            "power.get" => self.send(*b"1POWR", b"?".to_vec()).map(to_json_string),
+            "power.set" => {
+                let power = match params.get("on").and_then(Value::as_bool) {
+                    Some(true) => b'1',
+                    Some(false) => b'0',
+                    None => return Err(PJLINK_JSON_RPC_INVALID_PARAMS),
+                };
+                self.send(*b"1POWR", vec![power]).map(to_json_string)
+            }
+            "input.get" => self.send(input_command_body(params)?, b"?".to_vec()).map(to_json_string),
+            "input.set" => {
+                let input = params.get("input").and_then(Value::as_str).ok_or(PJLINK_JSON_RPC_INVALID_PARAMS)?;
+                self.send(input_command_body(params)?, input.as_bytes().to_vec()).map(to_json_string)
+            }
+            "status.get" => {
+                self.session.ensure_authenticated().map_err(to_json_rpc_error)?;
+                let members = [("power", b"1POWR"), ("input", b"1INPT"), ("error_status", b"1ERST"), ("av_mute", b"1AVMT"), ("name", b"1NAME")]
+                    .iter()
+                    .map(|(name, command_body_with_class)| {
+                        let value = self.send(**command_body_with_class, b"?".to_vec()).map(to_json_string).unwrap_or(Value::Null);
+                        (String::from(*name), value)
+                    })
+                    .collect::<Map<String, Value>>();
+                Ok(Value::Object(members))
+            }
+            "status.subscribe" => {
+                self.session.ensure_authenticated().map_err(to_json_rpc_error)?;
+                if self.subscription.is_none() {
+                    self.subscribe().map_err(|e| {
+                        warn!("Failed to start JSON-RPC notification thread! ConnectionId: {}, {}", self.connection_id, e);
+                        PJLINK_JSON_RPC_INTERNAL_ERROR
+                    })?;
+                }
+                Ok(Value::Bool(true))
+            }
+            "command.send" => {
+                let command_body_with_class = params.get("command")
+                    .and_then(Value::as_str)
+                    .and_then(|command| <[u8; 5]>::try_from(command.as_bytes()).ok())
+                    .ok_or(PJLINK_JSON_RPC_INVALID_PARAMS)?;
+                let parameter = params.get("parameter").and_then(Value::as_str).ok_or(PJLINK_JSON_RPC_INVALID_PARAMS)?;
+                self.send(command_body_with_class, parameter.as_bytes().to_vec()).map(to_json_string)
+            }
+            _ => Err(PJLINK_JSON_RPC_METHOD_NOT_FOUND),
+        }
+    }
+
+    /// Sends a command through the session, returning the transmission
+    /// parameter of the response, or the PJLink error as a JSON-RPC error.
+    fn send(&mut self, command_body_with_class: [u8; 5], transmission_parameter: Vec<u8>) -> Result<Vec<u8>, PjLinkJsonRpcError> {
+        let raw_command = PjLinkRawPayload::new_command(command_body_with_class, transmission_parameter);
+
+        match self.session.send(&raw_command).map_err(to_json_rpc_error)? {
+            PjLinkResponse::Ok => Ok(b"OK".to_vec()),
+            PjLinkResponse::Empty => Ok(Vec::new()),
+            PjLinkResponse::Single(value) => Ok(vec![value]),
+            PjLinkResponse::Multiple(value) => Ok(value),
+            PjLinkResponse::Undefined => Err((-32001, "ERR1")),
+            PjLinkResponse::OutOfParameter => Err((-32002, "ERR2")),
+            PjLinkResponse::UnavailableTime => Err((-32003, "ERR3")),
+            PjLinkResponse::ProjectorOrDisplayFailure => Err((-32004, "ERR4")),
+        }
+    }
+
+    /// Relays status messages as `status.notify` notifications, until the
+    /// connection is dropped.
+    fn subscribe(&mut self) -> Result<(), PjLinkError> {
+        let (subscriber_id, status_messages) = self.status_subscribers.subscribe_with_id();
+        let writer = self.writer.clone();
+
+        let thread = spawn_named_thread(format!("pjlink-jsonrpc-notify-{}", self.connection_id), move || {
+            for status_message in status_messages {
+                if write_line(&writer, &to_notification(&status_message)).is_err() {
+                    break;
+                }
+            }
+        });
+        match thread {
+            Ok(thread) => {
+                self.subscription = Option::Some((subscriber_id, thread));
+                Ok(())
+            }
+            Err(e) => {
+                self.status_subscribers.unsubscribe(subscriber_id);
+                Err(e)
+            }
+        }
+    }
+}
+
+impl Drop for PjLinkJsonRpcConnection {
+    /// Stops the notification thread once the client is gone, instead of on
+    /// the next status message.
+    fn drop(&mut self) {
+        if let Ok(writer) = self.writer.lock() {
+            let _ = writer.shutdown(Shutdown::Both);
+        }
+        if let Some((subscriber_id, thread)) = self.subscription.take() {
+            self.status_subscribers.unsubscribe(subscriber_id);
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Returns the `INPT` command body of the `class` parameter, 1 if missing.
+fn input_command_body(params: &Value) -> Result<[u8; 5], PjLinkJsonRpcError> {
+    match params.get("class").map(Value::as_u64) {
+        None | Some(Some(1)) => Ok(*b"1INPT"),
+        Some(Some(2)) => Ok(*b"2INPT"),
+        Some(_) => Err(PJLINK_JSON_RPC_INVALID_PARAMS),
+    }
+}
+
+fn to_json_rpc_error(error: PjLinkGatewayError) -> PjLinkJsonRpcError {
+    match error {
+        PjLinkGatewayError::Unauthenticated => PJLINK_JSON_RPC_UNAUTHENTICATED,
+        PjLinkGatewayError::Denied => PJLINK_JSON_RPC_PASSWORD_DENIED,
+    }
+}
+
+fn to_json_string(value: Vec<u8>) -> Value {
+    Value::String(String::from_utf8_lossy(&value).into_owned())
+}
+
+fn to_notification(status_message: &PjLinkStatusCommand) -> Value {
+    let raw_payload = status_message.to_raw_payload();
+
+    json!({
+        "jsonrpc": "2.0",
+        "method": "status.notify",
+        "params": {
+            "command": String::from_utf8_lossy(&raw_payload.command_body_with_class[1..]),
+            "value": to_json_string(raw_payload.transmission_parameter),
+        },
+    })
+}
+
+fn error_response(id: Value, (code, message): PjLinkJsonRpcError) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+/// Writes `message` as one line, so responses and notifications written
+/// by different threads don't interleave.
+fn write_line(writer: &Mutex<TcpStream>, message: &Value) -> io::Result<()> {
+    let mut writer = writer.lock().map_err(|_| io::Error::other("JSON-RPC writer poisoned"))?;
+    writer.write_all(format!("{}\n", message).as_bytes())
+}
+
+impl<'a> PjLinkListener<'a> {
+    /// Accepts JSON-RPC 2.0 clients on `json_rpc_listener`, for scripts
+    /// that would rather not speak PJLink framing.
+    ///
+    /// Requests and responses are JSON values, one per line. Each client
+    /// is served like a PJLink connection, with one connection ID, and its
+    /// calls are answered by this listener's handler and options:
+    ///
+    /// | Method | Params | Result |
+    /// |---|---|---|
+    /// | `auth` | `{"password": "secret"}` | `true`. Read-only passwords only allow queries afterwards |
+    /// | `power.get` | | `"0"` to `"3"`, see [PjLinkPowerCommandStatus](crate::PjLinkPowerCommandStatus) |
+    /// | `power.set` | `{"on": true}` | `"OK"` |
+    /// | `input.get` | `{"class": 2}`, optional | Input, like `"31"` |
+    /// | `input.set` | `{"input": "31", "class": 2}`, class optional | `"OK"` |
+    /// | `status.get` | | Object with `power`, `input`, `error_status`, `av_mute` and `name`, `null` if the handler answered an error |
+    /// | `status.subscribe` | | `true`. Then, status messages sent by [notify](crate::PjLinkListener::notify) are sent as `status.notify` notifications, with `command` and `value` params |
+    /// | `command.send` | `{"command": "1LAMP", "parameter": "?"}` | Response parameter |
+    ///
+    /// If the listener requires a password, other methods fail with code
+    /// `-32000` until `auth` succeeds. Wrong passwords fail with `-32005`,
+    /// and the connection is closed after the response.
+    /// `ERR1` to `ERR4` responses are errors with codes `-32001` to
+    /// `-32004`. Blocks the current thread. Available with the `jsonrpc`
+    /// feature.
+    ///
+    /// ## Examples
+    /// ```no_run
+    /// use std::net::TcpListener;
+    /// use std::thread;
+    /// use pjlink_bridge::*;
+    ///
+    /// # fn example(listener: PjLinkListenerShared<'static>) {
+    /// let json_rpc_listener = TcpListener::bind("127.0.0.1:4353").unwrap();
+    /// let listener_clone = listener.clone();
+    ///
+    /// // printf '%s\n%s\n' '{"jsonrpc":"2.0","id":1,"method":"auth","params":{"password":"secret"}}' \
+    /// //     '{"jsonrpc":"2.0","id":2,"method":"power.set","params":{"on":true}}' | nc 127.0.0.1 4353
+    /// thread::spawn(move || listener_clone.listen_json_rpc(json_rpc_listener));
+    /// listener.listen();
+    /// # }
+    /// ```
+    pub fn listen_json_rpc(&self, json_rpc_listener: TcpListener) {
+        if let Ok(local_addr) = json_rpc_listener.local_addr() {
+            info!("Running JSON-RPC Listener on {}", local_addr);
+        }
+
+        for stream in json_rpc_listener.incoming() {
+            let (writer, stream) = match stream.and_then(|stream| Ok((stream.try_clone()?, stream))) {
+                Ok(streams) => streams,
+                Err(e) => {
+                    debug!("Error on received JSON-RPC connection! {}", e);
+                    continue;
+                }
+            };
+            if let Err(e) = self.shared_options.tcp.apply_to_stream(&stream) {
+                debug!("Failed to apply TCP options to connection! {}", e);
+            }
+
+            let connection_handler = self.connection_handler();
+            let Some(permit) = connection_handler.try_open_connection(stream.peer_addr().ok()) else {
+                continue;
+            };
+            let session = PjLinkGatewaySession::new(connection_handler, stream.peer_addr().ok());
+            let connection_id = session.connection_id();
+            let mut connection = PjLinkJsonRpcConnection {
+                session,
+                status_subscribers: self.shared_status_subscribers.clone(),
+                connection_id,
+                writer: Arc::new(Mutex::new(writer)),
+                subscription: Option::None,
+            };
+            if let Err(e) = spawn_named_thread(format!("pjlink-jsonrpc-{}", connection_id), move || {
+                let _permit = permit;
+                connection.serve(stream);
+            }) {
+                warn!("Failed to start JSON-RPC connection thread! {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use crate::{PjLinkCommand, PjLinkHandler, PjLinkListenerOptions, PjLinkPowerCommandParameter, PjLinkPowerCommandStatus};
+
+    struct PowerHandler {
+        power: u8,
+    }
+
+    impl PjLinkHandler for PowerHandler {
+        fn get_password(&mut self, _connection_id: &u64) -> Option<String> {
+            Option::Some(String::from("secret"))
+        }
+
+        fn handle_command(&mut self, command: PjLinkCommand, _raw_command: &PjLinkRawPayload, _connection_id: &u64) -> PjLinkResponse {
+            match command {
+                PjLinkCommand::Power1(PjLinkPowerCommandParameter::Query) => PjLinkResponse::Single(self.power),
+                PjLinkCommand::Power1(PjLinkPowerCommandParameter::On) => {
+                    self.power = PjLinkPowerCommandStatus::On;
+                    PjLinkResponse::Ok
+                }
+                _ => PjLinkResponse::Undefined,
+            }
+        }
+    }
+
+    #[test]
+    fn it_maps_methods_onto_the_handler() {
+        let json_rpc_listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = json_rpc_listener.local_addr().unwrap();
+        let listener = PjLinkListener::new_without_broadcast(
+            Arc::new(Mutex::new(PowerHandler { power: PjLinkPowerCommandStatus::Off })),
+            TcpListener::bind("127.0.0.1:0").unwrap(),
+        );
+        let listener_clone = listener.clone();
+        thread::spawn(move || listener_clone.listen_json_rpc(json_rpc_listener));
+
+        let mut client = TcpStream::connect(address).unwrap();
+        let mut reader = BufReader::new(client.try_clone().unwrap());
+        let mut call = |request: &str| {
+            client.write_all(format!("{}\n", request).as_bytes()).unwrap();
+            let mut response = String::new();
+            reader.read_line(&mut response).unwrap();
+            response
+        };
+
+        assert_eq!(
+            call(r#"{"jsonrpc":"2.0","id":0,"method":"auth","params":{"password":"secret"}}"#),
+            "{\"jsonrpc\":\"2.0\",\"id\":0,\"result\":true}\n",
+        );
+        assert_eq!(
+            call(r#"{"jsonrpc":"2.0","id":1,"method":"power.set","params":{"on":true}}"#),
+            "{\"jsonrpc\":\"2.0\",\"id\":1,\"result\":\"OK\"}\n",
+        );
+        assert_eq!(call(r#"{"jsonrpc":"2.0","id":"a","method":"power.get"}"#), "{\"jsonrpc\":\"2.0\",\"id\":\"a\",\"result\":\"1\"}\n");
+        assert_eq!(
+            call(r#"{"jsonrpc":"2.0","id":2,"method":"input.get"}"#),
+            "{\"jsonrpc\":\"2.0\",\"id\":2,\"error\":{\"code\":-32001,\"message\":\"ERR1\"}}\n",
+        );
+        assert_eq!(
+            call(r#"{"jsonrpc":"2.0","id":3,"method":"lamp.get"}"#),
+            "{\"jsonrpc\":\"2.0\",\"id\":3,\"error\":{\"code\":-32601,\"message\":\"Method not found\"}}\n",
+        );
+        assert_eq!(call("{"), "{\"jsonrpc\":\"2.0\",\"id\":null,\"error\":{\"code\":-32700,\"message\":\"Parse error\"}}\n");
+        let stats = listener.stats();
+        assert_eq!((stats.active_connections, stats.commands_processed), (1, 3));
+
+        assert_eq!(call(r#"{"jsonrpc":"2.0","id":4,"method":"status.subscribe"}"#), "{\"jsonrpc\":\"2.0\",\"id\":4,\"result\":true}\n");
+        listener.notify(&PjLinkStatusCommand::Power2(PjLinkPowerCommandStatus::Cooling)).unwrap();
+        assert_eq!(
+            call(r#"{"jsonrpc":"2.0","method":"power.get"}"#),
+            "{\"jsonrpc\":\"2.0\",\"method\":\"status.notify\",\"params\":{\"command\":\"POWR\",\"value\":\"2\"}}\n",
+        );
+
+        // Disconnecting unsubscribes without waiting for another message
+        drop((client, reader));
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while listener.shared_status_subscribers.len() > 0 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(listener.shared_status_subscribers.len(), 0);
+    }
+
+    #[test]
+    fn it_refuses_calls_until_authenticated() {
+        let json_rpc_listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = json_rpc_listener.local_addr().unwrap();
+        let handler = Arc::new(Mutex::new(PowerHandler { power: PjLinkPowerCommandStatus::Off }));
+        let listener = PjLinkListener::new_without_broadcast(handler.clone(), TcpListener::bind("127.0.0.1:0").unwrap());
+        thread::spawn(move || listener.listen_json_rpc(json_rpc_listener));
+
+        let mut client = TcpStream::connect(address).unwrap();
+        let mut reader = BufReader::new(client.try_clone().unwrap());
+        let mut call = |request: &str| {
+            client.write_all(format!("{}\n", request).as_bytes()).unwrap();
+            let mut response = String::new();
+            reader.read_line(&mut response).unwrap();
+            response
+        };
+
+        assert_eq!(
+            call(r#"{"jsonrpc":"2.0","id":1,"method":"power.set","params":{"on":true}}"#),
+            "{\"jsonrpc\":\"2.0\",\"id\":1,\"error\":{\"code\":-32000,\"message\":\"Unauthenticated\"}}\n",
+        );
+        assert_eq!(
+            call(r#"{"jsonrpc":"2.0","id":2,"method":"status.get"}"#),
+            "{\"jsonrpc\":\"2.0\",\"id\":2,\"error\":{\"code\":-32000,\"message\":\"Unauthenticated\"}}\n",
+        );
+        assert_eq!(
+            call(r#"{"jsonrpc":"2.0","id":3,"method":"auth","params":{"password":"wrong"}}"#),
+            "{\"jsonrpc\":\"2.0\",\"id\":3,\"error\":{\"code\":-32005,\"message\":\"ERRA\"}}\n",
+        );
+        let mut response = String::new();
+        assert_eq!(reader.read_line(&mut response).unwrap(), 0);
+        assert_eq!(handler.lock().unwrap().power, PjLinkPowerCommandStatus::Off);
+    }
+
+    #[test]
+    fn it_closes_connections_over_the_limit() {
+        let json_rpc_listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = json_rpc_listener.local_addr().unwrap();
+        let handler = Arc::new(Mutex::new(PowerHandler { power: PjLinkPowerCommandStatus::Off }));
+        let listener = PjLinkListener::new_with_options(
+            handler,
+            TcpListener::bind("127.0.0.1:0").unwrap(),
+            Option::None,
+            PjLinkListenerOptions { max_connections: Option::Some(1), ..Default::default() },
+        );
+        thread::spawn(move || listener.listen_json_rpc(json_rpc_listener));
+
+        let mut first = TcpStream::connect(address).unwrap();
+        let mut first_reader = BufReader::new(first.try_clone().unwrap());
+        first.write_all(b"{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"status.get\"}\n").unwrap();
+        let mut response = String::new();
+        first_reader.read_line(&mut response).unwrap();
+        assert!(response.contains("Unauthenticated"));
+
+        let second = TcpStream::connect(address).unwrap();
+        let mut response = String::new();
+        assert_eq!(BufReader::new(second).read_line(&mut response).unwrap(), 0);
+
+        drop((first, first_reader));
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            let mut third = TcpStream::connect(address).unwrap();
+            third.write_all(b"{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"status.get\"}\n").unwrap();
+            let mut response = String::new();
+            if BufReader::new(third).read_line(&mut response).unwrap_or_default() > 0 || Instant::now() > deadline {
+                assert!(response.contains("Unauthenticated"));
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
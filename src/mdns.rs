@@ -0,0 +1,109 @@
+//! DNS-SD/mDNS advertisement of the PJLink service (`mdns` feature).
+
+use std::io;
+use log::debug;
+use mdns_sd::{ServiceDaemon, ServiceInfo};
+
+use crate::PjLinkError;
+
+/// DNS-SD service type of PJLink.
+pub const PJLINK_MDNS_SERVICE_TYPE: &str = "_pjlink._tcp.local.";
+
+/// Advertises a PJLink server as `_pjlink._tcp` over mDNS, so DNS-SD
+/// browsers find it besides PJLink `%2SRCH` searches.
+///
+/// TXT records hold the projector name (`name`) and PJLink class
+/// (`class`). The advertisement is withdrawn when dropped.
+///
+/// Available with the `mdns` feature.
+///
+/// ## Examples
+/// ```no_run
+/// use std::sync::{Arc, Mutex};
+/// use pjlink_bridge::*;
+///
+/// # fn example(projector: Arc<Mutex<dyn PjLinkHandler>>) {
+/// let handle = PjLinkServer::listen_tcp_only(projector, "0.0.0.0".into(), "4352".into()).unwrap();
+/// let port = handle.local_tcp_addr().unwrap().port();
+///
+/// let _advertisement = PjLinkMdnsAdvertisement::start("Hall projector", PjLinkClassCommandStatus::Class2, port).unwrap();
+/// handle.join().unwrap();
+/// # }
+/// ```
+pub struct PjLinkMdnsAdvertisement {
+    daemon: ServiceDaemon,
+    fullname: String,
+}
+
+impl PjLinkMdnsAdvertisement {
+    /// Starts advertising on every network interface, on a new mDNS
+    /// responder thread.
+    ///
+    /// **Arguments**:
+    /// * `name`: Projector name, used as service instance name
+    /// * `class`: Supported PJLink class, as a [PjLinkClassCommandStatus](crate::PjLinkClassCommandStatus) value
+    /// * `port`: TCP port the server listens on
+    pub fn start(name: &str, class: u8, port: u16) -> Result<PjLinkMdnsAdvertisement, PjLinkError> {
+        let daemon = ServiceDaemon::new().map_err(mdns_error)?;
+        let class = (class as char).to_string();
+        let properties = [("name", name), ("class", class.as_str())];
+        let service_info = ServiceInfo::new(PJLINK_MDNS_SERVICE_TYPE, name, &mdns_host_name(name), (), port, &properties[..])
+            .map_err(mdns_error)?
+            .enable_addr_auto();
+        let fullname = service_info.get_fullname().to_string();
+
+        daemon.register(service_info).map_err(mdns_error)?;
+        debug!("mDNS: advertising {} on port {}", fullname, port);
+
+        Ok(PjLinkMdnsAdvertisement { daemon, fullname })
+    }
+
+    /// Returns the full service instance name, like
+    /// `Hall projector._pjlink._tcp.local.`.
+    pub fn fullname(&self) -> &str {
+        &self.fullname
+    }
+}
+
+impl Drop for PjLinkMdnsAdvertisement {
+    fn drop(&mut self) {
+        if let Err(e) = self.daemon.unregister(&self.fullname) {
+            debug!("mDNS: failed to withdraw {}. {}", self.fullname, e);
+        }
+        if let Err(e) = self.daemon.shutdown() {
+            debug!("mDNS: failed to stop responder. {}", e);
+        }
+    }
+}
+
+/// Returns a host name derived from `name`, keeping only characters valid in
+/// DNS labels, like `hall-projector.local.`.
+fn mdns_host_name(name: &str) -> String {
+    let label: String = name.chars()
+        .map(|char| match char {
+            'a'..='z' | 'A'..='Z' | '0'..='9' => char.to_ascii_lowercase(),
+            _ => '-',
+        })
+        .collect();
+    let label = label.trim_matches('-');
+
+    match label.is_empty() {
+        true => String::from("pjlink.local."),
+        false => format!("{}.local.", label),
+    }
+}
+
+fn mdns_error(error: mdns_sd::Error) -> PjLinkError {
+    PjLinkError::Io(io::Error::other(format!("mDNS: {}", error)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_derives_host_names_from_projector_names() {
+        assert_eq!(mdns_host_name("Hall Projector 2"), "hall-projector-2.local.");
+        assert_eq!(mdns_host_name("  ★ "), "pjlink.local.");
+    }
+}
@@ -0,0 +1,226 @@
+//! Middleware layered around a [PjLinkHandler](crate::PjLinkHandler).
+
+use crate::{
+    PjLinkAuthAttempt, PjLinkCommand, PjLinkHandler, PjLinkInvalidFrameAction, PjLinkInvalidFrameContext, PjLinkRawPayload,
+    PjLinkResponse,
+};
+
+/// Information about the command being handled, passed to
+/// [PjLinkMiddleware](self::PjLinkMiddleware) hooks.
+pub struct PjLinkMiddlewareContext<'a> {
+    /// Connection ID
+    pub connection_id: u64,
+    /// Command as received from the controller
+    pub raw_command: &'a PjLinkRawPayload,
+}
+
+/// Hooks called around [PjLinkHandler::handle_command](crate::PjLinkHandler::handle_command)
+/// by a [PjLinkMiddlewareHandler](self::PjLinkMiddlewareHandler).
+///
+/// Useful for cross-cutting concerns (logging, metrics, filtering, response
+/// rewriting) without modifying the handler itself. Both hooks do nothing by
+/// default.
+pub trait PjLinkMiddleware: Send {
+    /// Called before the handler. Returning a response skips the handler and
+    /// every middleware layered after this one.
+    fn before_command(&mut self, _command: &PjLinkCommand, _context: &PjLinkMiddlewareContext) -> Option<PjLinkResponse> {
+        Option::None
+    }
+
+    /// Called after the handler (or a short-circuiting middleware) with its
+    /// response. The returned response is sent to the controller.
+    fn after_command(&mut self, _context: &PjLinkMiddlewareContext, response: PjLinkResponse) -> PjLinkResponse {
+        response
+    }
+}
+
+/// [PjLinkHandler](crate::PjLinkHandler) that runs a middleware chain around
+/// another handler.
+///
+/// `before_command` hooks run in the order middlewares were layered, and
+/// `after_command` hooks run in reverse order, so the first layered
+/// middleware sees the final response.
+///
+/// ## Examples
+/// ```
+/// use std::sync::{Arc, Mutex};
+/// use pjlink_bridge::*;
+///
+/// struct Projector;
+///
+/// impl PjLinkHandler for Projector {
+///     fn get_password(&mut self, _connection_id: &u64) -> Option<String> {
+///         None
+///     }
+///
+///     fn handle_command(&mut self, _command: PjLinkCommand, _raw_command: &PjLinkRawPayload, _connection_id: &u64) -> PjLinkResponse {
+///         PjLinkResponse::Ok
+///     }
+/// }
+///
+/// struct CommandLogger;
+///
+/// impl PjLinkMiddleware for CommandLogger {
+///     fn after_command(&mut self, context: &PjLinkMiddlewareContext, response: PjLinkResponse) -> PjLinkResponse {
+///         println!("{}", String::from_utf8_lossy(&context.raw_command.command_body_with_class));
+///         response
+///     }
+/// }
+///
+/// let handler = PjLinkMiddlewareHandler::new(Projector).layer(CommandLogger);
+/// let shared_handler: PjLinkHandlerShared = Arc::new(Mutex::new(handler));
+/// ```
+pub struct PjLinkMiddlewareHandler<H: PjLinkHandler> {
+    handler: H,
+    middlewares: Vec<Box<dyn PjLinkMiddleware>>,
+}
+
+impl<H: PjLinkHandler> PjLinkMiddlewareHandler<H> {
+    /// Wraps `handler` without any middleware.
+    pub fn new(handler: H) -> PjLinkMiddlewareHandler<H> {
+        PjLinkMiddlewareHandler {
+            handler,
+            middlewares: Vec::new(),
+        }
+    }
+
+    /// Adds a middleware after the already layered ones.
+    pub fn layer<M: PjLinkMiddleware + 'static>(mut self, middleware: M) -> PjLinkMiddlewareHandler<H> {
+        self.middlewares.push(Box::new(middleware));
+        self
+    }
+
+    /// Returns the wrapped handler.
+    pub fn handler(&self) -> &H {
+        &self.handler
+    }
+
+    /// Returns the wrapped handler, mutably.
+    pub fn handler_mut(&mut self) -> &mut H {
+        &mut self.handler
+    }
+}
+
+impl<H: PjLinkHandler> PjLinkHandler for PjLinkMiddlewareHandler<H> {
+    fn get_password(&mut self, connection_id: &u64) -> Option<String> {
+        self.handler.get_password(connection_id)
+    }
+
+    fn handle_command(&mut self, command: PjLinkCommand, raw_command: &PjLinkRawPayload, connection_id: &u64) -> PjLinkResponse {
+        let context = PjLinkMiddlewareContext {
+            connection_id: *connection_id,
+            raw_command,
+        };
+
+        let mut short_circuit: Option<(usize, PjLinkResponse)> = Option::None;
+        for (index, middleware) in self.middlewares.iter_mut().enumerate() {
+            if let Some(response) = middleware.before_command(&command, &context) {
+                short_circuit = Option::Some((index + 1, response));
+                break;
+            }
+        }
+
+        let (ran_middlewares, mut response) = match short_circuit {
+            Some(short_circuit) => short_circuit,
+            None => (
+                self.middlewares.len(),
+                self.handler.handle_command(command, raw_command, connection_id),
+            ),
+        };
+
+        for middleware in self.middlewares[..ran_middlewares].iter_mut().rev() {
+            response = middleware.after_command(&context, response);
+        }
+
+        response
+    }
+
+    fn on_auth_attempt(&mut self, attempt: &PjLinkAuthAttempt) {
+        self.handler.on_auth_attempt(attempt);
+    }
+
+    fn handle_unknown(&mut self, raw_command: &PjLinkRawPayload, connection_id: &u64) -> Option<PjLinkResponse> {
+        self.handler.handle_unknown(raw_command, connection_id)
+    }
+
+    fn handle_invalid(&mut self, raw: &[u8], context: &PjLinkInvalidFrameContext) -> PjLinkInvalidFrameAction {
+        self.handler.handle_invalid(raw, context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    struct RecordingHandler {
+        calls: u32,
+    }
+
+    impl PjLinkHandler for RecordingHandler {
+        fn get_password(&mut self, _connection_id: &u64) -> Option<String> {
+            Option::None
+        }
+
+        fn handle_command(&mut self, _command: PjLinkCommand, _raw_command: &PjLinkRawPayload, _connection_id: &u64) -> PjLinkResponse {
+            self.calls += 1;
+            PjLinkResponse::Ok
+        }
+    }
+
+    struct Recorder {
+        name: &'static str,
+        events: Arc<Mutex<Vec<String>>>,
+        short_circuit: bool,
+    }
+
+    impl PjLinkMiddleware for Recorder {
+        fn before_command(&mut self, _command: &PjLinkCommand, _context: &PjLinkMiddlewareContext) -> Option<PjLinkResponse> {
+            self.events.lock().unwrap().push(format!("before {}", self.name));
+            if self.short_circuit {
+                Option::Some(PjLinkResponse::UnavailableTime)
+            } else {
+                Option::None
+            }
+        }
+
+        fn after_command(&mut self, _context: &PjLinkMiddlewareContext, response: PjLinkResponse) -> PjLinkResponse {
+            self.events.lock().unwrap().push(format!("after {}", self.name));
+            response
+        }
+    }
+
+    fn recorder(name: &'static str, events: &Arc<Mutex<Vec<String>>>, short_circuit: bool) -> Recorder {
+        Recorder { name, events: events.clone(), short_circuit }
+    }
+
+    #[test]
+    fn it_runs_middlewares_around_handler() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut handler = PjLinkMiddlewareHandler::new(RecordingHandler { calls: 0 })
+            .layer(recorder("a", &events, false))
+            .layer(recorder("b", &events, false));
+
+        let raw_command = PjLinkRawPayload::new_command(*b"1POWR", vec![b'1']);
+        let response = handler.handle_command(PjLinkCommand::from_raw_payload(&raw_command), &raw_command, &0);
+
+        assert!(matches!(response, PjLinkResponse::Ok));
+        assert_eq!(handler.handler().calls, 1);
+        assert_eq!(*events.lock().unwrap(), vec!["before a", "before b", "after b", "after a"]);
+    }
+
+    #[test]
+    fn it_skips_handler_when_middleware_responds() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut handler = PjLinkMiddlewareHandler::new(RecordingHandler { calls: 0 })
+            .layer(recorder("a", &events, true))
+            .layer(recorder("b", &events, false));
+
+        let raw_command = PjLinkRawPayload::new_command(*b"1POWR", vec![b'1']);
+        let response = handler.handle_command(PjLinkCommand::from_raw_payload(&raw_command), &raw_command, &0);
+
+        assert!(matches!(response, PjLinkResponse::UnavailableTime));
+        assert_eq!(handler.handler().calls, 0);
+        assert_eq!(*events.lock().unwrap(), vec!["before a", "after a"]);
+    }
+}
@@ -0,0 +1,230 @@
+//! Typed projector model, implementing the PJLink protocol on top of it.
+
+use crate::{
+    PjLinkClassCommandStatus, PjLinkCommand, PjLinkDeviceInfo, PjLinkErrorStatusCommandStatusItem, PjLinkHandler,
+    PjLinkInputCommandParameter, PjLinkInputCommandStatus, PjLinkIntoResponse, PjLinkPowerCommandParameter,
+    PjLinkRawPayload, PjLinkResponse,
+};
+use crate::input::input_parameter_bytes;
+
+/// Projector described by typed methods instead of PJLink commands.
+///
+/// Every type implementing it is a [PjLinkHandler](crate::PjLinkHandler),
+/// which parses commands, validates parameters and maps results to the
+/// responses the specification requires:
+/// * Unknown parameters are answered with `ERR2`, without calling the model
+/// * Selecting an input not listed by [inputs](self::PjLinkProjectorModel::inputs) is answered with `ERR2`
+/// * Class 2 commands are answered with `ERR1` if [class](self::PjLinkProjectorModel::class) is 1
+/// * Queries of [device_info](self::PjLinkProjectorModel::device_info) fields left as `None`, and
+///   commands without a method, are answered with `ERR1`
+///
+/// Methods return a `PjLinkResponse` error, like
+/// [UnavailableTime](crate::PjLinkResponse::UnavailableTime), to answer it
+/// instead.
+///
+/// ## Examples
+/// ```
+/// use std::sync::{Arc, Mutex};
+/// use pjlink_bridge::*;
+///
+/// struct Projector {
+///     is_on: bool,
+/// }
+///
+/// impl PjLinkProjectorModel for Projector {
+///     fn power_state(&mut self) -> Result<u8, PjLinkResponse> {
+///         Ok(if self.is_on { PjLinkPowerCommandStatus::On } else { PjLinkPowerCommandStatus::Off })
+///     }
+///
+///     fn set_power(&mut self, on: bool) -> Result<(), PjLinkResponse> {
+///         self.is_on = on;
+///         Ok(())
+///     }
+/// }
+///
+/// let shared_handler: PjLinkHandlerShared = Arc::new(Mutex::new(Projector { is_on: false }));
+/// ```
+pub trait PjLinkProjectorModel: Send {
+    /// Returns the connection password. No authentication by default.
+    fn password(&mut self) -> Option<String> {
+        Option::None
+    }
+
+    /// Returns the supported PJLink class, as a [PjLinkClassCommandStatus](crate::PjLinkClassCommandStatus)
+    /// value. Class 1 by default.
+    fn class(&self) -> u8 {
+        PjLinkClassCommandStatus::Class1
+    }
+
+    /// Returns the power state, as a [PjLinkPowerCommandStatus](crate::PjLinkPowerCommandStatus) value.
+    fn power_state(&mut self) -> Result<u8, PjLinkResponse>;
+
+    /// Turns the projector on or off.
+    fn set_power(&mut self, on: bool) -> Result<(), PjLinkResponse>;
+
+    /// Returns the selected input as type and number, like `[b'3', b'1']`.
+    /// Not supported by default.
+    fn current_input(&mut self) -> Result<[u8; 2], PjLinkResponse> {
+        Err(PjLinkResponse::Undefined)
+    }
+
+    /// Returns the available inputs as type and number. No inputs by
+    /// default.
+    fn inputs(&self) -> Vec<[u8; 2]> {
+        Vec::new()
+    }
+
+    /// Selects one of the [inputs](self::PjLinkProjectorModel::inputs). Not
+    /// supported by default.
+    fn set_input(&mut self, _input: [u8; 2]) -> Result<(), PjLinkResponse> {
+        Err(PjLinkResponse::Undefined)
+    }
+
+    /// Returns the error status of fan, lamp, temperature, cover open,
+    /// filter and other items, as [PjLinkErrorStatusCommandStatusItem](crate::PjLinkErrorStatusCommandStatusItem)
+    /// values. Every item is normal by default.
+    fn error_status(&mut self) -> Result<[u8; 6], PjLinkResponse> {
+        Ok([PjLinkErrorStatusCommandStatusItem::Normal; 6])
+    }
+
+    /// Returns static information, like name and manufacturer. Nothing by
+    /// default.
+    fn device_info(&self) -> PjLinkDeviceInfo {
+        PjLinkDeviceInfo::default()
+    }
+}
+
+impl<M: PjLinkProjectorModel> PjLinkHandler for M {
+    fn get_password(&mut self, _connection_id: &u64) -> Option<String> {
+        self.password()
+    }
+
+    fn handle_command(&mut self, command: PjLinkCommand, raw_command: &PjLinkRawPayload, _connection_id: &u64) -> PjLinkResponse {
+        if raw_command.command_body_with_class[0] == b'2' && self.class() == PjLinkClassCommandStatus::Class1 {
+            return PjLinkResponse::Undefined;
+        }
+
+        match command {
+            PjLinkCommand::Power1(PjLinkPowerCommandParameter::Query) => self.power_state().into_response(),
+            PjLinkCommand::Power1(PjLinkPowerCommandParameter::On) => self.set_power(true).into_response(),
+            PjLinkCommand::Power1(PjLinkPowerCommandParameter::Off) => self.set_power(false).into_response(),
+            PjLinkCommand::Power1(PjLinkPowerCommandParameter::Unknown) => PjLinkResponse::OutOfParameter,
+            PjLinkCommand::Input1(PjLinkInputCommandParameter::Query) | PjLinkCommand::Input2(PjLinkInputCommandParameter::Query) => {
+                self.current_input().map(|input| input.to_vec()).into_response()
+            }
+            PjLinkCommand::Input1(parameter) => select_input(self, &parameter, false),
+            PjLinkCommand::Input2(parameter) => select_input(self, &parameter, true),
+            PjLinkCommand::InputTogglingList1 => input_list(&self.inputs(), false),
+            PjLinkCommand::InputTogglingList2 => input_list(&self.inputs(), true),
+            PjLinkCommand::ErrorStatus1 => self.error_status().map(|error_status| error_status.to_vec()).into_response(),
+            PjLinkCommand::Class1 => self.class().into_response(),
+            _ => self.device_info().response_to(raw_command).unwrap_or(PjLinkResponse::Undefined),
+        }
+    }
+}
+
+/// Answers `%1INPT` and `%2INPT` selections, selecting only listed inputs.
+fn select_input<M: PjLinkProjectorModel>(model: &mut M, parameter: &PjLinkInputCommandParameter, is_class_2: bool) -> PjLinkResponse {
+    let input = match input_parameter_bytes(parameter) {
+        Some(input) if is_class_2 || is_class_1_input(&input) => input,
+        _ => return PjLinkResponse::OutOfParameter,
+    };
+    if !model.inputs().contains(&input) {
+        return PjLinkResponse::OutOfParameter;
+    }
+
+    model.set_input(input).into_response()
+}
+
+/// Returns `true` if Class 1 controllers can select `input`.
+fn is_class_1_input(input: &[u8; 2]) -> bool {
+    input[0] != PjLinkInputCommandStatus::Internal && input[1].is_ascii_digit()
+}
+
+/// Answers `%1INST ?`, listing Class 1 inputs only, and `%2INST ?`.
+fn input_list(inputs: &[[u8; 2]], is_class_2: bool) -> PjLinkResponse {
+    if inputs.is_empty() {
+        return PjLinkResponse::Undefined;
+    }
+
+    let list: Vec<&[u8]> = inputs.iter()
+        .filter(|input| is_class_2 || is_class_1_input(input))
+        .map(|input| &input[..])
+        .collect();
+
+    list.join(&b' ').into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PjLinkPowerCommandStatus, PJLINK_QUERY};
+
+    struct Projector {
+        is_on: bool,
+        input: [u8; 2],
+    }
+
+    impl PjLinkProjectorModel for Projector {
+        fn class(&self) -> u8 {
+            PjLinkClassCommandStatus::Class2
+        }
+
+        fn power_state(&mut self) -> Result<u8, PjLinkResponse> {
+            Ok(if self.is_on { PjLinkPowerCommandStatus::On } else { PjLinkPowerCommandStatus::Off })
+        }
+
+        fn set_power(&mut self, on: bool) -> Result<(), PjLinkResponse> {
+            if self.is_on == on {
+                return Err(PjLinkResponse::UnavailableTime);
+            }
+
+            self.is_on = on;
+            Ok(())
+        }
+
+        fn current_input(&mut self) -> Result<[u8; 2], PjLinkResponse> {
+            Ok(self.input)
+        }
+
+        fn inputs(&self) -> Vec<[u8; 2]> {
+            vec![*b"31", *b"5A"]
+        }
+
+        fn set_input(&mut self, input: [u8; 2]) -> Result<(), PjLinkResponse> {
+            self.input = input;
+            Ok(())
+        }
+
+        fn device_info(&self) -> PjLinkDeviceInfo {
+            PjLinkDeviceInfo { name: Some(String::from("Model")), ..Default::default() }
+        }
+    }
+
+    fn send(projector: &mut Projector, command_body_with_class: [u8; 5], transmission_parameter: &[u8]) -> PjLinkResponse {
+        let raw_command = PjLinkRawPayload::new_command(command_body_with_class, transmission_parameter.to_vec());
+        let command = PjLinkCommand::from_raw_payload(&raw_command);
+        projector.handle_command(command, &raw_command, &0)
+    }
+
+    #[test]
+    fn it_maps_commands_to_model_methods() {
+        let mut projector = Projector { is_on: false, input: *b"31" };
+
+        assert_eq!(send(&mut projector, *b"1POWR", b"1"), PjLinkResponse::Ok);
+        assert_eq!(send(&mut projector, *b"1POWR", b"1"), PjLinkResponse::UnavailableTime);
+        assert_eq!(send(&mut projector, *b"1POWR", &[PJLINK_QUERY]), PjLinkResponse::Single(PjLinkPowerCommandStatus::On));
+        assert_eq!(send(&mut projector, *b"1POWR", b"7"), PjLinkResponse::OutOfParameter);
+        assert_eq!(send(&mut projector, *b"1INPT", b"5A"), PjLinkResponse::OutOfParameter);
+        assert_eq!(send(&mut projector, *b"2INPT", b"21"), PjLinkResponse::OutOfParameter);
+        assert_eq!(send(&mut projector, *b"2INPT", b"5A"), PjLinkResponse::Ok);
+        assert_eq!(send(&mut projector, *b"1INPT", &[PJLINK_QUERY]), PjLinkResponse::Multiple(b"5A".to_vec()));
+        assert_eq!(send(&mut projector, *b"1INST", &[PJLINK_QUERY]), PjLinkResponse::Multiple(b"31".to_vec()));
+        assert_eq!(send(&mut projector, *b"2INST", &[PJLINK_QUERY]), PjLinkResponse::Multiple(b"31 5A".to_vec()));
+        assert_eq!(send(&mut projector, *b"1ERST", &[PJLINK_QUERY]), PjLinkResponse::Multiple(b"000000".to_vec()));
+        assert_eq!(send(&mut projector, *b"1CLSS", &[PJLINK_QUERY]), PjLinkResponse::Single(b'2'));
+        assert_eq!(send(&mut projector, *b"1NAME", &[PJLINK_QUERY]), PjLinkResponse::Multiple(b"Model".to_vec()));
+        assert_eq!(send(&mut projector, *b"1INF1", &[PJLINK_QUERY]), PjLinkResponse::Undefined);
+        assert!(projector.get_password(&0).is_none());
+    }
+}
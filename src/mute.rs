@@ -0,0 +1,89 @@
+//! Audio and video mute state handlers can embed to answer `AVMT`.
+
+use crate::{PjLinkMuteCommandParameter, PjLinkMuteCommandStatus, PjLinkResponse};
+
+/// Audio and video mute state, answering `%1AVMT` commands.
+///
+/// Audio and video are muted independently: `%1AVMT 21` mutes audio without
+/// touching video, and `%1AVMT 30` unmutes both. Queries are answered as the
+/// specification requires: `31` when both are muted, `11` or `21` when only
+/// video or audio is, and `30` when neither is.
+///
+/// ## Examples
+/// ```
+/// use pjlink_bridge::*;
+///
+/// let mut mute = PjLinkAvMuteState::default();
+/// mute.apply(PjLinkMuteCommandParameter::Video(true));
+/// mute.apply(PjLinkMuteCommandParameter::Audio(true));
+/// assert_eq!(mute.query(), *b"31");
+///
+/// mute.apply(PjLinkMuteCommandParameter::Video(false));
+/// assert_eq!(mute.query(), *b"21");
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PjLinkAvMuteState {
+    /// Whether audio is muted
+    pub audio: bool,
+    /// Whether video is muted
+    pub video: bool,
+}
+
+impl PjLinkAvMuteState {
+    /// Creates the state answered as `status` to `%1AVMT ?`, like `*b"21"`.
+    /// Unknown statuses are read as not muted.
+    pub fn from_status(status: [u8; 2]) -> PjLinkAvMuteState {
+        let is_muted = status[1] == PjLinkMuteCommandStatus::Mute;
+
+        PjLinkAvMuteState {
+            audio: is_muted && matches!(status[0], PjLinkMuteCommandStatus::Audio | PjLinkMuteCommandStatus::AudioAndVideo),
+            video: is_muted && matches!(status[0], PjLinkMuteCommandStatus::Video | PjLinkMuteCommandStatus::AudioAndVideo),
+        }
+    }
+
+    /// Returns the answer to `%1AVMT ?`.
+    pub fn query(&self) -> [u8; 2] {
+        match (self.audio, self.video) {
+            (true, true) => [PjLinkMuteCommandStatus::AudioAndVideo, PjLinkMuteCommandStatus::Mute],
+            (false, true) => [PjLinkMuteCommandStatus::Video, PjLinkMuteCommandStatus::Mute],
+            (true, false) => [PjLinkMuteCommandStatus::Audio, PjLinkMuteCommandStatus::Mute],
+            (false, false) => [PjLinkMuteCommandStatus::AudioAndVideo, PjLinkMuteCommandStatus::NonMute],
+        }
+    }
+
+    /// Answers a `%1AVMT` command, updating the state.
+    pub fn apply(&mut self, parameter: PjLinkMuteCommandParameter) -> PjLinkResponse {
+        match parameter {
+            PjLinkMuteCommandParameter::Audio(mute) => self.audio = mute,
+            PjLinkMuteCommandParameter::Video(mute) => self.video = mute,
+            PjLinkMuteCommandParameter::AudioAndVideo(mute) => {
+                self.audio = mute;
+                self.video = mute;
+            }
+            PjLinkMuteCommandParameter::Query => return PjLinkResponse::Multiple(self.query().to_vec()),
+            PjLinkMuteCommandParameter::Unknown => return PjLinkResponse::OutOfParameter,
+        }
+
+        PjLinkResponse::Ok
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_combines_audio_and_video_mute() {
+        let mut mute = PjLinkAvMuteState::from_status(*b"11");
+        assert_eq!(mute, PjLinkAvMuteState { audio: false, video: true });
+
+        assert_eq!(mute.apply(PjLinkMuteCommandParameter::Audio(false)), PjLinkResponse::Ok);
+        assert_eq!(mute.query(), *b"11");
+        mute.apply(PjLinkMuteCommandParameter::Audio(true));
+        assert_eq!(mute.apply(PjLinkMuteCommandParameter::Query), PjLinkResponse::Multiple(b"31".to_vec()));
+        mute.apply(PjLinkMuteCommandParameter::AudioAndVideo(false));
+        assert_eq!(mute.query(), *b"30");
+        assert_eq!(mute.apply(PjLinkMuteCommandParameter::Unknown), PjLinkResponse::OutOfParameter);
+        assert_eq!(PjLinkAvMuteState::from_status(*b"30"), PjLinkAvMuteState::default());
+    }
+}
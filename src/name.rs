@@ -0,0 +1,191 @@
+//! Validation of projector and input terminal names.
+
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{PjLinkIntoResponse, PjLinkResponse};
+
+/// Maximum length of projector (`NAME`) and input terminal (`INNM`) names,
+/// in bytes.
+///
+/// PJLink Class 2 allows UTF-8 in names, limited to 64 bytes.
+pub const PJLINK_NAME_MAX_LENGTH: usize = 64;
+
+/// A projector or input terminal name that can be safely sent in `%1NAME`
+/// and `%2INNM` responses.
+///
+/// Names are valid UTF-8, have at most [PJLINK_NAME_MAX_LENGTH](self::PJLINK_NAME_MAX_LENGTH)
+/// bytes and no control characters (which would break the response frame).
+/// Use [new](self::PjLinkName::new) to reject invalid names, or
+/// [truncated](self::PjLinkName::truncated) to fix user-provided ones.
+///
+/// ## Examples
+/// ```
+/// use pjlink_bridge::*;
+///
+/// let name = PjLinkName::new("Auditório").unwrap();
+/// assert_eq!(name.as_str(), "Auditório");
+///
+/// let long_name = "Sala de conferências ".repeat(4);
+/// assert!(PjLinkName::new(long_name.as_str()).is_err());
+/// assert!(PjLinkName::truncated(&long_name).as_str().len() <= PJLINK_NAME_MAX_LENGTH);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PjLinkName(String);
+
+impl PjLinkName {
+    /// Validates and creates a new [PjLinkName](self::PjLinkName).
+    ///
+    /// **Arguments**:
+    /// * `name`: Name string. Must have at most [PJLINK_NAME_MAX_LENGTH](self::PJLINK_NAME_MAX_LENGTH)
+    ///   bytes and no control characters.
+    pub fn new<S: Into<String>>(name: S) -> Result<PjLinkName, PjLinkNameError> {
+        let name = name.into();
+
+        if let Some((position, character)) = name
+            .chars()
+            .enumerate()
+            .find(|(_, character)| character.is_control())
+        {
+            return Err(PjLinkNameError::ControlCharacter { position, character });
+        }
+
+        if name.len() > PJLINK_NAME_MAX_LENGTH {
+            return Err(PjLinkNameError::TooLong(name.len()));
+        }
+
+        Ok(PjLinkName(name))
+    }
+
+    /// Validates and creates a new [PjLinkName](self::PjLinkName) from raw
+    /// bytes, rejecting invalid UTF-8 sequences.
+    pub fn from_bytes(name: &[u8]) -> Result<PjLinkName, PjLinkNameError> {
+        match std::str::from_utf8(name) {
+            Ok(name) => PjLinkName::new(name),
+            Err(e) => Err(PjLinkNameError::InvalidUtf8 { valid_up_to: e.valid_up_to() }),
+        }
+    }
+
+    /// Creates a [PjLinkName](self::PjLinkName) from any string, removing
+    /// control characters and truncating it on a character boundary to
+    /// [PJLINK_NAME_MAX_LENGTH](self::PJLINK_NAME_MAX_LENGTH) bytes.
+    pub fn truncated(name: &str) -> PjLinkName {
+        let mut truncated_name = String::with_capacity(name.len().min(PJLINK_NAME_MAX_LENGTH));
+
+        for character in name.chars().filter(|character| !character.is_control()) {
+            if truncated_name.len() + character.len_utf8() > PJLINK_NAME_MAX_LENGTH {
+                break;
+            }
+            truncated_name.push(character);
+        }
+
+        PjLinkName(truncated_name)
+    }
+
+    /// Returns the name as a string slice.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for PjLinkName {
+    type Err = PjLinkNameError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        PjLinkName::new(name)
+    }
+}
+
+impl TryFrom<String> for PjLinkName {
+    type Error = PjLinkNameError;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        PjLinkName::new(name)
+    }
+}
+
+impl From<PjLinkName> for String {
+    fn from(name: PjLinkName) -> Self {
+        name.0
+    }
+}
+
+impl PjLinkIntoResponse for PjLinkName {
+    fn into_response(self) -> PjLinkResponse {
+        self.0.into_response()
+    }
+}
+
+/// Reasons a name is rejected by [PjLinkName::new](self::PjLinkName::new)
+/// and [PjLinkName::from_bytes](self::PjLinkName::from_bytes).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PjLinkNameError {
+    /// Name is longer than [PJLINK_NAME_MAX_LENGTH](self::PJLINK_NAME_MAX_LENGTH).
+    /// Contains the received length, in bytes.
+    TooLong(usize),
+    /// Name isn't valid UTF-8.
+    InvalidUtf8 {
+        /// Length of the valid prefix, in bytes
+        valid_up_to: usize,
+    },
+    /// Name contains a control character, like a carriage return.
+    ControlCharacter {
+        /// Character position (zero-based)
+        position: usize,
+        /// The offending character
+        character: char,
+    },
+}
+
+impl fmt::Display for PjLinkNameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PjLinkNameError::TooLong(length) => write!(
+                f,
+                "PJLink name has {} bytes, maximum is {}",
+                length,
+                PJLINK_NAME_MAX_LENGTH
+            ),
+            PjLinkNameError::InvalidUtf8 { valid_up_to } => write!(
+                f,
+                "PJLink name isn't valid UTF-8 after byte {}",
+                valid_up_to
+            ),
+            PjLinkNameError::ControlCharacter { position, character } => write!(
+                f,
+                "PJLink name contains control character {:?} at position {}",
+                character,
+                position
+            ),
+        }
+    }
+}
+
+impl Error for PjLinkNameError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_validates_names() {
+        assert_eq!(PjLinkName::new("Sala 1").unwrap().as_str(), "Sala 1");
+        assert_eq!(PjLinkName::new("é".repeat(33)), Err(PjLinkNameError::TooLong(66)));
+        assert_eq!(
+            PjLinkName::new("Sala\r1"),
+            Err(PjLinkNameError::ControlCharacter { position: 4, character: '\r' })
+        );
+        assert_eq!(PjLinkName::from_bytes(b"Sala \xff"), Err(PjLinkNameError::InvalidUtf8 { valid_up_to: 5 }));
+    }
+
+    #[test]
+    fn it_truncates_on_character_boundaries() {
+        let name = PjLinkName::truncated(&format!("a{}", "é".repeat(40)));
+        assert_eq!(name.as_str().len(), 63);
+        assert!(name.as_str().ends_with('é'));
+
+        assert_eq!(PjLinkName::truncated("Sala\r\n1").as_str(), "Sala1");
+    }
+}
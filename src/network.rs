@@ -0,0 +1,145 @@
+//! IP network prefixes, used to restrict who can discover or authenticate.
+
+use std::error::Error;
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// An IP network prefix, like `192.168.0.0/24` or `fd00::/8`.
+///
+/// ## Examples
+/// ```
+/// use pjlink_bridge::*;
+///
+/// let network: PjLinkIpNetwork = "192.168.10.0/24".parse().unwrap();
+/// assert!(network.contains(&"192.168.10.42".parse().unwrap()));
+/// assert!(!network.contains(&"192.168.11.42".parse().unwrap()));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PjLinkIpNetwork {
+    address: IpAddr,
+    prefix_len: u8,
+}
+
+impl PjLinkIpNetwork {
+    /// Creates a new network prefix.
+    ///
+    /// **Arguments**:
+    /// * `address`: Network address. Host bits are ignored.
+    /// * `prefix_len`: Prefix length, up to 32 for IPv4 and 128 for IPv6
+    pub fn new(address: IpAddr, prefix_len: u8) -> Result<PjLinkIpNetwork, PjLinkIpNetworkError> {
+        let max_prefix_len = match address {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+
+        if prefix_len > max_prefix_len {
+            return Err(PjLinkIpNetworkError::InvalidPrefixLength(prefix_len));
+        }
+
+        Ok(PjLinkIpNetwork { address, prefix_len })
+    }
+
+    /// Returns `true` if `address` is inside this network. IPv4-mapped IPv6
+    /// addresses are matched against IPv4 networks.
+    pub fn contains(&self, address: &IpAddr) -> bool {
+        match (self.address, address) {
+            (IpAddr::V4(network), IpAddr::V4(address)) => {
+                Self::prefix_matches(u32::from(network).into(), u32::from(*address).into(), 32, self.prefix_len)
+            }
+            (IpAddr::V4(_), IpAddr::V6(address)) => match address.to_ipv4_mapped() {
+                Some(address) => self.contains(&IpAddr::V4(address)),
+                None => false,
+            },
+            (IpAddr::V6(network), IpAddr::V6(address)) => {
+                Self::prefix_matches(u128::from(network), u128::from(*address), 128, self.prefix_len)
+            }
+            (IpAddr::V6(_), IpAddr::V4(_)) => false,
+        }
+    }
+
+    fn prefix_matches(network: u128, address: u128, bits: u8, prefix_len: u8) -> bool {
+        if prefix_len == 0 {
+            return true;
+        }
+
+        let shift = u32::from(bits - prefix_len);
+        (network >> shift) == (address >> shift)
+    }
+}
+
+impl FromStr for PjLinkIpNetwork {
+    type Err = PjLinkIpNetworkError;
+
+    /// Parses `address/prefix_len`. An address without prefix length matches
+    /// only itself.
+    fn from_str(network: &str) -> Result<Self, Self::Err> {
+        let (address, prefix_len) = match network.split_once('/') {
+            Some((address, prefix_len)) => (address, Some(prefix_len)),
+            None => (network, None),
+        };
+
+        let address: IpAddr = address.parse()
+            .map_err(|_| PjLinkIpNetworkError::InvalidAddress(address.to_string()))?;
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len.parse()
+                .map_err(|_| PjLinkIpNetworkError::InvalidAddress(network.to_string()))?,
+            None if address.is_ipv4() => 32,
+            None => 128,
+        };
+
+        PjLinkIpNetwork::new(address, prefix_len)
+    }
+}
+
+impl fmt::Display for PjLinkIpNetwork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.address, self.prefix_len)
+    }
+}
+
+/// Reasons a network prefix is rejected by [PjLinkIpNetwork](self::PjLinkIpNetwork).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PjLinkIpNetworkError {
+    /// Address (or the whole prefix) can't be parsed.
+    InvalidAddress(String),
+    /// Prefix length is bigger than the address size.
+    InvalidPrefixLength(u8),
+}
+
+impl fmt::Display for PjLinkIpNetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PjLinkIpNetworkError::InvalidAddress(address) => write!(f, "invalid network address {:?}", address),
+            PjLinkIpNetworkError::InvalidPrefixLength(prefix_len) => write!(f, "invalid network prefix length {}", prefix_len),
+        }
+    }
+}
+
+impl Error for PjLinkIpNetworkError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_matches_addresses_inside_network() {
+        let network: PjLinkIpNetwork = "10.1.0.0/16".parse().unwrap();
+        assert!(network.contains(&"10.1.200.3".parse().unwrap()));
+        assert!(network.contains(&"::ffff:10.1.0.9".parse().unwrap()));
+        assert!(!network.contains(&"10.2.0.1".parse().unwrap()));
+
+        let any: PjLinkIpNetwork = "0.0.0.0/0".parse().unwrap();
+        assert!(any.contains(&"203.0.113.7".parse().unwrap()));
+
+        let v6: PjLinkIpNetwork = "fd00::/8".parse().unwrap();
+        assert!(v6.contains(&"fd12::1".parse().unwrap()));
+        assert!(!v6.contains(&"10.1.0.1".parse().unwrap()));
+    }
+
+    #[test]
+    fn it_rejects_invalid_networks() {
+        assert_eq!("10.0.0.0/33".parse::<PjLinkIpNetwork>(), Err(PjLinkIpNetworkError::InvalidPrefixLength(33)));
+        assert!("projector/8".parse::<PjLinkIpNetwork>().is_err());
+    }
+}
@@ -0,0 +1,412 @@
+//! PJLink Class 2 status notifications.
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use log::debug;
+
+use crate::{PjLinkClock, PjLinkError, PjLinkProjectorState, PjLinkStateSnapshot, PjLinkStateStore, PjLinkSystemClock, spawn_named_thread};
+
+/// Longest wait of the notification thread while a change is pending, so
+/// [PjLinkMockClock](crate::PjLinkMockClock) advances are seen without a
+/// wake-up.
+const PJLINK_DEBOUNCE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Destination of a [PjLinkStatusCommand](crate::PjLinkStatusCommand).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PjLinkNotificationTarget {
+    /// Sends the message to a single controller
+    Address(SocketAddr),
+    /// Sends the message to the limited broadcast address (255.255.255.255),
+    /// on the given port
+    Broadcast(u16),
+}
+
+impl PjLinkNotificationTarget {
+    /// Returns the socket address the message is sent to.
+    pub fn to_socket_addr(&self) -> SocketAddr {
+        match self {
+            PjLinkNotificationTarget::Address(address) => *address,
+            PjLinkNotificationTarget::Broadcast(port) => SocketAddr::new(IpAddr::V4(Ipv4Addr::BROADCAST), *port),
+        }
+    }
+}
+
+impl From<SocketAddr> for PjLinkNotificationTarget {
+    fn from(address: SocketAddr) -> Self {
+        PjLinkNotificationTarget::Address(address)
+    }
+}
+
+struct PjLinkStateTrackerInner {
+    current: PjLinkProjectorState,
+    lamp_hours: Vec<u32>,
+    sent: PjLinkProjectorState,
+    changed_at: Option<Instant>,
+    /// State changed since it was last saved to the store
+    is_save_pending: bool,
+    is_stopped: bool,
+}
+
+/// Tracks projector power, input and error status, and automatically sends
+/// Class 2 status notifications (`%2POWR`, `%2INPT` and `%2ERST`) over UDP
+/// when they change.
+///
+/// Handlers update the tracker whenever their state changes. Changes are
+/// debounced: notifications are sent only after the state stays the same for
+/// the debounce interval, and only for items whose final value differs from
+/// the last notified one. The first value set for each item is used as
+/// baseline and is not notified.
+///
+/// ## Examples
+/// ```no_run
+/// use std::time::Duration;
+/// use pjlink_bridge::*;
+///
+/// let tracker = PjLinkStateTracker::new(
+///     vec![PjLinkNotificationTarget::Address("192.168.0.10:4352".parse().unwrap())],
+///     Duration::from_millis(500),
+/// ).unwrap();
+///
+/// tracker.set_power(PjLinkPowerCommandStatus::Off);
+/// // Sends "%2POWR=3\r" after 500ms
+/// tracker.set_power(PjLinkPowerCommandStatus::WarmUp);
+/// ```
+pub struct PjLinkStateTracker {
+    shared: Arc<(Mutex<PjLinkStateTrackerInner>, Condvar)>,
+    worker: Option<JoinHandle<()>>,
+    clock: Arc<dyn PjLinkClock>,
+}
+
+impl PjLinkStateTracker {
+    /// Creates a new tracker and spawns its notification thread.
+    ///
+    /// **Arguments**:
+    /// * `destinations`: Where notifications are sent to
+    /// * `debounce`: Time the state must stay unchanged before notifying
+    pub fn new(destinations: Vec<PjLinkNotificationTarget>, debounce: Duration) -> Result<PjLinkStateTracker, PjLinkError> {
+        Self::new_with_snapshot(destinations, debounce, PjLinkStateSnapshot::default(), Option::None, Arc::new(PjLinkSystemClock))
+    }
+
+    /// Creates a new tracker debouncing with `clock`, instead of the system
+    /// clock. See [PjLinkMockClock](crate::PjLinkMockClock).
+    ///
+    /// **Arguments**:
+    /// * `destinations`: Where notifications are sent to
+    /// * `debounce`: Time the state must stay unchanged before notifying
+    /// * `clock`: Time source of the debounce
+    pub fn with_clock(
+        destinations: Vec<PjLinkNotificationTarget>,
+        debounce: Duration,
+        clock: Arc<dyn PjLinkClock>,
+    ) -> Result<PjLinkStateTracker, PjLinkError> {
+        Self::new_with_snapshot(destinations, debounce, PjLinkStateSnapshot::default(), Option::None, clock)
+    }
+
+    /// Creates a new tracker starting from the state saved in `store`, and
+    /// saving changes to it. The saved state is the baseline of
+    /// notifications, so only changes made while the bridge was down are
+    /// notified. See [PjLinkStateStore](crate::PjLinkStateStore).
+    ///
+    /// Changes are saved by the notification thread, so updating the state
+    /// doesn't wait for the store; changes made while a save is running are
+    /// saved together right after it. Dropping the tracker saves pending
+    /// changes first.
+    ///
+    /// **Arguments**:
+    /// * `destinations`: Where notifications are sent to
+    /// * `debounce`: Time the state must stay unchanged before notifying
+    /// * `store`: Where the state is loaded from and saved to
+    pub fn with_store(
+        destinations: Vec<PjLinkNotificationTarget>,
+        debounce: Duration,
+        store: Arc<dyn PjLinkStateStore>,
+    ) -> Result<PjLinkStateTracker, PjLinkError> {
+        let snapshot = store.load()?.unwrap_or_default();
+        Self::new_with_snapshot(destinations, debounce, snapshot, Option::Some(store), Arc::new(PjLinkSystemClock))
+    }
+
+    fn new_with_snapshot(
+        destinations: Vec<PjLinkNotificationTarget>,
+        debounce: Duration,
+        snapshot: PjLinkStateSnapshot,
+        store: Option<Arc<dyn PjLinkStateStore>>,
+        clock: Arc<dyn PjLinkClock>,
+    ) -> Result<PjLinkStateTracker, PjLinkError> {
+        let socket = UdpSocket::bind("0.0.0.0:0").map_err(|e| PjLinkError::bind("0.0.0.0:0", e))?;
+        socket.set_broadcast(true)?;
+
+        let shared = Arc::new((
+            Mutex::new(PjLinkStateTrackerInner {
+                current: snapshot.state,
+                lamp_hours: snapshot.lamp_hours,
+                sent: snapshot.state,
+                changed_at: Option::None,
+                is_save_pending: false,
+                is_stopped: false,
+            }),
+            Condvar::new(),
+        ));
+        let shared_clone = shared.clone();
+        let clock_clone = clock.clone();
+
+        let worker = spawn_named_thread(String::from("pjlink-notify"), move || {
+            Self::notify_loop(shared_clone, socket, destinations, debounce, store, clock_clone);
+        })?;
+
+        Ok(PjLinkStateTracker {
+            shared,
+            worker: Option::Some(worker),
+            clock,
+        })
+    }
+
+    /// Updates power status. See [PjLinkPowerCommandStatus](crate::PjLinkPowerCommandStatus).
+    pub fn set_power(&self, power: u8) {
+        self.update(|state| state.power = Option::Some(power));
+    }
+
+    /// Updates current input. See [PjLinkInputCommandStatus](crate::PjLinkInputCommandStatus).
+    pub fn set_input(&self, input_type: u8, input_value: u8) {
+        self.update(|state| state.input = Option::Some([input_type, input_value]));
+    }
+
+    /// Updates error status, in the same order as
+    /// [PjLinkCommand::ErrorStatus1](crate::PjLinkCommand::ErrorStatus1) response.
+    pub fn set_error_status(&self, error_status: [u8; 6]) {
+        self.update(|state| state.error_status = Option::Some(error_status));
+    }
+
+    /// Updates every item known in `state`. See [PjLinkProjectorState](crate::PjLinkProjectorState).
+    pub fn set_state(&self, state: &PjLinkProjectorState) {
+        self.update(|current| current.merge(state));
+    }
+
+    /// Updates cumulative lighting hours of each lamp. They aren't
+    /// notified, only saved to the store, if any.
+    pub fn set_lamp_hours(&self, lamp_hours: Vec<u32>) {
+        let (lock, condvar) = &*self.shared;
+        let mut inner = match lock.lock() {
+            Ok(inner) => inner,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        inner.lamp_hours = lamp_hours;
+        inner.is_save_pending = true;
+        condvar.notify_all();
+    }
+
+    /// Returns the current state and lamp hours, including the ones loaded
+    /// from the store.
+    pub fn snapshot(&self) -> PjLinkStateSnapshot {
+        let (lock, _) = &*self.shared;
+        let inner = match lock.lock() {
+            Ok(inner) => inner,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        PjLinkStateSnapshot { state: inner.current, lamp_hours: inner.lamp_hours.clone() }
+    }
+
+    fn update<F: FnOnce(&mut PjLinkProjectorState)>(&self, update_fn: F) {
+        let (lock, condvar) = &*self.shared;
+        let mut inner = match lock.lock() {
+            Ok(inner) => inner,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        update_fn(&mut inner.current);
+
+        let mut sent = inner.current;
+        sent.merge(&inner.sent);
+        inner.sent = sent;
+
+        inner.changed_at = Option::Some(self.clock.now());
+        inner.is_save_pending = true;
+        condvar.notify_all();
+    }
+
+    fn notify_loop(
+        shared: Arc<(Mutex<PjLinkStateTrackerInner>, Condvar)>,
+        socket: UdpSocket,
+        destinations: Vec<PjLinkNotificationTarget>,
+        debounce: Duration,
+        store: Option<Arc<dyn PjLinkStateStore>>,
+        clock: Arc<dyn PjLinkClock>,
+    ) {
+        let (lock, condvar) = &*shared;
+
+        loop {
+            let (snapshot, is_stopped) = {
+                let mut inner = match lock.lock() {
+                    Ok(inner) => inner,
+                    Err(_) => return,
+                };
+
+                loop {
+                    if inner.is_stopped || (inner.is_save_pending && store.is_some()) {
+                        break;
+                    }
+
+                    match inner.changed_at {
+                        Some(changed_at) if clock.elapsed_since(changed_at) >= debounce => break,
+                        Some(changed_at) => {
+                            let remaining = debounce.saturating_sub(clock.elapsed_since(changed_at));
+                            inner = match condvar.wait_timeout(inner, remaining.min(PJLINK_DEBOUNCE_POLL_INTERVAL)) {
+                                Ok((inner, _)) => inner,
+                                Err(_) => return,
+                            };
+                        }
+                        None => {
+                            inner = match condvar.wait(inner) {
+                                Ok(inner) => inner,
+                                Err(_) => return,
+                            };
+                        }
+                    }
+                }
+
+                let snapshot = match inner.is_save_pending {
+                    true => Option::Some(PjLinkStateSnapshot { state: inner.current, lamp_hours: inner.lamp_hours.clone() }),
+                    false => Option::None,
+                };
+                inner.is_save_pending = false;
+                (snapshot, inner.is_stopped)
+            };
+
+            // Saved without holding the lock, so updates don't wait for it
+            if let (Some(store), Some(snapshot)) = (&store, snapshot) {
+                if let Err(e) = store.save(&snapshot) {
+                    debug!("Failed to save projector state! {}", e);
+                }
+            }
+            if is_stopped {
+                return;
+            }
+
+            let notifications = {
+                let mut inner = match lock.lock() {
+                    Ok(inner) => inner,
+                    Err(_) => return,
+                };
+                match inner.changed_at {
+                    Some(changed_at) if clock.elapsed_since(changed_at) >= debounce => {
+                        let notifications = inner.current.diff(&inner.sent);
+                        inner.sent = inner.current;
+                        inner.changed_at = Option::None;
+                        notifications
+                    }
+                    _ => Vec::new(),
+                }
+            };
+
+            for notification in notifications {
+                for destination in destinations.iter() {
+                    if let Err(e) = notification.send_to(&socket, *destination) {
+                        debug!("UDP: Error on sending notification to {}. {}", destination.to_socket_addr(), e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Drop for PjLinkStateTracker {
+    fn drop(&mut self) {
+        let (lock, condvar) = &*self.shared;
+
+        if let Ok(mut inner) = lock.lock() {
+            inner.is_stopped = true;
+            condvar.notify_all();
+        }
+
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PjLinkMockClock, PjLinkStatusCommand};
+
+    /// Store whose saves wait while `gate` is held
+    #[derive(Default)]
+    struct GatedStore {
+        gate: Mutex<()>,
+        saved: Mutex<Vec<PjLinkStateSnapshot>>,
+    }
+
+    impl PjLinkStateStore for GatedStore {
+        fn load(&self) -> Result<Option<PjLinkStateSnapshot>, PjLinkError> {
+            Ok(Option::None)
+        }
+
+        fn save(&self, snapshot: &PjLinkStateSnapshot) -> Result<(), PjLinkError> {
+            let _gate = self.gate.lock().unwrap();
+            self.saved.lock().unwrap().push(snapshot.clone());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn it_sends_debounced_notifications() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let tracker = PjLinkStateTracker::new(vec![receiver.local_addr().unwrap().into()], Duration::from_millis(50)).unwrap();
+
+        tracker.set_power(b'0');
+        tracker.set_power(b'3');
+        tracker.set_power(b'1');
+
+        let mut buffer = [0u8; 32];
+        let size = receiver.recv(&mut buffer).unwrap();
+        assert_eq!(&buffer[..size], b"%2POWR=1\x0d");
+    }
+
+    #[test]
+    fn it_debounces_with_injected_clock() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let clock = Arc::new(PjLinkMockClock::new());
+        let tracker = PjLinkStateTracker::with_clock(vec![receiver.local_addr().unwrap().into()], Duration::from_secs(3600), clock.clone()).unwrap();
+
+        tracker.set_power(b'0');
+        tracker.set_power(b'1');
+        clock.advance(Duration::from_secs(3600));
+
+        let mut buffer = [0u8; 32];
+        let size = receiver.recv(&mut buffer).unwrap();
+        assert_eq!(&buffer[..size], b"%2POWR=1\x0d");
+    }
+
+    #[test]
+    fn it_saves_without_blocking_updates() {
+        let store = Arc::new(GatedStore::default());
+        let gate = store.gate.lock().unwrap();
+        let tracker = PjLinkStateTracker::with_store(vec![], Duration::from_millis(50), store.clone()).unwrap();
+
+        // Returns while the store is stuck saving
+        tracker.set_power(b'1');
+        tracker.set_power(b'0');
+        tracker.set_lamp_hours(vec![1200]);
+        drop(gate);
+
+        // Pending changes are saved before the tracker stops
+        drop(tracker);
+        let saved = store.saved.lock().unwrap();
+        assert_eq!(saved.last().unwrap().state.power, Some(b'0'));
+        assert_eq!(saved.last().unwrap().lamp_hours, vec![1200]);
+        assert!(saved.len() <= 3);
+    }
+
+    #[test]
+    fn it_encodes_status_commands() {
+        let mac_address = [*b"00", *b"1a", *b"2b", *b"3c", *b"4d", *b"5e"];
+        assert_eq!(PjLinkStatusCommand::Acknowledge2(mac_address).to_bytes(), b"%2ACKN=00:1a:2b:3c:4d:5e\x0d");
+        assert_eq!(PjLinkStatusCommand::Input2(b'3', b'1').to_bytes(), b"%2INPT=31\x0d");
+        assert_eq!(PjLinkStatusCommand::ErrorStatus2(*b"000200").to_bytes(), b"%2ERST=000200\x0d");
+    }
+}
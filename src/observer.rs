@@ -0,0 +1,95 @@
+//! Observation of handled commands.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use crate::PjLinkResponse;
+
+/// Kind of a [PjLinkResponse](crate::PjLinkResponse), without its value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PjLinkResponseKind {
+    /// [PjLinkResponse::Ok](crate::PjLinkResponse::Ok)
+    Ok,
+    /// [PjLinkResponse::Undefined](crate::PjLinkResponse::Undefined) (ERR1)
+    Undefined,
+    /// [PjLinkResponse::OutOfParameter](crate::PjLinkResponse::OutOfParameter) (ERR2)
+    OutOfParameter,
+    /// [PjLinkResponse::UnavailableTime](crate::PjLinkResponse::UnavailableTime) (ERR3)
+    UnavailableTime,
+    /// [PjLinkResponse::ProjectorOrDisplayFailure](crate::PjLinkResponse::ProjectorOrDisplayFailure) (ERR4)
+    ProjectorOrDisplayFailure,
+    /// [PjLinkResponse::Single](crate::PjLinkResponse::Single) or
+    /// [PjLinkResponse::Multiple](crate::PjLinkResponse::Multiple)
+    Value,
+    /// [PjLinkResponse::Empty](crate::PjLinkResponse::Empty)
+    Empty,
+}
+
+impl From<&PjLinkResponse> for PjLinkResponseKind {
+    fn from(response: &PjLinkResponse) -> Self {
+        match response {
+            PjLinkResponse::Ok => PjLinkResponseKind::Ok,
+            PjLinkResponse::Undefined => PjLinkResponseKind::Undefined,
+            PjLinkResponse::OutOfParameter => PjLinkResponseKind::OutOfParameter,
+            PjLinkResponse::UnavailableTime => PjLinkResponseKind::UnavailableTime,
+            PjLinkResponse::ProjectorOrDisplayFailure => PjLinkResponseKind::ProjectorOrDisplayFailure,
+            PjLinkResponse::Single(_) | PjLinkResponse::Multiple(_) => PjLinkResponseKind::Value,
+            PjLinkResponse::Empty => PjLinkResponseKind::Empty,
+        }
+    }
+}
+
+/// Timing of a handled command, as reported to
+/// [PjLinkCommandObserver::on_command_handled](self::PjLinkCommandObserver::on_command_handled).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PjLinkCommandTiming {
+    /// Connection ID
+    pub connection_id: u64,
+    /// Command body with class, like `*b"1POWR"`
+    pub command_body_with_class: [u8; 5],
+    /// Time spent in [PjLinkHandler::handle_command](crate::PjLinkHandler::handle_command)
+    pub duration: Duration,
+    /// Kind of the returned response
+    pub response_kind: PjLinkResponseKind,
+}
+
+/// Observes every command handled by a [PjLinkListener](crate::PjLinkListener).
+///
+/// Set it on [PjLinkListenerOptions](crate::PjLinkListenerOptions) to feed
+/// latency histograms or other telemetry without wrapping the handler.
+///
+/// ## Examples
+#[cfg_attr(feature = "server", doc = "```")]
+#[cfg_attr(not(feature = "server"), doc = "```ignore")]
+/// use std::sync::Arc;
+/// use pjlink_bridge::*;
+///
+/// struct LatencyLogger;
+///
+/// impl PjLinkCommandObserver for LatencyLogger {
+///     fn on_command_handled(&self, timing: &PjLinkCommandTiming) {
+///         println!(
+///             "{} took {:?} ({:?})",
+///             String::from_utf8_lossy(&timing.command_body_with_class),
+///             timing.duration,
+///             timing.response_kind
+///         );
+///     }
+/// }
+///
+/// let options = PjLinkListenerOptions {
+///     command_observer: Some(Arc::new(LatencyLogger)),
+///     ..Default::default()
+/// };
+/// ```
+pub trait PjLinkCommandObserver: Send + Sync {
+    /// Called after each [PjLinkHandler::handle_command](crate::PjLinkHandler::handle_command) call.
+    fn on_command_handled(&self, timing: &PjLinkCommandTiming);
+
+    /// Called when a connection is opened, after its security header is
+    /// sent. Does nothing by default.
+    fn on_connection_opened(&self, _connection_id: u64, _peer_addr: Option<SocketAddr>) {}
+
+    /// Called when a connection is closed. Does nothing by default.
+    fn on_connection_closed(&self, _connection_id: u64) {}
+}
@@ -0,0 +1,290 @@
+//! OpenTelemetry export of spans and counters (`otel` feature).
+
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::SystemTime;
+use opentelemetry::{Context, KeyValue};
+use opentelemetry::metrics::{Counter, Histogram, MeterProvider};
+use opentelemetry::trace::{Span, SpanKind, Status, TraceContextExt, Tracer, TracerProvider};
+use opentelemetry_otlp::{MetricExporter, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::metrics::SdkMeterProvider;
+use opentelemetry_sdk::trace::{SdkTracer, SdkTracerProvider};
+
+use crate::{PjLinkCommandObserver, PjLinkCommandTiming, PjLinkError, PjLinkListener, PjLinkListenerStats, PjLinkResponseKind};
+
+/// Instrumentation scope name of exported spans and metrics.
+const PJLINK_OTEL_SCOPE: &str = "pjlink-bridge";
+
+/// Name, description and value of a listener counter.
+type PjLinkStatsCounter = (&'static str, &'static str, fn(&PjLinkListenerStats) -> u64);
+
+/// Exports a span for each connection, with a child span for each handled
+/// command, and listener counters, to an OpenTelemetry collector.
+///
+/// Set it as [PjLinkListenerOptions::command_observer](crate::PjLinkListenerOptions::command_observer)
+/// to export spans, and call [observe_listener](self::PjLinkOtelExporter::observe_listener)
+/// to export the counters of [PjLinkListener::stats](crate::PjLinkListener::stats).
+/// Commands are also counted in `pjlink.commands`, and timed in
+/// `pjlink.command.duration`, by command and response kind. Available with
+/// the `otel` feature.
+///
+/// ## Examples
+/// ```no_run
+/// use std::net::TcpListener;
+/// use std::sync::Arc;
+/// use pjlink_bridge::*;
+///
+/// # fn example(handler: PjLinkHandlerShared) {
+/// let exporter = Arc::new(PjLinkOtelExporter::new("http://collector:4318", "lobby-projector").unwrap());
+/// let options = PjLinkListenerOptions {
+///     command_observer: Some(exporter.clone()),
+///     ..Default::default()
+/// };
+///
+/// let listener = PjLinkListener::new_with_options(handler, TcpListener::bind("0.0.0.0:4352").unwrap(), None, options);
+/// exporter.observe_listener(&listener);
+/// listener.listen();
+/// # }
+/// ```
+pub struct PjLinkOtelExporter {
+    tracer_provider: SdkTracerProvider,
+    meter_provider: SdkMeterProvider,
+    tracer: SdkTracer,
+    commands: Counter<u64>,
+    command_duration: Histogram<f64>,
+    connections: Mutex<HashMap<u64, Context>>,
+}
+
+impl PjLinkOtelExporter {
+    /// Creates an exporter sending OTLP over HTTP, in protobuf encoding.
+    ///
+    /// **Arguments**:
+    /// * `endpoint`: Collector base URL, like `http://localhost:4318`. Spans
+    ///   are sent to `/v1/traces` and metrics to `/v1/metrics`.
+    /// * `service_name`: `service.name` resource attribute, identifying the
+    ///   bridge, like the venue or projector name
+    pub fn new(endpoint: &str, service_name: &str) -> Result<PjLinkOtelExporter, PjLinkError> {
+        let endpoint = endpoint.trim_end_matches('/');
+        let span_exporter = SpanExporter::builder()
+            .with_http()
+            .with_endpoint(format!("{}/v1/traces", endpoint))
+            .build()
+            .map_err(|e| PjLinkError::Io(io::Error::other(e.to_string())))?;
+        let metric_exporter = MetricExporter::builder()
+            .with_http()
+            .with_endpoint(format!("{}/v1/metrics", endpoint))
+            .build()
+            .map_err(|e| PjLinkError::Io(io::Error::other(e.to_string())))?;
+
+        let resource = Resource::builder().with_service_name(service_name.to_string()).build();
+        let tracer_provider = SdkTracerProvider::builder()
+            .with_batch_exporter(span_exporter)
+            .with_resource(resource.clone())
+            .build();
+        let meter_provider = SdkMeterProvider::builder()
+            .with_periodic_exporter(metric_exporter)
+            .with_resource(resource)
+            .build();
+
+        Ok(Self::from_providers(tracer_provider, meter_provider))
+    }
+
+    /// Creates an exporter using providers configured by the application,
+    /// like ones exporting over gRPC or shared with other instrumentation.
+    pub fn from_providers(tracer_provider: SdkTracerProvider, meter_provider: SdkMeterProvider) -> PjLinkOtelExporter {
+        let meter = meter_provider.meter(PJLINK_OTEL_SCOPE);
+
+        PjLinkOtelExporter {
+            tracer: tracer_provider.tracer(PJLINK_OTEL_SCOPE),
+            commands: meter.u64_counter("pjlink.commands").with_description("Commands handled").build(),
+            command_duration: meter.f64_histogram("pjlink.command.duration")
+                .with_description("Time spent in the handler")
+                .with_unit("s")
+                .build(),
+            connections: Mutex::new(HashMap::new()),
+            tracer_provider,
+            meter_provider,
+        }
+    }
+
+    /// Exports the counters of `listener`, read when metrics are collected:
+    /// `pjlink.connections.active`, `pjlink.connections`,
+    /// `pjlink.commands.processed`, `pjlink.bytes.received` and
+    /// `pjlink.bytes.sent`, with the TCP address as `pjlink.listener`
+    /// attribute.
+    pub fn observe_listener(&self, listener: &PjLinkListener) {
+        let meter = self.meter_provider.meter(PJLINK_OTEL_SCOPE);
+        let listener_address = listener.local_tcp_addr().map(|address| address.to_string()).unwrap_or_default();
+        let attributes = [KeyValue::new("pjlink.listener", listener_address)];
+
+        let stats = listener.shared_stats.clone();
+        let gauge_attributes = attributes.clone();
+        meter.u64_observable_gauge("pjlink.connections.active")
+            .with_description("Open connections")
+            .with_callback(move |observer| observer.observe(stats.snapshot().active_connections, &gauge_attributes))
+            .build();
+
+        let counters: [PjLinkStatsCounter; 4] = [
+            ("pjlink.connections", "Connections accepted", |stats| stats.total_connections),
+            ("pjlink.commands.processed", "Commands handled", |stats| stats.commands_processed),
+            ("pjlink.bytes.received", "Bytes received from controllers", |stats| stats.bytes_received),
+            ("pjlink.bytes.sent", "Bytes sent to controllers", |stats| stats.bytes_sent),
+        ];
+        for (name, description, value) in counters {
+            let stats = listener.shared_stats.clone();
+            let counter_attributes = attributes.clone();
+            meter.u64_observable_counter(name)
+                .with_description(description)
+                .with_callback(move |observer| observer.observe(value(&stats.snapshot()), &counter_attributes))
+                .build();
+        }
+    }
+
+    /// Ends the spans of open connections, and exports everything not sent
+    /// yet. Spans and metrics are dropped afterwards.
+    pub fn shutdown(&self) -> Result<(), PjLinkError> {
+        let connections = match self.connections.lock() {
+            Ok(mut connections) => std::mem::take(&mut *connections),
+            Err(poisoned) => std::mem::take(&mut *poisoned.into_inner()),
+        };
+        for context in connections.values() {
+            context.span().end();
+        }
+
+        let traces = self.tracer_provider.shutdown();
+        let metrics = self.meter_provider.shutdown();
+        traces.and(metrics).map_err(|e| PjLinkError::Io(io::Error::other(e.to_string())))
+    }
+
+    /// Returns the context of the span of connection `connection_id`, or
+    /// the empty context if it's unknown, like for gateway calls.
+    fn connection_context(&self, connection_id: u64) -> Context {
+        match self.connections.lock() {
+            Ok(connections) => connections.get(&connection_id).cloned(),
+            Err(poisoned) => poisoned.into_inner().get(&connection_id).cloned(),
+        }
+            .unwrap_or_default()
+    }
+}
+
+impl PjLinkCommandObserver for PjLinkOtelExporter {
+    fn on_command_handled(&self, timing: &PjLinkCommandTiming) {
+        let command = String::from_utf8_lossy(&timing.command_body_with_class).into_owned();
+        let response_kind = format!("{:?}", timing.response_kind);
+        let attributes = [
+            KeyValue::new("pjlink.command", command.clone()),
+            KeyValue::new("pjlink.response_kind", response_kind),
+        ];
+
+        self.commands.add(1, &attributes);
+        self.command_duration.record(timing.duration.as_secs_f64(), &attributes);
+
+        let now = SystemTime::now();
+        let mut span = self.tracer.span_builder(format!("PJLINK {}", command))
+            .with_kind(SpanKind::Server)
+            .with_start_time(now.checked_sub(timing.duration).unwrap_or(now))
+            .with_attributes(attributes)
+            .start_with_context(&self.tracer, &self.connection_context(timing.connection_id));
+        match timing.response_kind {
+            PjLinkResponseKind::Ok | PjLinkResponseKind::Value | PjLinkResponseKind::Empty => {}
+            response_kind => span.set_status(Status::error(format!("{:?}", response_kind))),
+        }
+        span.end_with_timestamp(now);
+    }
+
+    fn on_connection_opened(&self, connection_id: u64, peer_addr: Option<SocketAddr>) {
+        let mut attributes = vec![KeyValue::new("pjlink.connection_id", connection_id as i64)];
+        if let Some(peer_addr) = peer_addr {
+            attributes.push(KeyValue::new("network.peer.address", peer_addr.ip().to_string()));
+            attributes.push(KeyValue::new("network.peer.port", i64::from(peer_addr.port())));
+        }
+
+        let span = self.tracer.span_builder("pjlink.connection")
+            .with_kind(SpanKind::Server)
+            .with_attributes(attributes)
+            .start(&self.tracer);
+        let context = Context::new().with_span(span);
+
+        match self.connections.lock() {
+            Ok(mut connections) => connections.insert(connection_id, context),
+            Err(poisoned) => poisoned.into_inner().insert(connection_id, context),
+        };
+    }
+
+    fn on_connection_closed(&self, connection_id: u64) {
+        let context = match self.connections.lock() {
+            Ok(mut connections) => connections.remove(&connection_id),
+            Err(poisoned) => poisoned.into_inner().remove(&connection_id),
+        };
+
+        if let Some(context) = context {
+            context.span().end();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::sync::Arc;
+    use std::thread;
+    use opentelemetry_sdk::error::OTelSdkResult;
+    use opentelemetry_sdk::trace::SpanData;
+    use crate::{PjLinkCommand, PjLinkHandler, PjLinkListenerOptions, PjLinkMemoryTransport, PjLinkRawPayload, PjLinkResponse, PjLinkServer};
+
+    #[derive(Debug, Clone, Default)]
+    struct RecordingExporter {
+        spans: Arc<Mutex<Vec<SpanData>>>,
+    }
+
+    impl opentelemetry_sdk::trace::SpanExporter for RecordingExporter {
+        async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+            self.spans.lock().unwrap().extend(batch);
+            Ok(())
+        }
+    }
+
+    struct PowerOnHandler;
+
+    impl PjLinkHandler for PowerOnHandler {
+        fn get_password(&mut self, _connection_id: &u64) -> Option<String> {
+            Option::None
+        }
+
+        fn handle_command(&mut self, _command: PjLinkCommand, _raw_command: &PjLinkRawPayload, _connection_id: &u64) -> PjLinkResponse {
+            PjLinkResponse::Single(b'1')
+        }
+    }
+
+    #[test]
+    fn it_exports_command_spans_inside_connection_spans() {
+        let span_exporter = RecordingExporter::default();
+        let exporter = Arc::new(PjLinkOtelExporter::from_providers(
+            SdkTracerProvider::builder().with_simple_exporter(span_exporter.clone()).build(),
+            SdkMeterProvider::builder().build(),
+        ));
+
+        let (mut client, server) = PjLinkMemoryTransport::pair();
+        let options = PjLinkListenerOptions { command_observer: Option::Some(exporter.clone()), ..Default::default() };
+        let server_thread = thread::spawn(move || {
+            PjLinkServer::serve_transport_with_options(Arc::new(Mutex::new(PowerOnHandler)), server, options)
+        });
+
+        let mut response = [0u8; 9];
+        client.read_exact(&mut response).unwrap();
+        client.write_all(b"%1POWR ?\r").unwrap();
+        client.read_exact(&mut response).unwrap();
+        drop(client);
+        server_thread.join().unwrap();
+
+        let spans = span_exporter.spans.lock().unwrap();
+        let names: Vec<&str> = spans.iter().map(|span| span.name.as_ref()).collect();
+        assert_eq!(names, vec!["PJLINK 1POWR", "pjlink.connection"]);
+        assert_eq!(spans[0].parent_span_id, spans[1].span_context.span_id());
+        assert_eq!(spans[0].span_context.trace_id(), spans[1].span_context.trace_id());
+    }
+}
@@ -0,0 +1,215 @@
+//! Power state machine handlers can embed to answer `POWR`.
+
+use std::convert::TryInto;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::{PjLinkClock, PjLinkPowerCommandParameter, PjLinkPowerCommandStatus, PjLinkResponse, PjLinkSystemClock};
+
+type PjLinkPowerChangeCallback = Box<dyn FnMut(u8) + Send>;
+
+/// Power state of a projector: off, warming up, on and cooling down, with
+/// timed transitions between them.
+///
+/// Answers `%1POWR ?` with the current state, and `%1POWR 1` and
+/// `%1POWR 0` as the specification requires: switching to the state the
+/// projector is already in (or going to) is answered with `OK`, and
+/// switching power while warming up or cooling down with `ERR3`.
+///
+/// States are [PjLinkPowerCommandStatus](crate::PjLinkPowerCommandStatus)
+/// values. Transitions finish lazily, when the state is read.
+///
+/// Also accumulates lamp hours: the lamp is lit while warming up and on.
+///
+/// ## Examples
+/// ```
+/// use std::time::Duration;
+/// use pjlink_bridge::*;
+///
+/// let mut power = PjLinkPowerStateMachine::new(Duration::from_secs(30), Duration::from_secs(60));
+/// power.on_change(|state| println!("Power is now {}", state as char));
+///
+/// assert_eq!(power.handle(PjLinkPowerCommandParameter::On), PjLinkResponse::Ok);
+/// assert_eq!(power.state(), PjLinkPowerCommandStatus::WarmUp);
+/// assert_eq!(power.handle(PjLinkPowerCommandParameter::Off), PjLinkResponse::UnavailableTime);
+/// assert_eq!(power.handle(PjLinkPowerCommandParameter::Query), PjLinkResponse::Single(PjLinkPowerCommandStatus::WarmUp));
+/// ```
+pub struct PjLinkPowerStateMachine {
+    state: u8,
+    warm_up: Duration,
+    cool_down: Duration,
+    transition_ends_at: Option<Instant>,
+    lamp_lit_since: Option<Instant>,
+    lamp_lit_duration: Duration,
+    clock: Arc<dyn PjLinkClock>,
+    on_change: Option<PjLinkPowerChangeCallback>,
+}
+
+impl PjLinkPowerStateMachine {
+    /// Creates a powered off state machine.
+    ///
+    /// **Arguments**:
+    /// * `warm_up`: Time spent warming up before turning on. Zero turns on immediately.
+    /// * `cool_down`: Time spent cooling down before turning off. Zero turns off immediately.
+    pub fn new(warm_up: Duration, cool_down: Duration) -> PjLinkPowerStateMachine {
+        PjLinkPowerStateMachine {
+            state: PjLinkPowerCommandStatus::Off,
+            warm_up,
+            cool_down,
+            transition_ends_at: Option::None,
+            lamp_lit_since: Option::None,
+            lamp_lit_duration: Duration::ZERO,
+            clock: Arc::new(PjLinkSystemClock),
+            on_change: Option::None,
+        }
+    }
+
+    /// Uses `clock` for transitions and lamp hours, instead of the system
+    /// clock. See [PjLinkMockClock](crate::PjLinkMockClock).
+    pub fn with_clock(mut self, clock: Arc<dyn PjLinkClock>) -> PjLinkPowerStateMachine {
+        self.clock = clock;
+        self
+    }
+
+    /// Returns the cumulative lamp lighting time, in whole hours, as
+    /// answered to `%1LAMP ?`.
+    pub fn lamp_hours(&mut self) -> u32 {
+        let lit_duration = self.lamp_lit_duration
+            + self.lamp_lit_since.map_or(Duration::ZERO, |lit_since| self.clock.elapsed_since(lit_since));
+
+        (lit_duration.as_secs() / 3600).try_into().unwrap_or(u32::MAX)
+    }
+
+    /// Sets the cumulative lamp lighting time, like hours saved before a
+    /// restart. See [PjLinkStateStore](crate::PjLinkStateStore).
+    pub fn set_lamp_hours(&mut self, lamp_hours: u32) {
+        self.lamp_lit_duration = Duration::from_secs(u64::from(lamp_hours) * 3600);
+        self.lamp_lit_since = self.lamp_lit_since.map(|_| self.clock.now());
+    }
+
+    /// Calls `callback` with the new state on every state change, like to
+    /// send notifications with [PjLinkStateTracker::set_power](crate::PjLinkStateTracker::set_power).
+    pub fn on_change<F: FnMut(u8) + Send + 'static>(&mut self, callback: F) {
+        self.on_change = Option::Some(Box::new(callback));
+    }
+
+    /// Returns the current state, finishing a due transition first.
+    pub fn state(&mut self) -> u8 {
+        if self.transition_ends_at.is_some_and(|ends_at| self.clock.now() >= ends_at) {
+            let target = match self.state {
+                PjLinkPowerCommandStatus::WarmUp => PjLinkPowerCommandStatus::On,
+                _ => PjLinkPowerCommandStatus::Off,
+            };
+            self.set_state(target, Option::None);
+        }
+
+        self.state
+    }
+
+    /// Answers a `%1POWR` command, starting a transition if needed.
+    pub fn handle(&mut self, parameter: PjLinkPowerCommandParameter) -> PjLinkResponse {
+        let state = self.state();
+
+        match (parameter, state) {
+            (PjLinkPowerCommandParameter::Query, _) => PjLinkResponse::Single(state),
+            (PjLinkPowerCommandParameter::Unknown, _) => PjLinkResponse::OutOfParameter,
+            (PjLinkPowerCommandParameter::On, PjLinkPowerCommandStatus::Off) => {
+                self.start_transition(PjLinkPowerCommandStatus::WarmUp, PjLinkPowerCommandStatus::On, self.warm_up);
+                PjLinkResponse::Ok
+            }
+            (PjLinkPowerCommandParameter::Off, PjLinkPowerCommandStatus::On) => {
+                self.start_transition(PjLinkPowerCommandStatus::Cooling, PjLinkPowerCommandStatus::Off, self.cool_down);
+                PjLinkResponse::Ok
+            }
+            (PjLinkPowerCommandParameter::On, PjLinkPowerCommandStatus::On | PjLinkPowerCommandStatus::WarmUp)
+            | (PjLinkPowerCommandParameter::Off, PjLinkPowerCommandStatus::Off | PjLinkPowerCommandStatus::Cooling) => PjLinkResponse::Ok,
+            _ => PjLinkResponse::UnavailableTime,
+        }
+    }
+
+    fn start_transition(&mut self, transition_state: u8, target_state: u8, duration: Duration) {
+        match duration.is_zero() {
+            true => self.set_state(target_state, Option::None),
+            false => self.set_state(transition_state, Option::Some(self.clock.now() + duration)),
+        }
+    }
+
+    fn set_state(&mut self, state: u8, transition_ends_at: Option<Instant>) {
+        let is_lamp_lit = matches!(state, PjLinkPowerCommandStatus::WarmUp | PjLinkPowerCommandStatus::On);
+        match (is_lamp_lit, self.lamp_lit_since) {
+            (true, None) => self.lamp_lit_since = Option::Some(self.clock.now()),
+            (false, Some(lit_since)) => {
+                self.lamp_lit_duration += self.clock.elapsed_since(lit_since);
+                self.lamp_lit_since = Option::None;
+            }
+            _ => {}
+        }
+
+        self.state = state;
+        self.transition_ends_at = transition_ends_at;
+
+        if let Some(on_change) = &mut self.on_change {
+            on_change(state);
+        }
+    }
+}
+
+impl fmt::Debug for PjLinkPowerStateMachine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PjLinkPowerStateMachine")
+            .field("state", &(self.state as char))
+            .field("warm_up", &self.warm_up)
+            .field("cool_down", &self.cool_down)
+            .field("transition_ends_at", &self.transition_ends_at)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use std::thread;
+    use crate::PjLinkMockClock;
+
+    #[test]
+    fn it_runs_timed_transitions_and_reports_changes() {
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let changes_clone = changes.clone();
+        let mut power = PjLinkPowerStateMachine::new(Duration::from_millis(20), Duration::ZERO);
+        power.on_change(move |state| changes_clone.lock().unwrap().push(state));
+
+        assert_eq!(power.handle(PjLinkPowerCommandParameter::Off), PjLinkResponse::Ok);
+        assert_eq!(power.handle(PjLinkPowerCommandParameter::On), PjLinkResponse::Ok);
+        assert_eq!(power.handle(PjLinkPowerCommandParameter::On), PjLinkResponse::Ok);
+        assert_eq!(power.handle(PjLinkPowerCommandParameter::Off), PjLinkResponse::UnavailableTime);
+
+        thread::sleep(Duration::from_millis(30));
+        assert_eq!(power.state(), PjLinkPowerCommandStatus::On);
+        assert_eq!(power.handle(PjLinkPowerCommandParameter::Off), PjLinkResponse::Ok);
+        assert_eq!(power.handle(PjLinkPowerCommandParameter::Unknown), PjLinkResponse::OutOfParameter);
+
+        assert_eq!(
+            *changes.lock().unwrap(),
+            vec![PjLinkPowerCommandStatus::WarmUp, PjLinkPowerCommandStatus::On, PjLinkPowerCommandStatus::Off]
+        );
+    }
+
+    #[test]
+    fn it_accumulates_lamp_hours_while_lit() {
+        let clock = Arc::new(PjLinkMockClock::new());
+        let mut power = PjLinkPowerStateMachine::new(Duration::from_secs(60), Duration::from_secs(60)).with_clock(clock.clone());
+        power.set_lamp_hours(10);
+
+        power.handle(PjLinkPowerCommandParameter::On);
+        clock.advance(Duration::from_secs(2 * 3600));
+        assert_eq!(power.state(), PjLinkPowerCommandStatus::On);
+        assert_eq!(power.lamp_hours(), 12);
+
+        power.handle(PjLinkPowerCommandParameter::Off);
+        clock.advance(Duration::from_secs(5 * 3600));
+        assert_eq!(power.state(), PjLinkPowerCommandStatus::Off);
+        assert_eq!(power.lamp_hours(), 12);
+    }
+}
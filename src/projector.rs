@@ -0,0 +1,133 @@
+//! Projector state updated outside PJLink connections.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{PjLinkError, PjLinkNotificationTarget, PjLinkProjectorState, PjLinkRawPayload, PjLinkResponse, PjLinkStateTracker};
+
+/// Thread-safe handle application code uses to report projector state
+/// changes that didn't come from a PJLink command, like a power button
+/// press or a lamp failure.
+///
+/// Set a clone as [PjLinkListenerOptions::projector](crate::PjLinkListenerOptions::projector),
+/// and the listener answers `POWR`, `INPT`, `ERST`, `AVMT` and `FREZ`
+/// queries from the stored state, without calling the handler. Items not
+/// set yet are still answered by the handler. Changes are also sent as
+/// Class 2 status notifications through a
+/// [PjLinkStateTracker](crate::PjLinkStateTracker).
+///
+/// ## Examples
+#[cfg_attr(feature = "server", doc = "```no_run")]
+#[cfg_attr(not(feature = "server"), doc = "```ignore")]
+/// use std::time::Duration;
+/// use pjlink_bridge::*;
+///
+/// let projector = PjLinkProjectorHandle::new(
+///     vec![PjLinkNotificationTarget::Address("192.168.0.10:4352".parse().unwrap())],
+///     Duration::from_millis(500),
+/// ).unwrap();
+///
+/// let options = PjLinkListenerOptions {
+///     projector: Some(projector.clone()),
+///     ..Default::default()
+/// };
+///
+/// // Power button pressed: "%1POWR ?" is answered with 3, and
+/// // "%2POWR=3\r" is notified
+/// projector.set_power(PjLinkPowerCommandStatus::WarmUp);
+/// ```
+#[derive(Clone)]
+pub struct PjLinkProjectorHandle {
+    tracker: Arc<PjLinkStateTracker>,
+}
+
+impl PjLinkProjectorHandle {
+    /// Creates a handle with a new [PjLinkStateTracker](crate::PjLinkStateTracker).
+    ///
+    /// **Arguments**:
+    /// * `destinations`: Where notifications are sent to
+    /// * `debounce`: Time the state must stay unchanged before notifying
+    pub fn new(destinations: Vec<PjLinkNotificationTarget>, debounce: Duration) -> Result<PjLinkProjectorHandle, PjLinkError> {
+        Ok(Self::from_tracker(PjLinkStateTracker::new(destinations, debounce)?))
+    }
+
+    /// Creates a handle storing state in `tracker`, like one created with
+    /// [PjLinkStateTracker::with_store](crate::PjLinkStateTracker::with_store).
+    pub fn from_tracker(tracker: PjLinkStateTracker) -> PjLinkProjectorHandle {
+        PjLinkProjectorHandle { tracker: Arc::new(tracker) }
+    }
+
+    /// Updates power status. See [PjLinkPowerCommandStatus](crate::PjLinkPowerCommandStatus).
+    pub fn set_power(&self, power: u8) {
+        self.tracker.set_power(power);
+    }
+
+    /// Updates current input. See [PjLinkInputCommandStatus](crate::PjLinkInputCommandStatus).
+    pub fn set_input(&self, input_type: u8, input_value: u8) {
+        self.tracker.set_input(input_type, input_value);
+    }
+
+    /// Updates error status, in the same order as
+    /// [PjLinkCommand::ErrorStatus1](crate::PjLinkCommand::ErrorStatus1) response.
+    pub fn set_error_status(&self, error_status: [u8; 6]) {
+        self.tracker.set_error_status(error_status);
+    }
+
+    /// Updates every item known in `state`, like mute and freeze.
+    pub fn set_state(&self, state: &PjLinkProjectorState) {
+        self.tracker.set_state(state);
+    }
+
+    /// Returns the current state.
+    pub fn state(&self) -> PjLinkProjectorState {
+        self.tracker.snapshot().state
+    }
+
+    /// Returns the answer to a query about an item of the current state, or
+    /// `None` if it's not a query, or the item wasn't set yet.
+    pub fn response_to(&self, raw_command: &PjLinkRawPayload) -> Option<PjLinkResponse> {
+        if !raw_command.is_query() {
+            return Option::None;
+        }
+
+        let state = self.state();
+        match &raw_command.command_body_with_class {
+            b"1POWR" => state.power.map(PjLinkResponse::Single),
+            b"1INPT" | b"2INPT" => state.input.map(|input| PjLinkResponse::Multiple(input.to_vec())),
+            b"1ERST" => state.error_status.map(|error_status| PjLinkResponse::Multiple(error_status.to_vec())),
+            b"1AVMT" => state.mute.map(|mute| PjLinkResponse::Multiple(mute.query().to_vec())),
+            b"2FREZ" => state.freeze.map(|freeze| PjLinkResponse::Single(if freeze { b'1' } else { b'0' })),
+            _ => Option::None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::UdpSocket;
+    use crate::PjLinkPowerCommandStatus;
+
+    #[test]
+    fn it_answers_queries_and_notifies_state_changes() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let projector = PjLinkProjectorHandle::new(
+            vec![PjLinkNotificationTarget::Address(receiver.local_addr().unwrap())],
+            Duration::from_millis(10),
+        ).unwrap();
+
+        let power_query = PjLinkRawPayload::new_command(*b"1POWR", b"?".to_vec());
+        assert_eq!(projector.response_to(&power_query), None);
+
+        projector.set_power(PjLinkPowerCommandStatus::Off);
+        projector.clone().set_power(PjLinkPowerCommandStatus::WarmUp);
+        assert_eq!(projector.response_to(&power_query), Some(PjLinkResponse::Single(PjLinkPowerCommandStatus::WarmUp)));
+        assert_eq!(projector.response_to(&PjLinkRawPayload::new_command(*b"1INPT", b"?".to_vec())), None);
+        assert_eq!(projector.response_to(&PjLinkRawPayload::new_command(*b"1POWR", b"1".to_vec())), None);
+
+        let mut buffer = [0u8; 16];
+        let length = receiver.recv(&mut buffer).unwrap();
+        assert_eq!(&buffer[..length], b"%2POWR=3\r");
+    }
+}
@@ -0,0 +1,1120 @@
+//! PJLink message parsing and encoding, without networking.
+//!
+//! Everything here works on byte buffers only, so clients, traffic analyzers
+//! or fuzzers can reuse the parser without the server machinery:
+//!
+//! ```
+//! use pjlink_bridge::protocol::*;
+//!
+//! let raw_command = decode_line(b"%1INPT 31").unwrap();
+//! assert_eq!(decode_command(b"%1INPT 31").unwrap(), PjLinkCommand::Input1(PjLinkInputCommandParameter::Digital(b'1')));
+//!
+//! let raw_response = raw_command.update_with_response(PjLinkResponse::Ok, &0);
+//! assert_eq!(encode_payload(&raw_response), b"%1INPT=OK\x0d");
+//! ```
+
+use log::debug;
+
+use crate::{PjLinkError, PjLinkLogContext};
+
+/// PJLink header character (%).
+/// 
+/// Every PJLink message (except authentication hello) starts with this
+/// character.
+pub const PJLINK_HEADER: u8 = b'%';
+/// PJLink command separator (0x20, space)
+/// 
+/// Messages coming from controller to projector use this character to
+/// separate command body from transmission parameter.
+/// 
+/// ### Command example
+/// ```"%1INPT 32\x0d"```
+pub const PJLINK_COMMAND_SEPARATOR: u8 = 0x20; // space
+/// PJLink response separator (=)
+/// 
+/// Messages coming from projector to controller (responses) use this
+/// character to separate command body from transmission parameter.
+/// 
+/// ### Response example
+/// ```"%2FREZ=OK\x0d"```
+pub const PJLINK_RESPONSE_SEPARATOR: u8 = 0x3d; // =
+/// PJLink terminator/end of sequence (0x0d, carriage return)
+/// 
+/// All PJLink messages must end with this character.
+pub const PJLINK_TERMINATOR: u8 = b'\x0d'; // carriage return
+/// PJLink query character (?), as char
+/// 
+/// All query requests use this chararcter to indicate a query request.
+pub const PJLINK_QUERY_CHAR: char = '?';
+/// PJLink query character (?), as u8
+/// 
+/// All query requests use this chararcter to indicate a query request.
+pub const PJLINK_QUERY: u8 = PJLINK_QUERY_CHAR as u8;
+
+/// PJLink nullified security header (PJLINK 0\x0d)
+/// 
+/// If the projector does not have authentication, this header is returned
+/// to controller. Afterwards, controller can send requests without
+/// password.
+#[cfg(feature = "server")]
+pub(crate) const PJLINK_NULLIFIED_SECURITY: &[u8; 9] = b"PJLINK 0\x0d";
+/// PJLink authentication header (PJLINK 1 )
+/// 
+/// If the projector does have authentication, this header is returned
+/// to controller with a hash (see PJLink specification). Afterwards,
+/// controller sends first request with a hashed MD5 salt+password.
+#[cfg(feature = "server")]
+pub(crate) const PJLINK_SECURITY: &[u8; 9] = b"PJLINK 1 ";
+/// PJLink authentication error (PJLINK ERRA\x0d)
+/// 
+/// Controller returned with an invalid or wrong password hash.
+#[cfg(feature = "server")]
+pub(crate) const PJLINK_SECURITY_ERRA: &[u8; 12] = b"PJLINK ERRA\x0d";
+
+/// PJLink Class 2 broadcast search start (%2SRCH\x0d)
+/// 
+/// This is the message sent from controller to the projector over
+/// UDP on broadcast address for querying all Class 2 projectors on local
+/// network. This command doesn't use a command separator.
+#[cfg(feature = "discovery")]
+pub(crate) const PJLINK_BROADCAST_SEARCH_START: &[u8; 7] = b"%2SRCH\x0d";
+/// PJLink Class 2 Acknoledge broadcast command body (ACKN)
+/// 
+/// This is the command body used for response message to broadcast
+/// search request.
+/// 
+/// ### Usage in response string
+/// ```"%2ACKN=00:00:00:00:00:00\x0d"```
+pub const PJLINK_BROADCAST_MESSAGE_ACKN: &[u8; 5] = b"2ACKN";
+/// PJLink Class 2 Lookup Notify command body (LKUP)
+/// 
+/// This is the command body used for spontaneous lookup message from projector
+/// to controller.
+/// 
+/// ### Usage in response string
+/// ```"%2LKUP=00:00:00:00:00:00\x0d"```
+pub const PJLINK_BROADCAST_MESSAGE_LKUP: &[u8; 5] = b"2LKUP";
+/// PJLink Class 2 Error Status Notify command body (ERST)
+/// 
+/// This is the command body used for spontaneous error status change message
+/// from projector to controller.
+/// 
+/// ### Usage in response string
+/// ```"%2ERST=001000\x0d"```
+pub const PJLINK_BROADCAST_MESSAGE_ERST: &[u8; 5] = b"2ERST";
+/// PJLink Class 2 Power Status Notify command body (POWR)
+/// 
+/// This is the command body used for spontaneous power status change message
+/// from projector to controller.
+/// 
+/// ### Usage in response string
+/// ```"%2POWR=1\x0d"```
+pub const PJLINK_BROADCAST_MESSAGE_POWR: &[u8; 5] = b"2POWR";
+/// PJLink Class 2 Input Notiy command body (POWER)
+/// 
+/// This is the command body used for spontaneous input change message
+/// from projector to controller.
+/// 
+/// ### Usage in response string
+/// ```"%2INPT=32\x0d"```
+pub const PJLINK_BROADCAST_MESSAGE_INPT: &[u8; 5] = b"2INPT";
+
+/// The maximum size of UDP datagrams sent to the server.
+/// 
+/// Rust's UDPSocket implementation needs a fixed buffer size due to
+/// UDP nature, this is the maximum broadcast message size present
+/// on PJLink specification.
+#[cfg(feature = "discovery")]
+pub(crate) const PJLINK_MAX_BROADCAST_BUFFER_SIZE: usize = 25;
+
+/// Length of a line with an empty transmission parameter (header, command
+/// body with class and separator), the shortest line that can be parsed.
+pub(crate) const PJLINK_MIN_LINE_LENGTH: usize = 7;
+
+/// PJLink default port (4352), for both TCP and UDP.
+pub const PJLINK_DEFAULT_PORT: u16 = 4352;
+
+/// PJLink Response Transmission Parameter: Sucessful Execution (OK)
+/// 
+/// This is the command response when the command is executed successfully,
+/// without any response.
+pub(crate) const PJLINK_RESPONSE_TRANSMISSION_PARAMETER_OK: &[u8] = b"OK";
+
+/// PJLink Response Transmission Parameter: Undefined Command (ERR1)
+/// 
+/// This is the command response when the command is unknown to the projector.
+pub(crate) const PJLINK_RESPONSE_TRANSMISSION_PARAMETER_ERR1: &[u8] = b"ERR1";
+
+/// PJLink Response Transmission Parameter: Out of Parameter (ERR2)
+/// 
+/// This is the command response when the command parameter is unknown or invalid.
+pub(crate) const PJLINK_RESPONSE_TRANSMISSION_PARAMETER_ERR2: &[u8] = b"ERR2";
+
+/// PJLink Response Transmission Parameter: Unavailable Time (ERR3)
+/// 
+/// This is the command response when the command cannot be received while projector is in
+/// standby.
+pub(crate) const PJLINK_RESPONSE_TRANSMISSION_PARAMETER_ERR3: &[u8] = b"ERR3";
+
+/// PJLink Response Transmission Parameter: Projector/Display failure (ERR4)
+/// 
+/// This is the command response when the projector cannot be operated properly anymore,
+/// due to an internal failure.
+pub(crate) const PJLINK_RESPONSE_TRANSMISSION_PARAMETER_ERR4: &[u8] = b"ERR4";
+
+/// PJLink Command/Response Line
+/// 
+/// This struct aims to match the PJLink's Command Line and Response Line,
+/// without the [terminator](self::PJLINK_TERMINATOR).
+/// 
+/// ## Examples
+/// ### Using [```new_command()```](PjLinkRawPayload::new_command)
+/// ```
+/// use pjlink_bridge::*;
+/// 
+/// let payload = PjLinkRawPayload::new_command(*b"1POWR", vec![PJLINK_QUERY]);
+/// ```
+/// ### Using [```new_response()```](PjLinkRawPayload::new_response)
+/// ```
+/// use pjlink_bridge::*;
+/// 
+/// let payload = PjLinkRawPayload::new_response(*b"1POWR", vec![b'0']);
+/// ```
+/// ### Struct instantiation 
+/// ```
+/// use pjlink_bridge::*;
+/// 
+/// let payload = PjLinkRawPayload {
+///     command_body_with_class: *b"1POWR",
+///     separator: PJLINK_COMMAND_SEPARATOR,
+///     transmission_parameter: vec![PJLINK_QUERY]
+/// };
+/// ```
+#[derive(Clone, PartialEq, Eq)]
+pub struct PjLinkRawPayload {
+    /// Contains PJLink's command body, with the class
+    pub command_body_with_class: [u8; 5],
+    /// Message separator.
+    /// [PJLINK_COMMAND_SEPARATOR](self::PJLINK_COMMAND_SEPARATOR) for a command,
+    /// [PJLINK_RESPONSE_SEPARATOR](self::PJLINK_RESPONSE_SEPARATOR) for a response,
+    pub separator: u8,
+    pub transmission_parameter: Vec<u8>,
+}
+
+impl PjLinkRawPayload {
+    /// Utility method for generating a PJLink Command line (uses 
+    /// [PJLINK_COMMAND_SEPARATOR](self::PJLINK_COMMAND_SEPARATOR) as separator)
+    /// 
+    /// **Arguments**:
+    /// * `command_body_with_class`: PJLink command body with class. Value example: `*b"1POWR"`
+    /// * `transmission_parameter`: PJLink transmission parameter.`
+    pub fn new_command(
+        command_body_with_class: [u8; 5],
+        transmission_parameter: Vec<u8>
+    ) -> PjLinkRawPayload {
+        PjLinkRawPayload {
+            command_body_with_class,
+            separator: PJLINK_COMMAND_SEPARATOR,
+            transmission_parameter
+        }
+    }
+
+    /// Utility method for generating a PJLink Response line (uses 
+    /// [PJLINK_RESPONSE_SEPARATOR](self::PJLINK_RESPONSE_SEPARATOR) as
+    /// separator)
+    /// 
+    /// **Arguments**:
+    /// * `command_body_with_class`: PJLink command body with class. Value example: `*b"1POWR"`
+    /// * `transmission_parameter`: PJLink transmission parameter.`
+    pub fn new_response(
+        command_body_with_class: [u8; 5],
+        transmission_parameter: Vec<u8>
+    ) -> PjLinkRawPayload {
+        PjLinkRawPayload {
+            command_body_with_class,
+            separator: PJLINK_RESPONSE_SEPARATOR,
+            transmission_parameter
+        }
+    }
+
+    /// Utility method for generating a PJLink Command/Response line from
+    /// a buffer.
+    ///
+    /// **Arguments**:
+    /// * `buffer`: Raw PJLink instruction buffer
+    /// * `connection_id`: Connection ID
+    ///
+    /// Panics if `buffer` isn't a valid line. Use [decode_line](self::decode_line)
+    /// to get an error instead.
+    #[deprecated(note = "panics on invalid lines, use decode_line instead")]
+    pub fn from_buffer(buffer: &[u8], connection_id: &u64) -> PjLinkRawPayload {
+        PjLinkRawPayloadRef::from_buffer_with_context(buffer, &PjLinkLogContext::new(*connection_id, Option::None))
+            .unwrap_or_else(|e| panic!("{}", e))
+            .to_payload()
+    }
+
+    /// Returns `true` if this is a query command, with a single
+    /// [PJLINK_QUERY](self::PJLINK_QUERY) transmission parameter.
+    pub fn is_query(&self) -> bool {
+        self.separator == PJLINK_COMMAND_SEPARATOR && self.transmission_parameter == [PJLINK_QUERY]
+    }
+
+    /// Returns `true` if this command only reads projector state: either
+    /// a [query](self::PjLinkRawPayload::is_query), or an INNM query, whose
+    /// transmission parameter is [PJLINK_QUERY](self::PJLINK_QUERY)
+    /// followed by the input type and value (`%2INNM ?11`).
+    pub fn is_query_command(&self) -> bool {
+        if self.is_query() {
+            return true;
+        }
+
+        self.separator == PJLINK_COMMAND_SEPARATOR
+            && self.command_body_with_class == *b"2INNM"
+            && self.transmission_parameter.len() == 3
+            && self.transmission_parameter[0] == PJLINK_QUERY
+    }
+
+    /// Borrows this payload as a [PjLinkRawPayloadRef](self::PjLinkRawPayloadRef).
+    pub fn as_payload_ref(&self) -> PjLinkRawPayloadRef<'_> {
+        PjLinkRawPayloadRef {
+            command_body_with_class: self.command_body_with_class,
+            separator: self.separator,
+            transmission_parameter: &self.transmission_parameter,
+        }
+    }
+
+    /// Updates a [PjLinkRawPayload](self::PjLinkRawPayload) instance with the provided
+    /// [PjLinkResponse](self::PjLinkResponse).
+    ///
+    /// **Arguments**:
+    /// * `response`: [PjLinkResponse](self::PjLinkResponse) enum item
+    /// * `connection_id`: Connection ID
+    pub fn update_with_response(self, response: PjLinkResponse, connection_id: &u64) -> PjLinkRawPayload {
+        let log_context = PjLinkLogContext::new(*connection_id, Option::None);
+        let transmission_parameter: Vec<u8> = match response {
+            PjLinkResponse::Multiple(response_value) => response_value,
+            response => response.transmission_parameter().to_vec(),
+        };
+        let command_body_with_class: [u8; 5] = self.command_body_with_class;
+        let separator: u8 = PJLINK_RESPONSE_SEPARATOR;
+        
+        debug!(
+            "Parsed Response. {}, CmdBodyWithClass: {}, Sep: {}, TxParam: {}",
+            log_context,
+            String::from_utf8_lossy(&command_body_with_class),
+            separator as char,
+            String::from_utf8_lossy(&transmission_parameter)
+        );
+
+        PjLinkRawPayload {
+            command_body_with_class,
+            separator,
+            transmission_parameter,
+        }
+    }
+}
+
+/// Borrowed [PjLinkRawPayload](self::PjLinkRawPayload), referencing the
+/// transmission parameter in the received buffer instead of copying it.
+///
+/// Used to parse commands without allocating.
+///
+/// ## Examples
+/// ```
+/// use pjlink_bridge::*;
+///
+/// let buffer = b"%1INPT 31";
+/// let raw_command = PjLinkRawPayloadRef::try_from_buffer(buffer).unwrap();
+/// assert_eq!(raw_command.transmission_parameter, b"31");
+///
+/// let mut reused_command = PjLinkRawPayload::new_command(*b"1POWR", Vec::with_capacity(128));
+/// raw_command.copy_into(&mut reused_command);
+/// assert_eq!(reused_command, PjLinkRawPayload::new_command(*b"1INPT", b"31".to_vec()));
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct PjLinkRawPayloadRef<'a> {
+    /// Contains PJLink's command body, with the class
+    pub command_body_with_class: [u8; 5],
+    /// Message separator. See [PjLinkRawPayload::separator](self::PjLinkRawPayload::separator).
+    pub separator: u8,
+    pub transmission_parameter: &'a [u8],
+}
+
+impl<'a> PjLinkRawPayloadRef<'a> {
+    /// Parses a PJLink Command/Response line from a buffer, without the
+    /// [terminator](self::PJLINK_TERMINATOR).
+    ///
+    /// **Arguments**:
+    /// * `buffer`: Raw PJLink instruction buffer
+    ///
+    /// Fails with [Parse](crate::PjLinkError::Parse) if `buffer` is shorter
+    /// than a line with an empty transmission parameter, doesn't start with
+    /// the [header](self::PJLINK_HEADER) or has an unknown separator, and with [InvalidClass](crate::PjLinkError::InvalidClass)
+    /// if the class isn't `1` or `2`.
+    pub fn try_from_buffer(buffer: &'a [u8]) -> Result<PjLinkRawPayloadRef<'a>, PjLinkError> {
+        if buffer.len() < PJLINK_MIN_LINE_LENGTH {
+            return Err(PjLinkError::parse(buffer, "line is too short"));
+        }
+        if buffer[0] != PJLINK_HEADER {
+            return Err(PjLinkError::parse(buffer, "missing header"));
+        }
+        if buffer[6] != PJLINK_COMMAND_SEPARATOR && buffer[6] != PJLINK_RESPONSE_SEPARATOR {
+            return Err(PjLinkError::parse(buffer, "invalid separator"));
+        }
+        if !matches!(buffer[1], b'1' | b'2') {
+            return Err(PjLinkError::InvalidClass { line: buffer.to_vec(), class: buffer[1] });
+        }
+
+        let mut command_body_with_class: [u8; 5] = Default::default();
+        command_body_with_class.copy_from_slice(&buffer[1..6]);
+
+        Ok(PjLinkRawPayloadRef {
+            command_body_with_class,
+            separator: buffer[6],
+            transmission_parameter: &buffer[7..],
+        })
+    }
+
+    pub(crate) fn from_buffer_with_context(
+        buffer: &'a [u8],
+        log_context: &PjLinkLogContext,
+    ) -> Result<PjLinkRawPayloadRef<'a>, PjLinkError> {
+        let command = Self::try_from_buffer(buffer)?;
+
+        debug!(
+            "Parsed command. {}, CmdBodyWithClass: {}, Sep: {}, TxParam: {}",
+            log_context,
+            String::from_utf8_lossy(&command.command_body_with_class),
+            command.separator as char,
+            String::from_utf8_lossy(command.transmission_parameter)
+        );
+
+        Ok(command)
+    }
+
+    /// Copies into a new, owned [PjLinkRawPayload](self::PjLinkRawPayload).
+    pub fn to_payload(&self) -> PjLinkRawPayload {
+        PjLinkRawPayload {
+            command_body_with_class: self.command_body_with_class,
+            separator: self.separator,
+            transmission_parameter: self.transmission_parameter.to_vec(),
+        }
+    }
+
+    /// Copies into an existing [PjLinkRawPayload](self::PjLinkRawPayload),
+    /// reusing its transmission parameter allocation.
+    pub fn copy_into(&self, target: &mut PjLinkRawPayload) {
+        target.command_body_with_class = self.command_body_with_class;
+        target.separator = self.separator;
+        target.transmission_parameter.clear();
+        target.transmission_parameter.extend_from_slice(self.transmission_parameter);
+    }
+}
+
+/// PJLink Response Transmission parameter
+/// 
+/// It's used as a response to [PjLinkCommand](self::PjLinkCommand) commands.
+#[derive(Clone, PartialEq, Eq)]
+pub enum PjLinkResponse {
+    /// Matches a PJLink Successful execution (```OK```) response parameter
+    /// 
+    /// ### As used in:
+    /// ```%1POWR=OK```
+    Ok,
+    /// Matches a PJLink Undefined command (```ERR1```) response parameter.
+    /// 
+    /// ### As used in:
+    /// ```%1NONE=ERR1```
+    Undefined,
+    /// Matches a PJLink Out of parameter (```ERR2```) response parameter.
+    /// 
+    /// ### As used in:
+    /// ```%1INPT=ERR2```
+    OutOfParameter,
+    /// Matches a PJLink Unavailable time (```ERR3```) response parameter.
+    /// 
+    /// ### As used in:
+    /// ```%1INPT=ERR3```
+    UnavailableTime,
+    /// Matches a PJLink Projector/Display failure (```ERR4```) response
+    /// parameter.
+    /// 
+    /// ### As used in:
+    /// ```%1INPT=ERR4```
+    ProjectorOrDisplayFailure,
+    /// A single character response parameter.
+    /// 
+    /// ### As used in:
+    /// ```%1POWR=1```
+    Single(u8),
+    /// A multiple character response parameter.
+    /// 
+    /// ### As used in:
+    /// ```%2INPT=2B```
+    Multiple(Vec<u8>),
+    /// An empty response parameter.
+    /// 
+    /// ### As used in:
+    /// ```%2SVER=```
+    Empty
+}
+
+impl PjLinkResponse {
+    /// Returns the response transmission parameter, like `OK`, `ERR2` or `1`.
+    ///
+    /// Borrows a static slice for `OK` and `ERRx` responses, so no allocation
+    /// is needed to send them.
+    pub fn transmission_parameter(&self) -> &[u8] {
+        match self {
+            PjLinkResponse::Ok => PJLINK_RESPONSE_TRANSMISSION_PARAMETER_OK,
+            PjLinkResponse::Undefined => PJLINK_RESPONSE_TRANSMISSION_PARAMETER_ERR1,
+            PjLinkResponse::OutOfParameter => PJLINK_RESPONSE_TRANSMISSION_PARAMETER_ERR2,
+            PjLinkResponse::UnavailableTime => PJLINK_RESPONSE_TRANSMISSION_PARAMETER_ERR3,
+            PjLinkResponse::ProjectorOrDisplayFailure => PJLINK_RESPONSE_TRANSMISSION_PARAMETER_ERR4,
+            PjLinkResponse::Single(value) => std::slice::from_ref(value),
+            PjLinkResponse::Multiple(value) => value,
+            PjLinkResponse::Empty => &[],
+        }
+    }
+
+    /// Matches `OK` and `ERRx` transmission parameters.
+    fn canned(transmission_parameter: &[u8]) -> Option<PjLinkResponse> {
+        match transmission_parameter {
+            PJLINK_RESPONSE_TRANSMISSION_PARAMETER_OK => Option::Some(Self::Ok),
+            PJLINK_RESPONSE_TRANSMISSION_PARAMETER_ERR1 => Option::Some(Self::Undefined),
+            PJLINK_RESPONSE_TRANSMISSION_PARAMETER_ERR2 => Option::Some(Self::OutOfParameter),
+            PJLINK_RESPONSE_TRANSMISSION_PARAMETER_ERR3 => Option::Some(Self::UnavailableTime),
+            PJLINK_RESPONSE_TRANSMISSION_PARAMETER_ERR4 => Option::Some(Self::ProjectorOrDisplayFailure),
+            _ => Option::None,
+        }
+    }
+}
+
+impl From<String> for PjLinkResponse {
+    fn from(from: String) -> Self {
+        from.into_bytes().into()
+    }
+}
+
+impl From<Vec<u8>> for PjLinkResponse {
+    fn from(from: Vec<u8>) -> Self {
+        match from.as_slice() {
+            [] | [_] => Self::from(from.as_slice()),
+            _ => Self::canned(&from).unwrap_or(Self::Multiple(from)),
+        }
+    }
+}
+
+impl From<&[u8]> for PjLinkResponse {
+    fn from(from: &[u8]) -> Self {
+        match from {
+            [] => Self::Empty,
+            [value] => Self::Single(*value),
+            _ => Self::canned(from).unwrap_or_else(|| Self::Multiple(from.to_vec())),
+        }
+    }
+}
+
+/// Parameters for [1POWR](self::PjLinkCommand::Power1) command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PjLinkPowerCommandParameter {
+    /// Power off action: `%1POWR 0`
+    Off,
+    /// Power on action: `%1POWR 1`
+    On,
+    /// Query action:`%1POWR ?`
+    ///
+    /// See: [PJLINK_QUERY](self::PJLINK_QUERY)
+    Query,
+    /// Used if an unknown parameter is received
+    Unknown,
+}
+
+/// Response status for [1POWR](self::PjLinkCommand::Power1) command
+pub struct PjLinkPowerCommandStatus;
+#[allow(non_upper_case_globals)]
+impl PjLinkPowerCommandStatus {
+    /// Projector is off: `%1POWR=0`
+    pub const Off: u8 = b'0';
+    /// Projector is on: `%1POWR=1`
+    pub const On: u8 = b'1';
+    /// Projector is in cooling state: `%1POWR=2`
+    pub const Cooling: u8 = b'2';
+    /// Projector is in warmup state: `%1POWR=3`
+    pub const WarmUp: u8 = b'3';
+}
+
+/// Response status for [1CLSS](self::PjLinkCommand::Class1) command
+pub struct PjLinkClassCommandStatus;
+#[allow(non_upper_case_globals)]
+impl PjLinkClassCommandStatus {
+    /// Projector supports Class 1 commands: `%1CLSS=1`
+    pub const Class1: u8 = b'1';
+    /// Projector supports Class 1 and 2 commands: `%1CLSS=2`
+    pub const Class2: u8 = b'2';
+}
+
+/// Response status for each item of [1ERST](self::PjLinkCommand::ErrorStatus1) command.
+///
+/// See: [PjLinkCommand::ErrorStatus1](self::PjLinkCommand::ErrorStatus1)
+pub struct PjLinkErrorStatusCommandStatusItem;
+#[allow(non_upper_case_globals)]
+impl PjLinkErrorStatusCommandStatusItem {
+    /// Item is normal state / is not checked
+    pub const Normal: u8 = b'0';
+    /// Item is in warning state
+    pub const Warning: u8 = b'1';
+    /// Item is in error state
+    pub const Error: u8 = b'2';
+}
+
+/// Parameter for [1INPT](self::PjLinkCommand::Input1) command 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PjLinkInputCommandParameter {
+    RGB(u8),
+    Video(u8),
+    Digital(u8),
+    Storage(u8),
+    Network(u8),
+    Internal(u8),
+    Query,
+    Unknown,
+}
+
+pub struct PjLinkInputCommandStatus;
+#[allow(non_upper_case_globals)]
+impl PjLinkInputCommandStatus {
+    pub const RGB: u8 = b'1';
+    pub const Video: u8 = b'2';
+    pub const Digital: u8 = b'3';
+    pub const Storage: u8 = b'4';
+    pub const Network: u8 = b'5';
+    pub const Internal: u8 = b'6';
+}
+
+pub struct PjLinkMuteCommandStatus;
+#[allow(non_upper_case_globals)]
+impl PjLinkMuteCommandStatus {
+    pub const Audio: u8 = b'2';
+    pub const Video: u8 = b'1';
+    pub const AudioAndVideo: u8 = b'3';
+    pub const Mute: u8 = b'1';
+    pub const NonMute: u8 = b'0';
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PjLinkMuteCommandParameter {
+    Audio(bool),
+    Video(bool),
+    AudioAndVideo(bool),
+    Query,
+    Unknown,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PjLinkVolumeCommandParameter {
+    Increase,
+    Decrase,
+    Unknown,
+}
+
+pub struct PjLinkInputResolutionCommandStatus;
+#[allow(non_upper_case_globals)]
+impl PjLinkInputResolutionCommandStatus {
+    pub const NoSignal: u8 = b'-';
+    pub const Unknown: u8 = b'*';
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PjLinkFreezeCommandParameter {
+    Freeze,
+    Unfreeze,
+    Query,
+    Unknown,
+}
+pub struct PjLinkFreezeCommandStatus;
+#[allow(non_upper_case_globals)]
+impl PjLinkFreezeCommandStatus {
+    pub const Freezed: u8 = b'1';
+    pub const Unfreezed: u8 = b'0';
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PjLinkCommand {
+    Search2,
+    Power1(PjLinkPowerCommandParameter),
+    Input1(PjLinkInputCommandParameter),
+    Input2(PjLinkInputCommandParameter),
+    AvMute1(PjLinkMuteCommandParameter),
+    ErrorStatus1,
+    Lamp1,
+    InputTogglingList1,
+    InputTogglingList2,
+    Name1,
+    InfoManufacturer1,
+    InfoProductName1,
+    InfoOther1,
+    Class1,
+    SerialNumber2,
+    SoftwareVersion2,
+    InputTerminalName2(PjLinkInputCommandParameter),
+    InputResolution2,
+    RecommendResolution2,
+    FilterUsageTime2,
+    LampReplacementModelNumber2,
+    FilterReplacementModelNumber2,
+    SpeakerVolumeAdjustment2(PjLinkVolumeCommandParameter),
+    MicrophoneVolumeAdjustment2(PjLinkVolumeCommandParameter),
+    Freeze2(PjLinkFreezeCommandParameter),
+    Unknown,
+}
+
+impl PjLinkCommand {
+    /// Returns `true` if the command changes projector state (power, input,
+    /// mute, freeze or volume) instead of querying it. Set commands with an
+    /// unknown parameter are also considered set commands.
+    pub fn is_set_command(&self) -> bool {
+        match self {
+            PjLinkCommand::Power1(parameter) => !matches!(parameter, PjLinkPowerCommandParameter::Query),
+            PjLinkCommand::Input1(parameter) | PjLinkCommand::Input2(parameter) => !matches!(parameter, PjLinkInputCommandParameter::Query),
+            PjLinkCommand::AvMute1(parameter) => !matches!(parameter, PjLinkMuteCommandParameter::Query),
+            PjLinkCommand::Freeze2(parameter) => !matches!(parameter, PjLinkFreezeCommandParameter::Query),
+            PjLinkCommand::SpeakerVolumeAdjustment2(_) | PjLinkCommand::MicrophoneVolumeAdjustment2(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if `command_body_with_class` is a command defined by
+    /// the PJLink specification, as opposed to vendor-specific commands.
+    ///
+    /// **Arguments**:
+    /// * `command_body_with_class`: PJLink command body with class. Value example: `*b"1POWR"`
+    pub fn is_standard_command_body(command_body_with_class: &[u8; 5]) -> bool {
+        let [class, command_body @ ..] = *command_body_with_class;
+
+        matches!(
+            (class, u32::from_be_bytes(command_body)),
+            (b'1', command_body::POWR | command_body::AVMT | command_body::ERST | command_body::LAMP | command_body::NAME
+                | command_body::INF1 | command_body::INF2 | command_body::INFO | command_body::CLSS)
+                | (b'1' | b'2', command_body::INPT | command_body::INST)
+                | (b'2', command_body::SNUM | command_body::SVER | command_body::INNM | command_body::IRES | command_body::RRES
+                    | command_body::FILT | command_body::RLMP | command_body::RFIL | command_body::SVOL | command_body::MVOL
+                    | command_body::FREZ)
+        )
+    }
+
+    pub fn from_raw_payload(raw_command: &PjLinkRawPayload) -> PjLinkCommand {
+        Self::from_raw_payload_ref(&raw_command.as_payload_ref())
+    }
+
+    /// Same as [from_raw_payload](self::PjLinkCommand::from_raw_payload), for
+    /// a borrowed payload.
+    pub fn from_raw_payload_ref(raw_command: &PjLinkRawPayloadRef) -> PjLinkCommand {
+        let transmission_parameter = raw_command.transmission_parameter;
+        let [class, command_body @ ..] = raw_command.command_body_with_class;
+        let is_class_2 = class == b'2';
+        let transmission_parameter_len = transmission_parameter.len();
+
+        match (class, u32::from_be_bytes(command_body)) {
+            (b'1', command_body::POWR) => {
+                let parameter = match transmission_parameter.first().map(|raw_parameter| *raw_parameter as char) {
+                    Some('1') => PjLinkPowerCommandParameter::On,
+                    Some('0') => PjLinkPowerCommandParameter::Off,
+                    Some(PJLINK_QUERY_CHAR) => PjLinkPowerCommandParameter::Query,
+                    _ => PjLinkPowerCommandParameter::Unknown, 
+                };
+
+                PjLinkCommand::Power1(parameter)
+            },
+            (b'1' | b'2', command_body::INPT) => {
+                let parameter: PjLinkInputCommandParameter;
+                if transmission_parameter_len == 1 && transmission_parameter[0] == PJLINK_QUERY {
+                    parameter = PjLinkInputCommandParameter::Query
+                } else if transmission_parameter_len == 2 {
+                    let (input_char, input_value) = (transmission_parameter[0], transmission_parameter[1]);
+                    parameter = Self::input_param_parse(is_class_2, input_char, input_value);
+                } else {
+                    parameter = PjLinkInputCommandParameter::Unknown
+                };
+
+                if is_class_2 {
+                    PjLinkCommand::Input2(parameter)
+                } else {
+                    PjLinkCommand::Input1(parameter)
+                }
+            }
+            (b'1', command_body::AVMT) => {
+                let parameter = if transmission_parameter_len == 1 && transmission_parameter[0] == PJLINK_QUERY {
+                    PjLinkMuteCommandParameter::Query
+                } else if transmission_parameter_len == 2 {
+                    let raw_parameter = (transmission_parameter[0], transmission_parameter[1]);
+                    match raw_parameter {
+                        (b'1', b'1') => PjLinkMuteCommandParameter::Video(true),
+                        (b'1', b'0') => PjLinkMuteCommandParameter::Video(false),
+                        (b'2', b'1') => PjLinkMuteCommandParameter::Audio(true),
+                        (b'2', b'0') => PjLinkMuteCommandParameter::Audio(false),
+                        (b'3', b'1') => PjLinkMuteCommandParameter::AudioAndVideo(true),
+                        (b'3', b'0') => PjLinkMuteCommandParameter::AudioAndVideo(false),
+                        _ => PjLinkMuteCommandParameter::Unknown
+                    }
+                } else {
+                    PjLinkMuteCommandParameter::Unknown
+                };
+
+                PjLinkCommand::AvMute1(parameter)
+            }
+            (b'1', command_body::ERST) => PjLinkCommand::ErrorStatus1,
+            (b'1', command_body::LAMP) => PjLinkCommand::Lamp1,
+            (b'1' | b'2', command_body::INST) => if is_class_2 {
+                PjLinkCommand::InputTogglingList2
+            } else {
+                PjLinkCommand::InputTogglingList1
+            }
+            (b'1', command_body::NAME) => PjLinkCommand::Name1,
+            (b'1', command_body::INF1) => PjLinkCommand::InfoManufacturer1,
+            (b'1', command_body::INF2) => PjLinkCommand::InfoProductName1,
+            (b'1', command_body::INFO) => PjLinkCommand::InfoOther1,
+            (b'1', command_body::CLSS) => PjLinkCommand::Class1,
+            (b'2', command_body::SNUM) => PjLinkCommand::SerialNumber2,
+            (b'2', command_body::SVER) => PjLinkCommand::SoftwareVersion2,
+            (b'2', command_body::INNM) => {
+                let parameter: PjLinkInputCommandParameter;
+                if transmission_parameter_len == 3 {
+                    if transmission_parameter[0] == PJLINK_QUERY {
+                        let (input_char, input_value) = (transmission_parameter[1], transmission_parameter[2]);
+                        parameter = Self::input_param_parse(true, input_char, input_value);
+                    } else {
+                        parameter = PjLinkInputCommandParameter::Unknown
+                    }
+                } else {
+                    parameter = PjLinkInputCommandParameter::Unknown
+                };
+
+                PjLinkCommand::InputTerminalName2(parameter)
+            },
+            (b'2', command_body::IRES) => PjLinkCommand::InputResolution2,
+            (b'2', command_body::RRES) => PjLinkCommand::RecommendResolution2,
+            (b'2', command_body::FILT) => PjLinkCommand::FilterUsageTime2,
+            (b'2', command_body::RLMP) => PjLinkCommand::LampReplacementModelNumber2,
+            (b'2', command_body::RFIL) => PjLinkCommand::FilterReplacementModelNumber2,
+            (b'2', command_body::SVOL) => {
+                if transmission_parameter_len == 1 {
+                    let is_increase = transmission_parameter[0] == b'1';
+                    let is_decrease = transmission_parameter[0] == b'0';
+                    return PjLinkCommand::SpeakerVolumeAdjustment2(if is_increase {
+                        PjLinkVolumeCommandParameter::Increase
+                    } else if is_decrease {
+                        PjLinkVolumeCommandParameter::Decrase
+                    } else {
+                        PjLinkVolumeCommandParameter::Unknown
+                    })
+                }
+
+                PjLinkCommand::Unknown
+            },
+            (b'2', command_body::MVOL) => {
+                if transmission_parameter_len == 1 {
+                    let is_increase = transmission_parameter[0] == b'1';
+                    let is_decrease = transmission_parameter[0] == b'0';
+                    return PjLinkCommand::MicrophoneVolumeAdjustment2(if is_increase {
+                        PjLinkVolumeCommandParameter::Increase
+                    } else if is_decrease {
+                        PjLinkVolumeCommandParameter::Decrase
+                    } else {
+                        PjLinkVolumeCommandParameter::Unknown
+                    })
+                }
+
+                PjLinkCommand::Unknown
+            },
+            (b'2', command_body::FREZ) => {
+                if transmission_parameter_len == 1 {
+                    if transmission_parameter[0] == PJLINK_QUERY {
+                        return PjLinkCommand::Freeze2(PjLinkFreezeCommandParameter::Query);
+                    } else {
+                        let is_freeze = transmission_parameter[0] == b'1';
+                        let is_unfreeze = transmission_parameter[0] == b'0';
+                        return PjLinkCommand::Freeze2(if is_freeze {
+                            PjLinkFreezeCommandParameter::Freeze
+                        } else if is_unfreeze {
+                            PjLinkFreezeCommandParameter::Unfreeze
+                        } else {
+                            PjLinkFreezeCommandParameter::Unknown
+                        })
+                    }
+                }
+
+                PjLinkCommand::Unknown
+            },
+            _ => PjLinkCommand::Unknown
+        }
+    }
+
+    fn input_param_parse(
+        is_class_2: bool,
+        input_char: u8,
+        input_value: u8,
+    ) -> PjLinkInputCommandParameter {
+        let is_invalid_below = input_value < b'1';
+        let is_class_1_invalid_higher = !is_class_2 && (input_value > b'9');
+        let is_class_2_invalid_higher = is_class_2
+                                        && ((input_value > b'9' && input_value < b'A')
+                                            || input_value > b'Z');
+
+        if  is_invalid_below || is_class_1_invalid_higher || is_class_2_invalid_higher {
+            PjLinkInputCommandParameter::Unknown                        
+        } else {
+            match input_char {
+                b'1' => PjLinkInputCommandParameter::RGB(input_value),
+                b'2' => PjLinkInputCommandParameter::Video(input_value),
+                b'3' => PjLinkInputCommandParameter::Digital(input_value),
+                b'4' => PjLinkInputCommandParameter::Storage(input_value),
+                b'5' => PjLinkInputCommandParameter::Network(input_value),
+                b'6' => if is_class_2 {
+                    PjLinkInputCommandParameter::Internal(input_value)
+                } else {
+                    PjLinkInputCommandParameter::Unknown
+                }
+                _ => PjLinkInputCommandParameter::Unknown
+            }
+        } 
+    }
+}
+
+/// Command bodies, without class, as big-endian integers, so commands are
+/// looked up with a single integer match instead of string comparisons.
+mod command_body {
+    pub(super) const POWR: u32 = u32::from_be_bytes(*b"POWR");
+    pub(super) const INPT: u32 = u32::from_be_bytes(*b"INPT");
+    pub(super) const AVMT: u32 = u32::from_be_bytes(*b"AVMT");
+    pub(super) const ERST: u32 = u32::from_be_bytes(*b"ERST");
+    pub(super) const LAMP: u32 = u32::from_be_bytes(*b"LAMP");
+    pub(super) const INST: u32 = u32::from_be_bytes(*b"INST");
+    pub(super) const NAME: u32 = u32::from_be_bytes(*b"NAME");
+    pub(super) const INF1: u32 = u32::from_be_bytes(*b"INF1");
+    pub(super) const INF2: u32 = u32::from_be_bytes(*b"INF2");
+    pub(super) const INFO: u32 = u32::from_be_bytes(*b"INFO");
+    pub(super) const CLSS: u32 = u32::from_be_bytes(*b"CLSS");
+    pub(super) const SNUM: u32 = u32::from_be_bytes(*b"SNUM");
+    pub(super) const SVER: u32 = u32::from_be_bytes(*b"SVER");
+    pub(super) const INNM: u32 = u32::from_be_bytes(*b"INNM");
+    pub(super) const IRES: u32 = u32::from_be_bytes(*b"IRES");
+    pub(super) const RRES: u32 = u32::from_be_bytes(*b"RRES");
+    pub(super) const FILT: u32 = u32::from_be_bytes(*b"FILT");
+    pub(super) const RLMP: u32 = u32::from_be_bytes(*b"RLMP");
+    pub(super) const RFIL: u32 = u32::from_be_bytes(*b"RFIL");
+    pub(super) const SVOL: u32 = u32::from_be_bytes(*b"SVOL");
+    pub(super) const MVOL: u32 = u32::from_be_bytes(*b"MVOL");
+    pub(super) const FREZ: u32 = u32::from_be_bytes(*b"FREZ");
+}
+
+/// PJLink Class 2 status messages, sent from projector to controllers over UDP.
+///
+/// ## Examples
+/// ```no_run
+/// use std::net::UdpSocket;
+/// use pjlink_bridge::*;
+///
+/// let command = PjLinkStatusCommand::Power2(PjLinkPowerCommandStatus::On);
+/// assert_eq!(command.to_bytes(), b"%2POWR=1\x0d");
+///
+/// let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+/// command.send_to(&socket, PjLinkNotificationTarget::Broadcast(PJLINK_DEFAULT_PORT)).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PjLinkStatusCommand {
+    Acknowledge2([[u8; 2]; 6]),
+    Lookup2([[u8; 2]; 6]),
+    ErrorStatus2([u8; 6]),
+    Power2(u8),
+    Input2(u8, u8),
+}
+
+impl PjLinkStatusCommand {
+    /// Converts the status message to a [PjLinkRawPayload](self::PjLinkRawPayload)
+    /// response line.
+    pub fn to_raw_payload(&self) -> PjLinkRawPayload {
+        let (command_body_with_class, transmission_parameter) = match self {
+            PjLinkStatusCommand::Acknowledge2(mac_address) => (*PJLINK_BROADCAST_MESSAGE_ACKN, Self::mac_to_parameter(mac_address)),
+            PjLinkStatusCommand::Lookup2(mac_address) => (*PJLINK_BROADCAST_MESSAGE_LKUP, Self::mac_to_parameter(mac_address)),
+            PjLinkStatusCommand::ErrorStatus2(error_status) => (*PJLINK_BROADCAST_MESSAGE_ERST, error_status.to_vec()),
+            PjLinkStatusCommand::Power2(power_status) => (*PJLINK_BROADCAST_MESSAGE_POWR, vec![*power_status]),
+            PjLinkStatusCommand::Input2(input_type, input_value) => (*PJLINK_BROADCAST_MESSAGE_INPT, vec![*input_type, *input_value]),
+        };
+
+        PjLinkRawPayload::new_response(command_body_with_class, transmission_parameter)
+    }
+
+    fn mac_to_parameter(mac_address: &[[u8; 2]; 6]) -> Vec<u8> {
+        let mut parameter = Vec::with_capacity(17);
+
+        for (index, octet) in mac_address.iter().enumerate() {
+            if index > 0 {
+                parameter.push(b':');
+            }
+            parameter.extend(octet);
+        }
+
+        parameter
+    }
+
+    /// Encodes the status message to its wire format, with header and terminator.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        encode_payload(&self.to_raw_payload())
+    }
+}
+
+/// Parses a command or response line, without the [terminator](self::PJLINK_TERMINATOR).
+///
+/// Replaces the deprecated [PjLinkRawPayload::from_buffer](self::PjLinkRawPayload::from_buffer),
+/// failing instead of panicking on invalid lines. See
+/// [PjLinkRawPayloadRef::try_from_buffer](self::PjLinkRawPayloadRef::try_from_buffer).
+pub fn decode_line(line: &[u8]) -> Result<PjLinkRawPayload, PjLinkError> {
+    PjLinkRawPayloadRef::try_from_buffer(line).map(|raw_payload| raw_payload.to_payload())
+}
+
+/// Parses a command line, without the [terminator](self::PJLINK_TERMINATOR),
+/// into a [PjLinkCommand](self::PjLinkCommand).
+pub fn decode_command(line: &[u8]) -> Result<PjLinkCommand, PjLinkError> {
+    PjLinkRawPayloadRef::try_from_buffer(line).map(|raw_command| PjLinkCommand::from_raw_payload_ref(&raw_command))
+}
+
+/// Encodes a command or response line to its wire format, with header and
+/// [terminator](self::PJLINK_TERMINATOR).
+pub fn encode_payload(raw_payload: &PjLinkRawPayload) -> Vec<u8> {
+    encode_line(&raw_payload.command_body_with_class, raw_payload.separator, &raw_payload.transmission_parameter)
+}
+
+/// Encodes the response to a command to its wire format, with header and
+/// [terminator](self::PJLINK_TERMINATOR).
+///
+/// Same as encoding the result of [PjLinkRawPayload::update_with_response](self::PjLinkRawPayload::update_with_response),
+/// without copying the response transmission parameter.
+///
+/// **Arguments**:
+/// * `command_body_with_class`: PJLink command body with class. Value example: `*b"1POWR"`
+/// * `response`: [PjLinkResponse](self::PjLinkResponse) enum item
+pub fn encode_response(command_body_with_class: &[u8; 5], response: &PjLinkResponse) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    encode_response_into(&mut buffer, command_body_with_class, response);
+    buffer
+}
+
+/// Same as [encode_response](self::encode_response), appending to an
+/// existing buffer so it can be reused between responses.
+///
+/// **Arguments**:
+/// * `buffer`: Buffer the encoded line is appended to
+/// * `command_body_with_class`: PJLink command body with class. Value example: `*b"1POWR"`
+/// * `response`: [PjLinkResponse](self::PjLinkResponse) enum item
+pub fn encode_response_into(buffer: &mut Vec<u8>, command_body_with_class: &[u8; 5], response: &PjLinkResponse) {
+    encode_line_into(buffer, command_body_with_class, PJLINK_RESPONSE_SEPARATOR, response.transmission_parameter())
+}
+
+fn encode_line(command_body_with_class: &[u8; 5], separator: u8, transmission_parameter: &[u8]) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(transmission_parameter.len() + 8);
+    encode_line_into(&mut buffer, command_body_with_class, separator, transmission_parameter);
+    buffer
+}
+
+fn encode_line_into(buffer: &mut Vec<u8>, command_body_with_class: &[u8; 5], separator: u8, transmission_parameter: &[u8]) {
+    buffer.reserve(transmission_parameter.len() + 8);
+    buffer.push(PJLINK_HEADER);
+    buffer.extend(command_body_with_class);
+    buffer.push(separator);
+
+    buffer.extend(transmission_parameter);
+    let buffer_last = buffer.len() - 1;
+
+    if buffer[buffer_last] == b'\x00' {
+        buffer[buffer_last] = PJLINK_TERMINATOR;
+    } else {
+        buffer.push(PJLINK_TERMINATOR);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_converts_1powr_query_to_powr_query_enum() {
+        let raw_command = PjLinkRawPayload::new_command(*b"1POWR", vec![PJLINK_QUERY]);
+        let command = PjLinkCommand::from_raw_payload(&raw_command);
+        assert!(matches!(command, PjLinkCommand::Power1(PjLinkPowerCommandParameter::Query)));
+    }
+
+    #[test]
+    fn it_converts_1powr_on_to_powr_on_enum() {
+        let raw_command = PjLinkRawPayload::new_command(*b"1POWR", vec![b'1']);
+        let command = PjLinkCommand::from_raw_payload(&raw_command);
+        assert!(matches!(command, PjLinkCommand::Power1(PjLinkPowerCommandParameter::On)));
+    }
+
+    #[test]
+    fn it_converts_1powr_off_to_powr_off_enum() {
+        let raw_command = PjLinkRawPayload::new_command(*b"1POWR", vec![b'0']);
+        let command = PjLinkCommand::from_raw_payload(&raw_command);
+        assert!(matches!(command, PjLinkCommand::Power1(PjLinkPowerCommandParameter::Off)));
+    }
+
+    #[test]
+    fn it_converts_1powr_garbage_to_powr_unknown_enum() {
+        let raw_command = PjLinkRawPayload::new_command(*b"1POWR", vec![b'b', b'2']);
+        let command = PjLinkCommand::from_raw_payload(&raw_command);
+        assert!(matches!(command, PjLinkCommand::Power1(PjLinkPowerCommandParameter::Unknown)));
+    }
+
+    #[test]
+    fn it_looks_up_command_bodies_with_their_class() {
+        let raw_command = PjLinkRawPayload::new_command(*b"1POWR", Vec::new());
+        assert_eq!(PjLinkCommand::from_raw_payload(&raw_command), PjLinkCommand::Power1(PjLinkPowerCommandParameter::Unknown));
+        assert_eq!(PjLinkCommand::from_raw_payload(&PjLinkRawPayload::new_command(*b"2POWR", vec![b'1'])), PjLinkCommand::Unknown);
+        assert!(PjLinkCommand::is_standard_command_body(b"2FREZ"));
+        assert!(!PjLinkCommand::is_standard_command_body(b"1FREZ"));
+    }
+
+    #[test]
+    fn it_encodes_decoded_lines() {
+        let raw_response = PjLinkRawPayload::new_response(*b"2SVER", Vec::new());
+        assert_eq!(encode_payload(&raw_response), b"%2SVER=\x0d");
+        assert_eq!(decode_line(b"%2SVER=").unwrap().transmission_parameter, Vec::<u8>::new());
+        assert!(decode_line(b"%2SVE").is_err());
+        assert!(decode_line(b"!1POWR ?").is_err());
+        assert!(decode_line(b"%1POWR:?").is_err());
+        assert!(matches!(decode_line(b"%3POWR ?"), Err(PjLinkError::InvalidClass { class: b'3', .. })));
+    }
+
+    #[test]
+    fn it_encodes_canned_responses() {
+        assert_eq!(encode_response(b"1POWR", &PjLinkResponse::ProjectorOrDisplayFailure), b"%1POWR=ERR4\x0d");
+
+        let mut buffer = b"%1POWR=OK\x0d".to_vec();
+        buffer.clear();
+        encode_response_into(&mut buffer, b"1AVMT", &PjLinkResponse::Multiple(b"31".to_vec()));
+        assert_eq!(buffer, b"%1AVMT=31\x0d");
+        assert_eq!(
+            encode_response(b"2INPT", &PjLinkResponse::Single(b'1')),
+            encode_payload(&PjLinkRawPayload::new_command(*b"2INPT", Vec::new()).update_with_response(PjLinkResponse::Single(b'1'), &0))
+        );
+    }
+
+    #[test]
+    fn it_converts_transmission_parameters_to_responses() {
+        assert_eq!(PjLinkResponse::from(b"ERR4".to_vec()), PjLinkResponse::ProjectorOrDisplayFailure);
+        assert_eq!(PjLinkResponse::from(String::from("OK")), PjLinkResponse::Ok);
+        assert_eq!(PjLinkResponse::from(b"1".to_vec()), PjLinkResponse::Single(b'1'));
+        assert_eq!(PjLinkResponse::from(&b"2B"[..]), PjLinkResponse::Multiple(b"2B".to_vec()));
+        assert_eq!(PjLinkResponse::from(Vec::new()), PjLinkResponse::Empty);
+    }
+
+    #[test]
+    fn it_parses_borrowed_payload() {
+        let buffer = b"%1AVMT 31";
+        let raw_command = PjLinkRawPayloadRef::try_from_buffer(buffer).unwrap();
+        assert_eq!(raw_command.to_payload(), decode_line(buffer).unwrap());
+        assert_eq!(PjLinkCommand::from_raw_payload_ref(&raw_command), decode_command(buffer).unwrap());
+
+        let mut reused_command = PjLinkRawPayload::new_command(*b"2INNM", b"11".to_vec());
+        raw_command.copy_into(&mut reused_command);
+        assert_eq!(reused_command.as_payload_ref(), raw_command);
+    }
+}
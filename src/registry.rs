@@ -0,0 +1,105 @@
+//! Additional commands answered by the listener.
+
+use std::collections::HashMap;
+
+use crate::{PjLinkRawPayload, PjLinkResponse};
+
+type PjLinkCommandDispatch = Box<dyn Fn(&[u8], &u64) -> PjLinkResponse + Send + Sync>;
+
+/// Commands beyond the PJLink specification (like vendor extensions or
+/// `%2TEST`), answered by the listener without changing [PjLinkCommand](crate::PjLinkCommand).
+///
+/// Each command is registered with a parse closure, which turns the
+/// transmission parameter into a value (or `None` to answer `ERR2`), and a
+/// dispatch callback, which answers the parsed value.
+///
+/// Dispatch callbacks run while the listener holds the handler lock, so they
+/// must not lock the handler themselves. Listeners declared as Class 1 only
+/// answer registered Class 2 commands with `ERR1` too, without dispatching
+/// them.
+///
+/// ## Examples
+#[cfg_attr(feature = "server", doc = "```")]
+#[cfg_attr(not(feature = "server"), doc = "```ignore")]
+/// use pjlink_bridge::*;
+///
+/// let mut commands = PjLinkCommandRegistry::new();
+/// commands.register(
+///     *b"2TEST",
+///     |parameter| std::str::from_utf8(parameter).ok()?.parse::<u8>().ok(),
+///     |pattern, _connection_id| match pattern {
+///         0..=9 => PjLinkResponse::Ok,
+///         _ => PjLinkResponse::OutOfParameter,
+///     },
+/// );
+///
+/// let test_pattern = PjLinkRawPayload::new_command(*b"2TEST", b"3".to_vec());
+/// assert_eq!(commands.response_to(&test_pattern, &0), Some(PjLinkResponse::Ok));
+///
+/// let options = PjLinkListenerOptions { commands, ..Default::default() };
+/// ```
+#[derive(Default)]
+pub struct PjLinkCommandRegistry {
+    commands: HashMap<[u8; 5], PjLinkCommandDispatch>,
+}
+
+impl PjLinkCommandRegistry {
+    pub fn new() -> PjLinkCommandRegistry {
+        PjLinkCommandRegistry::default()
+    }
+
+    /// Registers a command, replacing any command previously registered
+    /// with the same body.
+    ///
+    /// **Arguments**:
+    /// * `command_body_with_class`: PJLink command body with class. Value example: `*b"2TEST"`
+    /// * `parse`: Parses the transmission parameter. Returning `None` answers `ERR2`.
+    /// * `dispatch`: Answers the parsed parameter, with the connection ID
+    pub fn register<T, P, D>(&mut self, command_body_with_class: [u8; 5], parse: P, dispatch: D) -> &mut PjLinkCommandRegistry
+    where
+        P: Fn(&[u8]) -> Option<T> + Send + Sync + 'static,
+        D: Fn(T, &u64) -> PjLinkResponse + Send + Sync + 'static,
+    {
+        self.commands.insert(
+            command_body_with_class,
+            Box::new(move |transmission_parameter, connection_id| match parse(transmission_parameter) {
+                Some(parameter) => dispatch(parameter, connection_id),
+                None => PjLinkResponse::OutOfParameter,
+            }),
+        );
+
+        self
+    }
+
+    /// Returns whether a command is registered with this body.
+    pub fn contains(&self, command_body_with_class: &[u8; 5]) -> bool {
+        self.commands.contains_key(command_body_with_class)
+    }
+
+    /// Returns the response to `raw_command`, if its body is registered.
+    pub fn response_to(&self, raw_command: &PjLinkRawPayload, connection_id: &u64) -> Option<PjLinkResponse> {
+        self.commands.get(&raw_command.command_body_with_class)
+            .map(|dispatch| dispatch(&raw_command.transmission_parameter, connection_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_answers_registered_commands() {
+        let mut commands = PjLinkCommandRegistry::new();
+        commands.register(*b"1LENS", |parameter| match parameter {
+            [shift @ b'0'..=b'9'] => Some(shift - b'0'),
+            _ => None,
+        }, |shift, connection_id| PjLinkResponse::Multiple(format!("{}-{}", connection_id, shift).into_bytes()));
+
+        let send = |transmission_parameter: &[u8]| {
+            commands.response_to(&PjLinkRawPayload::new_command(*b"1LENS", transmission_parameter.to_vec()), &7)
+        };
+        assert_eq!(send(b"4"), Some(PjLinkResponse::Multiple(b"7-4".to_vec())));
+        assert_eq!(send(b"x"), Some(PjLinkResponse::OutOfParameter));
+        assert!(commands.response_to(&PjLinkRawPayload::new_command(*b"1POWR", b"?".to_vec()), &7).is_none());
+    }
+}
@@ -0,0 +1,86 @@
+//! Listener configuration replaceable while the server runs.
+
+use std::sync::{Arc, RwLock};
+
+use crate::{PjLinkListenerOptions, PjLinkNotificationTarget, PjLinkPasswordProvider, PjLinkProjectorDescriptor};
+
+/// Part of the listener configuration that can be replaced while the server
+/// runs, with [PjLinkServerHandle::reload](crate::PjLinkServerHandle::reload),
+/// without dropping controller connections.
+///
+/// The whole configuration is swapped at once, so every command is answered
+/// with either the old or the new one, never a mix of both:
+/// * [descriptor](self::PjLinkReloadableConfig::descriptor) answers the next
+///   commands of every connection
+/// * [password_provider](self::PjLinkReloadableConfig::password_provider) is
+///   used by new connections; open sessions keep the provider they started
+///   with
+/// * [notification_targets](self::PjLinkReloadableConfig::notification_targets)
+///   receive the next [PjLinkListener::notify](crate::PjLinkListener::notify) messages
+///
+/// Whether the UDP search socket is opened is decided at startup, so
+/// reloading a descriptor with a different class doesn't open or close it.
+///
+/// ## Examples
+/// ```
+/// use std::sync::{Arc, Mutex};
+/// use pjlink_bridge::*;
+///
+/// # fn example(projector: Arc<Mutex<dyn PjLinkHandler>>) {
+/// let handle = PjLinkServer::listen_tcp_only(projector, "127.0.0.1".into(), "0".into()).unwrap();
+///
+/// let mut config = (*handle.config()).clone();
+/// config.descriptor = Some(PjLinkProjectorDescriptor::new(PjLinkClassCommandStatus::Class2));
+/// config.notification_targets = vec![PjLinkNotificationTarget::Broadcast(PJLINK_DEFAULT_PORT)];
+/// handle.reload(config);
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct PjLinkReloadableConfig {
+    /// Static capabilities. See [PjLinkListenerOptions::descriptor](crate::PjLinkListenerOptions::descriptor).
+    pub descriptor: Option<PjLinkProjectorDescriptor>,
+    /// Source of connection passwords. See [PjLinkListenerOptions::password_provider](crate::PjLinkListenerOptions::password_provider).
+    pub password_provider: Option<Arc<dyn PjLinkPasswordProvider>>,
+    /// Destinations of status notifications. See [PjLinkListenerOptions::notification_targets](crate::PjLinkListenerOptions::notification_targets).
+    pub notification_targets: Vec<PjLinkNotificationTarget>,
+}
+
+impl PjLinkReloadableConfig {
+    /// Returns the initial configuration set on `options`.
+    pub fn from_options(options: &PjLinkListenerOptions) -> PjLinkReloadableConfig {
+        PjLinkReloadableConfig {
+            descriptor: options.descriptor.clone(),
+            password_provider: options.password_provider.clone(),
+            notification_targets: options.notification_targets.clone(),
+        }
+    }
+}
+
+/// Current [PjLinkReloadableConfig](self::PjLinkReloadableConfig) of a
+/// listener, shared with its connections.
+#[derive(Default)]
+pub(crate) struct PjLinkConfigState {
+    current: RwLock<Arc<PjLinkReloadableConfig>>,
+}
+
+impl PjLinkConfigState {
+    pub(crate) fn new(config: PjLinkReloadableConfig) -> PjLinkConfigState {
+        PjLinkConfigState { current: RwLock::new(Arc::new(config)) }
+    }
+
+    pub(crate) fn load(&self) -> Arc<PjLinkReloadableConfig> {
+        match self.current.read() {
+            Ok(current) => current.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    pub(crate) fn store(&self, config: PjLinkReloadableConfig) {
+        let config = Arc::new(config);
+
+        match self.current.write() {
+            Ok(mut current) => *current = config,
+            Err(poisoned) => *poisoned.into_inner() = config,
+        }
+    }
+}
@@ -0,0 +1,270 @@
+//! Replay of captured sessions, for regression testing handlers against
+//! real-world traffic.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::{
+    PjLinkCaptureDirection, PjLinkCaptureRecord, PjLinkDigest, PjLinkError, PjLinkHandlerShared, PjLinkListenerOptions, PjLinkMd5Digest, PjLinkMemoryTransport,
+    PjLinkServer, PjLinkSwappablePassword, PJLINK_TERMINATOR,
+};
+
+/// How long replay waits for each response line.
+const PJLINK_REPLAY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Length of the password hash prefixing the first command of
+/// authenticated sessions.
+const PJLINK_PASSWORD_HASH_LENGTH: usize = 32;
+
+/// Received line whose response differs from the captured one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PjLinkReplayDifference {
+    /// Connection ID in the capture
+    pub connection_id: u64,
+    /// Line sent, without terminator and password hash
+    pub line: Vec<u8>,
+    /// Response in the capture, with terminators
+    pub expected: Vec<u8>,
+    /// Response received while replaying, with terminators. Shorter than
+    /// expected if the connection was closed or timed out.
+    pub actual: Vec<u8>,
+}
+
+/// Captured connection: whether it authenticated, and each received line
+/// with the bytes sent back.
+#[derive(Debug, Clone, Default)]
+struct PjLinkReplayConnection {
+    connection_id: u64,
+    requires_auth: bool,
+    exchanges: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+/// Feeds the lines of a capture recorded by
+/// [PjLinkSessionCapture](crate::PjLinkSessionCapture) back to a handler,
+/// or to a live server, and returns the responses that changed.
+///
+/// Each captured connection is replayed on its own connection, one after
+/// the other, in capture order. Password hashes are removed from captured
+/// lines, since they only match the original session salt:
+/// [run_against_handler](self::PjLinkReplay::run_against_handler) disables
+/// authentication, and [run_against_address](self::PjLinkReplay::run_against_address)
+/// authenticates with the given password. Connections whose authentication
+/// failed in the capture are skipped.
+///
+/// ## Examples
+/// ```no_run
+/// use std::sync::{Arc, Mutex};
+/// use pjlink_bridge::*;
+///
+/// # fn example(projector: Arc<Mutex<dyn PjLinkHandler>>) {
+/// let replay = PjLinkReplay::open("/tmp/pjlink.ndjson").unwrap();
+///
+/// for difference in replay.run_against_handler(projector) {
+///     println!(
+///         "{:?}: expected {:?}, got {:?}",
+///         String::from_utf8_lossy(&difference.line),
+///         String::from_utf8_lossy(&difference.expected),
+///         String::from_utf8_lossy(&difference.actual),
+///     );
+/// }
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PjLinkReplay {
+    connections: Vec<PjLinkReplayConnection>,
+}
+
+impl PjLinkReplay {
+    /// Reads a capture file, in either format.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<PjLinkReplay, PjLinkError> {
+        let records = PjLinkCaptureRecord::read_all(File::open(path)?)?;
+        Ok(Self::from_records(&records))
+    }
+
+    /// Groups captured records by connection.
+    pub fn from_records(records: &[PjLinkCaptureRecord]) -> PjLinkReplay {
+        let mut connections: Vec<PjLinkReplayConnection> = Vec::new();
+        let mut indexes = HashMap::new();
+
+        for record in records {
+            let index = *indexes.entry(record.connection_id).or_insert_with(|| {
+                connections.push(PjLinkReplayConnection { connection_id: record.connection_id, ..Default::default() });
+                connections.len() - 1
+            });
+            let connection = &mut connections[index];
+
+            match (record.direction, connection.exchanges.last_mut()) {
+                (PjLinkCaptureDirection::Received, _) => {
+                    let line = match connection.requires_auth && connection.exchanges.is_empty() {
+                        true => record.data.get(PJLINK_PASSWORD_HASH_LENGTH..).unwrap_or_default().to_vec(),
+                        false => record.data.clone(),
+                    };
+                    connection.exchanges.push((line, Vec::new()));
+                }
+                (PjLinkCaptureDirection::Sent, Some((_, expected))) => expected.extend_from_slice(&record.data),
+                (PjLinkCaptureDirection::Sent, None) => connection.requires_auth = record.data.starts_with(b"PJLINK 1"),
+            }
+        }
+
+        connections.retain(|connection| {
+            connection.exchanges.first().is_none_or(|(_, expected)| !expected.starts_with(b"PJLINK ERRA"))
+        });
+
+        PjLinkReplay { connections }
+    }
+
+    /// Returns the number of received lines that will be replayed.
+    pub fn line_count(&self) -> usize {
+        self.connections.iter().map(|connection| connection.exchanges.len()).sum()
+    }
+
+    /// Replays the capture against `handler`, in-process, with
+    /// authentication disabled.
+    pub fn run_against_handler(&self, handler: PjLinkHandlerShared) -> Vec<PjLinkReplayDifference> {
+        let mut differences = Vec::new();
+
+        for connection in self.connections.iter() {
+            let (mut client, server) = PjLinkMemoryTransport::pair();
+            client.set_read_timeout(Option::Some(PJLINK_REPLAY_TIMEOUT));
+
+            let handler = handler.clone();
+            let options = PjLinkListenerOptions {
+                password_provider: Option::Some(Arc::new(PjLinkSwappablePassword::new(Option::None))),
+                ..Default::default()
+            };
+            let server_thread = thread::spawn(move || PjLinkServer::serve_transport_with_options(handler, server, options));
+
+            replay_connection(client, connection, Option::None, &mut differences);
+            let _ = server_thread.join();
+        }
+
+        differences
+    }
+
+    /// Replays the capture against a running server, authenticating with
+    /// `password` if the server requires it.
+    pub fn run_against_address<A: ToSocketAddrs>(
+        &self,
+        address: A,
+        password: Option<&str>,
+    ) -> Result<Vec<PjLinkReplayDifference>, PjLinkError> {
+        let addresses: Vec<_> = address.to_socket_addrs()?.collect();
+        let mut differences = Vec::new();
+
+        for connection in self.connections.iter() {
+            let stream = TcpStream::connect(&addresses[..])?;
+            stream.set_read_timeout(Option::Some(PJLINK_REPLAY_TIMEOUT))?;
+            stream.set_write_timeout(Option::Some(PJLINK_REPLAY_TIMEOUT))?;
+
+            replay_connection(stream, connection, password, &mut differences);
+        }
+
+        Ok(differences)
+    }
+}
+
+/// Replays the lines of one connection over `stream`, until they run out
+/// or the server stops answering.
+fn replay_connection<T: Read + Write>(
+    stream: T,
+    connection: &PjLinkReplayConnection,
+    password: Option<&str>,
+    differences: &mut Vec<PjLinkReplayDifference>,
+) {
+    let mut reader = BufReader::new(stream);
+    let mut header = Vec::new();
+    let mut password_hash = match reader.read_until(PJLINK_TERMINATOR, &mut header) {
+        Ok(_) => header.strip_prefix(b"PJLINK 1 ")
+            .map(|salt| {
+                let mut salted_password = salt.strip_suffix(&[PJLINK_TERMINATOR]).unwrap_or(salt).to_vec();
+                salted_password.extend_from_slice(password.unwrap_or_default().as_bytes());
+                PjLinkMd5Digest.hex_digest(&salted_password)
+            }),
+        Err(_) => Option::None,
+    };
+
+    for (line, expected) in connection.exchanges.iter() {
+        let mut buffer = password_hash.take().map(String::into_bytes).unwrap_or_default();
+        buffer.extend_from_slice(line);
+        buffer.push(PJLINK_TERMINATOR);
+
+        let mut actual = Vec::new();
+        let mut is_closed = reader.get_mut().write_all(&buffer).and_then(|_| reader.get_mut().flush()).is_err();
+        for _ in 0..expected.iter().filter(|byte| **byte == PJLINK_TERMINATOR).count() {
+            if is_closed {
+                break;
+            }
+            is_closed = !matches!(reader.read_until(PJLINK_TERMINATOR, &mut actual), Ok(length) if length > 0);
+        }
+
+        if actual != *expected {
+            differences.push(PjLinkReplayDifference {
+                connection_id: connection.connection_id,
+                line: line.clone(),
+                expected: expected.clone(),
+                actual,
+            });
+        }
+        if is_closed {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use crate::{PjLinkCommand, PjLinkHandler, PjLinkRawPayload, PjLinkResponse};
+
+    struct PowerOnHandler;
+
+    impl PjLinkHandler for PowerOnHandler {
+        fn get_password(&mut self, _connection_id: &u64) -> Option<String> {
+            Option::Some(String::from("secret"))
+        }
+
+        fn handle_command(&mut self, _command: PjLinkCommand, _raw_command: &PjLinkRawPayload, _connection_id: &u64) -> PjLinkResponse {
+            PjLinkResponse::Single(b'1')
+        }
+    }
+
+    #[test]
+    fn it_replays_captures_and_reports_changed_responses() {
+        let record = |connection_id: u64, direction: PjLinkCaptureDirection, data: &[u8]| PjLinkCaptureRecord {
+            timestamp_us: 0,
+            connection_id,
+            peer_addr: None,
+            direction,
+            data: data.to_vec(),
+        };
+        let hash = "0123456789abcdef0123456789abcdef";
+        let records = vec![
+            record(1, PjLinkCaptureDirection::Sent, b"PJLINK 1 00000001\r"),
+            record(1, PjLinkCaptureDirection::Received, format!("{}%1POWR ?", hash).as_bytes()),
+            record(1, PjLinkCaptureDirection::Sent, b"%1POWR=1\r"),
+            record(2, PjLinkCaptureDirection::Sent, b"PJLINK 1 00000002\r"),
+            record(2, PjLinkCaptureDirection::Received, format!("{}%1POWR ?", hash).as_bytes()),
+            record(2, PjLinkCaptureDirection::Sent, b"PJLINK ERRA\r"),
+            record(1, PjLinkCaptureDirection::Received, b"%1INPT ?"),
+            record(1, PjLinkCaptureDirection::Sent, b"%1INPT=31\r"),
+        ];
+
+        let replay = PjLinkReplay::from_records(&records);
+        assert_eq!(replay.line_count(), 2);
+
+        let differences = replay.run_against_handler(Arc::new(Mutex::new(PowerOnHandler)));
+        assert_eq!(differences, vec![PjLinkReplayDifference {
+            connection_id: 1,
+            line: b"%1INPT ?".to_vec(),
+            expected: b"%1INPT=31\r".to_vec(),
+            actual: b"%1INPT=1\r".to_vec(),
+        }]);
+    }
+}
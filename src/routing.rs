@@ -0,0 +1,133 @@
+//! Conversions used when routing commands to handler methods.
+
+use crate::PjLinkResponse;
+
+/// Converts handler method results into a [PjLinkResponse](crate::PjLinkResponse).
+///
+/// Used by the `#[pjlink_handler]` attribute (`macros` feature), so routed
+/// methods can return plain values:
+/// * `()`: `OK`
+/// * `u8`: a single character, like a [PjLinkPowerCommandStatus](crate::PjLinkPowerCommandStatus) value
+/// * `bool`: `1` or `0`
+/// * `String`, `&str` and `Vec<u8>`: the value, or an empty response if empty
+/// * `Result<T, PjLinkResponse>`: the value, or the error response, like `ERR3`
+///
+/// ## Examples
+/// ```
+/// use pjlink_bridge::*;
+///
+/// assert_eq!(().into_response(), PjLinkResponse::Ok);
+/// assert_eq!(b'1'.into_response(), PjLinkResponse::Single(b'1'));
+/// assert_eq!("".into_response(), PjLinkResponse::Empty);
+/// ```
+pub trait PjLinkIntoResponse {
+    fn into_response(self) -> PjLinkResponse;
+}
+
+impl PjLinkIntoResponse for PjLinkResponse {
+    fn into_response(self) -> PjLinkResponse {
+        self
+    }
+}
+
+impl PjLinkIntoResponse for () {
+    fn into_response(self) -> PjLinkResponse {
+        PjLinkResponse::Ok
+    }
+}
+
+impl PjLinkIntoResponse for u8 {
+    fn into_response(self) -> PjLinkResponse {
+        PjLinkResponse::Single(self)
+    }
+}
+
+impl PjLinkIntoResponse for bool {
+    fn into_response(self) -> PjLinkResponse {
+        PjLinkResponse::Single(if self { b'1' } else { b'0' })
+    }
+}
+
+impl PjLinkIntoResponse for Vec<u8> {
+    fn into_response(self) -> PjLinkResponse {
+        match self.is_empty() {
+            true => PjLinkResponse::Empty,
+            false => PjLinkResponse::Multiple(self),
+        }
+    }
+}
+
+impl PjLinkIntoResponse for String {
+    fn into_response(self) -> PjLinkResponse {
+        self.into_bytes().into_response()
+    }
+}
+
+impl PjLinkIntoResponse for &str {
+    fn into_response(self) -> PjLinkResponse {
+        Vec::from(self.as_bytes()).into_response()
+    }
+}
+
+impl<T: PjLinkIntoResponse> PjLinkIntoResponse for Result<T, PjLinkResponse> {
+    fn into_response(self) -> PjLinkResponse {
+        match self {
+            Ok(value) => value.into_response(),
+            Err(response) => response,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "macros"))]
+mod tests {
+    use crate::*;
+
+    struct RoutedProjector {
+        power: u8,
+    }
+
+    #[pjlink_handler]
+    impl RoutedProjector {
+        fn password(&mut self, _connection_id: &u64) -> Option<String> {
+            Option::None
+        }
+
+        fn power_query(&mut self) -> u8 {
+            self.power
+        }
+
+        fn set_power(&mut self, on: bool) -> Result<(), PjLinkResponse> {
+            if on && self.power == PjLinkPowerCommandStatus::On {
+                return Err(PjLinkResponse::UnavailableTime);
+            }
+
+            self.power = if on { PjLinkPowerCommandStatus::On } else { PjLinkPowerCommandStatus::Off };
+            Ok(())
+        }
+
+        fn name_query(&mut self) -> &'static str {
+            "Routed"
+        }
+    }
+
+    fn send(projector: &mut RoutedProjector, command_body_with_class: [u8; 5], transmission_parameter: &[u8]) -> Vec<u8> {
+        let raw_command = PjLinkRawPayload::new_command(command_body_with_class, transmission_parameter.to_vec());
+        let command = PjLinkCommand::from_raw_payload(&raw_command);
+        let response = projector.handle_command(command, &raw_command, &0);
+
+        raw_command.update_with_response(response, &0).transmission_parameter
+    }
+
+    #[test]
+    fn it_routes_commands_to_methods() {
+        let mut projector = RoutedProjector { power: PjLinkPowerCommandStatus::Off };
+
+        assert_eq!(send(&mut projector, *b"1POWR", b"1"), b"OK");
+        assert_eq!(send(&mut projector, *b"1POWR", b"?"), b"1");
+        assert_eq!(send(&mut projector, *b"1POWR", b"1"), b"ERR3");
+        assert_eq!(send(&mut projector, *b"1POWR", b"7"), b"ERR2");
+        assert_eq!(send(&mut projector, *b"1NAME", b"?"), b"Routed");
+        assert_eq!(send(&mut projector, *b"1INPT", b"?"), b"ERR1");
+        assert!(projector.get_password(&0).is_none());
+    }
+}
@@ -0,0 +1,121 @@
+//! Random salts of PJLink authentication security headers.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(any(test, feature = "rand"))]
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+#[cfg(any(test, feature = "rand"))]
+use rand::RngCore;
+
+/// Generates the random salt sent in the `PJLINK 1` security header.
+///
+/// Uses [PjLinkRandSalt](self::PjLinkRandSalt), a thread-local RNG from
+/// `rand`, by default, or [PjLinkStdSalt](self::PjLinkStdSalt) without the
+/// `rand` feature. Set another implementation as
+/// [PjLinkListenerOptions::salt_rng](crate::PjLinkListenerOptions::salt_rng)
+/// to use a hardware RNG. With the `rand` feature, any `rand` RNG in a
+/// [Mutex](std::sync::Mutex) is a salt source too, so tests can use a
+/// seeded one.
+///
+/// ## Examples
+#[cfg_attr(feature = "server", doc = "```")]
+#[cfg_attr(not(feature = "server"), doc = "```ignore")]
+/// use std::sync::Arc;
+/// use pjlink_bridge::*;
+///
+/// struct HardwareRng;
+///
+/// impl PjLinkSaltSource for HardwareRng {
+///     fn next_salt(&self) -> u32 {
+///         // Read the hardware RNG here
+///         0x498e4a67
+///     }
+/// }
+///
+/// let options = PjLinkListenerOptions {
+///     salt_rng: Some(Arc::new(HardwareRng)),
+///     ..Default::default()
+/// };
+/// ```
+pub trait PjLinkSaltSource: Send + Sync {
+    /// Returns a new salt.
+    fn next_salt(&self) -> u32;
+}
+
+/// [PjLinkSaltSource](self::PjLinkSaltSource) using the thread-local RNG of
+/// `rand`. Available with the `rand` feature.
+#[cfg(any(test, feature = "rand"))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PjLinkRandSalt;
+
+#[cfg(any(test, feature = "rand"))]
+impl PjLinkSaltSource for PjLinkRandSalt {
+    fn next_salt(&self) -> u32 {
+        rand::thread_rng().next_u32()
+    }
+}
+
+#[cfg(any(test, feature = "rand"))]
+impl<R: RngCore + Send> PjLinkSaltSource for Mutex<R> {
+    fn next_salt(&self) -> u32 {
+        match self.lock() {
+            Ok(mut rng) => rng.next_u32(),
+            Err(poisoned) => poisoned.into_inner().next_u32(),
+        }
+    }
+}
+
+/// [PjLinkSaltSource](self::PjLinkSaltSource) hashing a counter and the
+/// current time with the randomly keyed hasher of the standard library.
+///
+/// Used by default without the `rand` feature. Salts are unpredictable
+/// without reading process memory, but it's not a cryptographic RNG.
+#[derive(Debug, Clone, Default)]
+pub struct PjLinkStdSalt {
+    hasher: RandomState,
+}
+
+impl PjLinkStdSalt {
+    /// Creates a salt source with new random hasher keys.
+    pub fn new() -> PjLinkStdSalt {
+        Self::default()
+    }
+}
+
+impl PjLinkSaltSource for PjLinkStdSalt {
+    fn next_salt(&self) -> u32 {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+        let mut hasher = self.hasher.build_hasher();
+        hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+        hasher.write_u128(SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_nanos()).unwrap_or_default());
+        hasher.finish() as u32
+    }
+}
+
+/// Returns a salt from `source`, or from the default source if `None`.
+#[cfg(feature = "server")]
+pub(crate) fn next_salt(source: Option<&dyn PjLinkSaltSource>) -> u32 {
+    match source {
+        Some(source) => source.next_salt(),
+        #[cfg(any(test, feature = "rand"))]
+        None => PjLinkRandSalt.next_salt(),
+        #[cfg(not(any(test, feature = "rand")))]
+        None => PjLinkStdSalt::new().next_salt(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_generates_different_salts_without_rand() {
+        let source = PjLinkStdSalt::new();
+        let salts: Vec<u32> = (0..8).map(|_| source.next_salt()).collect();
+
+        assert!(salts.windows(2).any(|pair| pair[0] != pair[1]));
+    }
+}
@@ -0,0 +1,1322 @@
+//! Listener machinery: TCP and UDP servers, and connection handling.
+
+use std::thread::JoinHandle;
+#[cfg(feature = "discovery")]
+use std::thread;
+use std::sync::{
+    Mutex,
+    RwLock,
+    Arc,
+    mpsc,
+    atomic,
+    atomic::AtomicU64
+};
+use std::net::{SocketAddr, TcpListener, ToSocketAddrs, UdpSocket};
+use std::fmt;
+use std::io;
+use std::io::Write;
+use std::time::Duration;
+use log::{info, warn, debug};
+#[cfg(feature = "discovery")]
+use log::trace;
+
+use crate::{
+    PjLinkClassCommandStatus, PjLinkClock, PjLinkCommandObserver, PjLinkCommandRegistry, PjLinkDeviceInfo, PjLinkDigest, PjLinkError,
+    PjLinkFramingMode, PjLinkHandler, PjLinkHandlerShared, PjLinkListenerStats, PjLinkNotificationTarget, PjLinkParameterLimit,
+    PjLinkPasswordProvider, PjLinkPowerCommandStatus, PjLinkProjectorDescriptor, PjLinkProjectorHandle, PjLinkQueryHandler,
+    PjLinkRawPayload, PjLinkReauthPolicy, PjLinkReloadableConfig, PjLinkResponse, PjLinkSaltSource, PjLinkServerEvent, PjLinkServerHandle,
+    PjLinkStatusCommand, PjLinkSystemClock, PjLinkTcpOptions, PjLinkTransport, PjLinkUdpHealth,
+    spawn_named_thread,
+};
+#[cfg(feature = "discovery")]
+use crate::{
+    PjLinkDatagramRejection, PjLinkIpNetwork, PjLinkMacAddressProvider, PjLinkMulticastGroup, PjLinkSearchRateLimit, PjLinkSearchRateLimiter,
+    PjLinkSearchResponsePort, PjLinkSearchResponseSocket,
+};
+#[cfg(feature = "capture")]
+use crate::PjLinkSessionCapture;
+#[cfg(feature = "discovery")]
+use crate::discovery::{default_mac_address_provider, encode_search_response, validate_search_datagram};
+#[cfg(feature = "discovery")]
+use crate::events::send_event;
+use crate::framing::PjLinkFrameReader;
+use crate::health::{PjLinkTcpHealthState, PjLinkUdpHealthState};
+#[cfg(feature = "discovery")]
+use crate::health::{PJLINK_UDP_REBIND_AFTER_ERRORS, udp_error_backoff};
+#[cfg(feature = "discovery")]
+use crate::protocol::PJLINK_MAX_BROADCAST_BUFFER_SIZE;
+use crate::reload::PjLinkConfigState;
+use crate::session::{PjLinkSession, PjLinkSessionStep};
+use crate::stats::{PjLinkConnectionPermit, PjLinkStatsState};
+
+pub struct PjLinkServer {}
+
+impl PjLinkServer{
+    pub fn listen_tcp_udp<'a>(
+        handler: PjLinkHandlerShared,
+        tcp_bind_address: String,
+        udp_bind_address: String,
+        port: String,
+    ) -> Result<PjLinkServerHandle<'a>, PjLinkError> {
+        Self::listen_tcp_udp_with_options(handler, tcp_bind_address, udp_bind_address, port, PjLinkListenerOptions::default())
+    }
+
+    pub fn listen_tcp_udp_with_options<'a>(
+        handler: PjLinkHandlerShared,
+        tcp_bind_address: String,
+        udp_bind_address: String,
+        port: String,
+        options: PjLinkListenerOptions,
+    ) -> Result<PjLinkServerHandle<'a>, PjLinkError> {
+        let tcp_listener = Self::bind_tcp(format!("{}:{}", tcp_bind_address, port))?;
+
+        let udp_socket = match options.is_class_1_only() {
+            true => Option::None,
+            false => Option::Some(Self::bind_udp(format!("{}:{}", udp_bind_address, port))?),
+        };
+        let listener = PjLinkListener::new_with_options(handler, tcp_listener, udp_socket, options);
+        let handle = Self::spawn_tcp_listener(&listener)?;
+        let udp_handle = Self::spawn_udp_listener(&listener)?;
+
+        Ok(PjLinkServerHandle::new(listener, handle, udp_handle))
+    }
+
+    pub fn listen_tcp_only<'a>(
+        handler: PjLinkHandlerShared,
+        tcp_bind_address: String,
+        port: String
+    ) -> Result<PjLinkServerHandle<'a>, PjLinkError> {
+        Self::listen_tcp_only_with_options(handler, tcp_bind_address, port, PjLinkListenerOptions::default())
+    }
+
+    pub fn listen_tcp_only_with_options<'a>(
+        handler: PjLinkHandlerShared,
+        tcp_bind_address: String,
+        port: String,
+        options: PjLinkListenerOptions,
+    ) -> Result<PjLinkServerHandle<'a>, PjLinkError> {
+        let tcp_listener = Self::bind_tcp(format!("{}:{}", tcp_bind_address, port))?;
+        let listener = PjLinkListener::new_with_options(handler, tcp_listener, Option::None, options);
+        let handle = Self::spawn_tcp_listener(&listener)?;
+
+        Ok(PjLinkServerHandle::new(listener, handle, Option::None))
+    }
+
+    /// Serves a projector on sockets bound by the caller, like sockets
+    /// inherited from a service manager (see [PjLinkActivatedSockets](crate::PjLinkActivatedSockets)),
+    /// so the process can restart without closing the port.
+    ///
+    /// **Arguments**:
+    /// * `handler`: Handler of received connections
+    /// * `tcp_listener`: Listener accepting PJLink connections
+    /// * `udp_socket`: Socket receiving Class 2 search requests, if any
+    ///
+    /// Fails with [Io](crate::PjLinkError::Io) if the listener threads can't
+    /// be started.
+    ///
+    /// ## Examples
+    /// ```
+    /// use std::net::TcpListener;
+    /// use std::sync::{Arc, Mutex};
+    /// use pjlink_bridge::*;
+    ///
+    /// # struct Projector;
+    /// # impl PjLinkHandler for Projector {
+    /// #     fn get_password(&mut self, _connection_id: &u64) -> Option<String> { None }
+    /// #     fn handle_command(&mut self, _command: PjLinkCommand, _raw_command: &PjLinkRawPayload, _connection_id: &u64) -> PjLinkResponse {
+    /// #         PjLinkResponse::Undefined
+    /// #     }
+    /// # }
+    /// let tcp_listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    /// let tcp_addr = tcp_listener.local_addr().unwrap();
+    /// let handle = PjLinkServer::from_listeners(Arc::new(Mutex::new(Projector)), tcp_listener, None).unwrap();
+    ///
+    /// assert_eq!(handle.local_tcp_addr().unwrap(), tcp_addr);
+    /// ```
+    pub fn from_listeners<'a>(
+        handler: PjLinkHandlerShared,
+        tcp_listener: TcpListener,
+        udp_socket: Option<UdpSocket>,
+    ) -> Result<PjLinkServerHandle<'a>, PjLinkError> {
+        Self::from_listeners_with_options(handler, tcp_listener, udp_socket, PjLinkListenerOptions::default())
+    }
+
+    pub fn from_listeners_with_options<'a>(
+        handler: PjLinkHandlerShared,
+        tcp_listener: TcpListener,
+        udp_socket: Option<UdpSocket>,
+        options: PjLinkListenerOptions,
+    ) -> Result<PjLinkServerHandle<'a>, PjLinkError> {
+        let udp_socket = udp_socket.filter(|_| !options.is_class_1_only());
+        let listener = PjLinkListener::new_with_options(handler, tcp_listener, udp_socket, options);
+        let handle = Self::spawn_tcp_listener(&listener)?;
+        let udp_handle = Self::spawn_udp_listener(&listener)?;
+
+        Ok(PjLinkServerHandle::new(listener, handle, udp_handle))
+    }
+
+    /// Serves a single PJLink connection over `transport` on the current
+    /// thread, without binding any socket, until it's closed.
+    ///
+    /// Useful with [PjLinkMemoryTransport](crate::PjLinkMemoryTransport) to
+    /// test handlers in-process.
+    ///
+    /// **Arguments**:
+    /// * `handler`: Handler of the connection
+    /// * `transport`: Connection to serve. See [PjLinkTransport](crate::PjLinkTransport).
+    pub fn serve_transport<T: PjLinkTransport>(handler: PjLinkHandlerShared, transport: T) {
+        Self::serve_transport_with_options(handler, transport, PjLinkListenerOptions::default())
+    }
+
+    pub fn serve_transport_with_options<T: PjLinkTransport>(
+        handler: PjLinkHandlerShared,
+        transport: T,
+        options: PjLinkListenerOptions,
+    ) {
+        let mut connection_handler = PjLinkConnectionHandler {
+            handler,
+            shared_connection_counter: Arc::new(AtomicU64::new(0)),
+            config: Arc::new(PjLinkConfigState::new(PjLinkReloadableConfig::from_options(&options))),
+            options: Arc::new(options),
+            stats: Arc::new(PjLinkStatsState::default()),
+        };
+        connection_handler.handle_connection(transport);
+    }
+
+    /// Spawns the UDP listener thread, if the listener has a UDP socket.
+    #[cfg(feature = "discovery")]
+    fn spawn_udp_listener(listener: &PjLinkListenerShared<'static>) -> Result<Option<JoinHandle<()>>, PjLinkError> {
+        let udp_addr = match listener.local_udp_addr() {
+            Some(udp_addr) => udp_addr,
+            None => return Ok(Option::None),
+        };
+        let listener_clone = listener.clone();
+
+        spawn_named_thread(format!("pjlink-udp-{}", udp_addr.port()), move || {
+            info!("Running UDP Listener on {}", udp_addr);
+            listener_clone.listen_multicast();
+        }).map(Option::Some)
+    }
+
+    /// Without the `discovery` feature, the UDP socket only sends status
+    /// messages, so no thread receives on it.
+    #[cfg(not(feature = "discovery"))]
+    fn spawn_udp_listener(_listener: &PjLinkListenerShared<'static>) -> Result<Option<JoinHandle<()>>, PjLinkError> {
+        Ok(Option::None)
+    }
+
+    pub(crate) fn bind_tcp<A: ToSocketAddrs + fmt::Display>(address: A) -> Result<TcpListener, PjLinkError> {
+        TcpListener::bind(&address).map_err(|e| PjLinkError::bind(address, e))
+    }
+
+    pub(crate) fn bind_udp<A: ToSocketAddrs + fmt::Display>(address: A) -> Result<UdpSocket, PjLinkError> {
+        UdpSocket::bind(&address).map_err(|e| PjLinkError::bind(address, e))
+    }
+
+    /// Starts the TCP accept thread of `listener`, named after its port.
+    fn spawn_tcp_listener(listener: &PjLinkListenerShared<'static>) -> Result<JoinHandle<()>, PjLinkError> {
+        let tcp_addr = listener.local_tcp_addr()?;
+        let listener_clone = listener.clone();
+
+        spawn_named_thread(format!("pjlink-tcp-accept-{}", tcp_addr.port()), move || {
+            info!("Running TCP Listener on {}", tcp_addr);
+            listener_clone.listen();
+        })
+    }
+}
+
+/// Optional behavior of a [PjLinkListener](crate::PjLinkListener).
+///
+/// Use [Default](std::default::Default) to get the standard behavior and
+/// override only the needed fields.
+///
+/// ## Examples
+/// ```
+/// use std::sync::Arc;
+/// use pjlink_bridge::*;
+///
+/// let options = PjLinkListenerOptions {
+///     password_provider: Some(Arc::new(PjLinkSwappablePassword::new(None))),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Default)]
+pub struct PjLinkListenerOptions {
+    /// Overrides [PjLinkHandler::get_password](crate::PjLinkHandler::get_password)
+    /// as the source of connection passwords. Can be replaced while the
+    /// server runs, see [PjLinkReloadableConfig](crate::PjLinkReloadableConfig).
+    pub password_provider: Option<Arc<dyn PjLinkPasswordProvider>>,
+    /// Skips authentication for connections from loopback addresses
+    /// (`127.0.0.0/8` and `::1`), answering them with nullified security,
+    /// so co-located health checkers and gateways don't need the password.
+    /// Disabled by default.
+    pub loopback_bypasses_auth: bool,
+    /// Computes password digests. MD5, as PJLink specifies, if `None`. See
+    /// [PjLinkDigest](crate::PjLinkDigest).
+    pub digest: Option<Arc<dyn PjLinkDigest>>,
+    /// Generates the salts of security headers. A thread-local RNG if
+    /// `None`. Tests can set a seeded RNG to get known salts. See
+    /// [PjLinkSaltSource](crate::PjLinkSaltSource).
+    pub salt_rng: Option<Arc<dyn PjLinkSaltSource>>,
+    /// Time source of session expiry, see [reauth_policy](crate::PjLinkListenerOptions::reauth_policy).
+    /// The system clock if `None`. See [PjLinkClock](crate::PjLinkClock).
+    pub clock: Option<Arc<dyn PjLinkClock>>,
+    /// Limits how long authenticated sessions last before the controller
+    /// must connect and authenticate again. Unlimited by default.
+    pub reauth_policy: PjLinkReauthPolicy,
+    /// Networks allowed to discover this projector. `%2SRCH` requests coming
+    /// from other addresses are ignored. If empty, all networks are allowed.
+    #[cfg(feature = "discovery")]
+    pub search_allowed_networks: Vec<PjLinkIpNetwork>,
+    /// Multicast group the UDP socket joins, so discovery works across
+    /// subnets. Status messages sent to multicast groups use its TTL. Only
+    /// broadcast is used by default. See [PjLinkMulticastGroup](crate::PjLinkMulticastGroup).
+    #[cfg(feature = "discovery")]
+    pub multicast: Option<PjLinkMulticastGroup>,
+    /// MAC address answered to `%2SRCH` requests. Looked up on the system if
+    /// `None`. See [PjLinkMacAddressProvider](crate::PjLinkMacAddressProvider).
+    #[cfg(feature = "discovery")]
+    pub mac_address: Option<Arc<dyn PjLinkMacAddressProvider>>,
+    /// Port `%2ACKN` answers to `%2SRCH` requests are sent to. Defaults to
+    /// the port of the UDP socket, as the specification requires.
+    #[cfg(feature = "discovery")]
+    pub search_response_port: PjLinkSearchResponsePort,
+    /// Socket `%2ACKN` answers are sent from. Defaults to the UDP socket
+    /// search requests are received on.
+    #[cfg(feature = "discovery")]
+    pub search_response_socket: PjLinkSearchResponseSocket,
+    /// Limits on answers to `%2SRCH` requests, per source and overall.
+    /// Unlimited by default.
+    #[cfg(feature = "discovery")]
+    pub search_rate_limit: PjLinkSearchRateLimit,
+    /// Socket options applied to the TCP listener and accepted connections.
+    pub tcp: PjLinkTcpOptions,
+    /// Notified with the duration and response kind of every handled command.
+    pub command_observer: Option<Arc<dyn PjLinkCommandObserver>>,
+    /// Declares the supported PJLink class, as a [PjLinkClassCommandStatus](crate::PjLinkClassCommandStatus)
+    /// value. The listener then answers `%1CLSS ?` with it, unless the handler
+    /// answers something other than `ERR1`.
+    ///
+    /// Declaring [Class1](crate::PjLinkClassCommandStatus::Class1) also answers
+    /// Class 2 commands with `ERR1` without calling the handler, and doesn't
+    /// open the UDP search socket.
+    pub class: Option<u8>,
+    /// Static information answered by the listener, like name and
+    /// manufacturer. See [PjLinkDeviceInfo](crate::PjLinkDeviceInfo).
+    pub device_info: PjLinkDeviceInfo,
+    /// Static capabilities answered and validated by the listener, like
+    /// inputs and class. See [PjLinkProjectorDescriptor](crate::PjLinkProjectorDescriptor).
+    /// Its class is used when [class](crate::PjLinkListenerOptions::class)
+    /// isn't set. Can be replaced while the server runs, see
+    /// [PjLinkReloadableConfig](crate::PjLinkReloadableConfig).
+    pub descriptor: Option<PjLinkProjectorDescriptor>,
+    /// Destinations of status messages sent by [PjLinkListener::notify](crate::PjLinkListener::notify).
+    /// Can be replaced while the server runs, see
+    /// [PjLinkReloadableConfig](crate::PjLinkReloadableConfig).
+    pub notification_targets: Vec<PjLinkNotificationTarget>,
+    /// Closes connections that don't complete a command line within this
+    /// time after its first byte, so clients trickling bytes can't hold a
+    /// connection thread forever. Waiting for the first byte is not limited.
+    /// Unlimited by default.
+    pub frame_timeout: Option<Duration>,
+    /// Whether line feeds and trailing spaces around received lines are
+    /// ignored. Strict by default. See [PjLinkFramingMode](crate::PjLinkFramingMode).
+    pub framing: PjLinkFramingMode,
+    /// Answers commands whose transmission parameter is longer than this
+    /// limit with `ERR2`, without calling the handler. Defaults to the
+    /// specification's 128 bytes. Connections sending much longer lines are
+    /// closed, see [max_frame_length](crate::PjLinkParameterLimit::max_frame_length).
+    /// See [PjLinkParameterLimit](crate::PjLinkParameterLimit).
+    pub parameter_limit: PjLinkParameterLimit,
+    /// Replaces `ERR1` answers of the handler to queries that are mandatory
+    /// in the specification with defaults, logging a warning, so an
+    /// incomplete handler doesn't make the projector non-conformant:
+    /// `%1POWR ?` answers standby, `%1ERST ?` all normal, `%1AVMT ?` mute
+    /// off, `%2FREZ ?` not frozen, `%1CLSS ?` the declared class (or 1),
+    /// and name, information, serial number and software version queries an
+    /// empty value. Disabled by default.
+    pub spec_completion: bool,
+    /// State updated outside PJLink connections. Queries about items it
+    /// knows are answered from it, without calling the handler. See
+    /// [PjLinkProjectorHandle](crate::PjLinkProjectorHandle).
+    pub projector: Option<PjLinkProjectorHandle>,
+    /// Closes connections that don't send a first command within this time
+    /// after the security header, or whose first command fails
+    /// authentication, so port scanners and stalled controllers can't hold
+    /// a connection thread. Unlimited by default.
+    pub handshake_timeout: Option<Duration>,
+    /// Closes connections accepted while this many are already open, right
+    /// after accepting them, so a flood of controllers can't exhaust
+    /// connection threads. Counts PJLink, WebSocket and JSON-RPC
+    /// connections together. Unlimited by default.
+    pub max_connections: Option<usize>,
+    /// Additional commands answered without calling the handler. See
+    /// [PjLinkCommandRegistry](crate::PjLinkCommandRegistry).
+    pub commands: PjLinkCommandRegistry,
+    /// Receives a [PjLinkServerEvent](crate::PjLinkServerEvent) for every
+    /// connection, failed authentication, handled command and answered
+    /// search request.
+    pub event_sender: Option<mpsc::Sender<PjLinkServerEvent>>,
+    /// Answers query commands (`?` parameter, or `?<input>` for INNM)
+    /// instead of the handler, without locking it, so monitoring traffic
+    /// isn't serialized behind control commands. See [PjLinkSplitHandler](crate::PjLinkSplitHandler).
+    ///
+    /// Authentication doesn't lock the handler either, so it isn't told
+    /// about [auth attempts](crate::PjLinkHandler::on_auth_attempt): use
+    /// [event_sender](self::PjLinkListenerOptions::event_sender) instead. Set
+    /// a [password_provider](self::PjLinkListenerOptions::password_provider)
+    /// too, or the handler is still locked for the password of every
+    /// connection.
+    pub query_handler: Option<Arc<dyn PjLinkQueryHandler>>,
+    /// Records every received line and sent response of every connection.
+    /// See [PjLinkSessionCapture](crate::PjLinkSessionCapture).
+    #[cfg(feature = "capture")]
+    pub capture: Option<Arc<PjLinkSessionCapture>>,
+    /// Logs every received line and sent response as a hex and ASCII dump,
+    /// at trace level with target [PJLINK_WIRE_LOG_TARGET](crate::PJLINK_WIRE_LOG_TARGET).
+    /// Helps finding controllers sending almost valid PJLink. Disabled by
+    /// default.
+    pub hex_dump: bool,
+}
+
+impl PjLinkListenerOptions {
+    /// Returns `true` if [class](crate::PjLinkListenerOptions::class), or
+    /// the [descriptor](crate::PjLinkListenerOptions::descriptor) class, is
+    /// declared as [Class1](crate::PjLinkClassCommandStatus::Class1).
+    pub fn is_class_1_only(&self) -> bool {
+        self.declared_class() == Option::Some(PjLinkClassCommandStatus::Class1)
+    }
+
+    fn declared_class(&self) -> Option<u8> {
+        self.declared_class_with(&self.descriptor)
+    }
+
+    /// Same as [declared_class](crate::PjLinkListenerOptions::declared_class),
+    /// with a reloaded descriptor.
+    pub(crate) fn declared_class_with(&self, descriptor: &Option<PjLinkProjectorDescriptor>) -> Option<u8> {
+        self.class.or_else(|| descriptor.as_ref().map(|descriptor| descriptor.class))
+    }
+}
+
+pub struct PjLinkListener<'a> {
+    _nil: &'a bool,
+    pub(crate) shared_handler: PjLinkHandlerShared,
+    shared_connection_counter: Arc<AtomicU64>,
+    pub(crate) shared_options: Arc<PjLinkListenerOptions>,
+    shared_config: Arc<PjLinkConfigState>,
+    pub(crate) tcp_listener: TcpListener,
+    udp_socket: RwLock<Option<Arc<UdpSocket>>>,
+    pub(crate) tcp_health: PjLinkTcpHealthState,
+    pub(crate) udp_health: PjLinkUdpHealthState,
+    #[cfg(feature = "discovery")]
+    pub(crate) search_limiter: PjLinkSearchRateLimiter,
+    pub(crate) shared_stats: Arc<PjLinkStatsState>,
+    pub(crate) shared_status_subscribers: Arc<PjLinkStatusSubscribers>,
+}
+
+pub type PjLinkListenerShared<'a> = Arc<PjLinkListener<'a>>;
+
+impl<'a> PjLinkListener<'a> {
+    pub fn new(
+        shared_handler: PjLinkHandlerShared,
+        tcp_listener: TcpListener,
+        udp_socket: UdpSocket
+    ) -> PjLinkListenerShared<'a> {
+        Self::new_with_options(shared_handler, tcp_listener, Option::Some(udp_socket), PjLinkListenerOptions::default())
+    }
+
+    pub fn new_without_broadcast(
+        shared_handler: Arc<Mutex<dyn PjLinkHandler>>,
+        tcp_listener: TcpListener
+    ) -> PjLinkListenerShared<'a> {
+        Self::new_with_options(shared_handler, tcp_listener, Option::None, PjLinkListenerOptions::default())
+    }
+
+    pub fn new_with_options(
+        shared_handler: PjLinkHandlerShared,
+        tcp_listener: TcpListener,
+        udp_socket: Option<UdpSocket>,
+        options: PjLinkListenerOptions,
+    ) -> PjLinkListenerShared<'a> {
+        Self::new_shared(shared_handler, tcp_listener, udp_socket, Arc::new(options), Arc::new(AtomicU64::new(0)))
+    }
+
+    pub(crate) fn new_shared(
+        shared_handler: PjLinkHandlerShared,
+        tcp_listener: TcpListener,
+        udp_socket: Option<UdpSocket>,
+        shared_options: Arc<PjLinkListenerOptions>,
+        shared_connection_counter: Arc<AtomicU64>,
+    ) -> PjLinkListenerShared<'a> {
+        if let Err(e) = shared_options.tcp.apply_to_listener(&tcp_listener) {
+            warn!("Failed to apply TCP options to listener! {}", e);
+        }
+
+        Arc::new(PjLinkListener {
+            _nil: &false,
+            shared_handler,
+            shared_connection_counter,
+            shared_config: Arc::new(PjLinkConfigState::new(PjLinkReloadableConfig::from_options(&shared_options))),
+            shared_options,
+            tcp_listener,
+            udp_socket: RwLock::new(udp_socket.map(Arc::new)),
+            tcp_health: PjLinkTcpHealthState::default(),
+            udp_health: PjLinkUdpHealthState::default(),
+            #[cfg(feature = "discovery")]
+            search_limiter: PjLinkSearchRateLimiter::default(),
+            shared_stats: Arc::new(PjLinkStatsState::default()),
+            shared_status_subscribers: Arc::new(PjLinkStatusSubscribers::default()),
+        })
+    }
+
+    pub fn listen(&self) {
+        let listener = &self.tcp_listener;
+
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if let Err(e) = self.shared_options.tcp.apply_to_stream(&stream) {
+                        debug!("Failed to apply TCP options to connection! {}", e);
+                    }
+
+                    if let Err(e) = self.connection_handler().spawn_connection(stream) {
+                        warn!("Failed to start connection thread! {}", e);
+                    }
+                },
+                Err(e) => {
+                    debug!("Error on received connection! {}", e);
+                    self.tcp_health.record_error(&e);
+                }
+            }
+        }
+    }
+
+    /// Serves a single PJLink connection over `transport` on the current
+    /// thread, until it's closed. Uses the same handler, options and
+    /// statistics as connections accepted by [listen](crate::PjLinkListener::listen).
+    ///
+    /// **Arguments**:
+    /// * `transport`: Connection to serve. See [PjLinkTransport](crate::PjLinkTransport).
+    pub fn serve_transport<T: PjLinkTransport>(&self, transport: T) {
+        self.connection_handler().handle_connection(transport);
+    }
+
+    pub(crate) fn connection_handler(&self) -> PjLinkConnectionHandler {
+        PjLinkConnectionHandler {
+            handler: self.shared_handler.clone(),
+            shared_connection_counter: self.shared_connection_counter.clone(),
+            options: self.shared_options.clone(),
+            config: self.shared_config.clone(),
+            stats: self.shared_stats.clone(),
+        }
+    }
+
+    /// Sends a Class 2 status message using the listener's UDP socket, or a
+    /// temporary socket if the listener has no UDP socket.
+    pub fn send_status(&self, command: &PjLinkStatusCommand, target: PjLinkNotificationTarget) -> Result<(), PjLinkError> {
+        match self.current_udp_socket() {
+            Some(socket) => {
+                socket.set_broadcast(true)?;
+                #[cfg(feature = "discovery")]
+                if let Some(multicast) = &self.shared_options.multicast {
+                    multicast.set_ttl(&socket)?;
+                }
+                command.send_to(&socket, target)
+            },
+            None => {
+                let socket = UdpSocket::bind("0.0.0.0:0").map_err(|e| PjLinkError::bind("0.0.0.0:0", e))?;
+                socket.set_broadcast(true)?;
+                #[cfg(feature = "discovery")]
+                if let Some(multicast) = &self.shared_options.multicast {
+                    multicast.set_ttl(&socket)?;
+                }
+                command.send_to(&socket, target)
+            }
+        }
+    }
+
+    /// Sends a Class 2 status message to every
+    /// [notification target](crate::PjLinkReloadableConfig::notification_targets),
+    /// like [send_status](crate::PjLinkListener::send_status). Stops at the
+    /// first failing target.
+    ///
+    /// The message is also sent to every receiver returned by
+    /// [subscribe_notifications](crate::PjLinkListener::subscribe_notifications).
+    pub fn notify(&self, command: &PjLinkStatusCommand) -> Result<(), PjLinkError> {
+        self.shared_status_subscribers.publish(command);
+
+        for target in self.shared_config.load().notification_targets.iter() {
+            self.send_status(command, *target)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns a receiver of every status message sent by
+    /// [notify](crate::PjLinkListener::notify) from now on, for gateways
+    /// relaying notifications to their own clients. Dropping the receiver
+    /// unsubscribes.
+    pub fn subscribe_notifications(&self) -> mpsc::Receiver<PjLinkStatusCommand> {
+        self.shared_status_subscribers.subscribe()
+    }
+
+    /// Returns the current reloadable configuration.
+    pub fn config(&self) -> Arc<PjLinkReloadableConfig> {
+        self.shared_config.load()
+    }
+
+    /// Replaces the descriptor, password provider and notification targets
+    /// at once, keeping open connections. See
+    /// [PjLinkReloadableConfig](crate::PjLinkReloadableConfig).
+    pub fn reload(&self, config: PjLinkReloadableConfig) {
+        self.shared_config.store(config);
+    }
+
+    /// Returns connection and traffic statistics of this listener.
+    pub fn stats(&self) -> PjLinkListenerStats {
+        self.shared_stats.snapshot()
+    }
+
+    /// Returns the address the TCP listener is bound to.
+    pub fn local_tcp_addr(&self) -> Result<SocketAddr, PjLinkError> {
+        Ok(self.tcp_listener.local_addr()?)
+    }
+
+    /// Returns the address the UDP socket is bound to, or `None` if this
+    /// listener has no UDP socket.
+    pub fn local_udp_addr(&self) -> Option<SocketAddr> {
+        self.current_udp_socket().and_then(|socket| socket.local_addr().ok())
+    }
+
+    /// Returns the UDP listener health, or `None` if this listener has no
+    /// UDP socket.
+    pub fn udp_health(&self) -> Option<PjLinkUdpHealth> {
+        self.current_udp_socket().map(|_| self.udp_health.snapshot())
+    }
+
+    /// Listens to UDP datagrams (Class 2 search requests).
+    ///
+    /// Receive errors are retried with an exponential backoff; if they
+    /// persist, the socket is bound again on the same address.
+    ///
+    /// Available with the `discovery` feature.
+    #[cfg(feature = "discovery")]
+    pub fn listen_multicast(&self) {
+        if self.shared_options.is_class_1_only() {
+            info!("UDP: Class 1 only, search requests won't be answered");
+            return;
+        }
+
+        let mut socket = match self.current_udp_socket() {
+            Some(socket) => socket,
+            None => return,
+        };
+        let local_addr = match socket.local_addr() {
+            Ok(local_addr) => local_addr,
+            Err(e) => {
+                warn!("UDP: Cannot get socket local address, UDP listener stopped. {}", e);
+                return;
+            }
+        };
+
+        self.udp_health.set_running(true);
+
+        loop {
+            if let Err(e) = socket.set_broadcast(true) {
+                debug!("UDP: Cannot enable broadcast on socket. {}", e);
+            }
+            if let Some(multicast) = &self.shared_options.multicast {
+                match multicast.join(&socket) {
+                    Ok(_) => info!("UDP: Joined multicast group {}", multicast.group),
+                    Err(e) => warn!("UDP: Cannot join multicast group {}. {}", multicast.group, e),
+                }
+            }
+
+            let mut connection_handler = PjLinkConnectionHandler {
+                handler: self.shared_handler.clone(),
+                shared_connection_counter: self.shared_connection_counter.clone(),
+                options: self.shared_options.clone(),
+                config: self.shared_config.clone(),
+                stats: self.shared_stats.clone(),
+            };
+            connection_handler.handle_connection_multicast(&socket, local_addr.port(), &self.udp_health, &self.search_limiter);
+
+            warn!("UDP: Listener is failing persistently, binding socket again on {}", local_addr);
+            self.set_udp_socket(Option::None);
+            drop(socket);
+
+            socket = loop {
+                match UdpSocket::bind(local_addr) {
+                    Ok(new_socket) => {
+                        let new_socket = Arc::new(new_socket);
+                        self.set_udp_socket(Option::Some(new_socket.clone()));
+                        self.udp_health.record_rebind();
+                        info!("UDP: Socket bound again on {}", local_addr);
+                        break new_socket;
+                    }
+                    Err(e) => {
+                        let consecutive_errors = self.udp_health.record_error(&e);
+                        debug!("UDP: Error on binding socket again on {}. {}", local_addr, e);
+                        thread::sleep(udp_error_backoff(consecutive_errors));
+                    }
+                }
+            };
+        }
+    }
+
+    pub(crate) fn current_udp_socket(&self) -> Option<Arc<UdpSocket>> {
+        match self.udp_socket.read() {
+            Ok(udp_socket) => udp_socket.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    #[cfg(feature = "discovery")]
+    fn set_udp_socket(&self, socket: Option<Arc<UdpSocket>>) {
+        match self.udp_socket.write() {
+            Ok(mut udp_socket) => *udp_socket = socket,
+            Err(poisoned) => *poisoned.into_inner() = socket,
+        }
+    }
+}
+
+/// Receivers of [PjLinkListener::notify](crate::PjLinkListener::notify)
+/// status messages.
+#[derive(Default)]
+pub(crate) struct PjLinkStatusSubscribers {
+    senders: Mutex<Vec<(u64, mpsc::Sender<PjLinkStatusCommand>)>>,
+    next_subscriber_id: AtomicU64,
+}
+
+impl PjLinkStatusSubscribers {
+    pub(crate) fn subscribe(&self) -> mpsc::Receiver<PjLinkStatusCommand> {
+        self.subscribe_with_id().1
+    }
+
+    /// Subscribes like [subscribe](self::PjLinkStatusSubscribers::subscribe),
+    /// also returning the ID to [unsubscribe](self::PjLinkStatusSubscribers::unsubscribe) with.
+    pub(crate) fn subscribe_with_id(&self) -> (u64, mpsc::Receiver<PjLinkStatusCommand>) {
+        let subscriber_id = self.next_subscriber_id.fetch_add(1, atomic::Ordering::SeqCst);
+        let (sender, receiver) = mpsc::channel();
+        match self.senders.lock() {
+            Ok(mut senders) => senders.push((subscriber_id, sender)),
+            Err(poisoned) => poisoned.into_inner().push((subscriber_id, sender)),
+        }
+
+        (subscriber_id, receiver)
+    }
+
+    /// Drops the sender of a subscriber right away, instead of on the next
+    /// message, so threads waiting on its receiver stop.
+    #[cfg_attr(not(feature = "jsonrpc"), allow(dead_code))]
+    pub(crate) fn unsubscribe(&self, subscriber_id: u64) {
+        let mut senders = match self.senders.lock() {
+            Ok(senders) => senders,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        senders.retain(|(id, _)| *id != subscriber_id);
+    }
+
+    /// Returns the number of subscribers not dropped yet.
+    #[cfg(all(test, feature = "jsonrpc"))]
+    pub(crate) fn len(&self) -> usize {
+        self.senders.lock().map(|senders| senders.len()).unwrap_or_default()
+    }
+
+    /// Sends `command` to every subscriber, dropping the ones whose
+    /// receiver is gone.
+    pub(crate) fn publish(&self, command: &PjLinkStatusCommand) {
+        let mut senders = match self.senders.lock() {
+            Ok(senders) => senders,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        senders.retain(|(_, sender)| sender.send(command.clone()).is_ok());
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct PjLinkConnectionHandler {
+    pub(crate) handler: Arc<Mutex<dyn PjLinkHandler>>,
+    pub(crate) shared_connection_counter: Arc<AtomicU64>,
+    pub(crate) options: Arc<PjLinkListenerOptions>,
+    pub(crate) config: Arc<PjLinkConfigState>,
+    pub(crate) stats: Arc<PjLinkStatsState>,
+}
+
+impl PjLinkConnectionHandler {
+    /// Returns the listener [clock](crate::PjLinkListenerOptions::clock).
+    pub(crate) fn clock(&self) -> &dyn PjLinkClock {
+        self.options.clock.as_deref().unwrap_or(&PjLinkSystemClock)
+    }
+
+    fn handle_connection<T: PjLinkTransport>(&mut self, stream: T) {
+        let connection_id = self.next_connection_id();
+        self.handle_connection_with_id(stream, connection_id);
+    }
+
+    /// Serves the connection on a new thread named `pjlink-conn-<id>`. The
+    /// connection is dropped if the thread can't be started, or if
+    /// [max_connections](crate::PjLinkListenerOptions::max_connections)
+    /// connections are already open.
+    pub(crate) fn spawn_connection<T: PjLinkTransport + Send + 'static>(&self, stream: T) -> Result<(), PjLinkError> {
+        let Some(permit) = self.try_open_connection(stream.peer_addr()) else {
+            return Ok(());
+        };
+        let mut connection_handler = self.clone();
+        let connection_id = self.next_connection_id();
+
+        spawn_named_thread(format!("pjlink-conn-{}", connection_id), move || {
+            let _permit = permit;
+            connection_handler.handle_connection_with_id(stream, connection_id);
+        })?;
+
+        Ok(())
+    }
+
+    pub(crate) fn handle_connection_with_id<T: PjLinkTransport>(&mut self, mut stream: T, connection_id: u64) {
+        let mut output_buffer = Vec::<u8>::new();
+        let mut session = PjLinkSession::open(self, connection_id, stream.peer_addr(), &mut output_buffer);
+        let log_context = *session.log_context();
+
+        if let Err(e) = Self::send_output(&mut stream, &output_buffer, &session) {
+            debug!("Failed to send security header! {}, {}", log_context, e);
+            return;
+        }
+
+        let mut frame_reader = PjLinkFrameReader::new(
+            self.options.frame_timeout,
+            self.options.parameter_limit.max_frame_length(),
+            self.options.framing,
+        );
+        let mut input_command_buffer = Vec::<u8>::new();
+
+        loop {
+            debug!("Waiting for command! {}", log_context);
+            frame_reader.set_deadline(session.handshake_deadline(self.options.handshake_timeout));
+
+            if let Err(e) = frame_reader.read_frame(&mut stream, &mut input_command_buffer, &log_context) {
+                debug!("Failed to read command! {}, {}", log_context, e);
+                break;
+            }
+
+            output_buffer.clear();
+            let step = session.handle_frame(self, &mut input_command_buffer, &mut output_buffer);
+
+            if let Err(e) = Self::send_output(&mut stream, &output_buffer, &session) {
+                warn!("Failed to write response! {}, {}", log_context, e);
+                break;
+            }
+            if step == PjLinkSessionStep::Close {
+                break;
+            }
+        }
+    }
+
+    fn send_output<T: Write>(stream: &mut T, output_buffer: &[u8], session: &PjLinkSession) -> Result<(), io::Error> {
+        if output_buffer.is_empty() {
+            return Ok(());
+        }
+
+        stream.write_all(output_buffer)?;
+        stream.flush()?;
+        session.record_sent(output_buffer.len());
+
+        Ok(())
+    }
+
+    /// Reserves a slot for a connection from `peer_addr`, unless
+    /// [max_connections](crate::PjLinkListenerOptions::max_connections)
+    /// connections are already open.
+    pub(crate) fn try_open_connection(&self, peer_addr: Option<SocketAddr>) -> Option<PjLinkConnectionPermit> {
+        let permit = self.stats.try_open_connection(self.options.max_connections);
+        if permit.is_none() {
+            warn!("Refusing connection from {:?}, too many open connections!", peer_addr);
+        }
+
+        permit
+    }
+
+    pub(crate) fn next_connection_id(&self) -> u64 {
+        self.shared_connection_counter.fetch_add(1, atomic::Ordering::SeqCst)
+    }
+
+    /// Handles UDP datagrams until receive errors persist for
+    /// `PJLINK_UDP_REBIND_AFTER_ERRORS` consecutive times.
+    #[cfg(feature = "discovery")]
+    fn handle_connection_multicast(
+        &mut self,
+        stream: &UdpSocket,
+        port: u16,
+        health: &PjLinkUdpHealthState,
+        search_limiter: &PjLinkSearchRateLimiter,
+    ) {
+        let mut input_command_buffer = [0u8; PJLINK_MAX_BROADCAST_BUFFER_SIZE];
+
+        'message: loop{
+            match stream.recv_from(&mut input_command_buffer) {
+                Ok((length, origin)) => {
+                    health.record_success();
+                    self.handle_datagram(&input_command_buffer[..length], origin, port, health, search_limiter, |response, destination| {
+                        stream.send_to(response, destination)
+                    });
+                }
+                Err(e) if e.kind() == io::ErrorKind::ConnectionReset => {
+                    // Windows reports ICMP port unreachable from previous sends as
+                    // a receive error, the socket is still usable.
+                    debug!("UDP message handling failed: {}", e);
+                    continue 'message;
+                }
+                Err(e) => {
+                    let consecutive_errors = health.record_error(&e);
+                    debug!("UDP message handling failed: {}. ConsecutiveErrors: {}", e, consecutive_errors);
+
+                    if consecutive_errors >= PJLINK_UDP_REBIND_AFTER_ERRORS {
+                        return;
+                    }
+
+                    thread::sleep(udp_error_backoff(consecutive_errors));
+                    continue 'message;
+                }
+            }
+        }
+    }
+
+    /// Answers a received UDP datagram, if it's a valid search request from
+    /// an allowed origin, within the search rate limit. Other datagrams are
+    /// counted in `health`.
+    ///
+    /// `send_to` sends answers from the listener socket, unless
+    /// [search_response_socket](crate::PjLinkListenerOptions::search_response_socket)
+    /// asks for an ephemeral one.
+    #[cfg(feature = "discovery")]
+    pub(crate) fn handle_datagram<F>(
+        &self,
+        datagram: &[u8],
+        origin: SocketAddr,
+        port: u16,
+        health: &PjLinkUdpHealthState,
+        search_limiter: &PjLinkSearchRateLimiter,
+        send_to: F,
+    ) where
+        F: FnOnce(&[u8], SocketAddr) -> io::Result<usize>,
+    {
+        trace!("UDP message received! Origin: {}, RawMessage: {:?}", origin, datagram);
+
+        let validation = validate_search_datagram(datagram, &origin).and_then(|_| {
+            match self.is_search_allowed(&origin) {
+                true => Ok(()),
+                false => Err(PjLinkDatagramRejection::OriginNotAllowed),
+            }
+        }).and_then(|_| {
+            match search_limiter.try_answer(&self.options.search_rate_limit, origin.ip()) {
+                true => Ok(()),
+                false => Err(PjLinkDatagramRejection::RateLimited),
+            }
+        });
+        if let Err(reason) = validation {
+            debug!("UDP message rejected! Origin: {}, Reason: {}, ParsedMessage: {:?}", origin, reason, String::from_utf8_lossy(datagram));
+            health.record_rejected();
+            send_event(&self.options.event_sender, PjLinkServerEvent::UdpDatagramRejected { origin, reason });
+            return;
+        }
+
+        debug!("UDP: 2SRCH received! Origin: {}", origin);
+
+        let mac_address_provider = self.options.mac_address.as_deref().or_else(default_mac_address_provider);
+        let mac_address = match mac_address_provider.and_then(|provider| provider.mac_address()) {
+            Some(mac_address) => mac_address,
+            None => {
+                debug!("UDP: 2SRCH: Cannot infer MAC Address, sending null");
+                [0u8; 6]
+            }
+        };
+
+        let output_buffer = encode_search_response(mac_address);
+        let destination = SocketAddr::new(origin.ip(), self.options.search_response_port.resolve(&origin, port));
+        debug!("UDP: Will send response to: {}", destination);
+
+        let sent = match self.options.search_response_socket {
+            PjLinkSearchResponseSocket::Listener => send_to(&output_buffer, destination),
+            PjLinkSearchResponseSocket::Ephemeral => Self::send_from_ephemeral_socket(&output_buffer, destination),
+        };
+        match sent {
+            Ok(_) => debug!("UDP message sent! ParsedMessage: {:?}", String::from_utf8_lossy(&output_buffer)),
+            Err(e) => debug!("UDP: Error on sending datagram message to remote host. {}", e),
+        }
+        send_event(&self.options.event_sender, PjLinkServerEvent::UdpSearchAnswered { origin });
+    }
+
+    /// Returns the response the listener sends by itself, without calling
+    /// the handler, if options require one for this command.
+    pub(crate) fn builtin_response(&self, raw_command: &PjLinkRawPayload, connection_id: &u64) -> Option<PjLinkResponse> {
+        if self.options.parameter_limit.is_exceeded_by(&raw_command.transmission_parameter) {
+            debug!(
+                "Transmission parameter too long, answering ERR2! ConnectionId: {}, Length: {}",
+                connection_id,
+                raw_command.transmission_parameter.len(),
+            );
+            return Option::Some(PjLinkResponse::OutOfParameter);
+        }
+
+        let config = self.config.load();
+        let declared_class = self.options.declared_class_with(&config.descriptor);
+
+        if declared_class == Option::Some(PjLinkClassCommandStatus::Class1) && raw_command.command_body_with_class[0] == b'2' {
+            return Option::Some(PjLinkResponse::Undefined);
+        }
+
+        self.options.commands.response_to(raw_command, connection_id)
+            .or_else(|| self.options.device_info.response_to(raw_command))
+            .or_else(|| config.descriptor.as_ref().and_then(|descriptor| descriptor.response_to(raw_command)))
+            .or_else(|| self.options.projector.as_ref().and_then(|projector| projector.response_to(raw_command)))
+    }
+
+    /// Replaces `ERR1` answers of the handler to `%1CLSS ?` with the
+    /// declared class, if any, and to other mandatory queries with defaults
+    /// if [spec_completion](crate::PjLinkListenerOptions::spec_completion)
+    /// is enabled.
+    pub(crate) fn fallback_response(&self, raw_command: &PjLinkRawPayload, response: PjLinkResponse) -> PjLinkResponse {
+        if response != PjLinkResponse::Undefined || !raw_command.is_query() {
+            return response;
+        }
+
+        let declared_class = self.options.declared_class_with(&self.config.load().descriptor);
+        let default_response = match (&raw_command.command_body_with_class, declared_class) {
+            (b"1CLSS", Some(class)) => return PjLinkResponse::Single(class),
+            _ if !self.options.spec_completion => return response,
+            (b"1CLSS", None) => PjLinkResponse::Single(PjLinkClassCommandStatus::Class1),
+            (b"1POWR", _) => PjLinkResponse::Single(PjLinkPowerCommandStatus::Off),
+            (b"1ERST", _) => PjLinkResponse::Multiple(b"000000".to_vec()),
+            (b"1AVMT", _) => PjLinkResponse::Multiple(b"30".to_vec()),
+            (b"2FREZ", _) => PjLinkResponse::Single(b'0'),
+            (b"1NAME" | b"1INF1" | b"1INF2" | b"1INFO" | b"2SNUM" | b"2SVER", _) => PjLinkResponse::Empty,
+            _ => return response,
+        };
+
+        warn!(
+            "Handler answered ERR1 to a mandatory command, answering a default instead! CmdBodyWithClass: {}, Response: {}",
+            String::from_utf8_lossy(&raw_command.command_body_with_class),
+            String::from_utf8_lossy(default_response.transmission_parameter()),
+        );
+        default_response
+    }
+
+    #[cfg(feature = "discovery")]
+    fn is_search_allowed(&self, message_origin: &SocketAddr) -> bool {
+        let allowed_networks = &self.options.search_allowed_networks;
+        allowed_networks.is_empty()
+            || allowed_networks.iter().any(|network| network.contains(&message_origin.ip()))
+    }
+
+    #[cfg(feature = "discovery")]
+    fn send_from_ephemeral_socket(output_buffer: &[u8], destination: SocketAddr) -> io::Result<usize> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.send_to(output_buffer, destination)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::thread;
+    use crate::{PjLinkCommand, PjLinkInvalidFrameAction, PjLinkInvalidFrameContext, PjLinkMemoryTransport, PjLinkResponseKind, PJLINK_MAX_PARAMETER_LENGTH};
+
+    #[allow(dead_code)]
+    struct PjLinkMockHandler {
+        handle_command_fn: fn(PjLinkCommand, &PjLinkRawPayload) -> PjLinkResponse,
+        get_password_fn: fn() -> Option<String>
+    }
+
+    impl PjLinkHandler for PjLinkMockHandler {
+        fn handle_command(&mut self, command: PjLinkCommand, raw_command: &PjLinkRawPayload, _connection_id: &u64) -> PjLinkResponse {
+            (self.handle_command_fn)(command, raw_command)
+        }
+
+        fn get_password(&mut self, _connection_id: &u64) -> Option<String> {
+            (self.get_password_fn)()
+        }
+    }
+
+    fn _simple_mock_handler() -> PjLinkHandlerShared {
+        Arc::new(Mutex::new(PjLinkMockHandler {
+            handle_command_fn: |_command, _raw_command| PjLinkResponse::OutOfParameter,
+            get_password_fn: || Option::None
+        }))
+    }
+
+    #[test]
+    fn it_answers_class_commands_in_class_1_only_mode() {
+        let handler = Arc::new(Mutex::new(PjLinkMockHandler {
+            handle_command_fn: |command, _raw_command| match command {
+                PjLinkCommand::Class1 => PjLinkResponse::Undefined,
+                _ => PjLinkResponse::Ok,
+            },
+            get_password_fn: || Option::None
+        }));
+        let mut options = PjLinkListenerOptions { class: Some(PjLinkClassCommandStatus::Class1), ..Default::default() };
+        options.commands.register(*b"2TEST", |_parameter| Some(()), |_, _connection_id| PjLinkResponse::Ok);
+        let (mut client, server) = PjLinkMemoryTransport::pair();
+        thread::spawn(move || PjLinkServer::serve_transport_with_options(handler, server, options));
+
+        client.write_all(b"%1CLSS ?\r%2SVOL 1\r%2TEST 1\r%1POWR 1\r").unwrap();
+        let expected = b"PJLINK 0\r%1CLSS=1\r%2SVOL=ERR1\r%2TEST=ERR1\r%1POWR=OK\r";
+        let mut response = [0u8; 52];
+        client.read_exact(&mut response).unwrap();
+        assert_eq!(&response, expected);
+    }
+
+    #[test]
+    fn it_answers_class_query_unless_handler_does() {
+        let serve = |handle_command_fn: fn(PjLinkCommand, &PjLinkRawPayload) -> PjLinkResponse| {
+            let handler = Arc::new(Mutex::new(PjLinkMockHandler { handle_command_fn, get_password_fn: || Option::None }));
+            let options = PjLinkListenerOptions { class: Some(PjLinkClassCommandStatus::Class2), ..Default::default() };
+            let (mut client, server) = PjLinkMemoryTransport::pair();
+            thread::spawn(move || PjLinkServer::serve_transport_with_options(handler, server, options));
+
+            client.write_all(b"%1CLSS ?\r").unwrap();
+            let mut response = [0u8; 18];
+            client.read_exact(&mut response).unwrap();
+            response
+        };
+
+        assert_eq!(&serve(|_command, _raw_command| PjLinkResponse::Undefined), b"PJLINK 0\r%1CLSS=2\r");
+        assert_eq!(&serve(|_command, _raw_command| PjLinkResponse::Single(b'1')), b"PJLINK 0\r%1CLSS=1\r");
+    }
+
+    #[test]
+    fn it_answers_invalid_class_with_err1() {
+        let (mut client, server) = PjLinkMemoryTransport::pair();
+        let handler = Arc::new(Mutex::new(PjLinkMockHandler {
+            handle_command_fn: |_command, _raw_command| PjLinkResponse::Ok,
+            get_password_fn: || Option::None
+        }));
+        thread::spawn(move || PjLinkServer::serve_transport(handler, server));
+
+        client.write_all(b"%3POWR 1\r%1POWR 1\r").unwrap();
+        let expected = b"PJLINK 0\r%3POWR=ERR1\r%1POWR=OK\r";
+        let mut response = [0u8; 31];
+        client.read_exact(&mut response).unwrap();
+        assert_eq!(&response, expected);
+    }
+
+    #[test]
+    fn it_ignores_line_feeds_and_trailing_spaces_in_lenient_mode() {
+        let (mut client, server) = PjLinkMemoryTransport::pair();
+        let handler = Arc::new(Mutex::new(PjLinkMockHandler {
+            handle_command_fn: |_command, raw_command| PjLinkResponse::Multiple(raw_command.transmission_parameter.clone()),
+            get_password_fn: || Option::None
+        }));
+        let options = PjLinkListenerOptions { framing: PjLinkFramingMode::Lenient, ..Default::default() };
+        thread::spawn(move || PjLinkServer::serve_transport_with_options(handler, server, options));
+
+        client.write_all(b"%1INPT 31\r\n%1INPT 32  \r\n\r\n%1INPT 33\r").unwrap();
+        let expected = b"PJLINK 0\r%1INPT=31\r%1INPT=32\r%1INPT=33\r";
+        let mut response = [0u8; 39];
+        client.read_exact(&mut response).unwrap();
+        assert_eq!(&response, expected);
+    }
+
+    #[test]
+    fn it_completes_mandatory_queries_the_handler_leaves_undefined() {
+        let (mut client, server) = PjLinkMemoryTransport::pair();
+        let handler = Arc::new(Mutex::new(PjLinkMockHandler {
+            handle_command_fn: |_command, _raw_command| PjLinkResponse::Undefined,
+            get_password_fn: || Option::None
+        }));
+        let options = PjLinkListenerOptions { spec_completion: true, ..Default::default() };
+        thread::spawn(move || PjLinkServer::serve_transport_with_options(handler, server, options));
+
+        client.write_all(b"%1POWR ?\r%1ERST ?\r%1CLSS ?\r%1NAME ?\r%1POWR 1\r%1INPT ?\r").unwrap();
+        let expected = b"PJLINK 0\r%1POWR=0\r%1ERST=000000\r%1CLSS=1\r%1NAME=\r%1POWR=ERR1\r%1INPT=ERR1\r";
+        let mut response = [0u8; 73];
+        client.read_exact(&mut response).unwrap();
+        assert_eq!(&response, expected);
+    }
+
+    #[test]
+    fn it_answers_oversized_parameters_with_err2() {
+        let (mut client, server) = PjLinkMemoryTransport::pair();
+        let handler = Arc::new(Mutex::new(PjLinkMockHandler {
+            handle_command_fn: |_command, _raw_command| PjLinkResponse::Ok,
+            get_password_fn: || Option::None
+        }));
+        thread::spawn(move || PjLinkServer::serve_transport(handler, server));
+
+        let mut commands = format!("%2XNAM {}\r", "a".repeat(PJLINK_MAX_PARAMETER_LENGTH + 1)).into_bytes();
+        commands.extend_from_slice(format!("%2XNAM {}\r", "a".repeat(PJLINK_MAX_PARAMETER_LENGTH)).as_bytes());
+        client.write_all(&commands).unwrap();
+        let expected = b"PJLINK 0\r%2XNAM=ERR2\r%2XNAM=OK\r";
+        let mut response = [0u8; 31];
+        client.read_exact(&mut response).unwrap();
+        assert_eq!(&response, expected);
+    }
+
+    #[test]
+    fn it_lets_handler_respond_to_invalid_frames() {
+        struct GarbageHandler;
+
+        impl PjLinkHandler for GarbageHandler {
+            fn get_password(&mut self, _connection_id: &u64) -> Option<String> {
+                Option::None
+            }
+
+            fn handle_command(&mut self, _command: PjLinkCommand, _raw_command: &PjLinkRawPayload, _connection_id: &u64) -> PjLinkResponse {
+                PjLinkResponse::Ok
+            }
+
+            fn handle_invalid(&mut self, raw: &[u8], context: &PjLinkInvalidFrameContext) -> PjLinkInvalidFrameAction {
+                match (raw, context.error) {
+                    (b"hello", PjLinkError::Parse { .. }) => PjLinkInvalidFrameAction::Respond(b"%1POWR=ERR3".to_vec()),
+                    _ => PjLinkInvalidFrameAction::Ignore,
+                }
+            }
+        }
+
+        let (mut client, server) = PjLinkMemoryTransport::pair();
+        thread::spawn(move || PjLinkServer::serve_transport(Arc::new(Mutex::new(GarbageHandler)), server));
+
+        client.write_all(b"hello\r%1POWR:1\r%1POWR 1\r").unwrap();
+        let expected = b"PJLINK 0\r%1POWR=ERR3\r%1POWR=OK\r";
+        let mut response = [0u8; 31];
+        client.read_exact(&mut response).unwrap();
+        assert_eq!(&response, expected);
+    }
+
+    #[test]
+    fn it_passes_vendor_commands_to_handle_unknown() {
+        struct VendorHandler;
+
+        impl PjLinkHandler for VendorHandler {
+            fn get_password(&mut self, _connection_id: &u64) -> Option<String> {
+                Option::None
+            }
+
+            fn handle_command(&mut self, _command: PjLinkCommand, _raw_command: &PjLinkRawPayload, _connection_id: &u64) -> PjLinkResponse {
+                PjLinkResponse::Undefined
+            }
+
+            fn handle_unknown(&mut self, raw_command: &PjLinkRawPayload, _connection_id: &u64) -> Option<PjLinkResponse> {
+                match &raw_command.command_body_with_class {
+                    b"1LENS" => Option::Some(PjLinkResponse::from(raw_command.transmission_parameter.clone())),
+                    _ => Option::None,
+                }
+            }
+        }
+
+        let (mut client, server) = PjLinkMemoryTransport::pair();
+        thread::spawn(move || PjLinkServer::serve_transport(Arc::new(Mutex::new(VendorHandler)), server));
+
+        client.write_all(b"%1LENS 42\r%1ZOOM ?\r").unwrap();
+        let expected = b"PJLINK 0\r%1LENS=42\r%1ZOOM=ERR1\r";
+        let mut response = [0u8; 31];
+        client.read_exact(&mut response).unwrap();
+        assert_eq!(&response, expected);
+    }
+
+    #[test]
+    fn it_sends_lifecycle_events() {
+        let handler = Arc::new(Mutex::new(PjLinkMockHandler {
+            handle_command_fn: |_command, _raw_command| PjLinkResponse::Ok,
+            get_password_fn: || Option::None
+        }));
+        let (event_sender, events) = mpsc::channel();
+        let options = PjLinkListenerOptions { event_sender: Some(event_sender), ..Default::default() };
+        let (mut client, server) = PjLinkMemoryTransport::pair();
+        thread::spawn(move || PjLinkServer::serve_transport_with_options(handler, server, options));
+
+        client.write_all(b"%1POWR 1\r").unwrap();
+        let mut response = [0u8; 19];
+        client.read_exact(&mut response).unwrap();
+        drop(client);
+
+        let events: Vec<PjLinkServerEvent> = events.iter().collect();
+        assert_eq!(events, vec![
+            PjLinkServerEvent::ConnectionOpened { connection_id: 0, peer_addr: None },
+            PjLinkServerEvent::CommandHandled {
+                connection_id: 0,
+                command_body_with_class: *b"1POWR",
+                response_kind: PjLinkResponseKind::Ok,
+            },
+            PjLinkServerEvent::ConnectionClosed { connection_id: 0, peer_addr: None },
+        ]);
+    }
+
+    #[test]
+    fn it_answers_err3_while_the_handler_is_poisoned() {
+        let handler = Arc::new(Mutex::new(PjLinkMockHandler {
+            handle_command_fn: |_command, _raw_command| PjLinkResponse::Ok,
+            get_password_fn: || Option::None
+        }));
+        let handler_clone = handler.clone();
+        let _ = thread::spawn(move || {
+            let _handler = handler_clone.lock().unwrap();
+            panic!("handler failure");
+        }).join();
+
+        let (event_sender, events) = mpsc::channel();
+        let options = PjLinkListenerOptions { event_sender: Some(event_sender), ..Default::default() };
+        let (mut client, server) = PjLinkMemoryTransport::pair();
+        thread::spawn(move || PjLinkServer::serve_transport_with_options(handler, server, options));
+
+        client.write_all(b"%1POWR 1\r").unwrap();
+        let mut response = [0u8; 21];
+        client.read_exact(&mut response).unwrap();
+        assert_eq!(&response, b"PJLINK 0\r%1POWR=ERR3\r");
+        drop(client);
+
+        assert!(events.iter().any(|event| event == PjLinkServerEvent::HandlerPoisoned { connection_id: 0 }));
+    }
+
+    #[test]
+    #[cfg(feature = "discovery")]
+    fn it_answers_searches_from_the_listener_socket() {
+        let handler = Arc::new(Mutex::new(PjLinkMockHandler {
+            handle_command_fn: |_command, _raw_command| PjLinkResponse::Ok,
+            get_password_fn: || Option::None
+        }));
+        let udp_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let udp_address = udp_socket.local_addr().unwrap();
+        let options = PjLinkListenerOptions {
+            search_response_port: PjLinkSearchResponsePort::Origin,
+            mac_address: Some(Arc::new([0x00, 0x1a, 0x2b, 0x3c, 0x4d, 0x5e])),
+            ..Default::default()
+        };
+        let listener = PjLinkListener::new_with_options(handler, TcpListener::bind("127.0.0.1:0").unwrap(), Some(udp_socket), options);
+        thread::spawn(move || listener.listen_multicast());
+
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        client.send_to(b"%2SRCH\r", udp_address).unwrap();
+
+        let mut response = [0u8; PJLINK_MAX_BROADCAST_BUFFER_SIZE];
+        let (length, response_origin) = client.recv_from(&mut response).unwrap();
+        assert_eq!(response_origin, udp_address);
+        assert_eq!(&response[..length], b"%2ACKN=00:1A:2B:3C:4D:5E\r");
+    }
+
+    #[test]
+    fn it_names_connection_threads() {
+        let handler = Arc::new(Mutex::new(PjLinkMockHandler {
+            handle_command_fn: |_command, _raw_command| match thread::current().name() {
+                Some(name) if name.starts_with("pjlink-conn-") => PjLinkResponse::Ok,
+                _ => PjLinkResponse::Undefined,
+            },
+            get_password_fn: || Option::None
+        }));
+        let tcp_listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = tcp_listener.local_addr().unwrap();
+        let listener = PjLinkListener::new_without_broadcast(handler, tcp_listener);
+        thread::spawn(move || listener.listen());
+
+        let mut client = std::net::TcpStream::connect(address).unwrap();
+        client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        client.write_all(b"%1POWR 1\r").unwrap();
+        let mut response = [0u8; 19];
+        client.read_exact(&mut response).unwrap();
+        assert_eq!(&response, b"PJLINK 0\r%1POWR=OK\r");
+    }
+}
\ No newline at end of file
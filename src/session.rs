@@ -0,0 +1,504 @@
+//! Protocol state of a single PJLink connection, independent of how its
+//! bytes are read and written.
+
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+use log::{debug, log_enabled, trace, warn, Level};
+
+use crate::{
+    PjLinkAuthAttempt, PjLinkAuthOutcome, PjLinkCaptureDirection, PjLinkCommand, PjLinkCommandObserver, PjLinkCommandTiming, PjLinkConnectionHandler, PjLinkDigest, PjLinkLogContext,
+    PjLinkError, PjLinkFramingMode, PjLinkInvalidFrameAction, PjLinkInvalidFrameContext, PjLinkPasswordProvider, PjLinkRawPayload, PjLinkRawPayloadRef, PjLinkResponse, PjLinkResponseKind, PjLinkServerEvent, encode_response_into, format_hex_dump, PJLINK_HEADER, PJLINK_WIRE_LOG_TARGET, PJLINK_TERMINATOR,
+};
+use crate::protocol::{PJLINK_NULLIFIED_SECURITY, PJLINK_SECURITY, PJLINK_SECURITY_ERRA};
+use crate::digest::default_digest;
+use crate::events::send_event;
+use crate::salt::next_salt;
+use crate::stats::PjLinkConnectionStatsGuard;
+
+/// What to do with a connection after a frame is handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PjLinkSessionStep {
+    /// Send the output, then wait for the next frame.
+    Continue,
+    /// Send the output, if any, then close the connection.
+    Close,
+}
+
+/// Authentication and command handling of one connection.
+///
+/// The session only produces bytes to send, so the same logic serves
+/// blocking transports and the event loop.
+pub(crate) struct PjLinkSession {
+    connection_id: u64,
+    peer_addr: Option<SocketAddr>,
+    log_context: PjLinkLogContext,
+    stats: PjLinkConnectionStatsGuard,
+    password: Option<String>,
+    read_only_password: Option<String>,
+    is_read_only: bool,
+    password_salt: Option<String>,
+    use_auth: bool,
+    has_authenticated: bool,
+    opened_at: Instant,
+    is_handshake_complete: bool,
+    authenticated_at: Option<Instant>,
+    authenticated_commands: u64,
+    session_generation: Option<u64>,
+    password_provider: Option<Arc<dyn PjLinkPasswordProvider>>,
+    raw_command: PjLinkRawPayload,
+    event_sender: Option<Sender<PjLinkServerEvent>>,
+    command_observer: Option<Arc<dyn PjLinkCommandObserver>>,
+}
+
+impl PjLinkSession {
+    /// Starts a session, appending the security header to `output`.
+    pub(crate) fn open(
+        connection: &PjLinkConnectionHandler,
+        connection_id: u64,
+        peer_addr: Option<SocketAddr>,
+        output: &mut Vec<u8>,
+    ) -> PjLinkSession {
+        let password_provider = connection.config.load().password_provider.clone();
+        let mut session = PjLinkSession {
+            connection_id,
+            peer_addr,
+            log_context: PjLinkLogContext::new(connection_id, peer_addr),
+            stats: connection.stats.register(connection_id, peer_addr),
+            password: Option::None,
+            read_only_password: Option::None,
+            is_read_only: false,
+            password_salt: Option::None,
+            use_auth: false,
+            has_authenticated: false,
+            opened_at: Instant::now(),
+            is_handshake_complete: false,
+            authenticated_at: Option::None,
+            authenticated_commands: 0,
+            session_generation: password_provider.as_ref().map(|provider| provider.session_generation()),
+            password_provider: password_provider.clone(),
+            raw_command: PjLinkRawPayload::new_command(Default::default(), Vec::new()),
+            event_sender: connection.options.event_sender.clone(),
+            command_observer: connection.options.command_observer.clone(),
+        };
+
+        let is_auth_bypassed = connection.options.loopback_bypasses_auth && peer_addr.is_some_and(|peer_addr| is_loopback(&peer_addr));
+
+        session.password = match &password_provider {
+            _ if is_auth_bypassed => Option::None,
+            Some(provider) => provider.get_password_for_peer(&connection_id, peer_addr.as_ref()).map(String::from),
+            // A poisoned handler is only asked for the password, so
+            // controllers still get a security header, and ERR3 answers
+            // afterwards
+            None => match connection.handler.lock() {
+                Ok(mut handler) => handler.get_password(&connection_id),
+                Err(poisoned) => poisoned.into_inner().get_password(&connection_id),
+            },
+        };
+        session.read_only_password = password_provider.as_ref()
+            .filter(|_| !is_auth_bypassed)
+            .and_then(|provider| provider.get_read_only_password_for_peer(&connection_id, peer_addr.as_ref()))
+            .map(String::from);
+        session.write_security_header(connection, output);
+        session.record_wire(connection, PjLinkCaptureDirection::Sent, output);
+        send_event(&session.event_sender, PjLinkServerEvent::ConnectionOpened { connection_id, peer_addr });
+        if let Some(command_observer) = &session.command_observer {
+            command_observer.on_connection_opened(connection_id, peer_addr);
+        }
+
+        session
+    }
+
+    pub(crate) fn log_context(&self) -> &PjLinkLogContext {
+        &self.log_context
+    }
+
+    /// Returns when the connection must be closed if no frame gets past
+    /// authentication before, given the listener
+    /// [handshake_timeout](crate::PjLinkListenerOptions::handshake_timeout).
+    /// Returns `None` once the handshake is complete.
+    pub(crate) fn handshake_deadline(&self, handshake_timeout: Option<Duration>) -> Option<Instant> {
+        match self.is_handshake_complete {
+            true => Option::None,
+            false => handshake_timeout.map(|handshake_timeout| self.opened_at + handshake_timeout),
+        }
+    }
+
+    /// Records bytes written to the connection.
+    pub(crate) fn record_sent(&self, bytes: usize) {
+        self.stats.record_sent(bytes);
+    }
+
+    /// Handles a received frame, without terminator, appending the bytes to
+    /// send to `output`.
+    pub(crate) fn handle_frame(
+        &mut self,
+        connection: &PjLinkConnectionHandler,
+        frame: &mut Vec<u8>,
+        output: &mut Vec<u8>,
+    ) -> PjLinkSessionStep {
+        let output_start = output.len();
+        self.record_wire(connection, PjLinkCaptureDirection::Received, frame);
+
+        let step = self.handle_frame_uncaptured(connection, frame, output);
+        if output.len() > output_start {
+            self.record_wire(connection, PjLinkCaptureDirection::Sent, &output[output_start..]);
+        }
+
+        step
+    }
+
+    /// Records `data` to the listener capture and hex dump log, if enabled.
+    fn record_wire(&self, connection: &PjLinkConnectionHandler, direction: PjLinkCaptureDirection, data: &[u8]) {
+        #[cfg(feature = "capture")]
+        if let Some(capture) = &connection.options.capture {
+            capture.record(self.connection_id, self.peer_addr, direction, data);
+        }
+
+        if connection.options.hex_dump && log_enabled!(target: PJLINK_WIRE_LOG_TARGET, Level::Trace) {
+            let direction = match direction {
+                PjLinkCaptureDirection::Received => "Received",
+                PjLinkCaptureDirection::Sent => "Sent",
+            };
+            trace!(
+                target: PJLINK_WIRE_LOG_TARGET,
+                "{} {} bytes. {}\n{}",
+                direction,
+                data.len(),
+                self.log_context,
+                format_hex_dump(data),
+            );
+        }
+    }
+
+    fn handle_frame_uncaptured(
+        &mut self,
+        connection: &PjLinkConnectionHandler,
+        frame: &mut Vec<u8>,
+        output: &mut Vec<u8>,
+    ) -> PjLinkSessionStep {
+        let log_context = self.log_context;
+        self.stats.record_received(frame.len() + 1);
+
+        if connection.options.framing == PjLinkFramingMode::Lenient {
+            connection.options.framing.trim_in_place(frame);
+            if frame.is_empty() {
+                return PjLinkSessionStep::Continue;
+            }
+        }
+
+        if let (Some(provider), Some(generation)) = (&self.password_provider, self.session_generation) {
+            if provider.session_generation() != generation {
+                debug!("Password changed, terminating session! {}", log_context);
+                return PjLinkSessionStep::Close;
+            }
+        }
+
+        if let Some(authenticated_at) = self.authenticated_at {
+            let now = connection.clock().now();
+            if connection.options.reauth_policy.is_expired_at(authenticated_at, self.authenticated_commands, now) {
+                debug!("Session expired, closing to force re-authentication! {}", log_context);
+                return PjLinkSessionStep::Close;
+            }
+        }
+
+        if self.use_auth && (!self.has_authenticated || frame.first() != Option::Some(&PJLINK_HEADER)) {
+            let digest = connection.options.digest.as_deref().or_else(default_digest);
+            if let Some(auth_outcome) = self.check_password_hash(digest, frame, output) {
+                if !self.record_auth_outcome(connection, auth_outcome) {
+                    return PjLinkSessionStep::Close;
+                }
+            }
+        }
+
+        self.is_handshake_complete = true;
+
+        let raw_command_ref = match PjLinkRawPayloadRef::from_buffer_with_context(frame, &log_context) {
+            Ok(raw_command_ref) => raw_command_ref,
+            Err(e) => return self.handle_invalid_frame(connection, frame, e, output),
+        };
+        raw_command_ref.copy_into(&mut self.raw_command);
+        let command = PjLinkCommand::from_raw_payload_ref(&raw_command_ref);
+        let raw_command = &self.raw_command;
+        let command_body = String::from_utf8_lossy(&raw_command.command_body_with_class);
+
+        let mut handle_started_at = Instant::now();
+        let is_unknown = matches!(command, PjLinkCommand::Unknown);
+        let query_handler = connection.options.query_handler.as_ref().filter(|_| raw_command.is_query_command());
+
+        let response = match query_handler {
+            // Vendor, registered and malformed commands parse as unknown, so
+            // anything but a query is refused
+            _ if self.is_read_only && !raw_command.is_query_command() => {
+                debug!("Set command on read-only session, answering ERR2! {}, CmdBodyWithClass: {}", log_context, command_body);
+                PjLinkResponse::OutOfParameter
+            }
+            // Queries are answered without locking the handler
+            Some(query_handler) => match connection.builtin_response(raw_command, &self.connection_id) {
+                Some(response) => response,
+                None => connection.fallback_response(raw_command, query_handler.handle_query(command, raw_command, &self.connection_id)),
+            },
+            None => match connection.handler.lock() {
+                Ok(mut handler) => {
+                    handle_started_at = Instant::now();
+                    match connection.builtin_response(raw_command, &self.connection_id) {
+                        Some(response) => response,
+                        None => {
+                            let vendor_response = match command {
+                                PjLinkCommand::Unknown if !PjLinkCommand::is_standard_command_body(&raw_command.command_body_with_class) => {
+                                    handler.handle_unknown(raw_command, &self.connection_id)
+                                }
+                                _ => Option::None,
+                            };
+                            let response = match vendor_response {
+                                Some(response) => response,
+                                None => handler.handle_command(command, raw_command, &self.connection_id),
+                            };
+                            connection.fallback_response(raw_command, response)
+                        }
+                    }
+                }
+                // A previous handler call panicked, its state can't be trusted
+                Err(_) => {
+                    warn!("Handler lock poisoned, answering ERR3! {}, CmdBodyWithClass: {}", log_context, command_body);
+                    send_event(&self.event_sender, PjLinkServerEvent::HandlerPoisoned { connection_id: self.connection_id });
+                    PjLinkResponse::UnavailableTime
+                }
+            },
+        };
+        self.stats.record_command();
+        self.stats.record_response(raw_command.command_body_with_class, &response, is_unknown);
+
+        if let Some(command_observer) = &connection.options.command_observer {
+            command_observer.on_command_handled(&PjLinkCommandTiming {
+                connection_id: self.connection_id,
+                command_body_with_class: raw_command.command_body_with_class,
+                duration: handle_started_at.elapsed(),
+                response_kind: PjLinkResponseKind::from(&response),
+            });
+        }
+
+        send_event(&self.event_sender, PjLinkServerEvent::CommandHandled {
+            connection_id: self.connection_id,
+            command_body_with_class: raw_command.command_body_with_class,
+            response_kind: PjLinkResponseKind::from(&response),
+        });
+
+        debug!("Sending response. {}, CmdBodyWithClass: {}, TxParam: {}", log_context, command_body, response);
+        encode_response_into(output, &raw_command.command_body_with_class, &response);
+        if self.authenticated_at.is_some() {
+            self.authenticated_commands += 1;
+        }
+
+        PjLinkSessionStep::Continue
+    }
+
+    /// Returns `true` while the session waits for a password.
+    #[cfg(any(feature = "grpc", feature = "jsonrpc"))]
+    pub(crate) fn is_auth_pending(&self) -> bool {
+        self.use_auth && !self.has_authenticated
+    }
+
+    /// Authenticates with a plain password, for gateways whose clients send
+    /// it in their own protocol instead of hashing it into the first frame.
+    /// The handler and events see the attempt like a PJLink one.
+    #[cfg(any(feature = "grpc", feature = "jsonrpc"))]
+    pub(crate) fn authenticate(&mut self, connection: &PjLinkConnectionHandler, password: &str) -> PjLinkAuthOutcome {
+        let matches = |expected: &Option<String>| expected.as_deref()
+            .is_some_and(|expected| constant_time_eq(expected.as_bytes(), password.as_bytes()));
+        let auth_outcome = if matches(&self.password) {
+            PjLinkAuthOutcome::Accepted
+        } else if matches(&self.read_only_password) {
+            PjLinkAuthOutcome::AcceptedReadOnly
+        } else {
+            PjLinkAuthOutcome::Denied
+        };
+        debug!("Gateway password {:?}! {}", auth_outcome, self.log_context);
+
+        self.record_auth_outcome(connection, auth_outcome);
+        self.is_handshake_complete = true;
+        auth_outcome
+    }
+
+    /// Tells the handler about an authentication attempt and applies its
+    /// outcome. Returns `false` if the session must be closed.
+    fn record_auth_outcome(&mut self, connection: &PjLinkConnectionHandler, auth_outcome: PjLinkAuthOutcome) -> bool {
+        let auth_attempt = PjLinkAuthAttempt {
+            connection_id: self.connection_id,
+            peer_addr: self.peer_addr,
+            outcome: auth_outcome,
+        };
+
+        // With a query handler, authenticating doesn't wait for the handler
+        // lock either
+        if connection.options.query_handler.is_none() {
+            if let Ok(mut handler) = connection.handler.lock() {
+                handler.on_auth_attempt(&auth_attempt);
+            }
+        }
+
+        if auth_outcome.is_accepted() {
+            self.has_authenticated = true;
+            self.is_read_only = auth_outcome == PjLinkAuthOutcome::AcceptedReadOnly;
+            self.authenticated_at = Option::Some(connection.clock().now());
+            true
+        } else {
+            send_event(&self.event_sender, PjLinkServerEvent::AuthFailed {
+                connection_id: self.connection_id,
+                peer_addr: self.peer_addr,
+                outcome: auth_outcome,
+            });
+            false
+        }
+    }
+
+    /// Lets the handler decide what to do with a frame that failed parsing.
+    fn handle_invalid_frame(
+        &self,
+        connection: &PjLinkConnectionHandler,
+        frame: &[u8],
+        error: PjLinkError,
+        output: &mut Vec<u8>,
+    ) -> PjLinkSessionStep {
+        debug!("Received invalid command! {}, {}", self.log_context, error);
+        let context = PjLinkInvalidFrameContext {
+            connection_id: self.connection_id,
+            peer_addr: self.peer_addr,
+            error: &error,
+        };
+        let action = match connection.handler.lock() {
+            Ok(mut handler) => handler.handle_invalid(frame, &context),
+            Err(_) => PjLinkInvalidFrameAction::Default,
+        };
+
+        match (action, &error) {
+            (PjLinkInvalidFrameAction::Default, PjLinkError::InvalidClass { line, .. }) => {
+                let mut command_body_with_class: [u8; 5] = Default::default();
+                command_body_with_class.copy_from_slice(&line[1..6]);
+                encode_response_into(output, &command_body_with_class, &PjLinkResponse::Undefined);
+                PjLinkSessionStep::Continue
+            }
+            (PjLinkInvalidFrameAction::Default, _) | (PjLinkInvalidFrameAction::Close, _) => PjLinkSessionStep::Close,
+            (PjLinkInvalidFrameAction::Ignore, _) => PjLinkSessionStep::Continue,
+            (PjLinkInvalidFrameAction::Respond(line), _) => {
+                output.extend_from_slice(&line);
+                output.push(PJLINK_TERMINATOR);
+                PjLinkSessionStep::Continue
+            }
+        }
+    }
+
+    /// Writes the security header, requiring authentication if the session
+    /// has a password or a read-only one.
+    fn write_security_header(&mut self, connection: &PjLinkConnectionHandler, output: &mut Vec<u8>) {
+        if self.password.is_none() && self.read_only_password.is_none() {
+            debug!("PJLink Security: nullified; {}", self.log_context);
+            output.extend(PJLINK_NULLIFIED_SECURITY);
+        } else {
+            let string_salt = format!("{:08X}", next_salt(connection.options.salt_rng.as_deref()));
+            output.extend(PJLINK_SECURITY);
+            output.extend(string_salt.as_bytes());
+            output.push(PJLINK_TERMINATOR);
+            debug!(
+                "PJLink Security: password; {}, Response: {}",
+                self.log_context,
+                String::from_utf8_lossy(output)
+            );
+            self.password_salt = Option::Some(string_salt);
+            self.use_auth = true;
+        }
+    }
+
+    /// Checks the password hash prefixing the first command, and removes it
+    /// from `frame`. Appends `PJLINK ERRA` to `output` if it's wrong, or if
+    /// there's no `digest` to check it with.
+    fn check_password_hash(&self, digest: Option<&dyn PjLinkDigest>, frame: &mut Vec<u8>, output: &mut Vec<u8>) -> Option<PjLinkAuthOutcome> {
+        let log_context = &self.log_context;
+        let mut auth_outcome = Option::None;
+
+        if !self.has_authenticated {
+            if frame.len() > 32 {
+                let input_password_hash = &frame[0..32];
+
+                debug!(
+                    "Received password hash! {}, Hash: {}",
+                    log_context,
+                    String::from_utf8_lossy(input_password_hash)
+                );
+
+                let digest = match digest {
+                    Some(digest) => digest,
+                    None => {
+                        warn!("No password digest available, enable the md5 feature or set one! {}", log_context);
+                        output.extend(PJLINK_SECURITY_ERRA);
+                        return Option::Some(PjLinkAuthOutcome::Denied);
+                    }
+                };
+
+                if self.password.is_some()
+                    && constant_time_eq(self.password_hash(digest, self.password.as_deref()).as_bytes(), input_password_hash) {
+                    debug!("Password accepted! {}", log_context);
+                    auth_outcome = Option::Some(PjLinkAuthOutcome::Accepted);
+                } else if self.read_only_password.is_some()
+                    && constant_time_eq(self.password_hash(digest, self.read_only_password.as_deref()).as_bytes(), input_password_hash) {
+                    debug!("Read-only password accepted! {}", log_context);
+                    auth_outcome = Option::Some(PjLinkAuthOutcome::AcceptedReadOnly);
+                } else {
+                    debug!("Password denied! {}", log_context);
+                    auth_outcome = Option::Some(PjLinkAuthOutcome::Denied);
+                }
+            } else {
+                debug!("Password denied (command is too short)! {}", log_context);
+                auth_outcome = Option::Some(PjLinkAuthOutcome::Malformed);
+            }
+
+            if !auth_outcome.is_some_and(|auth_outcome| auth_outcome.is_accepted()) {
+                output.extend(PJLINK_SECURITY_ERRA);
+                return auth_outcome;
+            }
+        }
+
+        if frame.len() > 32 {
+            frame.drain(0..32);
+        }
+
+        auth_outcome
+    }
+
+    /// Returns the digest controllers send for `password` in this session.
+    fn password_hash(&self, digest: &dyn PjLinkDigest, password: Option<&str>) -> String {
+        let mut internal_password_string = self.password_salt.clone()
+            .unwrap_or_default();
+        internal_password_string.push_str(password.unwrap_or_default());
+
+        digest.hex_digest(internal_password_string.as_bytes())
+    }
+}
+
+/// Compares passwords or digests in time depending only on their length,
+/// so response times don't tell how many leading bytes were right.
+fn constant_time_eq(expected: &[u8], received: &[u8]) -> bool {
+    expected.len() == received.len()
+        && expected.iter().zip(received).fold(0u8, |difference, (expected, received)| difference | (expected ^ received)) == 0
+}
+
+/// Returns `true` for loopback addresses, including IPv4-mapped IPv6 ones.
+fn is_loopback(peer_addr: &SocketAddr) -> bool {
+    match peer_addr.ip() {
+        IpAddr::V4(address) => address.is_loopback(),
+        IpAddr::V6(address) => address.is_loopback() || address.to_ipv4_mapped().is_some_and(|address| address.is_loopback()),
+    }
+}
+
+impl Drop for PjLinkSession {
+    fn drop(&mut self) {
+        send_event(&self.event_sender, PjLinkServerEvent::ConnectionClosed {
+            connection_id: self.connection_id,
+            peer_addr: self.peer_addr,
+        });
+        if let Some(command_observer) = &self.command_observer {
+            command_observer.on_connection_closed(self.connection_id);
+        }
+    }
+}
@@ -0,0 +1,337 @@
+//! SNMP traps on error status changes.
+
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::Mutex;
+use std::time::Instant;
+use log::{debug, warn};
+
+use crate::{PjLinkError, PjLinkErrorStatusCommandStatusItem};
+
+/// Default trap OID, under the NET-SNMP experimental arc
+/// (`netSnmpPlaypen`, 1.3.6.1.4.1.8072.9999). Deployments with their own
+/// enterprise number should use it instead.
+pub const PJLINK_SNMP_DEFAULT_TRAP_OID: &[u32] = &[1, 3, 6, 1, 4, 1, 8072, 9999, 4352];
+
+/// `sysUpTime.0`
+const SNMP_SYS_UP_TIME_OID: &[u32] = &[1, 3, 6, 1, 2, 1, 1, 3, 0];
+/// `snmpTrapOID.0`
+const SNMP_TRAP_OID_OID: &[u32] = &[1, 3, 6, 1, 6, 3, 1, 1, 4, 1, 0];
+
+const BER_INTEGER: u8 = 0x02;
+const BER_OCTET_STRING: u8 = 0x04;
+const BER_OBJECT_IDENTIFIER: u8 = 0x06;
+const BER_SEQUENCE: u8 = 0x30;
+const BER_TIME_TICKS: u8 = 0x43;
+const SNMP_V2_TRAP_PDU: u8 = 0xa7;
+const SNMP_VERSION_2C: i64 = 1;
+
+/// Sends SNMPv2c traps when an error status item (`%1ERST`) gets worse, for
+/// network management systems that don't understand PJLink notifications.
+///
+/// Handlers update it whenever their error status changes, like
+/// [PjLinkStateTracker::set_error_status](crate::PjLinkStateTracker::set_error_status).
+/// A trap is sent when any item goes from normal to warning or error, or
+/// from warning to error. Items recovering don't send traps. Error status
+/// starts as all normal.
+///
+/// Traps have the `snmpTrapOID.0` set to the trap OID, and these variables:
+/// * `<trap OID>.1.1` to `<trap OID>.1.6`: fan, lamp, temperature, cover open,
+///   filter and other status, as integers (0 normal, 1 warning, 2 error)
+/// * `<trap OID>.2`: the `%1ERST` response, like `"020000"`
+///
+/// ## Examples
+/// ```no_run
+/// use pjlink_bridge::*;
+///
+/// let traps = PjLinkSnmpTrapSender::new(
+///     vec!["192.168.0.5:162".parse().unwrap()],
+///     "public",
+///     PJLINK_SNMP_DEFAULT_TRAP_OID,
+/// ).unwrap();
+///
+/// // Sends a trap: lamp is now in error
+/// traps.set_error_status(*b"020000").unwrap();
+/// ```
+#[derive(Debug)]
+pub struct PjLinkSnmpTrapSender {
+    /// Sends to IPv4 destinations, if any
+    ipv4_socket: Option<UdpSocket>,
+    /// Sends to IPv6 destinations, if any
+    ipv6_socket: Option<UdpSocket>,
+    destinations: Vec<SocketAddr>,
+    community: String,
+    trap_oid: Vec<u32>,
+    started_at: Instant,
+    state: Mutex<PjLinkSnmpTrapState>,
+}
+
+#[derive(Debug)]
+struct PjLinkSnmpTrapState {
+    error_status: [u8; 6],
+    next_request_id: i32,
+}
+
+impl PjLinkSnmpTrapSender {
+    /// Creates a sender bound to an ephemeral UDP port, for each address
+    /// family of `destinations`.
+    ///
+    /// Fails with [Io](crate::PjLinkError::Io) if `trap_oid` isn't a valid
+    /// object identifier: at least two arcs, the first one `0`, `1` or `2`,
+    /// and the second one below `40` unless the first one is `2`.
+    ///
+    /// **Arguments**:
+    /// * `destinations`: Trap receivers, usually on port 162
+    /// * `community`: SNMPv2c community
+    /// * `trap_oid`: Identifies the traps. See [PJLINK_SNMP_DEFAULT_TRAP_OID](self::PJLINK_SNMP_DEFAULT_TRAP_OID).
+    pub fn new(destinations: Vec<SocketAddr>, community: &str, trap_oid: &[u32]) -> Result<PjLinkSnmpTrapSender, PjLinkError> {
+        validate_oid(trap_oid)?;
+        let bind = |is_ipv6: bool, address: &str| match destinations.iter().any(|destination| destination.is_ipv6() == is_ipv6) {
+            true => UdpSocket::bind(address).map(Option::Some).map_err(|e| PjLinkError::bind(address, e)),
+            false => Ok(Option::None),
+        };
+        let ipv4_socket = bind(false, "0.0.0.0:0")?;
+        let ipv6_socket = bind(true, "[::]:0")?;
+
+        Ok(PjLinkSnmpTrapSender {
+            ipv4_socket,
+            ipv6_socket,
+            destinations,
+            community: community.to_string(),
+            trap_oid: trap_oid.to_vec(),
+            started_at: Instant::now(),
+            state: Mutex::new(PjLinkSnmpTrapState {
+                error_status: [PjLinkErrorStatusCommandStatusItem::Normal; 6],
+                next_request_id: 1,
+            }),
+        })
+    }
+
+    /// Updates error status, in the same order as
+    /// [PjLinkCommand::ErrorStatus1](crate::PjLinkCommand::ErrorStatus1)
+    /// response, sending a trap if any item got worse. Returns `true` if a
+    /// trap was sent.
+    ///
+    /// The trap is sent to every destination even if sending to one of them
+    /// fails; the first failure is returned afterwards.
+    pub fn set_error_status(&self, error_status: [u8; 6]) -> Result<bool, PjLinkError> {
+        let request_id = {
+            let mut state = match self.state.lock() {
+                Ok(state) => state,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            let has_worsened = error_status.iter().zip(state.error_status.iter())
+                .any(|(current, previous)| severity(*current) > severity(*previous));
+            state.error_status = error_status;
+
+            if !has_worsened {
+                return Ok(false);
+            }
+
+            let request_id = state.next_request_id;
+            state.next_request_id = request_id.wrapping_add(1).max(1);
+            request_id
+        };
+
+        let trap = self.encode_trap(request_id, &error_status);
+        let mut first_error = Option::None;
+        for destination in self.destinations.iter() {
+            debug!("SNMP: sending trap to {}, ErrorStatus: {}", destination, String::from_utf8_lossy(&error_status));
+            let socket = match destination.is_ipv6() {
+                true => self.ipv6_socket.as_ref(),
+                false => self.ipv4_socket.as_ref(),
+            };
+            // Both sockets are bound in new for the families of destinations
+            if let Some(Err(e)) = socket.map(|socket| socket.send_to(&trap, destination)) {
+                warn!("SNMP: failed to send trap to {}! {}", destination, e);
+                first_error.get_or_insert(e);
+            }
+        }
+
+        match first_error {
+            Some(e) => Err(PjLinkError::Io(e)),
+            None => Ok(true),
+        }
+    }
+
+    fn encode_trap(&self, request_id: i32, error_status: &[u8; 6]) -> Vec<u8> {
+        let up_time = (self.started_at.elapsed().as_millis() / 10) as u32;
+
+        let mut variables = Vec::new();
+        variables.extend(encode_variable(SNMP_SYS_UP_TIME_OID, &encode_unsigned(BER_TIME_TICKS, up_time)));
+        variables.extend(encode_variable(SNMP_TRAP_OID_OID, &encode_oid(&self.trap_oid)));
+        for (index, item) in error_status.iter().enumerate() {
+            let mut item_oid = self.trap_oid.clone();
+            item_oid.extend([1, index as u32 + 1]);
+            variables.extend(encode_variable(&item_oid, &encode_integer(i64::from(severity(*item)))));
+        }
+        let mut status_oid = self.trap_oid.clone();
+        status_oid.push(2);
+        variables.extend(encode_variable(&status_oid, &encode_tlv(BER_OCTET_STRING, error_status)));
+
+        let mut pdu = encode_integer(i64::from(request_id));
+        pdu.extend(encode_integer(0));
+        pdu.extend(encode_integer(0));
+        pdu.extend(encode_tlv(BER_SEQUENCE, &variables));
+
+        let mut message = encode_integer(SNMP_VERSION_2C);
+        message.extend(encode_tlv(BER_OCTET_STRING, self.community.as_bytes()));
+        message.extend(encode_tlv(SNMP_V2_TRAP_PDU, &pdu));
+
+        encode_tlv(BER_SEQUENCE, &message)
+    }
+}
+
+/// Returns 0 for normal, 1 for warning and 2 for error items. Unknown values
+/// are read as normal.
+fn severity(item: u8) -> u8 {
+    match item {
+        PjLinkErrorStatusCommandStatusItem::Warning => 1,
+        PjLinkErrorStatusCommandStatusItem::Error => 2,
+        _ => 0,
+    }
+}
+
+/// Checks that `oid` can be encoded: BER packs its first two arcs in one.
+fn validate_oid(oid: &[u32]) -> Result<(), PjLinkError> {
+    let reason = match oid {
+        [first, second, ..] if *first < 2 && *second >= 40 => Option::Some("second arc must be below 40"),
+        [2, second, ..] if second.checked_add(80).is_none() => Option::Some("second arc is too large"),
+        [first, _, ..] if *first > 2 => Option::Some("first arc must be 0, 1 or 2"),
+        [_, _, ..] => Option::None,
+        _ => Option::Some("at least two arcs are required"),
+    };
+
+    match reason {
+        Some(reason) => Err(PjLinkError::Io(io::Error::new(io::ErrorKind::InvalidInput, format!("invalid SNMP trap OID: {}", reason)))),
+        None => Ok(()),
+    }
+}
+
+fn encode_tlv(tag: u8, value: &[u8]) -> Vec<u8> {
+    let mut encoded = vec![tag];
+    let length = value.len();
+
+    if length < 0x80 {
+        encoded.push(length as u8);
+    } else {
+        let length_bytes: Vec<u8> = length.to_be_bytes().iter().copied().skip_while(|byte| *byte == 0).collect();
+        encoded.push(0x80 | length_bytes.len() as u8);
+        encoded.extend(length_bytes);
+    }
+
+    encoded.extend_from_slice(value);
+    encoded
+}
+
+fn encode_integer(value: i64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let mut start = 0;
+
+    // Drops leading bytes that only repeat the sign bit
+    while start < bytes.len() - 1
+        && ((bytes[start] == 0x00 && bytes[start + 1] & 0x80 == 0) || (bytes[start] == 0xff && bytes[start + 1] & 0x80 != 0)) {
+        start += 1;
+    }
+
+    encode_tlv(BER_INTEGER, &bytes[start..])
+}
+
+fn encode_unsigned(tag: u8, value: u32) -> Vec<u8> {
+    let mut encoded = encode_integer(i64::from(value));
+    encoded[0] = tag;
+    encoded
+}
+
+fn encode_oid(oid: &[u32]) -> Vec<u8> {
+    let mut value = Vec::new();
+
+    let (first, rest) = match oid {
+        [first, second, rest @ ..] => (first * 40 + second, rest),
+        [first] => (first * 40, &[][..]),
+        [] => (0, &[][..]),
+    };
+    for arc in std::iter::once(&first).chain(rest.iter()) {
+        let mut arc_bytes = vec![(*arc & 0x7f) as u8];
+        let mut remaining = *arc >> 7;
+        while remaining > 0 {
+            arc_bytes.push((remaining & 0x7f) as u8 | 0x80);
+            remaining >>= 7;
+        }
+        value.extend(arc_bytes.iter().rev());
+    }
+
+    encode_tlv(BER_OBJECT_IDENTIFIER, &value)
+}
+
+fn encode_variable(oid: &[u32], value: &[u8]) -> Vec<u8> {
+    let mut variable = encode_oid(oid);
+    variable.extend_from_slice(value);
+    encode_tlv(BER_SEQUENCE, &variable)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn it_sends_traps_when_errors_get_worse() {
+        assert_eq!(encode_integer(0), vec![0x02, 0x01, 0x00]);
+        assert_eq!(encode_integer(128), vec![0x02, 0x02, 0x00, 0x80]);
+        assert_eq!(encode_integer(-1), vec![0x02, 0x01, 0xff]);
+        assert_eq!(encode_oid(&[1, 3, 6, 1, 4, 1, 8072]), vec![0x06, 0x07, 0x2b, 0x06, 0x01, 0x04, 0x01, 0xbf, 0x08]);
+        assert_eq!(encode_tlv(0x04, &[0u8; 200])[..3], [0x04, 0x81, 200]);
+
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let traps = PjLinkSnmpTrapSender::new(vec![receiver.local_addr().unwrap()], "public", PJLINK_SNMP_DEFAULT_TRAP_OID).unwrap();
+
+        assert!(traps.set_error_status(*b"010000").unwrap());
+        assert!(!traps.set_error_status(*b"010000").unwrap());
+        assert!(!traps.set_error_status(*b"000000").unwrap());
+        assert!(traps.set_error_status(*b"000002").unwrap());
+
+        let mut buffer = [0u8; 512];
+        let length = receiver.recv(&mut buffer).unwrap();
+        let trap = &buffer[..length];
+        assert_eq!(trap[..3], [BER_SEQUENCE, 0x81, length as u8 - 3]);
+        assert_eq!(&trap[3..6], &encode_integer(SNMP_VERSION_2C)[..]);
+        assert_eq!(&trap[6..14], &encode_tlv(BER_OCTET_STRING, b"public")[..]);
+        assert_eq!(trap[14], SNMP_V2_TRAP_PDU);
+        assert!(trap.ends_with(&encode_tlv(BER_OCTET_STRING, b"010000")));
+    }
+
+    #[test]
+    fn it_sends_traps_to_every_destination_and_family() {
+        let ipv4_receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let ipv6_receiver = match UdpSocket::bind("[::1]:0") {
+            Ok(ipv6_receiver) => ipv6_receiver,
+            // No IPv6 loopback in this environment
+            Err(_) => return,
+        };
+        ipv4_receiver.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        ipv6_receiver.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        // Unreachable with an IPv4 socket, tried before the others
+        let broadcast = "255.255.255.255:162".parse().unwrap();
+        let traps = PjLinkSnmpTrapSender::new(
+            vec![broadcast, ipv6_receiver.local_addr().unwrap(), ipv4_receiver.local_addr().unwrap()],
+            "public",
+            PJLINK_SNMP_DEFAULT_TRAP_OID,
+        ).unwrap();
+
+        assert!(traps.set_error_status(*b"200000").is_err());
+        let mut buffer = [0u8; 512];
+        assert!(ipv6_receiver.recv(&mut buffer).unwrap() > 0);
+        assert!(ipv4_receiver.recv(&mut buffer).unwrap() > 0);
+    }
+
+    #[test]
+    fn it_refuses_trap_oids_that_cannot_be_encoded() {
+        assert!(PjLinkSnmpTrapSender::new(vec![], "public", &[1]).is_err());
+        assert!(PjLinkSnmpTrapSender::new(vec![], "public", &[3, 1]).is_err());
+        assert!(PjLinkSnmpTrapSender::new(vec![], "public", &[1, 40]).is_err());
+        assert!(PjLinkSnmpTrapSender::new(vec![], "public", &[2, u32::MAX]).is_err());
+        assert!(PjLinkSnmpTrapSender::new(vec![], "public", &[2, 999, 1]).is_ok());
+    }
+}
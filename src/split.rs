@@ -0,0 +1,179 @@
+//! Handlers split between queries and control commands.
+
+use std::sync::Arc;
+
+use crate::{PjLinkCommand, PjLinkHandler, PjLinkRawPayload, PjLinkResponse};
+
+/// Answers query commands, like `%1POWR ?` or `%2INNM ?11`, from shared state.
+///
+/// Takes `&self`, so it can be called from every connection at once. Set it
+/// as [PjLinkListenerOptions::query_handler](crate::PjLinkListenerOptions::query_handler)
+/// to answer queries without locking the handler.
+pub trait PjLinkQueryHandler: Send + Sync {
+    fn handle_query(&self, command: PjLinkCommand, raw_command: &PjLinkRawPayload, connection_id: &u64) -> PjLinkResponse;
+}
+
+/// Handles commands that aren't queries, like `%1POWR 1`, one at a time.
+pub trait PjLinkControlHandler: Send {
+    fn get_password(&mut self, connection_id: &u64) -> Option<String>;
+    fn handle_control(&mut self, command: PjLinkCommand, raw_command: &PjLinkRawPayload, connection_id: &u64) -> PjLinkResponse;
+}
+
+/// [PjLinkHandler](crate::PjLinkHandler) passing queries to a
+/// [PjLinkQueryHandler](self::PjLinkQueryHandler), and other commands to a
+/// [PjLinkControlHandler](self::PjLinkControlHandler).
+///
+/// Also setting the query handler as
+/// [PjLinkListenerOptions::query_handler](crate::PjLinkListenerOptions::query_handler)
+/// answers queries without locking this handler, so a slow control command
+/// doesn't delay monitoring controllers.
+///
+/// ## Examples
+#[cfg_attr(feature = "server", doc = "```")]
+#[cfg_attr(not(feature = "server"), doc = "```ignore")]
+/// use std::sync::{Arc, Mutex};
+/// use std::sync::atomic::{AtomicU8, Ordering};
+/// use pjlink_bridge::*;
+///
+/// struct PowerState(AtomicU8);
+///
+/// impl PjLinkQueryHandler for PowerState {
+///     fn handle_query(&self, command: PjLinkCommand, _raw_command: &PjLinkRawPayload, _connection_id: &u64) -> PjLinkResponse {
+///         match command {
+///             PjLinkCommand::Power1(_) => PjLinkResponse::Single(self.0.load(Ordering::SeqCst)),
+///             _ => PjLinkResponse::Undefined,
+///         }
+///     }
+/// }
+///
+/// struct PowerControl(Arc<PowerState>);
+///
+/// impl PjLinkControlHandler for PowerControl {
+///     fn get_password(&mut self, _connection_id: &u64) -> Option<String> {
+///         None
+///     }
+///
+///     fn handle_control(&mut self, command: PjLinkCommand, _raw_command: &PjLinkRawPayload, _connection_id: &u64) -> PjLinkResponse {
+///         match command {
+///             PjLinkCommand::Power1(PjLinkPowerCommandParameter::On) => {
+///                 (self.0).0.store(PjLinkPowerCommandStatus::On, Ordering::SeqCst);
+///                 PjLinkResponse::Ok
+///             }
+///             _ => PjLinkResponse::Undefined,
+///         }
+///     }
+/// }
+///
+/// let state = Arc::new(PowerState(AtomicU8::new(PjLinkPowerCommandStatus::Off)));
+/// let handler = PjLinkSplitHandler::new(state.clone(), PowerControl(state.clone()));
+/// let options = PjLinkListenerOptions {
+///     query_handler: Some(handler.query_handler()),
+///     ..Default::default()
+/// };
+/// let shared_handler: PjLinkHandlerShared = Arc::new(Mutex::new(handler));
+/// ```
+pub struct PjLinkSplitHandler<Q: PjLinkQueryHandler + 'static, C: PjLinkControlHandler> {
+    query_handler: Arc<Q>,
+    control_handler: C,
+}
+
+impl<Q: PjLinkQueryHandler + 'static, C: PjLinkControlHandler> PjLinkSplitHandler<Q, C> {
+    pub fn new(query_handler: Arc<Q>, control_handler: C) -> PjLinkSplitHandler<Q, C> {
+        PjLinkSplitHandler { query_handler, control_handler }
+    }
+
+    /// Returns the query handler, to set as
+    /// [PjLinkListenerOptions::query_handler](crate::PjLinkListenerOptions::query_handler).
+    pub fn query_handler(&self) -> Arc<dyn PjLinkQueryHandler> {
+        self.query_handler.clone()
+    }
+
+    /// Returns the control handler.
+    pub fn control_handler(&mut self) -> &mut C {
+        &mut self.control_handler
+    }
+}
+
+impl<Q: PjLinkQueryHandler + 'static, C: PjLinkControlHandler> PjLinkHandler for PjLinkSplitHandler<Q, C> {
+    fn get_password(&mut self, connection_id: &u64) -> Option<String> {
+        self.control_handler.get_password(connection_id)
+    }
+
+    fn handle_command(&mut self, command: PjLinkCommand, raw_command: &PjLinkRawPayload, connection_id: &u64) -> PjLinkResponse {
+        match raw_command.is_query_command() {
+            true => self.query_handler.handle_query(command, raw_command, connection_id),
+            false => self.control_handler.handle_control(command, raw_command, connection_id),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::sync::{mpsc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+    use crate::{PjLinkListenerOptions, PjLinkMemoryTransport, PjLinkPassword, PjLinkServer, PjLinkSwappablePassword, PJLINK_QUERY};
+
+    struct Status;
+
+    impl PjLinkQueryHandler for Status {
+        fn handle_query(&self, _command: PjLinkCommand, _raw_command: &PjLinkRawPayload, _connection_id: &u64) -> PjLinkResponse {
+            PjLinkResponse::Single(b'1')
+        }
+    }
+
+    struct Control;
+
+    impl PjLinkControlHandler for Control {
+        fn get_password(&mut self, _connection_id: &u64) -> Option<String> {
+            Option::None
+        }
+
+        fn handle_control(&mut self, _command: PjLinkCommand, _raw_command: &PjLinkRawPayload, _connection_id: &u64) -> PjLinkResponse {
+            PjLinkResponse::Ok
+        }
+    }
+
+    #[test]
+    fn it_answers_queries_without_locking_the_handler() {
+        let mut handler = PjLinkSplitHandler::new(Arc::new(Status), Control);
+        let query = PjLinkRawPayload::new_command(*b"1POWR", vec![PJLINK_QUERY]);
+        let control = PjLinkRawPayload::new_command(*b"1POWR", vec![b'1']);
+        let input_name_query = PjLinkRawPayload::new_command(*b"2INNM", b"?11".to_vec());
+        assert_eq!(handler.handle_command(PjLinkCommand::Unknown, &query, &0), PjLinkResponse::Single(b'1'));
+        assert_eq!(handler.handle_command(PjLinkCommand::Unknown, &input_name_query, &0), PjLinkResponse::Single(b'1'));
+        assert_eq!(handler.handle_command(PjLinkCommand::Unknown, &control, &0), PjLinkResponse::Ok);
+
+        let options = PjLinkListenerOptions {
+            query_handler: Some(handler.query_handler()),
+            password_provider: Some(Arc::new(PjLinkSwappablePassword::new(Some(PjLinkPassword::new("secret").unwrap())))),
+            ..Default::default()
+        };
+        let shared_handler = Arc::new(Mutex::new(handler));
+        let locked_handler = shared_handler.clone();
+
+        // Opening and authenticating the connection doesn't lock the
+        // handler either
+        let (response_sender, responses) = mpsc::channel();
+        let _guard = locked_handler.lock().unwrap();
+        let (mut client, server) = PjLinkMemoryTransport::pair();
+        thread::spawn(move || PjLinkServer::serve_transport_with_options(shared_handler, server, options));
+        thread::spawn(move || {
+            let mut security_header = [0u8; 18];
+            client.read_exact(&mut security_header).unwrap();
+            let mut salted_password = security_header[9..17].to_vec();
+            salted_password.extend_from_slice(b"secret");
+
+            let mut command = format!("{:x}", md5::compute(salted_password)).into_bytes();
+            command.extend_from_slice(b"%1POWR ?\r%2INNM ?11\r");
+            client.write_all(&command).unwrap();
+            let mut response = [0u8; 18];
+            client.read_exact(&mut response).unwrap();
+            response_sender.send(response).unwrap();
+        });
+
+        assert_eq!(&responses.recv_timeout(Duration::from_secs(5)).unwrap(), b"%1POWR=1\r%2INNM=1\r");
+    }
+}
@@ -0,0 +1,101 @@
+//! Snapshot of projector state.
+
+use crate::{PjLinkAvMuteState, PjLinkStatusCommand};
+
+/// Snapshot of the projector state reported to controllers. Items that
+/// aren't known are `None`.
+///
+/// [diff](self::PjLinkProjectorState::diff) returns the Class 2 status
+/// notifications announcing the changes between two snapshots. PJLink only
+/// defines notifications for power, input and error status; mute and freeze
+/// are kept for dashboards and other observers.
+///
+/// ## Examples
+/// ```
+/// use pjlink_bridge::*;
+///
+/// let before = PjLinkProjectorState {
+///     power: Some(PjLinkPowerCommandStatus::Off),
+///     input: Some(*b"31"),
+///     ..Default::default()
+/// };
+/// let after = PjLinkProjectorState {
+///     power: Some(PjLinkPowerCommandStatus::WarmUp),
+///     ..before
+/// };
+///
+/// assert_eq!(after.diff(&before), vec![PjLinkStatusCommand::Power2(PjLinkPowerCommandStatus::WarmUp)]);
+/// assert!(before.diff(&before).is_empty());
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PjLinkProjectorState {
+    /// Power status, as a [PjLinkPowerCommandStatus](crate::PjLinkPowerCommandStatus) value
+    pub power: Option<u8>,
+    /// Selected input, as type and number, like `*b"31"`
+    pub input: Option<[u8; 2]>,
+    /// Audio and video mute
+    pub mute: Option<PjLinkAvMuteState>,
+    /// Error status, in the same order as the
+    /// [PjLinkCommand::ErrorStatus1](crate::PjLinkCommand::ErrorStatus1) response
+    pub error_status: Option<[u8; 6]>,
+    /// Whether the image is frozen
+    pub freeze: Option<bool>,
+}
+
+impl PjLinkProjectorState {
+    /// Returns the notifications announcing items of `self` that differ from
+    /// `previous`. Unknown items of `self` are never notified.
+    pub fn diff(&self, previous: &PjLinkProjectorState) -> Vec<PjLinkStatusCommand> {
+        let mut notifications = Vec::new();
+
+        if let Some(power) = self.power.filter(|power| previous.power != Some(*power)) {
+            notifications.push(PjLinkStatusCommand::Power2(power));
+        }
+        if let Some([input_type, input_value]) = self.input.filter(|input| previous.input != Some(*input)) {
+            notifications.push(PjLinkStatusCommand::Input2(input_type, input_value));
+        }
+        if let Some(error_status) = self.error_status.filter(|error_status| previous.error_status != Some(*error_status)) {
+            notifications.push(PjLinkStatusCommand::ErrorStatus2(error_status));
+        }
+
+        notifications
+    }
+
+    /// Replaces the items known in `other`, keeping the others.
+    pub fn merge(&mut self, other: &PjLinkProjectorState) {
+        self.power = other.power.or(self.power);
+        self.input = other.input.or(self.input);
+        self.mute = other.mute.or(self.mute);
+        self.error_status = other.error_status.or(self.error_status);
+        self.freeze = other.freeze.or(self.freeze);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_diffs_only_changed_items() {
+        let sent = PjLinkProjectorState {
+            power: Some(b'0'),
+            input: Some(*b"11"),
+            error_status: Some(*b"000000"),
+            ..Default::default()
+        };
+        let current = PjLinkProjectorState {
+            power: Some(b'1'),
+            mute: Some(PjLinkAvMuteState { audio: true, video: false }),
+            freeze: Some(true),
+            ..sent
+        };
+
+        let notifications = current.diff(&sent);
+        assert_eq!(notifications.len(), 1);
+        assert!(matches!(notifications[0], PjLinkStatusCommand::Power2(b'1')));
+
+        let mut merged = sent;
+        merged.merge(&PjLinkProjectorState { input: Some(*b"31"), ..Default::default() });
+        assert_eq!(merged, PjLinkProjectorState { input: Some(*b"31"), ..sent });
+    }
+}
@@ -0,0 +1,278 @@
+//! Listener and connection statistics.
+
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::PjLinkResponse;
+
+/// Statistics of a [PjLinkListener](crate::PjLinkListener), returned by
+/// [PjLinkListener::stats](crate::PjLinkListener::stats).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PjLinkListenerStats {
+    /// Currently open TCP connections
+    pub active_connections: u64,
+    /// TCP connections accepted since the listener started
+    pub total_connections: u64,
+    /// Commands handled since the listener started
+    pub commands_processed: u64,
+    /// Bytes received from controllers since the listener started
+    pub bytes_received: u64,
+    /// Bytes sent to controllers since the listener started
+    pub bytes_sent: u64,
+    /// Statistics of each open connection, ordered by connection ID
+    pub connections: Vec<PjLinkConnectionStats>,
+    /// Error responses and unknown commands since the listener started, by
+    /// command body with class, like `*b"1POWR"`. Commands never answered
+    /// with an error are not listed.
+    pub command_errors: BTreeMap<[u8; 5], PjLinkCommandErrorStats>,
+}
+
+/// Error responses to a command body, in
+/// [PjLinkListenerStats::command_errors](self::PjLinkListenerStats::command_errors).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PjLinkCommandErrorStats {
+    /// `ERR1` responses
+    pub undefined: u64,
+    /// `ERR2` responses
+    pub out_of_parameter: u64,
+    /// `ERR3` responses
+    pub unavailable_time: u64,
+    /// `ERR4` responses
+    pub projector_or_display_failure: u64,
+    /// Commands not known to the library, whatever the response
+    pub unknown_commands: u64,
+}
+
+/// Statistics of an open TCP connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PjLinkConnectionStats {
+    /// Connection ID
+    pub connection_id: u64,
+    /// Controller address, if known
+    pub peer_addr: Option<SocketAddr>,
+    /// Time since the connection was accepted
+    pub uptime: Duration,
+    /// Commands handled on this connection
+    pub commands_processed: u64,
+    /// Bytes received on this connection
+    pub bytes_received: u64,
+    /// Bytes sent on this connection
+    pub bytes_sent: u64,
+}
+
+struct PjLinkConnectionStatsState {
+    peer_addr: Option<SocketAddr>,
+    connected_at: Instant,
+    commands_processed: u64,
+    bytes_received: u64,
+    bytes_sent: u64,
+}
+
+#[derive(Default)]
+pub(crate) struct PjLinkStatsState {
+    open_connections: AtomicUsize,
+    total_connections: AtomicU64,
+    commands_processed: AtomicU64,
+    bytes_received: AtomicU64,
+    bytes_sent: AtomicU64,
+    connections: Mutex<HashMap<u64, PjLinkConnectionStatsState>>,
+    command_errors: Mutex<BTreeMap<[u8; 5], PjLinkCommandErrorStats>>,
+}
+
+impl PjLinkStatsState {
+    /// Registers a new connection. It's unregistered when the returned guard
+    /// is dropped.
+    pub(crate) fn register(self: &Arc<Self>, connection_id: u64, peer_addr: Option<SocketAddr>) -> PjLinkConnectionStatsGuard {
+        self.total_connections.fetch_add(1, Ordering::SeqCst);
+
+        if let Ok(mut connections) = self.connections.lock() {
+            connections.insert(connection_id, PjLinkConnectionStatsState {
+                peer_addr,
+                connected_at: Instant::now(),
+                commands_processed: 0,
+                bytes_received: 0,
+                bytes_sent: 0,
+            });
+        }
+
+        PjLinkConnectionStatsGuard {
+            stats: self.clone(),
+            connection_id,
+        }
+    }
+
+    /// Reserves a connection slot, unless `max_connections` connections are
+    /// already open. The slot is released when the returned permit is
+    /// dropped.
+    pub(crate) fn try_open_connection(self: &Arc<Self>, max_connections: Option<usize>) -> Option<PjLinkConnectionPermit> {
+        self.open_connections
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |open_connections| match max_connections {
+                Some(max_connections) if open_connections >= max_connections => None,
+                _ => Some(open_connections + 1),
+            })
+            .ok()
+            .map(|_| PjLinkConnectionPermit { stats: self.clone() })
+    }
+
+    pub(crate) fn snapshot(&self) -> PjLinkListenerStats {
+        let mut connections: Vec<PjLinkConnectionStats> = match self.connections.lock() {
+            Ok(connections) => connections.iter()
+                .map(|(connection_id, connection)| PjLinkConnectionStats {
+                    connection_id: *connection_id,
+                    peer_addr: connection.peer_addr,
+                    uptime: connection.connected_at.elapsed(),
+                    commands_processed: connection.commands_processed,
+                    bytes_received: connection.bytes_received,
+                    bytes_sent: connection.bytes_sent,
+                })
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        connections.sort_by_key(|connection| connection.connection_id);
+
+        PjLinkListenerStats {
+            active_connections: connections.len() as u64,
+            total_connections: self.total_connections.load(Ordering::SeqCst),
+            commands_processed: self.commands_processed.load(Ordering::SeqCst),
+            bytes_received: self.bytes_received.load(Ordering::SeqCst),
+            bytes_sent: self.bytes_sent.load(Ordering::SeqCst),
+            connections,
+            command_errors: match self.command_errors.lock() {
+                Ok(command_errors) => command_errors.clone(),
+                Err(_) => BTreeMap::new(),
+            },
+        }
+    }
+
+    fn update_connection<F: FnOnce(&mut PjLinkConnectionStatsState)>(&self, connection_id: u64, update_fn: F) {
+        if let Ok(mut connections) = self.connections.lock() {
+            if let Some(connection) = connections.get_mut(&connection_id) {
+                update_fn(connection);
+            }
+        }
+    }
+}
+
+/// Records statistics of a single connection, and unregisters it on drop.
+pub(crate) struct PjLinkConnectionStatsGuard {
+    stats: Arc<PjLinkStatsState>,
+    connection_id: u64,
+}
+
+impl PjLinkConnectionStatsGuard {
+    pub(crate) fn record_received(&self, bytes: usize) {
+        self.stats.bytes_received.fetch_add(bytes as u64, Ordering::SeqCst);
+        self.stats.update_connection(self.connection_id, |connection| connection.bytes_received += bytes as u64);
+    }
+
+    pub(crate) fn record_sent(&self, bytes: usize) {
+        self.stats.bytes_sent.fetch_add(bytes as u64, Ordering::SeqCst);
+        self.stats.update_connection(self.connection_id, |connection| connection.bytes_sent += bytes as u64);
+    }
+
+    pub(crate) fn record_command(&self) {
+        self.stats.commands_processed.fetch_add(1, Ordering::SeqCst);
+        self.stats.update_connection(self.connection_id, |connection| connection.commands_processed += 1);
+    }
+
+    /// Counts `response` to `command_body_with_class` if it's an error, or
+    /// if the command is unknown.
+    pub(crate) fn record_response(&self, command_body_with_class: [u8; 5], response: &PjLinkResponse, is_unknown: bool) {
+        let is_error = matches!(
+            response,
+            PjLinkResponse::Undefined
+                | PjLinkResponse::OutOfParameter
+                | PjLinkResponse::UnavailableTime
+                | PjLinkResponse::ProjectorOrDisplayFailure
+        );
+        if !is_error && !is_unknown {
+            return;
+        }
+
+        if let Ok(mut command_errors) = self.stats.command_errors.lock() {
+            let errors = command_errors.entry(command_body_with_class).or_default();
+            match response {
+                PjLinkResponse::Undefined => errors.undefined += 1,
+                PjLinkResponse::OutOfParameter => errors.out_of_parameter += 1,
+                PjLinkResponse::UnavailableTime => errors.unavailable_time += 1,
+                PjLinkResponse::ProjectorOrDisplayFailure => errors.projector_or_display_failure += 1,
+                _ => {}
+            }
+            if is_unknown {
+                errors.unknown_commands += 1;
+            }
+        }
+    }
+}
+
+impl Drop for PjLinkConnectionStatsGuard {
+    fn drop(&mut self) {
+        if let Ok(mut connections) = self.stats.connections.lock() {
+            connections.remove(&self.connection_id);
+        }
+    }
+}
+
+/// Connection slot reserved by [try_open_connection](PjLinkStatsState::try_open_connection),
+/// released on drop.
+pub(crate) struct PjLinkConnectionPermit {
+    stats: Arc<PjLinkStatsState>,
+}
+
+impl Drop for PjLinkConnectionPermit {
+    fn drop(&mut self) {
+        self.stats.open_connections.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_aggregates_and_drops_connection_stats() {
+        let stats = Arc::new(PjLinkStatsState::default());
+
+        let first = stats.register(0, None);
+        let second = stats.register(1, None);
+        first.record_received(10);
+        first.record_command();
+        first.record_sent(9);
+        second.record_received(5);
+        second.record_response(*b"1POWR", &PjLinkResponse::Ok, false);
+        second.record_response(*b"1POWR", &PjLinkResponse::UnavailableTime, false);
+        second.record_response(*b"1LENS", &PjLinkResponse::Undefined, true);
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.active_connections, 2);
+        assert_eq!(snapshot.bytes_received, 15);
+        assert_eq!(snapshot.connections[0].commands_processed, 1);
+        assert_eq!(snapshot.connections[0].bytes_sent, 9);
+        assert_eq!(snapshot.command_errors.len(), 2);
+        assert_eq!(snapshot.command_errors[b"1POWR"], PjLinkCommandErrorStats { unavailable_time: 1, ..Default::default() });
+        assert_eq!(snapshot.command_errors[b"1LENS"], PjLinkCommandErrorStats { undefined: 1, unknown_commands: 1, ..Default::default() });
+
+        drop(first);
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.active_connections, 1);
+        assert_eq!(snapshot.total_connections, 2);
+        assert_eq!(snapshot.commands_processed, 1);
+    }
+
+    #[test]
+    fn it_limits_open_connections() {
+        let stats = Arc::new(PjLinkStatsState::default());
+
+        let first = stats.try_open_connection(Some(2));
+        let second = stats.try_open_connection(Some(2));
+        assert!(first.is_some() && second.is_some());
+        assert!(stats.try_open_connection(Some(2)).is_none());
+        assert!(stats.try_open_connection(None).is_some());
+
+        drop(first);
+        assert!(stats.try_open_connection(Some(2)).is_some());
+    }
+}
@@ -0,0 +1,221 @@
+//! Persistence of projector state across restarts.
+
+#[cfg(feature = "store")]
+use std::convert::TryInto;
+#[cfg(feature = "store")]
+use std::fs;
+#[cfg(feature = "store")]
+use std::io::{self, Write};
+#[cfg(feature = "store")]
+use std::path::{Path, PathBuf};
+#[cfg(feature = "store")]
+use serde::{Deserialize, Serialize};
+
+use crate::{PjLinkError, PjLinkProjectorState};
+#[cfg(feature = "store")]
+use crate::PjLinkAvMuteState;
+
+/// Projector state saved by a [PjLinkStateStore](self::PjLinkStateStore).
+///
+/// ## Examples
+/// ```
+/// use pjlink_bridge::*;
+///
+/// let snapshot = PjLinkStateSnapshot {
+///     state: PjLinkProjectorState {
+///         power: Some(PjLinkPowerCommandStatus::On),
+///         input: Some(*b"31"),
+///         ..Default::default()
+///     },
+///     lamp_hours: vec![1200],
+/// };
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PjLinkStateSnapshot {
+    /// Power, input, mute, error status and freeze
+    pub state: PjLinkProjectorState,
+    /// Cumulative lighting hours of each lamp, as answered to `%1LAMP ?`
+    pub lamp_hours: Vec<u32>,
+}
+
+/// Saves projector state, so a bridge restart doesn't reset lamp hours or
+/// report the wrong input to controllers.
+///
+/// [PjLinkStateTracker::with_store](crate::PjLinkStateTracker::with_store)
+/// loads the state when created and saves it after changes, from its
+/// notification thread. See
+/// `PjLinkJsonStateStore`, with the `store` feature, for a file-based store.
+pub trait PjLinkStateStore: Send + Sync {
+    /// Returns the saved state, or `None` if nothing was saved yet.
+    fn load(&self) -> Result<Option<PjLinkStateSnapshot>, PjLinkError>;
+
+    /// Replaces the saved state.
+    fn save(&self, snapshot: &PjLinkStateSnapshot) -> Result<(), PjLinkError>;
+}
+
+/// [PjLinkStateStore](self::PjLinkStateStore) saving state to a JSON file,
+/// like:
+/// ```json
+/// {"power":"1","input":"31","mute":"30","error_status":"000000","freeze":false,"lamp_hours":[1200]}
+/// ```
+///
+/// Unknown items are saved as `null`. Files are replaced atomically, by
+/// writing and syncing a temporary file next to it first, then syncing the
+/// directory after renaming it, so a crash while saving leaves either the
+/// previous or the new state. Available with the `store` feature.
+///
+/// ## Examples
+/// ```no_run
+/// use std::sync::Arc;
+/// use std::time::Duration;
+/// use pjlink_bridge::*;
+///
+/// let store = Arc::new(PjLinkJsonStateStore::new("/var/lib/pjlink-bridge/state.json"));
+/// let tracker = PjLinkStateTracker::with_store(vec![], Duration::from_millis(500), store).unwrap();
+///
+/// // Input selected before the restart
+/// let input = tracker.snapshot().state.input;
+/// ```
+#[cfg(feature = "store")]
+#[derive(Debug, Clone)]
+pub struct PjLinkJsonStateStore {
+    path: PathBuf,
+}
+
+#[cfg(feature = "store")]
+impl PjLinkJsonStateStore {
+    /// **Arguments**:
+    /// * `path`: State file. It's created on first save.
+    pub fn new<P: AsRef<Path>>(path: P) -> PjLinkJsonStateStore {
+        PjLinkJsonStateStore { path: path.as_ref().to_path_buf() }
+    }
+
+    fn invalid_file(&self, reason: &str) -> PjLinkError {
+        PjLinkError::Io(io::Error::new(io::ErrorKind::InvalidData, format!("state file {}: {}", self.path.display(), reason)))
+    }
+}
+
+#[cfg(feature = "store")]
+impl PjLinkStateStore for PjLinkJsonStateStore {
+    fn load(&self) -> Result<Option<PjLinkStateSnapshot>, PjLinkError> {
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Option::None),
+            Err(e) => return Err(e.into()),
+        };
+        let file: PjLinkJsonStateFile = serde_json::from_str(&contents).map_err(|e| self.invalid_file(&e.to_string()))?;
+
+        decode_snapshot(file).map(Option::Some).ok_or_else(|| self.invalid_file("unexpected value"))
+    }
+
+    fn save(&self, snapshot: &PjLinkStateSnapshot) -> Result<(), PjLinkError> {
+        let mut temporary_path = self.path.clone().into_os_string();
+        temporary_path.push(".tmp");
+
+        let contents = serde_json::to_vec(&encode_snapshot(snapshot)).map_err(|e| self.invalid_file(&e.to_string()))?;
+        let mut temporary_file = fs::File::create(&temporary_path)?;
+        temporary_file.write_all(&contents)?;
+        temporary_file.sync_all()?;
+        drop(temporary_file);
+        fs::rename(&temporary_path, &self.path)?;
+
+        // The rename is only durable once the directory entry is synced
+        #[cfg(unix)]
+        {
+            let directory = match self.path.parent() {
+                Some(directory) if !directory.as_os_str().is_empty() => directory,
+                _ => Path::new("."),
+            };
+            fs::File::open(directory)?.sync_all()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Contents of a [PjLinkJsonStateStore](self::PjLinkJsonStateStore) file.
+/// Missing and `null` items are unknown.
+#[cfg(feature = "store")]
+#[derive(Serialize, Deserialize)]
+struct PjLinkJsonStateFile {
+    power: Option<String>,
+    input: Option<String>,
+    mute: Option<String>,
+    error_status: Option<String>,
+    freeze: Option<bool>,
+    lamp_hours: Option<Vec<u32>>,
+}
+
+#[cfg(feature = "store")]
+fn encode_snapshot(snapshot: &PjLinkStateSnapshot) -> PjLinkJsonStateFile {
+    let state = &snapshot.state;
+    let text = |bytes: &[u8]| String::from_utf8_lossy(bytes).into_owned();
+
+    PjLinkJsonStateFile {
+        power: state.power.map(|power| text(&[power])),
+        input: state.input.map(|input| text(&input)),
+        mute: state.mute.map(|mute| text(&mute.query())),
+        error_status: state.error_status.map(|error_status| text(&error_status)),
+        freeze: state.freeze,
+        lamp_hours: Option::Some(snapshot.lamp_hours.clone()),
+    }
+}
+
+/// Decodes a saved snapshot, or returns `None` if an item has the wrong
+/// length.
+#[cfg(feature = "store")]
+fn decode_snapshot(file: PjLinkJsonStateFile) -> Option<PjLinkStateSnapshot> {
+    fn bytes<const N: usize>(value: Option<String>) -> Option<Option<[u8; N]>> {
+        match value {
+            Some(value) => value.as_bytes().try_into().ok().map(Option::Some),
+            None => Option::Some(Option::None),
+        }
+    }
+
+    Option::Some(PjLinkStateSnapshot {
+        state: PjLinkProjectorState {
+            power: bytes::<1>(file.power)?.map(|[power]| power),
+            input: bytes(file.input)?,
+            mute: bytes(file.mute)?.map(PjLinkAvMuteState::from_status),
+            error_status: bytes(file.error_status)?,
+            freeze: file.freeze,
+        },
+        lamp_hours: file.lamp_hours.unwrap_or_default(),
+    })
+}
+
+#[cfg(all(test, feature = "store"))]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn it_saves_and_loads_json_state() {
+        let path = env::temp_dir().join(format!("pjlink-bridge-state-{}.json", process::id()));
+        let _ = fs::remove_file(&path);
+        let store = PjLinkJsonStateStore::new(&path);
+        assert_eq!(store.load().unwrap(), None);
+
+        let snapshot = PjLinkStateSnapshot {
+            state: PjLinkProjectorState {
+                power: Some(b'1'),
+                input: Some(*b"31"),
+                mute: Some(PjLinkAvMuteState { audio: true, video: false }),
+                freeze: Some(false),
+                ..Default::default()
+            },
+            lamp_hours: vec![1200, 35],
+        };
+        store.save(&snapshot).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            r#"{"power":"1","input":"31","mute":"21","error_status":null,"freeze":false,"lamp_hours":[1200,35]}"#,
+        );
+        assert_eq!(store.load().unwrap(), Some(snapshot));
+
+        fs::write(&path, r#"{"input":"3"}"#).unwrap();
+        assert!(store.load().is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,156 @@
+//! TCP socket tuning.
+
+use std::io;
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+use socket2::{SockRef, TcpKeepalive};
+
+/// TCP socket options applied by a [PjLinkListener](crate::PjLinkListener).
+///
+/// Every field is optional; `None` keeps the operating system default.
+///
+/// PJLink exchanges small request/response frames, so disabling Nagle's
+/// algorithm (`nodelay: Some(true)`) usually reduces response latency.
+///
+/// ## Examples
+/// ```
+/// use pjlink_bridge::*;
+///
+/// let options = PjLinkListenerOptions {
+///     tcp: PjLinkTcpOptions {
+///         nodelay: Some(true),
+///         ..Default::default()
+///     },
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PjLinkTcpOptions {
+    /// Sets `TCP_NODELAY` on accepted connections.
+    pub nodelay: Option<bool>,
+    /// Enables `SO_LINGER` with the given timeout on accepted connections.
+    pub linger: Option<Duration>,
+    /// Sets `SO_SNDBUF` on the listener and accepted connections, in bytes.
+    pub send_buffer_size: Option<usize>,
+    /// Sets `SO_RCVBUF` on the listener and accepted connections, in bytes.
+    pub recv_buffer_size: Option<usize>,
+    /// Enables `SO_KEEPALIVE` on accepted connections, so connections to
+    /// controllers that went away without closing them are dropped.
+    pub keepalive: Option<PjLinkTcpKeepalive>,
+}
+
+/// TCP keepalive probing of idle connections, see
+/// [PjLinkTcpOptions::keepalive](self::PjLinkTcpOptions::keepalive).
+///
+/// `None` fields keep the operating system default, usually two hours of
+/// idle time before the first probe.
+///
+/// ## Examples
+/// ```
+/// use std::time::Duration;
+/// use pjlink_bridge::*;
+///
+/// let options = PjLinkTcpOptions {
+///     keepalive: Some(PjLinkTcpKeepalive {
+///         idle: Some(Duration::from_secs(60)),
+///         interval: Some(Duration::from_secs(10)),
+///     }),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PjLinkTcpKeepalive {
+    /// Idle time before the first probe is sent (`TCP_KEEPIDLE`).
+    pub idle: Option<Duration>,
+    /// Time between unanswered probes (`TCP_KEEPINTVL`). Ignored on
+    /// platforms that can't set it.
+    pub interval: Option<Duration>,
+}
+
+impl PjLinkTcpKeepalive {
+    fn to_socket2(self) -> TcpKeepalive {
+        let mut keepalive = TcpKeepalive::new();
+        if let Some(idle) = self.idle {
+            keepalive = keepalive.with_time(idle);
+        }
+        #[cfg(any(
+            target_os = "android",
+            target_os = "freebsd",
+            target_os = "ios",
+            target_os = "linux",
+            target_os = "macos",
+            target_os = "netbsd",
+            target_os = "windows",
+        ))]
+        if let Some(interval) = self.interval {
+            keepalive = keepalive.with_interval(interval);
+        }
+
+        keepalive
+    }
+}
+
+impl PjLinkTcpOptions {
+    /// Applies options to an accepted connection.
+    pub fn apply_to_stream(&self, stream: &TcpStream) -> Result<(), io::Error> {
+        self.apply_to_socket(&SockRef::from(stream))
+    }
+
+    /// Applies options to an accepted connection of any socket type.
+    pub(crate) fn apply_to_socket(&self, socket: &SockRef) -> Result<(), io::Error> {
+        if let Some(nodelay) = self.nodelay {
+            socket.set_nodelay(nodelay)?;
+        }
+        if let Some(linger) = self.linger {
+            socket.set_linger(Option::Some(linger))?;
+        }
+        if let Some(keepalive) = self.keepalive {
+            socket.set_tcp_keepalive(&keepalive.to_socket2())?;
+        }
+        self.apply_buffer_sizes(socket)
+    }
+
+    /// Applies options that are inherited by accepted connections to the
+    /// listener socket.
+    pub fn apply_to_listener(&self, listener: &TcpListener) -> Result<(), io::Error> {
+        self.apply_buffer_sizes(&SockRef::from(listener))
+    }
+
+    fn apply_buffer_sizes(&self, socket: &SockRef) -> Result<(), io::Error> {
+        if let Some(send_buffer_size) = self.send_buffer_size {
+            socket.set_send_buffer_size(send_buffer_size)?;
+        }
+        if let Some(recv_buffer_size) = self.recv_buffer_size {
+            socket.set_recv_buffer_size(recv_buffer_size)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_applies_options_to_accepted_stream() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let options = PjLinkTcpOptions {
+            nodelay: Some(true),
+            linger: Some(Duration::from_secs(1)),
+            keepalive: Some(PjLinkTcpKeepalive {
+                idle: Some(Duration::from_secs(60)),
+                interval: Some(Duration::from_secs(10)),
+            }),
+            ..Default::default()
+        };
+
+        options.apply_to_listener(&listener).unwrap();
+        let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        options.apply_to_stream(&stream).unwrap();
+
+        assert!(stream.nodelay().unwrap());
+        assert_eq!(SockRef::from(&stream).linger().unwrap(), Some(Duration::from_secs(1)));
+        assert!(SockRef::from(&stream).keepalive().unwrap());
+    }
+}
@@ -0,0 +1,218 @@
+//! PJLink client for integration tests (`client` feature).
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use crate::{PjLinkDigest, PjLinkError, PjLinkMd5Digest, PjLinkRawPayload, PJLINK_HEADER, PJLINK_QUERY, PJLINK_RESPONSE_SEPARATOR, PJLINK_TERMINATOR};
+
+/// Default read/write timeout of a [PjLinkTestClient](self::PjLinkTestClient).
+const PJLINK_TEST_CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Minimal PJLink controller for testing [PjLinkHandler](crate::PjLinkHandler)
+/// implementations through a running [PjLinkListener](crate::PjLinkListener).
+///
+/// Performs the authentication handshake on connect, and sends the password
+/// hash along with the first command when the server requires it.
+///
+/// Available with the `client` feature.
+///
+/// ## Examples
+/// ```no_run
+/// use pjlink_bridge::*;
+///
+/// let mut client = PjLinkTestClient::connect("127.0.0.1:4352", Some("secret")).unwrap();
+///
+/// client.assert_response("%1POWR 1", "%1POWR=OK");
+/// assert_eq!(client.query(*b"1POWR").unwrap(), b"1");
+/// ```
+pub struct PjLinkTestClient {
+    reader: BufReader<TcpStream>,
+    pending_password_hash: Option<String>,
+}
+
+impl PjLinkTestClient {
+    /// Connects to a PJLink server and reads its security header.
+    ///
+    /// **Arguments**:
+    /// * `address`: Server address
+    /// * `password`: Password to authenticate with, if the server requires one
+    pub fn connect<A: ToSocketAddrs>(address: A, password: Option<&str>) -> Result<PjLinkTestClient, PjLinkError> {
+        let stream = TcpStream::connect(address)?;
+        stream.set_read_timeout(Option::Some(PJLINK_TEST_CLIENT_TIMEOUT))?;
+        stream.set_write_timeout(Option::Some(PJLINK_TEST_CLIENT_TIMEOUT))?;
+
+        let mut client = PjLinkTestClient {
+            reader: BufReader::new(stream),
+            pending_password_hash: Option::None,
+        };
+
+        let header = client.read_line()?;
+        if header == b"PJLINK 0" {
+            return Ok(client);
+        }
+
+        let salt = header.strip_prefix(b"PJLINK 1 ")
+            .ok_or_else(|| PjLinkError::parse(&header, "unexpected security header"))?;
+        let password = password
+            .ok_or(PjLinkError::Authentication("server requires a password"))?;
+
+        let mut salted_password = salt.to_vec();
+        salted_password.extend_from_slice(password.as_bytes());
+        client.pending_password_hash = Option::Some(PjLinkMd5Digest.hex_digest(&salted_password));
+
+        Ok(client)
+    }
+
+    /// Sets read and write timeouts. `None` blocks indefinitely.
+    pub fn set_timeout(&self, timeout: Option<Duration>) -> Result<(), PjLinkError> {
+        self.reader.get_ref().set_read_timeout(timeout)?;
+        self.reader.get_ref().set_write_timeout(timeout)?;
+
+        Ok(())
+    }
+
+    /// Sends a raw command line, without terminator, and returns the raw
+    /// response line, also without terminator.
+    ///
+    /// Fails with [Authentication](crate::PjLinkError::Authentication) if
+    /// the server answers `PJLINK ERRA`.
+    pub fn send_raw(&mut self, line: &[u8]) -> Result<Vec<u8>, PjLinkError> {
+        let mut buffer = Vec::with_capacity(line.len() + 33);
+        if let Some(password_hash) = self.pending_password_hash.take() {
+            buffer.extend_from_slice(password_hash.as_bytes());
+        }
+        buffer.extend_from_slice(line);
+        buffer.push(PJLINK_TERMINATOR);
+
+        let stream = self.reader.get_mut();
+        stream.write_all(&buffer)?;
+        stream.flush()?;
+
+        let response = self.read_line()?;
+        if response == b"PJLINK ERRA" {
+            return Err(PjLinkError::Authentication("server answered PJLINK ERRA"));
+        }
+
+        Ok(response)
+    }
+
+    /// Sends a command and returns the parsed response.
+    ///
+    /// **Arguments**:
+    /// * `command_body_with_class`: PJLink command body with class. Value example: `*b"1POWR"`
+    /// * `transmission_parameter`: PJLink transmission parameter. Value example: `b"1"`
+    pub fn send_command(&mut self, command_body_with_class: [u8; 5], transmission_parameter: &[u8]) -> Result<PjLinkRawPayload, PjLinkError> {
+        let command = PjLinkRawPayload::new_command(command_body_with_class, transmission_parameter.to_vec());
+
+        let mut line = vec![PJLINK_HEADER];
+        line.extend_from_slice(&command.command_body_with_class);
+        line.push(command.separator);
+        line.extend_from_slice(&command.transmission_parameter);
+
+        let response = self.send_raw(&line)?;
+        if response.len() < 7
+            || response[0] != PJLINK_HEADER
+            || response[6] != PJLINK_RESPONSE_SEPARATOR
+            || response[1..6] != command_body_with_class {
+            return Err(PjLinkError::parse(&response, "unexpected response"));
+        }
+
+        Ok(PjLinkRawPayload::new_response(command_body_with_class, response[7..].to_vec()))
+    }
+
+    /// Sends a query (`?`) and returns the response parameter.
+    ///
+    /// **Arguments**:
+    /// * `command_body_with_class`: PJLink command body with class. Value example: `*b"1POWR"`
+    pub fn query(&mut self, command_body_with_class: [u8; 5]) -> Result<Vec<u8>, PjLinkError> {
+        self.send_command(command_body_with_class, &[PJLINK_QUERY])
+            .map(|response| response.transmission_parameter)
+    }
+
+    /// Sends a raw command line and panics unless the response matches.
+    ///
+    /// **Arguments**:
+    /// * `command`: Command line without terminator. Value example: `"%1POWR ?"`
+    /// * `expected_response`: Response line without terminator. Value example: `"%1POWR=0"`
+    pub fn assert_response(&mut self, command: &str, expected_response: &str) {
+        match self.send_raw(command.as_bytes()) {
+            Ok(response) => assert_eq!(
+                String::from_utf8_lossy(&response),
+                expected_response,
+                "unexpected response to {:?}",
+                command
+            ),
+            Err(e) => panic!("failed to send {:?}: {}", command, e),
+        }
+    }
+
+    fn read_line(&mut self) -> Result<Vec<u8>, PjLinkError> {
+        let mut line = Vec::new();
+        self.reader.read_until(PJLINK_TERMINATOR, &mut line)?;
+
+        match line.pop() {
+            Some(PJLINK_TERMINATOR) => Ok(line),
+            _ => Err(PjLinkError::Io(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed by server"))),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use crate::{PjLinkCommand, PjLinkHandler, PjLinkListener, PjLinkPowerCommandParameter, PjLinkResponse};
+
+    struct PowerHandler {
+        password: Option<String>,
+        power: u8,
+    }
+
+    impl PjLinkHandler for PowerHandler {
+        fn get_password(&mut self, _connection_id: &u64) -> Option<String> {
+            self.password.clone()
+        }
+
+        fn handle_command(&mut self, command: PjLinkCommand, _raw_command: &PjLinkRawPayload, _connection_id: &u64) -> PjLinkResponse {
+            match command {
+                PjLinkCommand::Power1(PjLinkPowerCommandParameter::Query) => PjLinkResponse::Single(self.power),
+                PjLinkCommand::Power1(PjLinkPowerCommandParameter::On) => {
+                    self.power = b'1';
+                    PjLinkResponse::Ok
+                }
+                _ => PjLinkResponse::Undefined,
+            }
+        }
+    }
+
+    fn spawn_server(password: Option<&str>) -> std::net::SocketAddr {
+        let tcp_listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = tcp_listener.local_addr().unwrap();
+        let handler = Arc::new(Mutex::new(PowerHandler { password: password.map(String::from), power: b'0' }));
+        let listener = PjLinkListener::new_without_broadcast(handler, tcp_listener);
+        thread::spawn(move || listener.listen());
+
+        address
+    }
+
+    #[test]
+    fn it_authenticates_and_sends_commands() {
+        let address = spawn_server(Some("secret"));
+        let mut client = PjLinkTestClient::connect(address, Some("secret")).unwrap();
+
+        client.assert_response("%1POWR 1", "%1POWR=OK");
+        assert_eq!(client.query(*b"1POWR").unwrap(), b"1");
+    }
+
+    #[test]
+    fn it_fails_with_wrong_password() {
+        let address = spawn_server(Some("secret"));
+        let mut client = PjLinkTestClient::connect(address, Some("wrong")).unwrap();
+
+        let error = client.query(*b"1POWR").unwrap_err();
+        assert!(matches!(error, PjLinkError::Authentication(_)));
+    }
+}
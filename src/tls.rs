@@ -0,0 +1,150 @@
+//! TLS transport (`tls` feature).
+
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::time::Duration;
+use log::{info, debug, warn};
+use rustls::{ServerConfig, ServerConnection, StreamOwned};
+
+use crate::{PjLinkListener, PjLinkTransport};
+
+/// Connection accepted by [PjLinkListener::listen_tls](crate::PjLinkListener::listen_tls).
+pub type PjLinkTlsStream = StreamOwned<ServerConnection, TcpStream>;
+
+impl PjLinkTransport for PjLinkTlsStream {
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        self.sock.peer_addr().ok()
+    }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.sock.set_read_timeout(timeout)
+    }
+}
+
+impl<'a> PjLinkListener<'a> {
+    /// Accepts TLS connections from `tls_listener`, besides the plain ones
+    /// received by [listen](self::PjLinkListener::listen), so PJLink can be
+    /// tunneled across untrusted networks while spec-compliant controllers
+    /// keep using the plain port.
+    ///
+    /// Connections share the handler, options, connection IDs and statistics
+    /// of this listener. The TLS handshake runs on the connection thread,
+    /// before the PJLink security header is sent.
+    ///
+    /// Blocks the current thread. Available with the `tls` feature.
+    ///
+    /// **Arguments**:
+    /// * `tls_listener`: Listener of TLS connections, usually on a port other than 4352
+    /// * `config`: rustls server configuration, with the certificate chain and private key
+    ///
+    /// ## Examples
+    /// ```no_run
+    /// use std::net::TcpListener;
+    /// use std::sync::Arc;
+    /// use std::thread;
+    /// use pjlink_bridge::*;
+    ///
+    /// # fn example(listener: PjLinkListenerShared<'static>, config: Arc<rustls::ServerConfig>) {
+    /// let tls_listener = TcpListener::bind("0.0.0.0:4353").unwrap();
+    /// let listener_clone = listener.clone();
+    ///
+    /// thread::spawn(move || listener_clone.listen_tls(tls_listener, config));
+    /// listener.listen();
+    /// # }
+    /// ```
+    pub fn listen_tls(&self, tls_listener: TcpListener, config: Arc<ServerConfig>) {
+        if let Ok(local_addr) = tls_listener.local_addr() {
+            info!("Running TLS Listener on {}", local_addr);
+        }
+
+        for stream in tls_listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if let Err(e) = self.shared_options.tcp.apply_to_stream(&stream) {
+                        debug!("Failed to apply TCP options to connection! {}", e);
+                    }
+
+                    let tls_connection = match ServerConnection::new(config.clone()) {
+                        Ok(tls_connection) => tls_connection,
+                        Err(e) => {
+                            debug!("Failed to create TLS session! {}", e);
+                            continue;
+                        }
+                    };
+
+                    if let Err(e) = self.connection_handler().spawn_connection(StreamOwned::new(tls_connection, stream)) {
+                        warn!("Failed to start connection thread! {}", e);
+                    }
+                },
+                Err(e) => debug!("Error on received TLS connection! {}", e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+    use std::io::{Read, Write};
+    use std::thread;
+    use std::sync::Mutex;
+    use std::time::Duration;
+    use rustls::{ClientConfig, ClientConnection, RootCertStore};
+    use rustls::pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer, ServerName};
+    use crate::{PjLinkCommand, PjLinkHandler, PjLinkRawPayload, PjLinkResponse};
+
+    struct PowerOffHandler;
+
+    impl PjLinkHandler for PowerOffHandler {
+        fn get_password(&mut self, _connection_id: &u64) -> Option<String> {
+            Option::None
+        }
+
+        fn handle_command(&mut self, _command: PjLinkCommand, _raw_command: &PjLinkRawPayload, _connection_id: &u64) -> PjLinkResponse {
+            PjLinkResponse::Single(b'0')
+        }
+    }
+
+    #[test]
+    fn it_serves_tls_connections() {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let certified_key = rcgen::generate_simple_self_signed(vec![String::from("localhost")]).unwrap();
+        let certificate = certified_key.cert.der().clone();
+        let private_key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(certified_key.key_pair.serialize_der()));
+
+        let server_config = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions().unwrap()
+            .with_no_client_auth()
+            .with_single_cert(vec![certificate.clone()], private_key).unwrap();
+
+        let tls_listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = tls_listener.local_addr().unwrap();
+        let listener = PjLinkListener::new_without_broadcast(
+            Arc::new(Mutex::new(PowerOffHandler)),
+            TcpListener::bind("127.0.0.1:0").unwrap(),
+        );
+        thread::spawn(move || listener.listen_tls(tls_listener, Arc::new(server_config)));
+
+        let mut root_store = RootCertStore::empty();
+        root_store.add(certificate).unwrap();
+        let client_config = ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions().unwrap()
+            .with_root_certificates(root_store)
+            .with_no_client_auth();
+        let client_connection = ClientConnection::new(
+            Arc::new(client_config),
+            ServerName::try_from("localhost").unwrap(),
+        ).unwrap();
+
+        let stream = TcpStream::connect(address).unwrap();
+        stream.set_read_timeout(Option::Some(Duration::from_secs(5))).unwrap();
+        let mut client = StreamOwned::new(client_connection, stream);
+        client.write_all(b"%1POWR ?\r").unwrap();
+
+        let mut response = [0u8; 18];
+        client.read_exact(&mut response).unwrap();
+        assert_eq!(&response, b"PJLINK 0\r%1POWR=0\r");
+    }
+}
@@ -0,0 +1,526 @@
+//! Byte stream transports the PJLink protocol can be served over.
+
+#[cfg(any(test, feature = "mock"))]
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+#[cfg(any(test, feature = "mock"))]
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+#[cfg(any(test, feature = "mock"))]
+use std::time::Instant;
+
+/// A bidirectional byte stream carrying a single PJLink connection.
+///
+/// [PjLinkListener::serve_transport](crate::PjLinkListener::serve_transport)
+/// runs the protocol loop (authentication, framing, command handling) over
+/// any transport, so TLS wrappers, Unix sockets, in-memory pipes or serial
+/// tunnels don't need their own implementation.
+///
+/// ## Examples
+/// ```
+/// use std::io::{self, Read, Write};
+/// use pjlink_bridge::*;
+///
+/// struct SerialPort;
+///
+/// impl Read for SerialPort {
+///     fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+///         Ok(0)
+///     }
+/// }
+///
+/// impl Write for SerialPort {
+///     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+///         Ok(buf.len())
+///     }
+///
+///     fn flush(&mut self) -> io::Result<()> {
+///         Ok(())
+///     }
+/// }
+///
+/// impl PjLinkTransport for SerialPort {}
+/// ```
+pub trait PjLinkTransport: Read + Write {
+    /// Returns the controller address, if the transport has one. Used in
+    /// logs, statistics and authentication events.
+    ///
+    /// Returns `None` by default.
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        Option::None
+    }
+
+    /// Sets how long reads wait for data before failing. `None` waits
+    /// indefinitely. Used to enforce
+    /// [frame_timeout](crate::PjLinkListenerOptions::frame_timeout) and
+    /// [handshake_timeout](crate::PjLinkListenerOptions::handshake_timeout).
+    ///
+    /// Does nothing by default, so the timeout isn't enforced.
+    fn set_read_timeout(&mut self, _timeout: Option<Duration>) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl PjLinkTransport for TcpStream {
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        TcpStream::peer_addr(self).ok()
+    }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+}
+
+#[cfg(any(test, feature = "mock"))]
+#[derive(Default)]
+struct PjLinkMemoryPipeState {
+    buffer: VecDeque<u8>,
+    is_closed: bool,
+}
+
+/// One direction of a [PjLinkMemoryTransport](self::PjLinkMemoryTransport) pair.
+#[cfg(any(test, feature = "mock"))]
+#[derive(Default)]
+struct PjLinkMemoryPipe {
+    state: Mutex<PjLinkMemoryPipeState>,
+    condvar: Condvar,
+}
+
+#[cfg(any(test, feature = "mock"))]
+impl PjLinkMemoryPipe {
+    fn close(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.is_closed = true;
+        }
+        self.condvar.notify_all();
+    }
+}
+
+/// In-process [PjLinkTransport](self::PjLinkTransport), created in connected
+/// pairs by [pair](self::PjLinkMemoryTransport::pair).
+///
+/// Bytes written to one end are read from the other. Reads block until data
+/// is available, and return end-of-file once the other end is dropped, so a
+/// handler, its authentication and framing can be tested deterministically
+/// without binding ports.
+///
+/// Available with the `mock` feature.
+///
+/// ## Examples
+/// ```
+/// use std::io::{Read, Write};
+/// use std::sync::{Arc, Mutex};
+/// use std::thread;
+/// use pjlink_bridge::*;
+///
+/// struct Projector;
+///
+/// impl PjLinkHandler for Projector {
+///     fn get_password(&mut self, _connection_id: &u64) -> Option<String> {
+///         None
+///     }
+///
+///     fn handle_command(&mut self, _command: PjLinkCommand, _raw_command: &PjLinkRawPayload, _connection_id: &u64) -> PjLinkResponse {
+///         PjLinkResponse::Single(b'0')
+///     }
+/// }
+///
+/// let (mut client, server) = PjLinkMemoryTransport::pair();
+/// thread::spawn(move || PjLinkServer::serve_transport(Arc::new(Mutex::new(Projector)), server));
+///
+/// client.write_all(b"%1POWR ?\r").unwrap();
+/// let mut response = [0u8; 18];
+/// client.read_exact(&mut response).unwrap();
+/// assert_eq!(&response, b"PJLINK 0\r%1POWR=0\r");
+/// ```
+#[cfg(any(test, feature = "mock"))]
+pub struct PjLinkMemoryTransport {
+    incoming: Arc<PjLinkMemoryPipe>,
+    outgoing: Arc<PjLinkMemoryPipe>,
+    read_timeout: Option<Duration>,
+    peer_addr: Option<SocketAddr>,
+}
+
+#[cfg(any(test, feature = "mock"))]
+impl PjLinkMemoryTransport {
+    /// Creates a connected pair of transports, usually used as client and
+    /// server ends.
+    pub fn pair() -> (PjLinkMemoryTransport, PjLinkMemoryTransport) {
+        let first_to_second = Arc::new(PjLinkMemoryPipe::default());
+        let second_to_first = Arc::new(PjLinkMemoryPipe::default());
+
+        (
+            PjLinkMemoryTransport {
+                incoming: second_to_first.clone(),
+                outgoing: first_to_second.clone(),
+                read_timeout: Option::None,
+                peer_addr: Option::None,
+            },
+            PjLinkMemoryTransport {
+                incoming: first_to_second,
+                outgoing: second_to_first,
+                read_timeout: Option::None,
+                peer_addr: Option::None,
+            },
+        )
+    }
+
+    /// Sets how long reads wait for data before failing with
+    /// [TimedOut](std::io::ErrorKind::TimedOut). `None` (the default) waits
+    /// indefinitely.
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) {
+        self.read_timeout = timeout;
+    }
+
+    /// Sets the address reported by [PjLinkTransport::peer_addr](self::PjLinkTransport::peer_addr),
+    /// to test address-based behavior.
+    pub fn set_peer_addr(&mut self, peer_addr: Option<SocketAddr>) {
+        self.peer_addr = peer_addr;
+    }
+}
+
+#[cfg(any(test, feature = "mock"))]
+impl Read for PjLinkMemoryTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let deadline = self.read_timeout.map(|timeout| Instant::now() + timeout);
+        let mut state = self.incoming.state.lock().map_err(|_| io::Error::other("memory transport poisoned"))?;
+
+        while state.buffer.is_empty() && !state.is_closed {
+            state = match deadline {
+                Some(deadline) => {
+                    let timeout = deadline.saturating_duration_since(Instant::now());
+                    if timeout.is_zero() {
+                        return Err(io::Error::new(io::ErrorKind::TimedOut, "memory transport read timed out"));
+                    }
+                    self.incoming.condvar.wait_timeout(state, timeout)
+                        .map_err(|_| io::Error::other("memory transport poisoned"))?.0
+                }
+                None => self.incoming.condvar.wait(state)
+                    .map_err(|_| io::Error::other("memory transport poisoned"))?,
+            };
+        }
+
+        let len = buf.len().min(state.buffer.len());
+        for (target, byte) in buf.iter_mut().zip(state.buffer.drain(..len)) {
+            *target = byte;
+        }
+
+        Ok(len)
+    }
+}
+
+#[cfg(any(test, feature = "mock"))]
+impl Write for PjLinkMemoryTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut state = self.outgoing.state.lock().map_err(|_| io::Error::other("memory transport poisoned"))?;
+        if state.is_closed {
+            return Err(io::Error::new(io::ErrorKind::BrokenPipe, "memory transport closed"));
+        }
+
+        state.buffer.extend(buf);
+        self.outgoing.condvar.notify_all();
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(any(test, feature = "mock"))]
+impl PjLinkTransport for PjLinkMemoryTransport {
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        self.peer_addr
+    }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        PjLinkMemoryTransport::set_read_timeout(self, timeout);
+        Ok(())
+    }
+}
+
+#[cfg(any(test, feature = "mock"))]
+impl Drop for PjLinkMemoryTransport {
+    fn drop(&mut self) {
+        self.incoming.close();
+        self.outgoing.close();
+    }
+}
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use std::thread;
+    use crate::{
+        PjLinkCommand, PjLinkHandler, PjLinkListenerOptions, PjLinkMockClock, PjLinkPassword, PjLinkPasswordProvider, PjLinkRawPayload,
+        PjLinkReauthPolicy, PjLinkResponse, PjLinkServer, PjLinkTieredPassword,
+    };
+
+    struct EchoPowerHandler;
+
+    impl PjLinkHandler for EchoPowerHandler {
+        fn get_password(&mut self, _connection_id: &u64) -> Option<String> {
+            Option::Some(String::from("secret"))
+        }
+
+        fn handle_command(&mut self, _command: PjLinkCommand, _raw_command: &PjLinkRawPayload, _connection_id: &u64) -> PjLinkResponse {
+            PjLinkResponse::Single(b'1')
+        }
+    }
+
+    /// Only sets a read-only password, leaving the control one unset
+    struct ReadOnlyPassword;
+
+    impl PjLinkPasswordProvider for ReadOnlyPassword {
+        fn get_password(&self, _connection_id: &u64) -> Option<PjLinkPassword> {
+            Option::None
+        }
+
+        fn get_read_only_password_for_peer(&self, _connection_id: &u64, _peer_addr: Option<&std::net::SocketAddr>) -> Option<PjLinkPassword> {
+            Option::Some(PjLinkPassword::new("monitoring").unwrap())
+        }
+    }
+
+    fn read_line(transport: &mut PjLinkMemoryTransport) -> Vec<u8> {
+        let mut line = Vec::new();
+        let mut byte = [0u8; 1];
+        while transport.read(&mut byte).unwrap() == 1 {
+            line.push(byte[0]);
+            if byte[0] == b'\r' {
+                break;
+            }
+        }
+        line
+    }
+
+    #[test]
+    fn it_transfers_bytes_and_closes_on_drop() {
+        let (mut first, mut second) = PjLinkMemoryTransport::pair();
+
+        first.write_all(b"ping").unwrap();
+        let mut buffer = [0u8; 4];
+        second.read_exact(&mut buffer).unwrap();
+        assert_eq!(&buffer, b"ping");
+
+        second.set_read_timeout(Option::Some(Duration::from_millis(10)));
+        assert_eq!(second.read(&mut buffer).unwrap_err().kind(), io::ErrorKind::TimedOut);
+
+        drop(first);
+        assert_eq!(second.read(&mut buffer).unwrap(), 0);
+        assert_eq!(second.write(b"pong").unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+    }
+
+    #[test]
+    fn it_serves_authenticated_connection() {
+        let (mut client, server) = PjLinkMemoryTransport::pair();
+        client.set_read_timeout(Option::Some(Duration::from_secs(5)));
+        let server_thread = thread::spawn(move || {
+            PjLinkServer::serve_transport(Arc::new(Mutex::new(EchoPowerHandler)), server)
+        });
+
+        let header = read_line(&mut client);
+        let salt = &header[b"PJLINK 1 ".len()..header.len() - 1];
+        let mut salted_password = salt.to_vec();
+        salted_password.extend_from_slice(b"secret");
+
+        let mut command = format!("{:x}", md5::compute(salted_password)).into_bytes();
+        command.extend_from_slice(b"%1POWR ?\r");
+        client.write_all(&command).unwrap();
+        assert_eq!(read_line(&mut client), b"%1POWR=1\r");
+
+        drop(client);
+        server_thread.join().unwrap();
+    }
+
+    #[test]
+    fn it_salts_security_header_with_injected_rng() {
+        use rand::{rngs::StdRng, RngCore, SeedableRng};
+
+        let (mut client, server) = PjLinkMemoryTransport::pair();
+        client.set_read_timeout(Option::Some(Duration::from_secs(5)));
+        let options = PjLinkListenerOptions { salt_rng: Option::Some(Arc::new(Mutex::new(StdRng::seed_from_u64(7)))), ..Default::default() };
+        thread::spawn(move || PjLinkServer::serve_transport_with_options(Arc::new(Mutex::new(EchoPowerHandler)), server, options));
+
+        let expected = format!("PJLINK 1 {:08X}\r", StdRng::seed_from_u64(7).next_u32());
+        assert_eq!(read_line(&mut client), expected.into_bytes());
+    }
+
+    #[test]
+    fn it_skips_authentication_for_loopback_connections() {
+        for (peer_addr, header) in [("127.0.0.1:50000", &b"PJLINK 0\r"[..]), ("[::1]:50000", b"PJLINK 0\r"), ("10.0.0.5:50000", b"PJLINK 1 ")] {
+            let (mut client, mut server) = PjLinkMemoryTransport::pair();
+            client.set_read_timeout(Option::Some(Duration::from_secs(5)));
+            server.set_peer_addr(Option::Some(peer_addr.parse().unwrap()));
+            let options = PjLinkListenerOptions { loopback_bypasses_auth: true, ..Default::default() };
+            thread::spawn(move || {
+                PjLinkServer::serve_transport_with_options(Arc::new(Mutex::new(EchoPowerHandler)), server, options)
+            });
+
+            assert!(read_line(&mut client).starts_with(header));
+        }
+    }
+
+    #[test]
+    fn it_expires_sessions_with_injected_clock() {
+        let (mut client, server) = PjLinkMemoryTransport::pair();
+        client.set_read_timeout(Option::Some(Duration::from_secs(5)));
+        let clock = Arc::new(PjLinkMockClock::new());
+        let options = PjLinkListenerOptions {
+            reauth_policy: PjLinkReauthPolicy { max_session_duration: Option::Some(Duration::from_secs(3600)), ..Default::default() },
+            clock: Option::Some(clock.clone()),
+            ..Default::default()
+        };
+        let server_thread = thread::spawn(move || {
+            PjLinkServer::serve_transport_with_options(Arc::new(Mutex::new(EchoPowerHandler)), server, options)
+        });
+
+        let header = read_line(&mut client);
+        let mut salted_password = header[b"PJLINK 1 ".len()..header.len() - 1].to_vec();
+        salted_password.extend_from_slice(b"secret");
+
+        let mut command = format!("{:x}", md5::compute(salted_password)).into_bytes();
+        command.extend_from_slice(b"%1POWR ?\r");
+        client.write_all(&command).unwrap();
+        assert_eq!(read_line(&mut client), b"%1POWR=1\r");
+
+        clock.advance(Duration::from_secs(3600));
+        client.write_all(b"%1POWR ?\r").unwrap();
+        assert_eq!(read_line(&mut client), b"");
+        server_thread.join().unwrap();
+    }
+
+    #[test]
+    fn it_restricts_read_only_password_sessions_to_queries() {
+        let (mut client, server) = PjLinkMemoryTransport::pair();
+        client.set_read_timeout(Option::Some(Duration::from_secs(5)));
+        let options = PjLinkListenerOptions {
+            password_provider: Option::Some(Arc::new(PjLinkTieredPassword::new(
+                PjLinkPassword::new("operator").unwrap(),
+                PjLinkPassword::new("monitoring").unwrap(),
+            ))),
+            ..Default::default()
+        };
+        let server_thread = thread::spawn(move || {
+            PjLinkServer::serve_transport_with_options(Arc::new(Mutex::new(EchoPowerHandler)), server, options)
+        });
+
+        let header = read_line(&mut client);
+        let mut salted_password = header[b"PJLINK 1 ".len()..header.len() - 1].to_vec();
+        salted_password.extend_from_slice(b"monitoring");
+
+        let mut command = format!("{:x}", md5::compute(salted_password)).into_bytes();
+        command.extend_from_slice(b"%1POWR ?\r");
+        client.write_all(&command).unwrap();
+        assert_eq!(read_line(&mut client), b"%1POWR=1\r");
+        client.write_all(b"%1POWR 1\r").unwrap();
+        assert_eq!(read_line(&mut client), b"%1POWR=ERR2\r");
+
+        drop(client);
+        server_thread.join().unwrap();
+    }
+
+    #[test]
+    fn it_answers_input_name_queries_on_read_only_sessions() {
+        let (mut client, server) = PjLinkMemoryTransport::pair();
+        client.set_read_timeout(Option::Some(Duration::from_secs(5)));
+        let options = PjLinkListenerOptions {
+            password_provider: Option::Some(Arc::new(PjLinkTieredPassword::new(
+                PjLinkPassword::new("operator").unwrap(),
+                PjLinkPassword::new("monitoring").unwrap(),
+            ))),
+            ..Default::default()
+        };
+        let server_thread = thread::spawn(move || {
+            PjLinkServer::serve_transport_with_options(Arc::new(Mutex::new(EchoPowerHandler)), server, options)
+        });
+
+        let header = read_line(&mut client);
+        let mut salted_password = header[b"PJLINK 1 ".len()..header.len() - 1].to_vec();
+        salted_password.extend_from_slice(b"monitoring");
+
+        let mut command = format!("{:x}", md5::compute(salted_password)).into_bytes();
+        command.extend_from_slice(b"%2INNM ?11\r");
+        client.write_all(&command).unwrap();
+        assert_eq!(read_line(&mut client), b"%2INNM=1\r");
+
+        drop(client);
+        server_thread.join().unwrap();
+    }
+
+    #[test]
+    fn it_refuses_vendor_set_commands_on_read_only_sessions() {
+        let (mut client, server) = PjLinkMemoryTransport::pair();
+        client.set_read_timeout(Option::Some(Duration::from_secs(5)));
+        let options = PjLinkListenerOptions {
+            password_provider: Option::Some(Arc::new(PjLinkTieredPassword::new(
+                PjLinkPassword::new("operator").unwrap(),
+                PjLinkPassword::new("monitoring").unwrap(),
+            ))),
+            ..Default::default()
+        };
+        let server_thread = thread::spawn(move || {
+            PjLinkServer::serve_transport_with_options(Arc::new(Mutex::new(EchoPowerHandler)), server, options)
+        });
+
+        let header = read_line(&mut client);
+        let mut salted_password = header[b"PJLINK 1 ".len()..header.len() - 1].to_vec();
+        salted_password.extend_from_slice(b"monitoring");
+
+        let mut command = format!("{:x}", md5::compute(salted_password)).into_bytes();
+        command.extend_from_slice(b"%1XLMP ?\r");
+        client.write_all(&command).unwrap();
+        assert_eq!(read_line(&mut client), b"%1XLMP=1\r");
+        client.write_all(b"%1XLMP 1\r").unwrap();
+        assert_eq!(read_line(&mut client), b"%1XLMP=ERR2\r");
+        client.write_all(b"%1POWR 7\r").unwrap();
+        assert_eq!(read_line(&mut client), b"%1POWR=ERR2\r");
+
+        drop(client);
+        server_thread.join().unwrap();
+    }
+
+    #[test]
+    fn it_requires_auth_when_only_a_read_only_password_is_set() {
+        let serve = || {
+            let (mut client, server) = PjLinkMemoryTransport::pair();
+            client.set_read_timeout(Option::Some(Duration::from_secs(5)));
+            let options = PjLinkListenerOptions { password_provider: Option::Some(Arc::new(ReadOnlyPassword)), ..Default::default() };
+            let server_thread = thread::spawn(move || {
+                PjLinkServer::serve_transport_with_options(Arc::new(Mutex::new(EchoPowerHandler)), server, options)
+            });
+            (client, server_thread)
+        };
+
+        // The unset control password doesn't act as an empty one
+        let (mut client, server_thread) = serve();
+        let header = read_line(&mut client);
+        assert!(header.starts_with(b"PJLINK 1 "));
+        let salt = header[b"PJLINK 1 ".len()..header.len() - 1].to_vec();
+        let mut command = format!("{:x}", md5::compute(salt)).into_bytes();
+        command.extend_from_slice(b"%1POWR 1\r");
+        client.write_all(&command).unwrap();
+        assert_eq!(read_line(&mut client), b"PJLINK ERRA\r");
+        server_thread.join().unwrap();
+
+        let (mut client, server_thread) = serve();
+        let header = read_line(&mut client);
+        let mut salted_password = header[b"PJLINK 1 ".len()..header.len() - 1].to_vec();
+        salted_password.extend_from_slice(b"monitoring");
+        let mut command = format!("{:x}", md5::compute(salted_password)).into_bytes();
+        command.extend_from_slice(b"%1POWR ?\r");
+        client.write_all(&command).unwrap();
+        assert_eq!(read_line(&mut client), b"%1POWR=1\r");
+        client.write_all(b"%1POWR 1\r").unwrap();
+        assert_eq!(read_line(&mut client), b"%1POWR=ERR2\r");
+
+        drop(client);
+        server_thread.join().unwrap();
+    }
+}
@@ -0,0 +1,134 @@
+//! Unix domain socket listener.
+
+use std::io;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::AtomicU64;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use log::{info, debug, warn};
+
+use crate::{
+    PjLinkConnectionHandler, PjLinkError, PjLinkHandlerShared, spawn_named_thread, PjLinkListener, PjLinkListenerOptions, PjLinkReloadableConfig, PjLinkServer, PjLinkTransport,
+};
+use crate::reload::PjLinkConfigState;
+use crate::stats::PjLinkStatsState;
+
+impl PjLinkTransport for UnixStream {
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        UnixStream::set_read_timeout(self, timeout)
+    }
+}
+
+impl PjLinkServer {
+    /// Serves PJLink over a Unix domain socket bound on `path`, for gateways
+    /// running on the same host or environments where TCP port 4352 can't be
+    /// opened.
+    ///
+    /// Unix domain socket connections have no peer address, so address-based
+    /// options (like search networks) don't apply to them.
+    ///
+    /// **Arguments**:
+    /// * `handler`: Handler of received connections
+    /// * `path`: Socket file path. Fails with [Bind](crate::PjLinkError::Bind) if it already exists.
+    ///
+    /// ## Examples
+    /// ```no_run
+    /// use std::sync::{Arc, Mutex};
+    /// use pjlink_bridge::*;
+    ///
+    /// # fn example(projector: Arc<Mutex<dyn PjLinkHandler>>) {
+    /// let handle = PjLinkServer::listen_unix(projector, "/run/pjlink-bridge.sock").unwrap();
+    /// handle.join().unwrap();
+    /// # }
+    /// ```
+    pub fn listen_unix<P: AsRef<Path>>(handler: PjLinkHandlerShared, path: P) -> Result<JoinHandle<()>, PjLinkError> {
+        Self::listen_unix_with_options(handler, path, PjLinkListenerOptions::default())
+    }
+
+    pub fn listen_unix_with_options<P: AsRef<Path>>(
+        handler: PjLinkHandlerShared,
+        path: P,
+        options: PjLinkListenerOptions,
+    ) -> Result<JoinHandle<()>, PjLinkError> {
+        let unix_listener = UnixListener::bind(path.as_ref())
+            .map_err(|e| PjLinkError::bind(path.as_ref().display(), e))?;
+        let connection_handler = PjLinkConnectionHandler {
+            handler,
+            shared_connection_counter: Arc::new(AtomicU64::new(0)),
+            config: Arc::new(PjLinkConfigState::new(PjLinkReloadableConfig::from_options(&options))),
+            options: Arc::new(options),
+            stats: Arc::new(PjLinkStatsState::default()),
+        };
+
+        info!("Running Unix Listener on {}", path.as_ref().display());
+        spawn_named_thread(String::from("pjlink-unix-accept"), move || {
+            listen_unix_internal(&unix_listener, connection_handler)
+        })
+    }
+}
+
+impl<'a> PjLinkListener<'a> {
+    /// Accepts connections from a Unix domain socket, besides the ones
+    /// received by [listen](self::PjLinkListener::listen). Connections share
+    /// the handler, options, connection IDs and statistics of this listener.
+    ///
+    /// Blocks the current thread.
+    pub fn listen_unix(&self, unix_listener: &UnixListener) {
+        listen_unix_internal(unix_listener, self.connection_handler());
+    }
+}
+
+fn listen_unix_internal(unix_listener: &UnixListener, connection_handler: PjLinkConnectionHandler) {
+    for stream in unix_listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                if let Err(e) = connection_handler.spawn_connection(stream) {
+                    warn!("Failed to start connection thread! {}", e);
+                }
+            },
+            Err(e) => debug!("Error on received Unix connection! {}", e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::io::{Read, Write};
+    use std::process;
+    use std::sync::Mutex;
+    use crate::{PjLinkCommand, PjLinkHandler, PjLinkRawPayload, PjLinkResponse};
+
+    struct PowerOffHandler;
+
+    impl PjLinkHandler for PowerOffHandler {
+        fn get_password(&mut self, _connection_id: &u64) -> Option<String> {
+            Option::None
+        }
+
+        fn handle_command(&mut self, _command: PjLinkCommand, _raw_command: &PjLinkRawPayload, _connection_id: &u64) -> PjLinkResponse {
+            PjLinkResponse::Single(b'0')
+        }
+    }
+
+    #[test]
+    fn it_serves_unix_socket_connections() {
+        let path = env::temp_dir().join(format!("pjlink-bridge-test-{}.sock", process::id()));
+        let _ = fs::remove_file(&path);
+        PjLinkServer::listen_unix(Arc::new(Mutex::new(PowerOffHandler)), &path).unwrap();
+
+        let mut stream = UnixStream::connect(&path).unwrap();
+        stream.set_read_timeout(Option::Some(std::time::Duration::from_secs(5))).unwrap();
+        stream.write_all(b"%1POWR ?\r").unwrap();
+
+        let mut response = [0u8; 18];
+        stream.read_exact(&mut response).unwrap();
+        assert_eq!(&response, b"PJLINK 0\r%1POWR=0\r");
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,92 @@
+//! Volume level handlers can embed to answer `SVOL` and `MVOL`.
+
+use crate::{PjLinkResponse, PjLinkVolumeCommandParameter};
+
+/// Absolute volume level adjusted by the relative `%2SVOL` and `%2MVOL`
+/// commands, kept within bounds.
+///
+/// Adjustments that would move the level past `min` or `max` are answered
+/// with `ERR2` and leave the level unchanged.
+///
+/// ## Examples
+/// ```
+/// use pjlink_bridge::*;
+///
+/// let mut speaker = PjLinkVolumeModel::new(0, 10, 5);
+/// speaker.set_level(9);
+///
+/// assert_eq!(speaker.adjust(PjLinkVolumeCommandParameter::Increase), PjLinkResponse::OutOfParameter);
+/// assert_eq!(speaker.adjust(PjLinkVolumeCommandParameter::Decrase), PjLinkResponse::Ok);
+/// assert_eq!(speaker.level(), 4);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PjLinkVolumeModel {
+    level: i32,
+    min: i32,
+    max: i32,
+    step: i32,
+}
+
+impl PjLinkVolumeModel {
+    /// Creates a model at level `min`.
+    ///
+    /// **Arguments**:
+    /// * `min`: Lowest level
+    /// * `max`: Highest level. Must not be lower than `min`.
+    /// * `step`: Level change of each adjustment
+    pub fn new(min: i32, max: i32, step: i32) -> PjLinkVolumeModel {
+        assert!(min <= max, "volume min must not be higher than max");
+
+        PjLinkVolumeModel { level: min, min, max, step }
+    }
+
+    /// Returns the current level.
+    pub fn level(&self) -> i32 {
+        self.level
+    }
+
+    /// Sets the current level, clamped to the bounds.
+    pub fn set_level(&mut self, level: i32) {
+        self.level = level.clamp(self.min, self.max);
+    }
+
+    /// Answers a `%2SVOL` or `%2MVOL` command, adjusting the level by one
+    /// step.
+    pub fn adjust(&mut self, parameter: PjLinkVolumeCommandParameter) -> PjLinkResponse {
+        let level = match parameter {
+            PjLinkVolumeCommandParameter::Increase => self.level.checked_add(self.step),
+            PjLinkVolumeCommandParameter::Decrase => self.level.checked_sub(self.step),
+            PjLinkVolumeCommandParameter::Unknown => Option::None,
+        };
+
+        match level {
+            Some(level) if (self.min..=self.max).contains(&level) => {
+                self.level = level;
+                PjLinkResponse::Ok
+            }
+            _ => PjLinkResponse::OutOfParameter,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_rejects_adjustments_past_bounds() {
+        let mut microphone = PjLinkVolumeModel::new(-6, 6, 3);
+
+        assert_eq!(microphone.adjust(PjLinkVolumeCommandParameter::Decrase), PjLinkResponse::OutOfParameter);
+        assert_eq!(microphone.level(), -6);
+        for _ in 0..4 {
+            assert_eq!(microphone.adjust(PjLinkVolumeCommandParameter::Increase), PjLinkResponse::Ok);
+        }
+        assert_eq!(microphone.level(), 6);
+        assert_eq!(microphone.adjust(PjLinkVolumeCommandParameter::Increase), PjLinkResponse::OutOfParameter);
+        assert_eq!(microphone.adjust(PjLinkVolumeCommandParameter::Unknown), PjLinkResponse::OutOfParameter);
+
+        microphone.set_level(100);
+        assert_eq!(microphone.level(), 6);
+    }
+}
@@ -0,0 +1,204 @@
+//! WebSocket gateway (`websocket` feature).
+
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::time::Duration;
+use log::{info, debug, warn};
+use tungstenite::{Message, WebSocket};
+
+use crate::{PjLinkError, PjLinkListener, PjLinkTransport, spawn_named_thread, PJLINK_TERMINATOR};
+
+/// [PjLinkTransport](crate::PjLinkTransport) over an accepted WebSocket
+/// connection, so browser-based controllers can talk PJLink without a native
+/// socket.
+///
+/// Each text or binary message received is a PJLink command line, with or
+/// without the trailing `\r`. Each line sent by the server (security header
+/// and responses) is sent as one text message, without the `\r`.
+pub struct PjLinkWebSocketTransport {
+    socket: WebSocket<TcpStream>,
+    read_buffer: Vec<u8>,
+    read_position: usize,
+    write_buffer: Vec<u8>,
+}
+
+impl PjLinkWebSocketTransport {
+    /// Runs the WebSocket opening handshake on an accepted connection.
+    pub fn accept(stream: TcpStream) -> Result<PjLinkWebSocketTransport, PjLinkError> {
+        let socket = tungstenite::accept(stream)
+            .map_err(|e| PjLinkError::Io(io::Error::new(io::ErrorKind::InvalidData, e.to_string())))?;
+
+        Ok(PjLinkWebSocketTransport {
+            socket,
+            read_buffer: Vec::new(),
+            read_position: 0,
+            write_buffer: Vec::new(),
+        })
+    }
+
+    fn to_io_error(error: tungstenite::Error) -> io::Error {
+        match error {
+            tungstenite::Error::Io(e) => e,
+            e => io::Error::other(e.to_string()),
+        }
+    }
+}
+
+impl Read for PjLinkWebSocketTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.read_position == self.read_buffer.len() {
+            let mut line = match self.socket.read() {
+                Ok(Message::Text(text)) => text.into_bytes(),
+                Ok(Message::Binary(data)) => data,
+                Ok(Message::Close(_)) => return Ok(0),
+                Ok(_) => continue,
+                Err(tungstenite::Error::ConnectionClosed) | Err(tungstenite::Error::AlreadyClosed) => return Ok(0),
+                Err(e) => return Err(Self::to_io_error(e)),
+            };
+            if line.is_empty() {
+                continue;
+            }
+            if line.last() != Some(&PJLINK_TERMINATOR) {
+                line.push(PJLINK_TERMINATOR);
+            }
+
+            self.read_buffer = line;
+            self.read_position = 0;
+        }
+
+        let len = buf.len().min(self.read_buffer.len() - self.read_position);
+        buf[..len].copy_from_slice(&self.read_buffer[self.read_position..self.read_position + len]);
+        self.read_position += len;
+
+        Ok(len)
+    }
+}
+
+impl Write for PjLinkWebSocketTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_buffer.extend_from_slice(buf);
+
+        while let Some(terminator_position) = self.write_buffer.iter().position(|byte| *byte == PJLINK_TERMINATOR) {
+            let line: Vec<u8> = self.write_buffer.drain(..=terminator_position).take(terminator_position).collect();
+            let message = match String::from_utf8(line) {
+                Ok(text) => Message::Text(text),
+                Err(e) => Message::Binary(e.into_bytes()),
+            };
+            self.socket.send(message).map_err(Self::to_io_error)?;
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.socket.flush().map_err(Self::to_io_error)
+    }
+}
+
+impl PjLinkTransport for PjLinkWebSocketTransport {
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        self.socket.get_ref().peer_addr().ok()
+    }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.socket.get_ref().set_read_timeout(timeout)
+    }
+}
+
+impl<'a> PjLinkListener<'a> {
+    /// Accepts WebSocket connections from `websocket_listener` and forwards
+    /// their messages to this listener's handler. See
+    /// [PjLinkWebSocketTransport](crate::PjLinkWebSocketTransport) for how
+    /// messages map to PJLink command lines.
+    ///
+    /// Connections share the handler, options, connection IDs and statistics
+    /// of this listener. Blocks the current thread. Available with the
+    /// `websocket` feature.
+    ///
+    /// ## Examples
+    /// ```no_run
+    /// use std::net::TcpListener;
+    /// use std::thread;
+    /// use pjlink_bridge::*;
+    ///
+    /// # fn example(listener: PjLinkListenerShared<'static>) {
+    /// let websocket_listener = TcpListener::bind("0.0.0.0:8080").unwrap();
+    /// let listener_clone = listener.clone();
+    ///
+    /// thread::spawn(move || listener_clone.listen_websocket(websocket_listener));
+    /// listener.listen();
+    /// # }
+    /// ```
+    pub fn listen_websocket(&self, websocket_listener: TcpListener) {
+        if let Ok(local_addr) = websocket_listener.local_addr() {
+            info!("Running WebSocket Listener on {}", local_addr);
+        }
+
+        for stream in websocket_listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if let Err(e) = self.shared_options.tcp.apply_to_stream(&stream) {
+                        debug!("Failed to apply TCP options to connection! {}", e);
+                    }
+
+                    let mut connection_handler = self.connection_handler();
+                    let Some(permit) = connection_handler.try_open_connection(stream.peer_addr().ok()) else {
+                        continue;
+                    };
+                    let connection_id = connection_handler.next_connection_id();
+                    let spawned = spawn_named_thread(format!("pjlink-conn-{}", connection_id), move || {
+                        let _permit = permit;
+                        match PjLinkWebSocketTransport::accept(stream) {
+                            Ok(transport) => connection_handler.handle_connection_with_id(transport, connection_id),
+                            Err(e) => debug!("Failed WebSocket handshake! {}", e),
+                        }
+                    });
+                    if let Err(e) = spawned {
+                        warn!("Failed to start connection thread! {}", e);
+                    }
+                },
+                Err(e) => debug!("Error on received WebSocket connection! {}", e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use crate::{PjLinkCommand, PjLinkHandler, PjLinkRawPayload, PjLinkResponse};
+
+    struct PowerOffHandler;
+
+    impl PjLinkHandler for PowerOffHandler {
+        fn get_password(&mut self, _connection_id: &u64) -> Option<String> {
+            Option::None
+        }
+
+        fn handle_command(&mut self, _command: PjLinkCommand, _raw_command: &PjLinkRawPayload, _connection_id: &u64) -> PjLinkResponse {
+            PjLinkResponse::Single(b'0')
+        }
+    }
+
+    #[test]
+    fn it_maps_messages_to_command_lines() {
+        let websocket_listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = websocket_listener.local_addr().unwrap();
+        let listener = PjLinkListener::new_without_broadcast(
+            Arc::new(Mutex::new(PowerOffHandler)),
+            TcpListener::bind("127.0.0.1:0").unwrap(),
+        );
+        thread::spawn(move || listener.listen_websocket(websocket_listener));
+
+        let (mut client, _) = tungstenite::connect(format!("ws://{}", address)).unwrap();
+        assert_eq!(client.read().unwrap(), Message::Text(String::from("PJLINK 0")));
+
+        client.send(Message::Text(String::from("%1POWR ?"))).unwrap();
+        assert_eq!(client.read().unwrap(), Message::Text(String::from("%1POWR=0")));
+
+        client.send(Message::Binary(b"%1POWR ?\r".to_vec())).unwrap();
+        assert_eq!(client.read().unwrap(), Message::Text(String::from("%1POWR=0")));
+    }
+}